@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::PathBuf;
+
+use crate::commands::confirm;
+use crate::commands::sync::{execute_with_params, SyncParams};
+use crate::core::approval::{approval_key, current_user, read_plan, verify_plan};
+
+/// Parameters for the approve command
+pub struct ApproveParams {
+    /// Plan file written by `sync --request`
+    pub plan: PathBuf,
+    /// Skip the confirmation prompt (`--yes`)
+    pub yes: bool,
+}
+
+/// Verify a signed sync plan, show what it does and execute it once confirmed
+pub async fn execute(params: ApproveParams) -> Result<()> {
+    let key = approval_key()?;
+    let plan = read_plan(&params.plan)?;
+    verify_plan(&plan, &key, chrono::Utc::now())?;
+
+    let sync: SyncParams =
+        serde_json::from_value(plan.sync.clone()).context("Invalid sync parameters in plan")?;
+
+    println!(
+        "{} {} at {}",
+        "Sync requested by".bold(),
+        plan.requested_by,
+        plan.requested_at
+    );
+    if plan.requested_by == current_user() {
+        println!(
+            "{} You are approving your own request",
+            "Note:".yellow().bold()
+        );
+    }
+
+    execute_with_params(SyncParams {
+        dry_run: true,
+        ..sync.clone()
+    })
+    .await?;
+
+    if !confirm("Approve and execute this sync?", params.yes)? {
+        println!("Operation cancelled.");
+        return Ok(());
+    }
+
+    // The referenced files could have changed while waiting for confirmation
+    verify_plan(&plan, &key, chrono::Utc::now())?;
+
+    // Approving confirms the whole plan, including its own confirmations
+    execute_with_params(SyncParams { yes: true, ..sync }).await
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::PathBuf;
+
+use crate::commands::OutputFormat;
+use crate::config::{self, MongoConfig};
+use crate::core::backup::{
+    self, estimate_backup, read_manifest, verify_against_manifest, BackupEstimate, BackupManifest,
+    BackupRole,
+};
+use crate::core::plan;
+use crate::core::sync::parse_environment;
+use crate::utils::table::Table;
+use crate::utils::{mongodb, theme, units};
+
+/// Parameters for the backup command
+pub struct BackupParams {
+    /// Environment to back up
+    pub env: String,
+    /// Database to back up
+    pub db: String,
+    /// Compress the backup (`--gzip`), also when `gzip` is set in the `[defaults]` config table
+    pub gzip: bool,
+    /// Only show the destination and size estimate
+    pub dry_run: bool,
+    pub output: OutputFormat,
+}
+
+/// Back up a database into the backup directory, with a manifest of its
+/// collection checksums
+pub async fn execute(params: BackupParams) -> Result<()> {
+    let env = parse_environment(&params.env)?;
+    let config = MongoConfig::from_env(env.clone())
+        .context(format!("Failed to get configuration for {}", env))?;
+    let database = config::scoped_database(&env, &params.db);
+    let gzip = params.gzip || config::file::get().defaults.gzip.unwrap_or(false);
+
+    if params.dry_run {
+        let estimate = estimate_backup(&config, &database, gzip).await?;
+        if params.output == OutputFormat::Json {
+            let mut json = plan::backup_plan(&estimate).to_json();
+            json["dry_run"] = serde_json::json!(true);
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        } else {
+            print_backup_estimate(&estimate);
+        }
+        return Ok(());
+    }
+
+    let (path, manifest) = take_backup(&config, &database, gzip).await?;
+    if params.output == OutputFormat::Json {
+        let json = serde_json::json!({ "path": path, "manifest": manifest });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        println!(
+            "{} {}:{} in {}",
+            "Backup completed:".green().bold(),
+            env,
+            database,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Back up a database and record its checksums next to the dump
+pub async fn take_backup(
+    config: &MongoConfig,
+    database: &str,
+    gzip: bool,
+) -> Result<(PathBuf, BackupManifest)> {
+    let path = mongodb::create_backup(config, database, gzip).await?;
+    let manifest = backup::write_manifest(config, database, &path, BackupRole::default())
+        .await
+        .context("Failed to record backup checksums")?;
+    Ok((path, manifest))
+}
+
+fn print_backup_estimate(estimate: &BackupEstimate) {
+    plan::backup_plan(estimate).print_dry_run();
+
+    println!(
+        "\n  {} {} documents, {} in {} collection(s)",
+        "Estimated data:".green(),
+        estimate.documents(),
+        units::format_bytes(estimate.size()),
+        estimate.collections.len()
+    );
+    if !estimate.collections.is_empty() {
+        let mut table = Table::new(&["COLLECTION", "DOCUMENTS", "SIZE"])
+            .align_right(1)
+            .align_right(2);
+        for collection in &estimate.collections {
+            table.add_row(vec![
+                collection.name.clone(),
+                collection.documents.to_string(),
+                units::format_bytes(collection.size),
+            ]);
+        }
+        table.print(4);
+    }
+
+    let size = format!(
+        "{}{}",
+        units::format_bytes(estimate.size()),
+        if estimate.gzip {
+            " before compression"
+        } else {
+            ""
+        }
+    );
+    match estimate.free {
+        Some(free) if free >= estimate.size() => println!(
+            "  {} {}, {} free {}",
+            "Backup size:".green(),
+            size,
+            units::format_bytes(free),
+            theme::check().green()
+        ),
+        Some(free) => println!(
+            "  {} {}, only {} free {}",
+            "Backup size:".green(),
+            size,
+            units::format_bytes(free).red(),
+            theme::cross().red()
+        ),
+        None => println!("  {} {}, free space unknown", "Backup size:".green(), size),
+    }
+}
+
+/// Parameters for the backups verify command
+pub struct VerifyBackupParams {
+    /// Backup directory containing a manifest
+    pub path: PathBuf,
+    /// Environment to check; defaults to the one the backup was taken from
+    pub env: Option<String>,
+    /// Database to check; defaults to the backed-up database
+    pub db: Option<String>,
+}
+
+/// Validate a restored database against the checksums recorded in a backup
+pub async fn verify(params: VerifyBackupParams) -> Result<()> {
+    let manifest = read_manifest(&params.path)?;
+
+    let env = parse_environment(params.env.as_deref().unwrap_or(&manifest.environment))?;
+    let database = params.db.unwrap_or_else(|| manifest.database.clone());
+    let config = MongoConfig::from_env(env.clone())
+        .context(format!("Failed to get configuration for {}", env))?;
+
+    println!(
+        "{} {}:{} against backup of {}:{} taken {}",
+        "Verifying".bold(),
+        env,
+        database,
+        manifest.environment,
+        manifest.database,
+        manifest.created_at
+    );
+
+    verify_against_manifest(&config, &database, &manifest).await
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use log::info;
+use std::path::PathBuf;
+
+use super::OutputFormat;
+use crate::config::MongoConfig;
+use crate::core::backup::{
+    collect_checksums, compare_checksums, incomparable_hashes, BackupManifest,
+};
+use crate::core::sync::parse_environment;
+use crate::utils::table::Table;
+
+/// Parameters for the checksum command
+pub struct ChecksumParams {
+    pub env: String,
+    pub db: String,
+    pub output: OutputFormat,
+    /// Earlier checksum manifest (JSON output or backup manifest) to compare against
+    pub compare: Option<PathBuf>,
+}
+
+/// Print per-collection document counts and content hashes of a database
+pub async fn execute(params: ChecksumParams) -> Result<()> {
+    let env = parse_environment(&params.env)?;
+    info!("Computing checksums of {} in {}", params.db, env);
+
+    let config = MongoConfig::from_env(env.clone())?;
+    let database = crate::config::scoped_database(&env, &params.db);
+    let manifest = BackupManifest {
+        environment: env.to_string(),
+        database: database.clone(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        run_id: Some(crate::utils::run_id::get().to_string()),
+        role: Default::default(),
+        collections: collect_checksums(&config, &database, true).await?,
+    };
+
+    match params.output {
+        OutputFormat::Text => {
+            let mut table = Table::new(&["COLLECTION", "DOCUMENTS", "HASH"]).align_right(1);
+            for collection in &manifest.collections {
+                table.add_row(vec![
+                    collection.name.clone(),
+                    collection.documents.to_string(),
+                    collection.hash.clone().unwrap_or_else(|| "-".to_string()),
+                ]);
+            }
+            table.print(0);
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&manifest)?);
+        }
+    }
+
+    if let Some(path) = &params.compare {
+        let content =
+            std::fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        let expected: BackupManifest = serde_json::from_str(&content)
+            .context(format!("Failed to parse {}", path.display()))?;
+        let problems = compare_checksums(&expected.collections, &manifest.collections);
+
+        // Counts of these collections are still compared
+        for collection in incomparable_hashes(&expected.collections, &manifest.collections) {
+            eprintln!("  {} {}, content not comparable", "?".yellow(), collection);
+        }
+
+        // Keep stdout parseable in JSON mode
+        if problems.is_empty() {
+            eprintln!(
+                "{} matches {}:{} as of {}",
+                "Unchanged:".green().bold(),
+                expected.environment,
+                expected.database,
+                expected.created_at
+            );
+        } else {
+            for problem in &problems {
+                eprintln!("  {} {}", "-".red(), problem);
+            }
+            anyhow::bail!(
+                "{} collection(s) differ from {}:{} as of {}",
+                problems.len(),
+                expected.environment,
+                expected.database,
+                expected.created_at
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::commands::sync::{execute_with_params, SyncParams};
+use crate::core::sync::{get_databases, parse_environment};
+use crate::utils::pattern::{exclude_matching, include_matching};
+
+/// Parameters for the clone command
+pub struct CloneParams {
+    pub from: String,
+    pub to: String,
+    /// Glob patterns of databases to copy; all databases when empty
+    pub include: Vec<String>,
+    /// Glob patterns of databases to leave out
+    pub exclude: Vec<String>,
+    pub backup: Option<bool>,
+    pub drop: Option<bool>,
+    pub gzip: bool,
+    pub dry_run: bool,
+    pub yes: bool,
+}
+
+/// Sync every non-system database of one environment into another
+pub async fn execute(params: CloneParams) -> Result<()> {
+    let source_env = parse_environment(&params.from)?;
+    let target_env = parse_environment(&params.to)?;
+    if source_env == target_env {
+        anyhow::bail!(
+            "Source and target are the same environment ({})",
+            source_env
+        );
+    }
+
+    let mut databases = get_databases(&source_env).await?;
+    if !params.include.is_empty() {
+        databases = include_matching(databases, &params.include);
+    }
+    let databases = exclude_matching(databases, &params.exclude);
+    if databases.is_empty() {
+        anyhow::bail!(
+            "No databases in {} match the include and exclude patterns",
+            source_env
+        );
+    }
+
+    println!(
+        "{} {} database(s) from {} to {}: {}",
+        "Cloning".bold(),
+        databases.len(),
+        source_env,
+        target_env,
+        databases.join(", ")
+    );
+
+    execute_with_params(SyncParams {
+        from: Some(params.from),
+        to: Some(params.to),
+        databases,
+        backup: params.backup,
+        drop: params.drop,
+        gzip: params.gzip,
+        dry_run: params.dry_run,
+        yes: params.yes,
+        ..Default::default()
+    })
+    .await
+}
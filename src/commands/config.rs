@@ -0,0 +1,196 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use crate::config::file::{self, EnvironmentConfig};
+use crate::config::{
+    detect_paas_uris, environment_from_var, secrets, suggest_environment_name, Environment,
+    PAAS_VARS,
+};
+use crate::utils::mongodb::mask_connection_string;
+use crate::utils::theme;
+
+/// Parameters for the config import-env command
+pub struct ImportEnvParams {
+    /// Config file to write; defaults to the user config file
+    pub output: Option<PathBuf>,
+    /// Store connection strings in the OS keyring instead of the file
+    pub keyring: bool,
+    /// Replace environments already present in the config file
+    pub force: bool,
+}
+
+/// Parameters for the config detect command
+pub struct DetectParams {
+    /// Environment name to register; asked for, or suggested from the host
+    pub name: Option<String>,
+    /// Config file to write; defaults to the user config file
+    pub output: Option<PathBuf>,
+    /// Store the connection string in the OS keyring instead of the file
+    pub keyring: bool,
+    /// Replace an environment already present in the config file
+    pub force: bool,
+    /// Register without asking
+    pub yes: bool,
+}
+
+/// Write the `MONGO_<ENV>_URI` variables (including those from `.env`) to the config file
+pub fn import_env(params: ImportEnvParams) -> Result<()> {
+    let uris: BTreeMap<String, String> = env::vars()
+        .filter_map(|(key, uri)| environment_from_var(&key).map(|name| (name.to_uppercase(), uri)))
+        .collect();
+    if uris.is_empty() {
+        bail!("No MONGO_<ENV>_URI variables found in the environment or .env");
+    }
+
+    let output = output_path(params.output)?;
+    let written = write_environments(&output, &uris, params.keyring, params.force)?;
+    print_written(&output, &uris, &written, params.keyring);
+    if !written.is_empty() {
+        println!(
+            "The MONGO_<ENV>_URI variables can now be removed from your environment and .env."
+        );
+    }
+
+    Ok(())
+}
+
+/// Register the connection string of a PaaS variable such as `MONGODB_URI`
+/// or `MONGO_URL` (including those from `.env`) as an environment, for app
+/// repositories that already define one
+pub fn detect(params: DetectParams) -> Result<()> {
+    let vars: Vec<(String, String)> = env::vars().collect();
+    let detected = detect_paas_uris(&vars);
+    let Some((var, uri)) = detected.first() else {
+        bail!(
+            "No MongoDB connection string found in {} or .env",
+            PAAS_VARS.join(", ")
+        );
+    };
+
+    println!(
+        "{} {} ({})",
+        "Found".bold(),
+        var.cyan(),
+        mask_connection_string(uri)
+    );
+    for (other, _) in &detected[1..] {
+        println!(
+            "{} {}, which holds another connection string",
+            "Skipped".dimmed(),
+            other
+        );
+    }
+
+    let suggested = suggest_environment_name(uri);
+    let name = match params.name {
+        Some(name) => name,
+        None if !params.yes && std::io::stdin().is_terminal() => {
+            inquire::Text::new("Environment name:")
+                .with_default(suggested)
+                .prompt()?
+        }
+        None => suggested.to_string(),
+    };
+    let env: Environment = name.parse()?;
+
+    let output = output_path(params.output)?;
+    let message = format!(
+        "Register {} as environment {} in {}?",
+        var,
+        env,
+        output.display()
+    );
+    if !super::confirm(&message, params.yes)? {
+        println!("{}", "Nothing registered".yellow());
+        return Ok(());
+    }
+
+    let uris = BTreeMap::from([(env.name().to_string(), uri.clone())]);
+    let written = write_environments(&output, &uris, params.keyring, params.force)?;
+    print_written(&output, &uris, &written, params.keyring);
+
+    Ok(())
+}
+
+/// Config file the commands write: the given one or the user config file
+fn output_path(output: Option<PathBuf>) -> Result<PathBuf> {
+    match output {
+        Some(path) => Ok(path),
+        None => file::user_config_path()
+            .context("Cannot determine the user config directory; pass --file"),
+    }
+}
+
+/// Add environments with these connection strings to a config file, keeping
+/// those already present unless `force` is set. Returns the names written.
+fn write_environments(
+    output: &Path,
+    uris: &BTreeMap<String, String>,
+    keyring: bool,
+    force: bool,
+) -> Result<Vec<String>> {
+    let mut document: toml_edit::DocumentMut = if output.exists() {
+        let content = std::fs::read_to_string(output)
+            .with_context(|| format!("Failed to read {}", output.display()))?;
+        content
+            .parse()
+            .with_context(|| format!("Failed to parse {}", output.display()))?
+    } else {
+        toml_edit::DocumentMut::new()
+    };
+
+    let environments = uris
+        .iter()
+        .map(|(name, uri)| {
+            let environment = EnvironmentConfig {
+                uri: (!keyring).then(|| uri.clone()),
+                keyring,
+                ..Default::default()
+            };
+            (name.clone(), environment)
+        })
+        .collect();
+    let written = file::add_environments(&mut document, &environments, force)?;
+
+    if keyring {
+        for name in &written {
+            secrets::set_uri(name, &uris[name])?;
+        }
+    }
+
+    file::write_private(output, &document.to_string())
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    Ok(written)
+}
+
+/// Print which environments were written and which were already configured
+fn print_written(
+    output: &Path,
+    uris: &BTreeMap<String, String>,
+    written: &[String],
+    keyring: bool,
+) {
+    for name in uris.keys() {
+        if written.contains(name) {
+            let location = if keyring { "keyring" } else { "config file" };
+            println!("{} {} ({})", theme::check().green(), name, location);
+        } else {
+            println!(
+                "{} {} already configured, skipped (use --force to replace)",
+                theme::cross().yellow(),
+                name
+            );
+        }
+    }
+
+    println!(
+        "\n{} {}",
+        "Wrote".bold(),
+        output.display().to_string().cyan()
+    );
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::core::export::{parse_fields, perform_export, ExportConfig, ExportFormat};
+use crate::core::sync::parse_environment;
+
+/// Parameters for the export command
+pub struct ExportParams {
+    pub env: String,
+    pub db: String,
+    pub format: ExportFormat,
+    /// Comma-separated field projection
+    pub fields: String,
+    pub out: PathBuf,
+    /// Comma-separated collection names; all collections when unset
+    pub collections: Option<String>,
+}
+
+pub async fn execute(params: ExportParams) -> Result<()> {
+    let env = parse_environment(&params.env)?;
+    let fields = parse_fields(&params.fields)?;
+    let collections = params
+        .collections
+        .as_deref()
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    perform_export(ExportConfig {
+        database: crate::config::scoped_database(&env, &params.db),
+        env,
+        collections,
+        fields,
+        format: params.format,
+        output_dir: params.out,
+    })
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use colored::Colorize;
+use futures::future::join_all;
+use log::info;
+use std::time::Duration;
+
+use super::OutputFormat;
+use crate::core::sync::get_databases;
+use crate::utils::pattern::glob_match;
+use crate::utils::table::Table;
+
+/// Parameters for the find-db command
+pub struct FindDbParams {
+    /// Database name or glob pattern
+    pub pattern: String,
+    /// Per-environment query timeout in seconds
+    pub timeout: u64,
+    pub output: OutputFormat,
+}
+
+/// Search every configured environment for databases matching a name or pattern
+pub async fn execute(params: FindDbParams) -> Result<()> {
+    info!(
+        "Searching environments for databases matching '{}'",
+        params.pattern
+    );
+
+    let environments = crate::config::get_available_environments();
+    if environments.is_empty() {
+        anyhow::bail!(
+            "No MongoDB environments configured. Use 'info' command to see how to configure environments."
+        );
+    }
+
+    let timeout = Duration::from_secs(params.timeout);
+    let queries = environments.iter().map(|env| async move {
+        match tokio::time::timeout(timeout, get_databases(env)).await {
+            Ok(Ok(databases)) => Ok(databases),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+        }
+    });
+    let results = join_all(queries).await;
+
+    let mut matches = Vec::new();
+    let mut errors = Vec::new();
+    for (env, result) in environments.iter().zip(results) {
+        match result {
+            Ok(databases) => matches.extend(
+                databases
+                    .into_iter()
+                    .filter(|db| glob_match(&params.pattern, db))
+                    .map(|db| (env.clone(), db)),
+            ),
+            Err(e) => errors.push((env.clone(), e)),
+        }
+    }
+
+    match params.output {
+        OutputFormat::Text => {
+            if matches.is_empty() {
+                println!(
+                    "{}",
+                    format!("No databases matching '{}' found", params.pattern).yellow()
+                );
+            } else {
+                let mut table = Table::new(&["ENVIRONMENT", "DATABASE"]);
+                for (env, db) in &matches {
+                    table.add_row(vec![env.to_string(), db.clone()]);
+                }
+                table.print(0);
+            }
+
+            for (env, error) in &errors {
+                eprintln!(
+                    "{} {} not searched: {}",
+                    "Warning:".yellow().bold(),
+                    env,
+                    error
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let json = serde_json::json!({
+                "matches": matches
+                    .iter()
+                    .map(|(env, db)| serde_json::json!({ "environment": env, "database": db }))
+                    .collect::<Vec<_>>(),
+                "errors": errors
+                    .iter()
+                    .map(|(env, error)| serde_json::json!({ "environment": env, "error": error }))
+                    .collect::<Vec<_>>(),
+            });
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use colored::Colorize;
+use log::info;
+
+use super::OutputFormat;
+use crate::config::MongoConfig;
+use crate::core::sync::parse_environment;
+use crate::utils::mongodb;
+use crate::utils::table::Table;
+use crate::utils::units::format_bytes;
+
+/// Parameters for the list-collections command
+pub struct ListCollectionsParams {
+    pub env: String,
+    pub db: String,
+    pub output: OutputFormat,
+}
+
+/// Print the collections of a database with document counts and sizes
+pub async fn execute(params: ListCollectionsParams) -> Result<()> {
+    let env = parse_environment(&params.env)?;
+    info!("Listing collections of {} in {}", params.db, env);
+
+    let config = MongoConfig::from_env(env.clone())?;
+    let database = crate::config::scoped_database(&env, &params.db);
+    let collections = mongodb::list_collection_stats(&config, &database).await?;
+
+    match params.output {
+        OutputFormat::Text => {
+            if collections.is_empty() {
+                println!(
+                    "{}",
+                    format!("No collections found in {}:{}", env, params.db).yellow()
+                );
+                return Ok(());
+            }
+
+            let mut table = Table::new(&[
+                "COLLECTION",
+                "TYPE",
+                "DOCUMENTS",
+                "SIZE",
+                "STORAGE",
+                "INDEXES",
+            ])
+            .align_right(2)
+            .align_right(3)
+            .align_right(4)
+            .align_right(5);
+            for collection in &collections {
+                table.add_row(vec![
+                    collection.name.clone(),
+                    collection.kind.clone(),
+                    collection.documents.to_string(),
+                    format_bytes(collection.size),
+                    format_bytes(collection.storage_size),
+                    format_bytes(collection.index_size),
+                ]);
+            }
+            table.print(0);
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&collections)?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use anyhow::Result;
+use log::info;
+
+use super::OutputFormat;
+use crate::core::sync::{get_databases, parse_environment};
+
+/// Parameters for the list-dbs command
+pub struct ListDbsParams {
+    pub env: String,
+    pub output: OutputFormat,
+}
+
+/// Print the non-system databases of an environment, one per line or as JSON
+pub async fn execute(params: ListDbsParams) -> Result<()> {
+    let env = parse_environment(&params.env)?;
+    info!("Listing databases in {}", env);
+
+    let databases = get_databases(&env).await?;
+
+    match params.output {
+        OutputFormat::Text => {
+            for db in databases {
+                println!("{}", db);
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&databases)?);
+        }
+    }
+
+    Ok(())
+}
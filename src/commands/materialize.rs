@@ -0,0 +1,69 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::core::materialize::{
+    parse_query, perform_materialize, CollectionRef, MaterializeConfig,
+};
+use crate::utils::mongodb;
+use crate::utils::theme;
+
+/// Parameters for the materialize command
+pub struct MaterializeParams {
+    /// Source collection as ENV:database.collection
+    pub from: String,
+    /// Target collection as ENV:database.collection
+    pub to: String,
+    /// Query filter in MongoDB Extended JSON
+    pub query: String,
+    pub drop: bool,
+    pub dry_run: bool,
+}
+
+pub async fn execute(params: MaterializeParams) -> Result<()> {
+    let source = params.from.parse::<CollectionRef>()?.scoped();
+    let target = params.to.parse::<CollectionRef>()?.scoped();
+    let query = parse_query(&params.query)?;
+
+    if source == target {
+        anyhow::bail!("Source and target collection are the same ({})", source);
+    }
+
+    if params.dry_run {
+        let source_config = crate::config::MongoConfig::from_env(source.env.clone())?;
+        let count = mongodb::count_documents(
+            &source_config,
+            &source.database,
+            &source.collection,
+            query.clone(),
+        )
+        .await?;
+
+        println!("\n{}", "=== DRY RUN MODE ===".yellow().bold());
+        println!(
+            "  {} {} {} {}",
+            "Collections:".green(),
+            source,
+            theme::arrow(),
+            target
+        );
+        println!("  {} {}", "Query:".green(), query);
+        println!("  {} {}", "Matching documents:".green(), count);
+        println!(
+            "  {} {}",
+            "Drop target collection:".green(),
+            if params.drop { "Yes" } else { "No" }
+        );
+        println!("\n{}", "No changes were made.".yellow());
+        return Ok(());
+    }
+
+    perform_materialize(MaterializeConfig {
+        source,
+        target,
+        query,
+        drop_target: params.drop,
+    })
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,360 @@
+//! `arcula mcp`: a Model Context Protocol server on stdio, so AI assistants
+//! and agents can inspect environments and, when allowed, refresh them.
+//!
+//! The tools are the operations of `arcula rpc` plus the backup history.
+//! Syncs and backups are only offered with `--allow-writes`, and syncs into
+//! production environments are left to `sync --request` and `approve` unless
+//! the client has an admin token.
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::commands::rpc::{
+    self, parse_request, Policy, Request, Response, RpcError, INVALID_PARAMS, METHOD_NOT_FOUND,
+};
+use crate::config::get_backup_dir;
+use crate::core::access;
+use crate::core::backup::list_backups;
+use crate::utils::events::{self, ProgressEvent};
+
+/// Protocol versions the server speaks, newest first
+pub const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Backups listed by `history` unless the client asks for another number
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+/// Progress token of the tool call running now and the progress events sent for it
+static PROGRESS: Mutex<Option<(Value, u64)>> = Mutex::new(None);
+
+/// A tool of the server and whether it changes anything
+struct Tool {
+    name: &'static str,
+    description: &'static str,
+    writes: bool,
+    input_schema: fn() -> Value,
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        name: "list_environments",
+        description: "List the MongoDB environments arcula can connect to",
+        writes: false,
+        input_schema: no_arguments,
+    },
+    Tool {
+        name: "list_databases",
+        description: "List the databases of an environment, without system databases",
+        writes: false,
+        input_schema: environment_arguments,
+    },
+    Tool {
+        name: "list_collections",
+        description: "List the collections of a database with document counts, data, storage and index sizes",
+        writes: false,
+        input_schema: database_arguments,
+    },
+    Tool {
+        name: "history",
+        description: "List the backups taken by earlier syncs, newest first, with their run ID, environment, database and collection counts",
+        writes: false,
+        input_schema: history_arguments,
+    },
+    Tool {
+        name: "sync",
+        description: "Copy a database from one environment to another, backing up the target first. \
+                      Use dry_run to get the plan and size estimate without changing anything. \
+                      Production targets need an admin token.",
+        writes: true,
+        input_schema: sync_arguments,
+    },
+    Tool {
+        name: "backup",
+        description: "Back up a database into the backup directory, with a manifest of its collection checksums",
+        writes: true,
+        input_schema: backup_arguments,
+    },
+];
+
+fn no_arguments() -> Value {
+    json!({"type": "object", "properties": {}})
+}
+
+fn environment_arguments() -> Value {
+    json!({
+        "type": "object",
+        "properties": {"env": {"type": "string", "description": "Environment name, e.g. STG"}},
+        "required": ["env"],
+    })
+}
+
+fn database_arguments() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "env": {"type": "string", "description": "Environment name, e.g. STG"},
+            "db": {"type": "string", "description": "Database name"},
+        },
+        "required": ["env", "db"],
+    })
+}
+
+fn history_arguments() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "env": {"type": "string", "description": "Only backups of this environment"},
+            "db": {"type": "string", "description": "Only backups of this database"},
+            "limit": {"type": "integer", "minimum": 1, "description": "Most backups to list, 20 by default"},
+        },
+    })
+}
+
+fn sync_arguments() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "from": {"type": "string", "description": "Source environment"},
+            "to": {"type": "string", "description": "Target environment"},
+            "db": {"type": "string", "description": "Source database"},
+            "target_db": {"type": "string", "description": "Target database, the source database name by default"},
+            "backup": {"type": "boolean", "description": "Back up the target first"},
+            "drop": {"type": "boolean", "description": "Drop the target collections before importing"},
+            "clear": {"type": "boolean", "description": "Delete the target documents before importing"},
+            "collections": {"type": "array", "items": {"type": "string"}, "description": "Only these collections"},
+            "exclude_collections": {"type": "array", "items": {"type": "string"}, "description": "Leave these collections out"},
+            "ns_map": {"type": "array", "items": {"type": "string"}, "description": "Collection renames, e.g. app.users=app.customers"},
+            "limits": {"type": "array", "items": {"type": "string"}, "description": "Most documents copied of every collection, e.g. 1000, or of one, e.g. events=1000"},
+            "sampling": {"type": "string", "enum": ["first", "random"], "description": "Copy the first or random documents of limited collections"},
+            "preset": {"type": "string", "description": "Preset from the config file filling in the other arguments"},
+            "dry_run": {"type": "boolean", "description": "Only return the plan and size estimate"},
+            "yes": {"type": "boolean", "description": "Confirm a sync within the same environment"},
+        },
+        "required": ["db"],
+    })
+}
+
+fn backup_arguments() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "env": {"type": "string", "description": "Environment name, e.g. STG"},
+            "db": {"type": "string", "description": "Database name"},
+            "gzip": {"type": "boolean", "description": "Compress the backup"},
+            "dry_run": {"type": "boolean", "description": "Only return the destination and size estimate"},
+        },
+        "required": ["env", "db"],
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HistoryParams {
+    env: Option<String>,
+    db: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+/// Tools a client of a server with this policy may call, as listed by `tools/list`
+pub fn tools(policy: Policy) -> Value {
+    let tools: Vec<Value> = TOOLS
+        .iter()
+        .filter(|tool| policy.writes || !tool.writes)
+        .map(|tool| {
+            json!({
+                "name": tool.name,
+                "description": tool.description,
+                "inputSchema": (tool.input_schema)(),
+                "annotations": {"readOnlyHint": !tool.writes},
+            })
+        })
+        .collect();
+    json!({ "tools": tools })
+}
+
+/// Backups taken by earlier syncs, newest first
+fn history(params: HistoryParams) -> Result<Value> {
+    let mut backups = list_backups(&get_backup_dir())?;
+    backups.retain(|(_, manifest)| {
+        params
+            .env
+            .as_ref()
+            .is_none_or(|env| manifest.environment.eq_ignore_ascii_case(env))
+            && params.db.as_ref().is_none_or(|db| &manifest.database == db)
+    });
+    backups.sort_by(|a, b| b.1.created_at.cmp(&a.1.created_at));
+    let entries: Vec<Value> = backups
+        .into_iter()
+        .take(params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+        .map(|(path, manifest)| {
+            json!({
+                "path": path,
+                "run_id": manifest.run_id,
+                "environment": manifest.environment,
+                "database": manifest.database,
+                "role": manifest.role,
+                "created_at": manifest.created_at,
+                "collections": manifest.collections.len(),
+                "documents": manifest.collections.iter().map(|c| c.documents).sum::<u64>(),
+            })
+        })
+        .collect();
+    Ok(json!(entries))
+}
+
+/// Run a tool; failures of the tool itself are part of the result, as MCP
+/// clients show them to the model instead of treating them as protocol errors
+async fn call_tool(call: ToolCall, policy: Policy) -> std::result::Result<Value, RpcError> {
+    let Some(tool) = TOOLS.iter().find(|tool| tool.name == call.name) else {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("Unknown tool '{}'", call.name),
+        ));
+    };
+    let result = match tool.name {
+        "history" => history(rpc::parse_params(call.arguments)?)
+            .map_err(|e| RpcError::new(rpc::OPERATION_FAILED, format!("{:#}", e))),
+        name => rpc::call(name, call.arguments, policy).await,
+    };
+    Ok(match result {
+        Ok(value) => json!({
+            "content": [{"type": "text", "text": serde_json::to_string_pretty(&value).unwrap_or_default()}],
+            "isError": false,
+        }),
+        Err(error) => {
+            let mut text = error.message;
+            if let Some(data) = error.data {
+                text.push('\n');
+                text.push_str(&serde_json::to_string_pretty(&data).unwrap_or_default());
+            }
+            json!({
+                "content": [{"type": "text", "text": text}],
+                "isError": true,
+            })
+        }
+    })
+}
+
+/// Answer an MCP request, returning its response unless the request is a
+/// notification
+pub async fn handle(request: Request, policy: Policy) -> Option<Response> {
+    let result = match request.method.as_str() {
+        "initialize" => {
+            let requested = request
+                .params
+                .get("protocolVersion")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let version = PROTOCOL_VERSIONS
+                .iter()
+                .find(|version| **version == requested)
+                .unwrap_or(&PROTOCOL_VERSIONS[0]);
+            Ok(json!({
+                "protocolVersion": version,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "arcula", "version": env!("CARGO_PKG_VERSION")},
+            }))
+        }
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(tools(policy)),
+        "tools/call" => match serde_json::from_value::<ToolCall>(request.params.clone()) {
+            Ok(call) => {
+                let token = request.params.pointer("/_meta/progressToken").cloned();
+                *PROGRESS.lock().unwrap_or_else(|e| e.into_inner()) = token.map(|t| (t, 0));
+                let result = call_tool(call, policy).await;
+                *PROGRESS.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                result
+            }
+            Err(e) => Err(RpcError::new(INVALID_PARAMS, e)),
+        },
+        // Notifications such as `notifications/initialized` need no answer
+        method if method.starts_with("notifications/") => return None,
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{}'", method),
+        )),
+    };
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => Response::success(id, result),
+        Err(error) => Response::failure(id, error),
+    })
+}
+
+/// Readable summary of a progress event, e.g. `app: progress import users (3/12)`
+fn describe(event: &ProgressEvent) -> String {
+    let kind = serde_json::to_value(event.event)
+        .ok()
+        .and_then(|kind| kind.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let mut message = format!("{}: {}", event.database, kind);
+    if let Some(phase) = event.phase {
+        message.push_str(&format!(" {}", phase));
+    }
+    if let (Some(collection), Some(done), Some(total)) =
+        (&event.collection, event.done, event.total)
+    {
+        message.push_str(&format!(" {} ({}/{})", collection, done, total));
+    }
+    if let Some(text) = event.message.as_ref().or(event.error.as_ref()) {
+        message.push_str(&format!(": {}", text));
+    }
+    message
+}
+
+/// Serve MCP requests from stdin until it is closed
+pub async fn execute(allow_writes: bool, allow_unauthenticated: bool) -> Result<()> {
+    let policy = access::authenticate(allow_unauthenticated)?.map_or(
+        Policy {
+            writes: true,
+            production_targets: false,
+        },
+        Policy::for_role,
+    );
+    let policy = Policy {
+        writes: allow_writes && policy.writes,
+        ..policy
+    };
+
+    // Progress events become progress notifications of the tool call that
+    // asked for them with a progress token
+    events::set_handler(|event| {
+        let mut progress = PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((token, count)) = progress.as_mut() {
+            *count += 1;
+            rpc::send(&json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": {
+                    "progressToken": token,
+                    "progress": count,
+                    "message": describe(event),
+                },
+            }));
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match parse_request(&line) {
+            Ok(request) => handle(request, policy).await,
+            Err(response) => Some(*response),
+        };
+        if let Some(response) = response {
+            rpc::send(&response);
+        }
+    }
+    Ok(())
+}
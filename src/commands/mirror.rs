@@ -0,0 +1,61 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::config;
+use crate::core::mirror::{run_mirror, MirrorConfig};
+use crate::core::sync::parse_environment;
+use crate::core::two_way::{run_two_way, TwoWayConfig};
+use crate::utils::{mongodb, run_id};
+
+/// Parameters for the mirror command
+pub struct MirrorParams {
+    pub from: String,
+    pub to: String,
+    pub db: String,
+    pub target_db: Option<String>,
+    pub resync: bool,
+    /// Replicate in both directions (experimental)
+    pub two_way: bool,
+    pub conflict_log: Option<PathBuf>,
+}
+
+pub async fn execute(params: MirrorParams) -> Result<()> {
+    let source_env = parse_environment(&params.from)?;
+    let target_env = parse_environment(&params.to)?;
+    let source_db = config::scoped_database(&source_env, &params.db);
+    let target_db = config::scoped_database(
+        &target_env,
+        params.target_db.as_deref().unwrap_or(&params.db),
+    );
+    mongodb::validate_db_name(&target_db)?;
+
+    if source_env == target_env && source_db == target_db {
+        anyhow::bail!(
+            "Source and target are the same database ({}:{})",
+            source_env,
+            target_db
+        );
+    }
+
+    if params.two_way {
+        return run_two_way(TwoWayConfig {
+            from_env: source_env,
+            to_env: target_env,
+            from_db: source_db,
+            to_db: target_db,
+            conflict_log: params.conflict_log.unwrap_or_else(|| {
+                PathBuf::from(format!("arcula-conflicts-{}.jsonl", run_id::get()))
+            }),
+        })
+        .await;
+    }
+
+    run_mirror(MirrorConfig {
+        source_env,
+        target_env,
+        source_db,
+        target_db,
+        resync: params.resync,
+    })
+    .await
+}
@@ -1,2 +1,44 @@
+use std::io::IsTerminal;
+
+pub mod approve;
+pub mod backups;
+pub mod checksum;
+pub mod clone;
+pub mod config;
+pub mod export;
+pub mod find_db;
 pub mod info;
+pub mod list_collections;
+pub mod list_dbs;
+pub mod materialize;
+pub mod mcp;
+pub mod mirror;
+pub mod prune_temp;
+pub mod restore;
+pub mod rpc;
 pub mod sync;
+
+/// Ask whether to go ahead with an action, answering yes right away with
+/// `--yes`. Fails instead of prompting when stdin is not a terminal, so
+/// scripts and cron jobs never wait for input.
+pub fn confirm(message: &str, yes: bool) -> anyhow::Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("{} Pass --yes to confirm without a terminal", message);
+    }
+    Ok(inquire::Confirm::new(message)
+        .with_default(false)
+        .prompt()?)
+}
+
+/// Output format for commands with scriptable output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON for scripts and automation
+    Json,
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::time::{Duration, SystemTime};
+
+use crate::config::{get_backup_dir, get_state_dir, get_temp_dir};
+use crate::core::prune::{find_stale_artifacts, PruneLocations};
+use crate::utils::units::{format_bytes, format_duration};
+
+/// Parameters for the prune-temp command
+pub struct PruneTempParams {
+    /// Minimum age of temp dumps and checkpoints to remove
+    pub older_than: Duration,
+    /// List what would be removed without removing it
+    pub dry_run: bool,
+}
+
+/// Remove temp dumps, broken backups and old checkpoints left behind by earlier runs
+pub async fn execute(params: PruneTempParams) -> Result<()> {
+    let locations = PruneLocations {
+        temp_dir: get_temp_dir(),
+        backup_dir: get_backup_dir(),
+        state_dir: get_state_dir(),
+    };
+    let artifacts = find_stale_artifacts(&locations, params.older_than, SystemTime::now())?;
+
+    if artifacts.is_empty() {
+        println!(
+            "Nothing to prune (temp dumps and checkpoints older than {}, broken backups)",
+            format_duration(params.older_than)
+        );
+        return Ok(());
+    }
+
+    let mut freed = 0;
+    let mut failed = 0;
+    for artifact in &artifacts {
+        let line = format!(
+            "{} {} ({}, {})",
+            artifact.kind,
+            artifact.path.display(),
+            artifact.reason,
+            format_bytes(artifact.size)
+        );
+        if params.dry_run {
+            println!("  {} {}", "Would remove".yellow(), line);
+            freed += artifact.size;
+            continue;
+        }
+        match artifact.remove() {
+            Ok(()) => {
+                println!("  {} {}", "Removed".green(), line);
+                freed += artifact.size;
+            }
+            Err(e) => {
+                println!("  {} {}: {:#}", "Failed".red(), line, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} {} artifact(s), {}",
+        if params.dry_run {
+            "Would free:".bold()
+        } else {
+            "Freed:".bold()
+        },
+        artifacts.len() - failed,
+        format_bytes(freed)
+    );
+    if failed > 0 {
+        anyhow::bail!("Failed to remove {} artifact(s)", failed);
+    }
+    Ok(())
+}
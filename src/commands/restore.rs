@@ -0,0 +1,126 @@
+use crate::commands::{confirm, OutputFormat};
+use crate::config::{get_backup_dir, MongoConfig};
+use crate::core::backup::{find_run_backups, plan_restore, BackupRole};
+use crate::core::plan;
+use crate::core::sync::parse_environment;
+use crate::utils::dump::DumpLayout;
+use crate::utils::mongodb::{self, ImportOptions};
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+/// Parameters for the restore command
+pub struct RestoreParams {
+    /// Run whose backup to restore
+    pub from_run: String,
+    /// Backed-up database, needed when the run backed up several
+    pub db: Option<String>,
+    /// Environment to restore into; defaults to the one the backup was taken from
+    pub to: Option<String>,
+    /// Whether to restore the source or the target backup of the run
+    pub role: BackupRole,
+    /// Collections to restore; the whole database when empty
+    pub collections: Vec<String>,
+    /// Skip the confirmation prompt (`--yes`)
+    pub yes: bool,
+    /// Only show which collections would be replaced
+    pub dry_run: bool,
+    pub output: OutputFormat,
+}
+
+/// Restore a database, or some of its collections, from the backup taken
+/// during an earlier run
+pub async fn execute(params: RestoreParams) -> Result<()> {
+    let backup_dir = get_backup_dir();
+    let backups: Vec<_> = find_run_backups(&backup_dir, &params.from_run)?
+        .into_iter()
+        .filter(|(_, manifest)| {
+            manifest.role == params.role
+                && params.db.as_ref().is_none_or(|db| &manifest.database == db)
+        })
+        .collect();
+
+    let (path, manifest) = match backups.as_slice() {
+        [] => anyhow::bail!(
+            "No {} backup of run {}{} found in {}",
+            params.role,
+            params.from_run,
+            params
+                .db
+                .as_ref()
+                .map(|db| format!(" for {}", db))
+                .unwrap_or_default(),
+            backup_dir.display()
+        ),
+        [backup] => backup,
+        _ => anyhow::bail!(
+            "Run {} backed up several databases ({}); pick one with --db",
+            params.from_run,
+            backups
+                .iter()
+                .map(|(_, manifest)| manifest.database.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    let missing: Vec<&str> = params
+        .collections
+        .iter()
+        .filter(|c| !manifest.collections.iter().any(|m| &m.name == *c))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Collection(s) not in the backup of {}: {}",
+            manifest.database,
+            missing.join(", ")
+        );
+    }
+
+    let env = parse_environment(params.to.as_deref().unwrap_or(&manifest.environment))?;
+    let config = MongoConfig::from_env(env.clone())
+        .context(format!("Failed to get configuration for {}", env))?;
+
+    let target = mongodb::list_collection_stats(&config, &manifest.database).await?;
+    let restore = plan_restore(manifest, &params.collections, &target);
+    let plan = plan::restore_plan(&env, path, manifest, &restore);
+    if params.dry_run {
+        if params.output == OutputFormat::Json {
+            let mut json = plan.to_json();
+            json["dry_run"] = serde_json::json!(true);
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        } else {
+            plan.print_dry_run();
+        }
+        return Ok(());
+    }
+
+    plan.print();
+    if !confirm(
+        "Replace these collections with the backed-up version?",
+        params.yes,
+    )? {
+        println!("Operation cancelled.");
+        return Ok(());
+    }
+
+    mongodb::import_database(
+        &config,
+        &manifest.database,
+        &DumpLayout::open(path),
+        ImportOptions {
+            drop: true,
+            collections: &params.collections,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    println!(
+        "{} {}:{}",
+        "Restore completed:".green().bold(),
+        env,
+        manifest.database
+    );
+    Ok(())
+}
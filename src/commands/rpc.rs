@@ -0,0 +1,367 @@
+//! `arcula rpc`: sync, backup and list operations as JSON-RPC 2.0 over stdio.
+//!
+//! Each line of stdin is a request and each line written to stdout a response
+//! or a `progress` notification carrying a progress event. Requests are
+//! handled one at a time, in order, and the process exits at the end of stdin.
+
+use anyhow::{Context, Result};
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::commands::backups::take_backup;
+use crate::commands::sync::{resolve_single_sync, SyncParams};
+use crate::config::{self, MongoConfig};
+use crate::core::access::{self, Role};
+use crate::core::backup::estimate_backup;
+use crate::core::plan;
+use crate::core::sync::{estimate_sync, get_databases, parse_environment, perform_sync};
+use crate::utils::{events, mongodb};
+
+/// The request wasn't valid JSON
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON wasn't a JSON-RPC 2.0 request
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The operation was started but failed, e.g. a sync whose import failed
+pub const OPERATION_FAILED: i64 = -32000;
+/// The policy of the server doesn't allow the operation
+pub const NOT_ALLOWED: i64 = -32001;
+
+/// What clients may do besides reading. `arcula rpc` allows everything its
+/// caller could run on the command line, `arcula mcp` only what it was
+/// started with, and both no more than the role of the client: the role of
+/// its token, viewer without configured tokens, or no limit of its own when
+/// started with `--allow-unauthenticated`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    /// Run syncs and take backups
+    pub writes: bool,
+    /// Sync into production environments
+    pub production_targets: bool,
+}
+
+impl Policy {
+    pub const UNRESTRICTED: Policy = Policy {
+        writes: true,
+        production_targets: true,
+    };
+
+    /// What a client with `role` may do
+    pub fn for_role(role: Role) -> Policy {
+        Policy {
+            writes: role >= Role::Operator,
+            production_targets: role == Role::Admin,
+        }
+    }
+}
+
+/// A JSON-RPC request; requests without an `id` are notifications and get
+/// no response
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    /// A failed operation, with the whole error chain as the message
+    fn failed(error: anyhow::Error) -> Self {
+        Self::new(OPERATION_FAILED, format!("{:#}", error))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    /// `null` when the request couldn't be read far enough to know its ID
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    pub fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Read a line of input as a request, or the error response to send instead
+pub fn parse_request(line: &str) -> std::result::Result<Request, Box<Response>> {
+    let value: Value = serde_json::from_str(line)
+        .map_err(|e| Response::failure(Value::Null, RpcError::new(PARSE_ERROR, e)))?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request: Request = serde_json::from_value(value)
+        .map_err(|e| Response::failure(id.clone(), RpcError::new(INVALID_REQUEST, e)))?;
+    if request.jsonrpc != "2.0" {
+        return Err(Response::failure(
+            id,
+            RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported"),
+        )
+        .into());
+    }
+    Ok(request)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentParams {
+    env: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DatabaseParams {
+    env: String,
+    db: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackupParams {
+    env: String,
+    db: String,
+    /// Compress the backup; `gzip` from the `[defaults]` config table when unset
+    #[serde(default)]
+    gzip: Option<bool>,
+    /// Return the destination and size estimate instead of backing up
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Parameters of `sync`: those of the sync command, plus what the CLI asks
+/// for interactively or takes as flags that aren't sync parameters
+#[derive(Debug, Deserialize)]
+struct SyncRequest {
+    /// Return the plan and size estimate instead of syncing
+    #[serde(default)]
+    dry_run: bool,
+    /// Confirm syncing within the same environment
+    #[serde(default)]
+    yes: bool,
+    #[serde(flatten)]
+    params: SyncParams,
+}
+
+/// Read the parameters of a method, which may be left out when none are required
+pub fn parse_params<T: DeserializeOwned>(params: Value) -> std::result::Result<T, RpcError> {
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+}
+
+/// Run the operation of a request, returning its response unless the
+/// request is a notification
+pub async fn handle(request: Request, policy: Policy) -> Option<Response> {
+    let result = call(&request.method, request.params, policy).await;
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => Response::success(id, result),
+        Err(error) => Response::failure(id, error),
+    })
+}
+
+/// Run an operation with its parameters as far as the policy allows
+pub async fn call(
+    method: &str,
+    params: Value,
+    policy: Policy,
+) -> std::result::Result<Value, RpcError> {
+    match method {
+        "list_environments" => Ok(list_environments()),
+        "list_databases" => list_databases(parse_params(params)?)
+            .await
+            .map_err(RpcError::failed),
+        "list_collections" => list_collections(parse_params(params)?)
+            .await
+            .map_err(RpcError::failed),
+        "backup" => {
+            let params: BackupParams = parse_params(params)?;
+            // Dry runs only read, so they are allowed either way
+            if !params.dry_run && !policy.writes {
+                return Err(RpcError::new(NOT_ALLOWED, "Backups are not allowed here"));
+            }
+            backup(params).await.map_err(RpcError::failed)
+        }
+        "sync" => sync(parse_params(params)?, policy).await,
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{}'", method),
+        )),
+    }
+}
+
+fn list_environments() -> Value {
+    serde_json::json!(config::get_available_environments())
+}
+
+async fn list_databases(params: EnvironmentParams) -> Result<Value> {
+    let env = parse_environment(&params.env)?;
+    Ok(serde_json::to_value(get_databases(&env).await?)?)
+}
+
+async fn list_collections(params: DatabaseParams) -> Result<Value> {
+    let env = parse_environment(&params.env)?;
+    let config = MongoConfig::from_env(env.clone())?;
+    let database = config::scoped_database(&env, &params.db);
+    Ok(serde_json::to_value(
+        mongodb::list_collection_stats(&config, &database).await?,
+    )?)
+}
+
+async fn backup(params: BackupParams) -> Result<Value> {
+    let env = parse_environment(&params.env)?;
+    let config = MongoConfig::from_env(env.clone())
+        .context(format!("Failed to get configuration for {}", env))?;
+    let database = config::scoped_database(&env, &params.db);
+    let gzip = params
+        .gzip
+        .unwrap_or_else(|| config::file::get().defaults.gzip.unwrap_or(false));
+
+    if params.dry_run {
+        return Ok(serde_json::to_value(
+            estimate_backup(&config, &database, gzip).await?,
+        )?);
+    }
+    let (path, manifest) = take_backup(&config, &database, gzip).await?;
+    Ok(serde_json::json!({
+        "path": path,
+        "manifest": manifest,
+    }))
+}
+
+async fn sync(request: SyncRequest, policy: Policy) -> std::result::Result<Value, RpcError> {
+    // Dry runs only read, so they are allowed either way
+    if !request.dry_run && !policy.writes {
+        return Err(RpcError::new(
+            NOT_ALLOWED,
+            "Syncs are not allowed here, only dry runs",
+        ));
+    }
+    let config = resolve_single_sync(request.params)
+        .await
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{:#}", e)))?;
+    if !request.dry_run && !policy.production_targets && config::is_production(&config.target_env) {
+        return Err(RpcError::new(
+            NOT_ALLOWED,
+            format!(
+                "{} is a production environment; sync into it with an admin token, or with 'arcula sync --request' and 'arcula approve'",
+                config.target_env
+            ),
+        ));
+    }
+    // The CLI asks before syncing within an environment; here it must be requested
+    if config.source_env == config.target_env && !request.yes && !request.dry_run {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            format!(
+                "Source and target are the same environment ({}); pass \"yes\": true to proceed",
+                config.source_env
+            ),
+        ));
+    }
+
+    if request.dry_run {
+        let mut plan = plan::sync_plan(&config).to_json();
+        plan["estimate"] =
+            serde_json::to_value(estimate_sync(&config).await.map_err(RpcError::failed)?)
+                .map_err(|e| RpcError::failed(e.into()))?;
+        return Ok(plan);
+    }
+
+    let report = perform_sync(config).await;
+    let value = serde_json::to_value(&report).map_err(|e| RpcError::failed(e.into()))?;
+    match &report.error {
+        None => Ok(value),
+        Some(error) => Err(RpcError {
+            data: Some(value),
+            ..RpcError::new(OPERATION_FAILED, error)
+        }),
+    }
+}
+
+/// Write a message as a line of stdout, which carries nothing else in RPC mode
+pub fn send(message: &impl Serialize) {
+    let result = serde_json::to_string(message)
+        .map_err(std::io::Error::from)
+        .and_then(|line| {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", line)?;
+            stdout.flush()
+        });
+    if let Err(e) = result {
+        error!("Failed to write RPC message: {}", e);
+    }
+}
+
+/// Serve requests from stdin until it is closed
+pub async fn execute(allow_unauthenticated: bool) -> Result<()> {
+    let policy =
+        access::authenticate(allow_unauthenticated)?.map_or(Policy::UNRESTRICTED, Policy::for_role);
+
+    events::set_handler(|event| {
+        send(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "progress",
+            "params": event,
+        }))
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match parse_request(&line) {
+            Ok(request) => handle(request, policy).await,
+            Err(response) => Some(*response),
+        };
+        if let Some(response) = response {
+            send(&response);
+        }
+    }
+    Ok(())
+}
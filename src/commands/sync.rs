@@ -1,20 +1,151 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use colored::Colorize;
 use inquire::{Confirm, MultiSelect, Select};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::core::sync::{get_databases, parse_environment, perform_sync, SyncConfig, SyncOptions};
+use crate::commands::{confirm, OutputFormat};
+use crate::config::file::Preset;
+use crate::config::{Environment, MongoConfig, ReadPreference};
+use crate::core::approval;
+use crate::core::backup::BackupRole;
+use crate::core::cache::{self, get_databases_cached};
+use crate::core::conflicts::Prefer;
+use crate::core::engine::EngineKind;
+use crate::core::mapping::{
+    load_collection_mapping, load_database_mapping, parse_namespace_mapping,
+};
+use crate::core::pipeline::load_pipelines;
+use crate::core::plan::{self, Operation, Plan};
+use crate::core::preflight::{BusyTargetAction, LowSpaceAction, SyncEstimate};
+use crate::core::progress::{ProgressRenderer, SyncProgress};
+use crate::core::redact;
+use crate::core::retry::RetryPolicy;
+use crate::core::sampling::{self, Sampling};
+use crate::core::sync::{
+    apply_tenant_rename, estimate_sync, find_self_sync, get_databases, match_databases,
+    match_tenant_databases, parse_environment, perform_sync, perform_sync_batch,
+    preview_target_overlap, resolve_target_db_template, CollectionStatus, SyncConfig, SyncOptions,
+    SyncReport, TargetOverlap, TransferSize,
+};
+use crate::core::variables::{self, Variables};
+use crate::core::verify::{ChecksumCheck, CountCheck, CountMismatchAction, VerifyMode};
+use crate::core::warnings;
+use crate::utils::docker::MongoContainer;
+use crate::utils::progress::is_quiet;
+use crate::utils::table::Table;
+use crate::utils::{docker, mongodb, pattern, theme, units};
 
-/// Parameters for synchronization operations
+/// Environment name of the container started for a `docker:<image>` target
+const DOCKER_ENVIRONMENT: &str = "DOCKER";
+
+/// How long a `docker:<image>` target may take to accept connections
+const CONTAINER_STARTUP: Duration = Duration::from_secs(60);
+
+/// Parameters for synchronization operations.
+///
+/// Serialized into the plan files of `sync --request`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SyncParams {
     pub from: Option<String>,
     pub to: Option<String>,
+    /// Leave the container of a `docker:<image>` target running
+    #[serde(skip)]
+    pub keep_container: bool,
     pub db: Option<String>,
+    /// Database names or glob patterns from a repeated `--db`, synced in one run
+    pub databases: Vec<String>,
     pub target_db: Option<String>,
     pub backup: Option<bool>,
     pub drop: Option<bool>,
     pub clear: Option<bool>,
+    #[serde(skip)]
     pub interactive: bool,
+    #[serde(skip)]
     pub dry_run: bool,
+    /// Answer confirmation prompts with yes (`--yes`)
+    #[serde(skip)]
+    pub yes: bool,
+    #[serde(skip)]
+    pub output: OutputFormat,
+    pub tenant_prefix: Option<String>,
+    pub map_file: Option<PathBuf>,
+    pub post_sync_script: Option<PathBuf>,
+    pub preset: Option<String>,
+    pub pipelines: Option<PathBuf>,
+    pub collection_map: Option<PathBuf>,
+    /// Collection renames from a repeated `--ns-map`, e.g. `app.users=app.customers`
+    pub ns_map: Vec<String>,
+    /// Fields from a repeated `--drop-field`, e.g. `users:password_hash`
+    pub drop_fields: Vec<String>,
+    pub verify_sample: Option<u64>,
+    pub verify_counts: Option<CountMismatchAction>,
+    pub verify: Option<VerifyMode>,
+    pub wait_for_target: Option<Duration>,
+    pub max_duration: Option<Duration>,
+    pub timeout: Option<Duration>,
+    pub retries: Option<u32>,
+    pub retry_backoff: Option<Duration>,
+    pub restore_on_timeout: bool,
+    pub no_auto_restore: bool,
+    pub backup_source: bool,
+    pub max_source_size: Option<u64>,
+    pub allow_empty_source: bool,
+    pub schema_only: bool,
+    pub data_only: bool,
+    pub prefer: Option<Prefer>,
+    pub updated_field: Option<String>,
+    pub on_busy_target: Option<BusyTargetAction>,
+    pub on_low_space: Option<LowSpaceAction>,
+    pub delta: bool,
+    pub delta_from: Option<PathBuf>,
+    pub collections: Vec<String>,
+    pub exclude_collections: Vec<String>,
+    pub skip_larger_than: Option<u64>,
+    pub keep_newest: Option<u64>,
+    pub newest_by: Option<String>,
+    /// Document limits from a repeated `--limit`, e.g. `events=1000`
+    pub limits: Vec<String>,
+    pub sampling: Option<Sampling>,
+    pub gzip: bool,
+    pub keep_dump: Option<PathBuf>,
+    pub temp_dir: Option<PathBuf>,
+    pub engine: Option<EngineKind>,
+    pub parallel_collections: Option<u64>,
+    pub insertion_workers: Option<u64>,
+    pub read_preference: Option<ReadPreference>,
+    pub oplog: bool,
+    pub resume: bool,
+    pub incremental_field: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Write a signed plan file for `approve` instead of syncing
+    #[serde(skip)]
+    pub request: Option<PathBuf>,
+    /// Glob patterns of databases to leave out of interactive database lists
+    #[serde(skip)]
+    pub hide_db: Vec<String>,
+    /// Values for `{name}` variables in the preset and pipelines file
+    pub vars: Variables,
+}
+
+impl SyncParams {
+    /// Files the sync reads its settings or scripts from, which a signed
+    /// plan pins by their contents
+    pub fn referenced_files(&self) -> Vec<&Path> {
+        [
+            &self.map_file,
+            &self.post_sync_script,
+            &self.pipelines,
+            &self.collection_map,
+            &self.delta_from,
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::as_path)
+        .collect()
+    }
 }
 
 /// Execute sync with individual parameters (deprecated, use execute_with_params instead)
@@ -39,7 +170,7 @@ pub async fn execute(
         drop,
         clear,
         interactive,
-        dry_run: false,
+        ..Default::default()
     };
 
     execute_with_params(params).await
@@ -47,13 +178,170 @@ pub async fn execute(
 
 /// Execute sync with SyncParams struct
 pub async fn execute_with_params(params: SyncParams) -> Result<()> {
+    let params = single_database(with_preset(params)?);
+
+    if let Some(image) = params.to.as_deref().and_then(docker::target_image) {
+        let image = image.to_string();
+        return execute_docker_sync(params, &image).await;
+    }
+    if params.keep_container {
+        return Err(anyhow!("--keep-container needs a docker:<image> target"));
+    }
+
+    if let Some(plan_path) = params.request.clone() {
+        return request_sync(params, &plan_path).await;
+    }
+
+    dispatch(&params).await
+}
+
+/// Sync into a throwaway MongoDB container started from an image, removing
+/// it afterwards unless `--keep-container` is given
+async fn execute_docker_sync(params: SyncParams, image: &str) -> Result<()> {
+    if params.dry_run || params.request.is_some() {
+        return Err(anyhow!(
+            "A docker target is only started to sync; it can't be combined with --dry-run or --request"
+        ));
+    }
+
+    let name = format!("arcula-{}", crate::utils::run_id::get());
+    if !is_quiet() {
+        println!("{} {} container {}", "Starting:".green(), image, name);
+    }
+    let container = MongoContainer::start(image, &name, "target").await?;
+    let target_env = Environment::new(DOCKER_ENVIRONMENT);
+    crate::config::register_environment(&target_env, &container.uri);
+    if !is_quiet() {
+        println!("{} {}", "Target:".green(), container.uri);
+    }
+
+    let params = SyncParams {
+        to: Some(target_env.name().to_string()),
+        // A new container holds nothing worth backing up
+        backup: Some(false),
+        // mongod needs a few seconds to accept connections
+        wait_for_target: params.wait_for_target.or(Some(CONTAINER_STARTUP)),
+        ..params
+    };
+    let result = dispatch(&params).await;
+
+    if params.keep_container {
+        let note = format!(
+            "{} {} is still running at {}; remove it with 'docker rm -f {}'",
+            "Container:".green(),
+            container.name,
+            container.uri,
+            container.name
+        );
+        // Keep stdout a single JSON document
+        match params.output {
+            OutputFormat::Text => println!("{}", note),
+            OutputFormat::Json => eprintln!("{}", note),
+        }
+    } else {
+        container.remove().await;
+    }
+    result
+}
+
+/// Fill parameters from the preset they name, if any
+fn with_preset(params: SyncParams) -> Result<SyncParams> {
+    Ok(match params.preset.clone() {
+        Some(name) => {
+            let preset = crate::config::get_preset(&name)?;
+            let preset = variables::apply_to_preset(&name, preset, &params.vars)?;
+            apply_preset(params, preset)
+        }
+        None => params,
+    })
+}
+
+/// Resolve the parameters of a sync of one database between two environments
+/// into its config without prompting, e.g. for `arcula rpc`
+pub async fn resolve_single_sync(params: SyncParams) -> Result<SyncConfig> {
+    let params = single_database(with_preset(params)?);
+    let docker_target = params
+        .to
+        .as_deref()
+        .is_some_and(|to| docker::target_image(to).is_some());
+    if params.tenant_prefix.is_some()
+        || params.map_file.is_some()
+        || !params.databases.is_empty()
+        || is_group_target(&params)
+        || docker_target
+    {
+        return Err(anyhow!(
+            "Only syncs of a single database between two environments are supported"
+        ));
+    }
+
+    let source_env = parse_environment(params.from.as_deref().context("from is required")?)?;
+    let target_env = parse_environment(params.to.as_deref().context("to is required")?)?;
+    resolve_sync_config(&params, source_env, target_env).await
+}
+
+/// Run the sync mode selected by the parameters
+async fn dispatch(params: &SyncParams) -> Result<()> {
+    if let Some(prefix) = &params.tenant_prefix {
+        return execute_tenant_sync(params, prefix).await;
+    }
+
+    if let Some(map_path) = &params.map_file {
+        return execute_mapped_sync(params, map_path).await;
+    }
+
+    if !params.databases.is_empty() {
+        return execute_multi_db_sync(params).await;
+    }
+
+    if is_group_target(params) {
+        return execute_group_sync(params).await;
+    }
+
     if params.interactive {
-        execute_interactive(&params).await
+        execute_interactive(params).await
     } else {
-        execute_non_interactive(&params).await
+        execute_non_interactive(params).await
     }
 }
 
+/// Preview a sync like `--dry-run` and write it to a signed plan file that
+/// `approve` executes later
+async fn request_sync(params: SyncParams, plan_path: &Path) -> Result<()> {
+    let key = approval::approval_key()?;
+
+    // The preset is already applied, so later changes to it don't alter the plan
+    let params = SyncParams {
+        dry_run: true,
+        interactive: false,
+        request: None,
+        preset: None,
+        ..params
+    };
+    dispatch(&params).await?;
+
+    let plan = approval::sign_plan(
+        serde_json::to_value(&params)?,
+        approval::hash_files(&params.referenced_files())?,
+        &approval::current_user(),
+        chrono::Utc::now(),
+        &key,
+    );
+    approval::write_plan(plan_path, &plan)?;
+
+    println!(
+        "\n{} {}",
+        "Sync plan written to".green().bold(),
+        plan_path.display()
+    );
+    println!(
+        "It runs once approved with: arcula approve {}",
+        plan_path.display()
+    );
+
+    Ok(())
+}
+
 async fn execute_interactive(params: &SyncParams) -> Result<()> {
     // Clean, streamlined UI - no introductory messages
 
@@ -72,7 +360,14 @@ async fn execute_interactive(params: &SyncParams) -> Result<()> {
     };
 
     // Step 2: Select source database with autocomplete
-    let source_dbs = get_databases(&source_env).await?;
+    let mut source_dbs = get_databases_cached(&source_env).await?;
+    // A cached list can predate the database, so check the cluster itself
+    // before rejecting it
+    if let Some(db_str) = &params.db {
+        if !source_dbs.contains(db_str) {
+            source_dbs = cache::refresh(&source_env).await?;
+        }
+    }
     if source_dbs.is_empty() {
         return Err(anyhow!("No databases found in source environment"));
     }
@@ -87,10 +382,13 @@ async fn execute_interactive(params: &SyncParams) -> Result<()> {
         db_str
     } else {
         // Use Select with autocomplete for source database selection
-        Select::new("2. Select source database:", source_dbs)
-            .with_page_size(10) // Show 10 items at a time
-            .with_help_message("Type to filter databases")
-            .prompt()?
+        Select::new(
+            "2. Select source database:",
+            visible_databases(source_dbs, params)?,
+        )
+        .with_page_size(10) // Show 10 items at a time
+        .with_help_message("Type to filter databases")
+        .prompt()?
     };
 
     // Step 3: Select target environment
@@ -113,10 +411,7 @@ async fn execute_interactive(params: &SyncParams) -> Result<()> {
             "Warning:".yellow().bold(),
             source_env
         );
-        let proceed = Confirm::new("Are you sure you want to proceed?")
-            .with_default(false)
-            .prompt()?;
-        if !proceed {
+        if !confirm("Are you sure you want to proceed?", params.yes)? {
             println!("Operation cancelled.");
             return Ok(());
         }
@@ -124,13 +419,14 @@ async fn execute_interactive(params: &SyncParams) -> Result<()> {
 
     // Step 4: Select target database with autocomplete
     let target_db_name = if let Some(tgt_db) = &params.target_db {
-        tgt_db.clone()
+        resolve_target_db_template(tgt_db, &source_db, chrono::Utc::now())?
     } else {
         // Fetch available databases from target environment for autocomplete
-        let target_dbs = get_databases(&target_env).await?;
+        let target_dbs = get_databases_cached(&target_env).await?;
         if target_dbs.is_empty() {
             return Err(anyhow!("No databases found in target environment"));
         }
+        let target_dbs = visible_databases(target_dbs, params)?;
 
         // If source DB exists in target environment, use it as default selection
         let default_index = target_dbs.iter().position(|db| *db == source_db);
@@ -150,12 +446,32 @@ async fn execute_interactive(params: &SyncParams) -> Result<()> {
         select.prompt()?
     };
 
-    // Step 5: Configure sync settings
-    let mut options = SyncOptions {
-        create_backup: params.backup.unwrap_or(true),
-        drop_collections: params.drop.unwrap_or(true),
-        clear_collections: params.clear.unwrap_or(false),
-    };
+    let mut options = options_from_params(params)?;
+
+    // Step 5: Select collections, all of them unless --collections was given
+    if options.collections.is_empty() && !options.schema_only {
+        let source_config = MongoConfig::from_env(source_env.clone())?;
+        let mut collections = mongodb::list_collection_names(
+            &source_config,
+            &crate::config::scoped_database(&source_env, &source_db),
+        )
+        .await?;
+        collections.retain(|c| options.includes(c));
+        let all: Vec<usize> = (0..collections.len()).collect();
+        let selected = MultiSelect::new("5. Select collections:", collections.clone())
+            .with_default(&all)
+            .with_page_size(10)
+            .with_help_message("Space to toggle, type to filter, Enter to confirm")
+            .prompt()?;
+        if selected.is_empty() {
+            return Err(anyhow!("No collections selected"));
+        }
+        if selected.len() < collections.len() {
+            options.collections = selected;
+        }
+    }
+
+    // Step 6: Configure sync settings
 
     // Create option labels
     let option_labels = vec![
@@ -177,7 +493,7 @@ async fn execute_interactive(params: &SyncParams) -> Result<()> {
     }
 
     // Show MultiSelect for options
-    let selected_options = MultiSelect::new("5. Configure sync settings:", option_labels)
+    let selected_options = MultiSelect::new("6. Configure sync settings:", option_labels)
         .with_default(&defaults)
         .with_help_message("Space to toggle, Enter to confirm")
         .prompt()?;
@@ -191,100 +507,177 @@ async fn execute_interactive(params: &SyncParams) -> Result<()> {
     // Update settings for consistency
     options.update_collection_settings();
 
+    if options.create_backup {
+        options.auto_restore = Confirm::new(
+            "Restore the backup automatically if the import fails?",
+        )
+        .with_default(options.auto_restore)
+        .with_help_message(
+            "Choose no to inspect the target first and restore it later with 'arcula restore'",
+        )
+        .prompt()?;
+    }
+
     // Format operation pattern for confirmation
     let operation_pattern = format!(
-        "{}:{} → {}:{}  B:[{}] D:[{}] C:[{}]",
+        "{}:{} {} {}:{}  B:[{}] D:[{}] C:[{}]",
         source_env,
         source_db,
+        theme::arrow(),
         target_env,
         target_db_name,
         if options.create_backup {
-            "✓".green()
+            theme::check().green()
         } else {
-            "✗".yellow()
+            theme::cross().yellow()
         },
         if options.drop_collections {
-            "✓".green()
+            theme::check().green()
         } else {
-            "✗".yellow()
+            theme::cross().yellow()
         },
         if options.clear_collections {
-            "✓".green()
+            theme::check().green()
         } else {
-            "✗".yellow()
+            theme::cross().yellow()
         }
     );
 
-    // Step 6: Confirm and execute sync
-    let proceed = Confirm::new("6. Ready to proceed with synchronization?")
-        .with_default(true)
-        .with_help_message(&operation_pattern)
-        .prompt()?;
+    // Create sync config
+    let config = sync_config(source_env, target_env, &source_db, &target_db_name, options);
 
-    if !proceed {
-        return Ok(());
+    // The dry-run summary shows the overlap itself
+    if !params.dry_run {
+        if let Some(overlap) = target_overlap(&config).await {
+            let mut plan = Plan::new(Operation::Sync);
+            plan::add_target_overlap(&mut plan, &overlap, config.options.drop_collections);
+            print!("{}", plan.render_text());
+        }
     }
 
-    // Create sync config
-    let config = SyncConfig {
-        source_env,
-        target_env,
-        source_db,
-        target_db: target_db_name,
-        options,
-    };
+    // Step 7: Confirm and execute sync
+    if !params.yes {
+        let proceed = Confirm::new("7. Ready to proceed with synchronization?")
+            .with_default(true)
+            .with_help_message(&operation_pattern)
+            .prompt()?;
+
+        if !proceed {
+            return Ok(());
+        }
+    }
 
     if params.dry_run {
-        print_dry_run_summary(&config);
+        print_dry_run_summary(&config, params.output).await?;
         return Ok(());
     }
 
-    perform_sync(config).await
+    finish_sync(config, params.output).await
 }
 
-fn print_dry_run_summary(config: &SyncConfig) {
-    println!("\n{}", "=== DRY RUN MODE ===".yellow().bold());
-    println!("The following synchronization would be performed:\n");
-    println!(
-        "  {} {} → {}",
-        "Environments:".green(),
-        config.source_env,
-        config.target_env
-    );
-    println!(
-        "  {} {} → {}",
-        "Databases:".green(),
-        config.source_db,
-        config.target_db
-    );
-    println!(
-        "  {} {}",
-        "Create backup:".green(),
-        if config.options.create_backup {
-            "Yes"
-        } else {
-            "No"
+/// Print the documents and bytes a sync would copy per collection, and
+/// whether its dump fits in the temporary directory
+async fn print_estimate(config: &SyncConfig) {
+    let estimate = match estimate_sync(config).await {
+        Ok(estimate) => estimate,
+        Err(e) => {
+            println!(
+                "{} Could not estimate the size of the sync: {}",
+                "Warning:".yellow().bold(),
+                e
+            );
+            return;
         }
-    );
+    };
+
     println!(
-        "  {} {}",
-        "Drop collections:".green(),
-        if config.options.drop_collections {
-            "Yes"
-        } else {
-            "No"
-        }
+        "\n  {} {} documents, {} in {} collection(s)",
+        "Estimated data:".green(),
+        estimate.documents(),
+        units::format_bytes(estimate.size()),
+        estimate.collections.len()
     );
-    println!(
-        "  {} {}",
-        "Clear collections:".green(),
-        if config.options.clear_collections {
-            "Yes"
-        } else {
-            "No"
+    if !estimate.collections.is_empty() {
+        let mut table = Table::new(&["COLLECTION", "DOCUMENTS", "SIZE"])
+            .align_right(1)
+            .align_right(2);
+        for collection in &estimate.collections {
+            table.add_row(vec![
+                collection.name.clone(),
+                collection.documents.to_string(),
+                units::format_bytes(collection.size),
+            ]);
         }
+        table.print(4);
+    }
+    if estimate.dump_size == 0 {
+        return;
+    }
+    let compression = if config.options.gzip {
+        " before compression"
+    } else {
+        ""
+    };
+    let dump = format!(
+        "{}{} in {}",
+        units::format_bytes(estimate.dump_size),
+        compression,
+        estimate.temp_dir.display()
     );
+    match (estimate.fits_temp_dir(), estimate.temp_free) {
+        (Some(true), Some(free)) => println!(
+            "  {} {}, {} free {}",
+            "Dump size:".green(),
+            dump,
+            units::format_bytes(free),
+            theme::check().green()
+        ),
+        (Some(false), Some(free)) => println!(
+            "  {} {}, only {} free {}",
+            "Dump size:".green(),
+            dump,
+            units::format_bytes(free).red(),
+            theme::cross().red()
+        ),
+        _ => println!("  {} {}, free space unknown", "Dump size:".green(), dump),
+    }
+}
+
+/// The target collections an import with drop or clear replaces, and those
+/// only on the target; `None` for imports that merge, or when the target
+/// couldn't be listed
+async fn target_overlap(config: &SyncConfig) -> Option<TargetOverlap> {
+    let options = &config.options;
+    if !options.drop_collections && !options.clear_collections {
+        return None;
+    }
+    match preview_target_overlap(config).await {
+        Ok(overlap) => Some(overlap),
+        Err(e) => {
+            println!(
+                "{} Could not compare target collections: {}",
+                "Warning:".yellow().bold(),
+                e
+            );
+            None
+        }
+    }
+}
+
+async fn print_dry_run_summary(config: &SyncConfig, output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Json {
+        let estimates: Vec<SyncEstimate> = estimate_sync(config).await.into_iter().collect();
+        return print_json(true, std::slice::from_ref(config), &estimates, &[]);
+    }
+
+    let mut plan = plan::sync_plan(config);
+    if let Some(overlap) = target_overlap(config).await {
+        plan::add_target_overlap(&mut plan, &overlap, config.options.drop_collections);
+    }
+    plan.print_dry_run();
+    print_estimate(config).await;
     println!("\n{}", "No changes were made.".yellow());
+    Ok(())
 }
 
 async fn execute_non_interactive(params: &SyncParams) -> Result<()> {
@@ -298,14 +691,35 @@ async fn execute_non_interactive(params: &SyncParams) -> Result<()> {
         None => return Err(anyhow!("Target environment is required (--to)")),
     };
 
+    // Confirmed like in interactive mode, except for plans that change nothing
     if source_env == target_env {
         println!(
-            "{} Source and target are the same environment ({}). Proceeding anyway.",
+            "{} Source and target are the same environment ({})",
             "Warning:".yellow().bold(),
             source_env
         );
+        if !params.dry_run && !confirm("Are you sure you want to proceed?", params.yes)? {
+            println!("Operation cancelled.");
+            return Ok(());
+        }
     }
 
+    let config = resolve_sync_config(params, source_env, target_env).await?;
+
+    if params.dry_run {
+        print_dry_run_summary(&config, params.output).await?;
+        return Ok(());
+    }
+
+    finish_sync(config, params.output).await
+}
+
+/// Config of the sync of the `--db` database, which must exist in the source
+async fn resolve_sync_config(
+    params: &SyncParams,
+    source_env: Environment,
+    target_env: Environment,
+) -> Result<SyncConfig> {
     let source_db = match &params.db {
         Some(db_str) => db_str.clone(),
         None => return Err(anyhow!("Source database is required (--db)")),
@@ -321,30 +735,991 @@ async fn execute_non_interactive(params: &SyncParams) -> Result<()> {
         ));
     }
 
-    let target_db_name = params
-        .target_db
-        .clone()
-        .unwrap_or_else(|| source_db.clone());
+    let target_db_name = match &params.target_db {
+        Some(template) => resolve_target_db_template(template, &source_db, chrono::Utc::now())?,
+        None => source_db.clone(),
+    };
+
+    let options = options_from_params(params)?;
 
+    Ok(sync_config(
+        source_env,
+        target_env,
+        &source_db,
+        &target_db_name,
+        options,
+    ))
+}
+
+/// Fill parameters not given on the command line from a preset
+fn apply_preset(params: SyncParams, preset: Preset) -> SyncParams {
+    SyncParams {
+        from: params.from.or(preset.from),
+        to: params.to.or(preset.to),
+        db: params.db.or(preset.db),
+        target_db: params.target_db.or(preset.target_db),
+        backup: params.backup.or(preset.backup),
+        drop: params.drop.or(preset.drop),
+        clear: params.clear.or(preset.clear),
+        post_sync_script: params.post_sync_script.or(preset.post_sync_script),
+        pipelines: params.pipelines.or(preset.pipelines),
+        collection_map: params.collection_map.or(preset.collection_map),
+        verify_sample: params.verify_sample.or(preset.verify_sample),
+        ..params
+    }
+}
+
+/// Databases to offer in a Select list, without those hidden by `--hide-db`
+/// or `hide_databases` in the config file
+fn visible_databases(databases: Vec<String>, params: &SyncParams) -> Result<Vec<String>> {
+    let mut patterns = crate::config::file::get().ui.hide_databases.clone();
+    patterns.extend(params.hide_db.iter().cloned());
+
+    let visible = pattern::exclude_matching(databases, &patterns);
+    if visible.is_empty() {
+        return Err(anyhow!(
+            "All databases are hidden by the patterns {}; pass --db to pick one anyway",
+            patterns.join(", ")
+        ));
+    }
+    Ok(visible)
+}
+
+/// Build sync options from command-line flags, falling back to configured defaults
+fn options_from_params(params: &SyncParams) -> Result<SyncOptions> {
+    let defaults = SyncOptions::default();
     let mut options = SyncOptions {
-        create_backup: params.backup.unwrap_or(true),
-        drop_collections: params.drop.unwrap_or(true),
-        clear_collections: params.clear.unwrap_or(false),
+        create_backup: params.backup.unwrap_or(defaults.create_backup),
+        drop_collections: params.drop.unwrap_or(defaults.drop_collections),
+        clear_collections: params.clear.unwrap_or(defaults.clear_collections),
+        post_sync_script: params.post_sync_script.clone(),
+        pipelines: match &params.pipelines {
+            Some(path) => load_pipelines(path, &params.vars)?,
+            None => defaults.pipelines,
+        },
+        collection_map: match &params.collection_map {
+            Some(path) => load_collection_mapping(path)?,
+            None => defaults.collection_map,
+        },
+        drop_fields: defaults.drop_fields,
+        transforms: defaults.transforms,
+        verify_sample: params.verify_sample.or(defaults.verify_sample),
+        verify_counts: params.verify_counts.or(defaults.verify_counts),
+        verify: params.verify,
+        wait_for_target: params.wait_for_target,
+        max_duration: params.max_duration.or(defaults.max_duration),
+        export_timeout: params.timeout.or(defaults.export_timeout),
+        import_timeout: params.timeout.or(defaults.import_timeout),
+        retry: RetryPolicy {
+            retries: params.retries.unwrap_or(defaults.retry.retries),
+            backoff: params.retry_backoff.unwrap_or(defaults.retry.backoff),
+        },
+        restore_on_timeout: params.restore_on_timeout || defaults.restore_on_timeout,
+        auto_restore: defaults.auto_restore && !params.no_auto_restore,
+        backup_source: params.backup_source || defaults.backup_source,
+        max_source_size: params.max_source_size.or(defaults.max_source_size),
+        allow_empty_source: params.allow_empty_source || defaults.allow_empty_source,
+        schema_only: params.schema_only || defaults.schema_only,
+        data_only: params.data_only || defaults.data_only,
+        prefer: params.prefer.or(defaults.prefer),
+        updated_field: params
+            .updated_field
+            .clone()
+            .unwrap_or(defaults.updated_field),
+        on_busy_target: params.on_busy_target.unwrap_or(defaults.on_busy_target),
+        busy_target_timeout: defaults.busy_target_timeout,
+        on_low_space: params.on_low_space.unwrap_or(defaults.on_low_space),
+        delta: params.delta || params.delta_from.is_some(),
+        delta_from: params.delta_from.clone(),
+        collections: params.collections.clone(),
+        exclude_collections: params.exclude_collections.clone(),
+        skip_larger_than: params.skip_larger_than,
+        keep_newest: params.keep_newest,
+        newest_by: params.newest_by.clone(),
+        document_limits: defaults.document_limits,
+        sampling: params.sampling.unwrap_or(defaults.sampling),
+        gzip: params.gzip || defaults.gzip,
+        keep_dump: params.keep_dump.clone(),
+        temp_dir: params.temp_dir.clone(),
+        engine: params.engine.unwrap_or(defaults.engine),
+        parallel_collections: params
+            .parallel_collections
+            .or(defaults.parallel_collections),
+        insertion_workers: params.insertion_workers.or(defaults.insertion_workers),
+        read_preference: params.read_preference.or(defaults.read_preference),
+        consistent_snapshot: params.oplog,
+        resume: params.resume,
+        incremental_field: None,
+        since: params.since,
     };
+    for value in &params.ns_map {
+        let mapping =
+            parse_namespace_mapping(value).context(format!("Invalid --ns-map '{}'", value))?;
+        // Renames on the command line replace those of the mapping file
+        options.collection_map.retain(|m| {
+            m.source_collection != mapping.source_collection
+                || (m.source_db.is_some() && m.source_db != mapping.source_db)
+        });
+        options.collection_map.push(mapping);
+    }
+    for value in &params.drop_fields {
+        let (collection, field) =
+            redact::parse_drop_field(value).context(format!("Invalid --drop-field '{}'", value))?;
+        options
+            .drop_fields
+            .entry(collection)
+            .or_default()
+            .push(field);
+    }
+    for value in &params.limits {
+        let (collection, count) =
+            sampling::parse_limit(value).context(format!("Invalid --limit '{}'", value))?;
+        options.document_limits.insert(collection, count);
+    }
+    for field in options.drop_fields.values().flatten() {
+        redact::validate_field(field).context("Invalid drop_fields in the config file")?;
+    }
+    for rule in options.transforms.values().flatten() {
+        rule.validate()
+            .context(format!("Invalid transform '{}' in the config file", rule))?;
+    }
+    if options.keep_dump.is_some() && options.engine != EngineKind::Tools {
+        return Err(anyhow!("--keep-dump needs --engine tools"));
+    }
+    if options.temp_dir.is_some() && options.engine != EngineKind::Tools {
+        return Err(anyhow!("--temp-dir needs --engine tools"));
+    }
+    if options.engine == EngineKind::Stream
+        && (!options.pipelines.is_empty() || options.keep_newest.is_some())
+    {
+        return Err(anyhow!(
+            "Pipelines can't be applied while streaming; use --engine tools"
+        ));
+    }
+    if options.engine == EngineKind::Stream && !options.drop_fields.is_empty() {
+        return Err(anyhow!(
+            "Fields can't be dropped while streaming; use --engine tools"
+        ));
+    }
+    if options.engine == EngineKind::Stream && !options.document_limits.is_empty() {
+        return Err(anyhow!(
+            "Collections can't be limited while streaming; use --engine tools"
+        ));
+    }
+    if options.engine == EngineKind::Stream && !options.transforms.is_empty() {
+        return Err(anyhow!(
+            "Transform rules can't be applied while streaming; use --engine driver or tools"
+        ));
+    }
+    // Target documents the import doesn't replace would change the hashes
+    if options.verify == Some(VerifyMode::Checksum)
+        && !options.drop_collections
+        && !options.clear_collections
+    {
+        return Err(anyhow!("--verify checksum needs --drop or --clear"));
+    }
+    if options.consistent_snapshot {
+        if options.engine != EngineKind::Tools {
+            return Err(anyhow!("--oplog needs --engine tools"));
+        }
+        // The replayed oplog would undo transformations and can't be narrowed
+        // to some collections of the dump
+        if !options.pipelines.is_empty()
+            || !options.drop_fields.is_empty()
+            || !options.transforms.is_empty()
+            || !options.document_limits.is_empty()
+            || !options.collection_map.is_empty()
+            || !options.collections.is_empty()
+            || !options.exclude_collections.is_empty()
+            || options.skip_larger_than.is_some()
+            || options.delta
+            || options.schema_only
+            || options.resume
+        {
+            return Err(anyhow!(
+                "--oplog copies whole databases and can't be combined with pipelines, \
+                 dropped fields, transform rules, --limit, collection mappings, collection selection, --delta, --schema-only or --resume"
+            ));
+        }
+    }
+    if params.since.is_some() || params.incremental_field.is_some() {
+        // Upserting selected documents needs the driver; the tools would
+        // drop or skip existing documents
+        if params
+            .engine
+            .is_some_and(|engine| engine != EngineKind::Driver)
+        {
+            return Err(anyhow!(
+                "--since and --incremental-field copy through --engine driver"
+            ));
+        }
+        if params.drop == Some(true) || params.clear == Some(true) {
+            return Err(anyhow!(
+                "--since and --incremental-field upsert into the target and can't drop or clear it"
+            ));
+        }
+        if options.consistent_snapshot || options.schema_only {
+            return Err(anyhow!(
+                "--since and --incremental-field can't be combined with --oplog or --schema-only"
+            ));
+        }
+        options.engine = EngineKind::Driver;
+        options.drop_collections = false;
+        options.clear_collections = false;
+        options.incremental_field = Some(
+            params
+                .incremental_field
+                .clone()
+                .unwrap_or_else(|| options.updated_field.clone()),
+        );
+    }
+    if params.timeout.is_some() && options.engine == EngineKind::Driver {
+        return Err(anyhow!(
+            "--timeout applies to mongodump and mongorestore; use --engine tools or stream"
+        ));
+    }
     options.update_collection_settings();
+    Ok(options)
+}
 
-    let config = SyncConfig {
+/// Sync of databases named as listed by `get_databases`, i.e. without the
+/// `db_prefix` of their environment
+fn sync_config(
+    source_env: Environment,
+    target_env: Environment,
+    source_db: &str,
+    target_db: &str,
+    mut options: SyncOptions,
+) -> SyncConfig {
+    // Renames limited to another source database don't apply to this sync
+    options
+        .collection_map
+        .retain(|m| m.source_db.as_deref().is_none_or(|db| db == source_db));
+    SyncConfig {
+        source_db: crate::config::scoped_database(&source_env, source_db),
+        target_db: crate::config::scoped_database(&target_env, target_db),
         source_env,
         target_env,
-        source_db,
-        target_db: target_db_name,
         options,
+    }
+}
+
+/// Resolve an environment from a flag value, prompting for it in interactive mode
+fn resolve_environment(
+    value: Option<&String>,
+    params: &SyncParams,
+    prompt: &str,
+    flag: &str,
+) -> Result<Environment> {
+    if let Some(env_str) = value {
+        return parse_environment(env_str);
+    }
+
+    if !params.interactive {
+        return Err(anyhow!("{} is required ({})", prompt, flag));
+    }
+
+    let env_options = crate::config::get_available_environments();
+    if env_options.is_empty() {
+        return Err(anyhow!("No MongoDB environments configured. Use 'info' command to see how to configure environments."));
+    }
+
+    Ok(Select::new(&format!("Select {}:", prompt.to_lowercase()), env_options).prompt()?)
+}
+
+fn is_group_target(params: &SyncParams) -> bool {
+    params.to.as_deref().is_some_and(|to| to.starts_with('@'))
+}
+
+/// Resolve the target environments, expanding `--to @group` into its members.
+///
+/// Group members equal to the source environment are skipped.
+fn resolve_target_environments(
+    params: &SyncParams,
+    source_env: &Environment,
+) -> Result<Vec<Environment>> {
+    if !is_group_target(params) {
+        let target_env =
+            resolve_environment(params.to.as_ref(), params, "Target environment", "--to")?;
+        return Ok(vec![target_env]);
+    }
+
+    let group = params.to.as_deref().unwrap_or_default();
+    let mut members = crate::config::get_environment_group(group)?;
+    if members.contains(source_env) {
+        println!(
+            "{} Skipping {} in {}: it is the source environment",
+            "Note:".yellow().bold(),
+            source_env,
+            group
+        );
+        members.retain(|env| env != source_env);
+    }
+
+    if members.is_empty() {
+        return Err(anyhow!(
+            "Group {} has no members other than the source",
+            group
+        ));
+    }
+
+    Ok(members)
+}
+
+/// Sync a single database to every member of an environment group (`--to @group`)
+async fn execute_group_sync(params: &SyncParams) -> Result<()> {
+    let source_env =
+        resolve_environment(params.from.as_ref(), params, "Source environment", "--from")?;
+    let target_envs = resolve_target_environments(params, &source_env)?;
+
+    let source_dbs = get_databases(&source_env).await?;
+    let source_db = match &params.db {
+        Some(db) => db.clone(),
+        None if params.interactive => Select::new(
+            "Select source database:",
+            visible_databases(source_dbs.clone(), params)?,
+        )
+        .with_page_size(10)
+        .with_help_message("Type to filter databases")
+        .prompt()?,
+        None => return Err(anyhow!("Source database is required (--db)")),
+    };
+    if !source_dbs.contains(&source_db) {
+        return Err(anyhow!(
+            "Database '{}' not found in '{}'. Available: {}",
+            source_db,
+            source_env,
+            source_dbs.join(", ")
+        ));
+    }
+
+    let target_db = match &params.target_db {
+        Some(template) => resolve_target_db_template(template, &source_db, chrono::Utc::now())?,
+        None => source_db.clone(),
     };
 
+    let options = options_from_params(params)?;
+    let configs = target_envs
+        .into_iter()
+        .map(|target_env| {
+            sync_config(
+                source_env.clone(),
+                target_env,
+                &source_db,
+                &target_db,
+                options.clone(),
+            )
+        })
+        .collect();
+
+    run_batch(params, configs, "group").await
+}
+
+/// A single `--db` without wildcards is an ordinary single-database sync
+fn single_database(mut params: SyncParams) -> SyncParams {
+    if let [db] = params.databases.as_slice() {
+        if !db.contains(['*', '?']) {
+            params.db = params.databases.pop();
+        }
+    }
+    params
+}
+
+/// Sync every database named or matched by a repeated `--db`
+async fn execute_multi_db_sync(params: &SyncParams) -> Result<()> {
+    let source_env =
+        resolve_environment(params.from.as_ref(), params, "Source environment", "--from")?;
+    let target_envs = resolve_target_environments(params, &source_env)?;
+
+    let source_dbs = get_databases(&source_env).await?;
+    let databases = match_databases(&source_dbs, &params.databases)
+        .map_err(|e| anyhow!("{} in '{}'", e, source_env))?;
+
+    if let Some(template) = &params.target_db {
+        if databases.len() > 1 && !template.contains("{source_db}") {
+            return Err(anyhow!(
+                "--target-db must contain {{source_db}} when syncing several databases"
+            ));
+        }
+    }
+
+    let options = options_from_params(params)?;
+    let now = chrono::Utc::now();
+    let mut configs = Vec::new();
+    for source_db in databases {
+        let target_db = match &params.target_db {
+            Some(template) => resolve_target_db_template(template, &source_db, now)?,
+            None => source_db.clone(),
+        };
+        for target_env in &target_envs {
+            configs.push(sync_config(
+                source_env.clone(),
+                target_env.clone(),
+                &source_db,
+                &target_db,
+                options.clone(),
+            ));
+        }
+    }
+
+    run_batch(params, configs, "database").await
+}
+
+/// Sync every database matching a tenant prefix, renaming each via `--target-db`
+async fn execute_tenant_sync(params: &SyncParams, prefix: &str) -> Result<()> {
+    let source_env =
+        resolve_environment(params.from.as_ref(), params, "Source environment", "--from")?;
+    let target_envs = resolve_target_environments(params, &source_env)?;
+
+    let source_dbs = get_databases(&source_env).await?;
+    let tenants = match_tenant_databases(&source_dbs, prefix);
+    if tenants.is_empty() {
+        return Err(anyhow!(
+            "No databases matching prefix '{}' found in '{}'",
+            prefix,
+            source_env
+        ));
+    }
+
+    let options = options_from_params(params)?;
+    let now = chrono::Utc::now();
+    let mut configs = Vec::new();
+    for (source_db, tenant_id) in tenants {
+        let target_db = match params.target_db.as_deref() {
+            Some(rule) => {
+                resolve_target_db_template(&apply_tenant_rename(rule, &tenant_id), &source_db, now)?
+            }
+            None => source_db.clone(),
+        };
+
+        if source_db == target_db && target_envs.contains(&source_env) {
+            return Err(anyhow!(
+                "Tenant rename rule maps databases onto themselves in '{}'",
+                source_env
+            ));
+        }
+
+        for target_env in &target_envs {
+            configs.push(sync_config(
+                source_env.clone(),
+                target_env.clone(),
+                &source_db,
+                &target_db,
+                options.clone(),
+            ));
+        }
+    }
+
+    run_batch(params, configs, "tenant").await
+}
+
+/// Sync every source → target pair listed in a mapping file
+async fn execute_mapped_sync(params: &SyncParams, map_path: &Path) -> Result<()> {
+    let mappings = load_database_mapping(map_path)?;
+
+    let source_env =
+        resolve_environment(params.from.as_ref(), params, "Source environment", "--from")?;
+    let target_envs = resolve_target_environments(params, &source_env)?;
+
+    let source_dbs = get_databases(&source_env).await?;
+    let missing: Vec<&str> = mappings
+        .iter()
+        .map(|m| m.source_db.as_str())
+        .filter(|db| !source_dbs.iter().any(|s| s == db))
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "Databases not found in '{}': {}",
+            source_env,
+            missing.join(", ")
+        ));
+    }
+
+    let options = options_from_params(params)?;
+    let now = chrono::Utc::now();
+    let mut configs = Vec::new();
+    for mapping in mappings {
+        let target_db = resolve_target_db_template(&mapping.target_db, &mapping.source_db, now)?;
+        for target_env in &target_envs {
+            configs.push(sync_config(
+                source_env.clone(),
+                target_env.clone(),
+                &mapping.source_db,
+                &target_db,
+                options.clone(),
+            ));
+        }
+    }
+
+    if let Some(config) = find_self_sync(&configs) {
+        return Err(anyhow!(
+            "Mapping file maps {} onto itself in '{}'",
+            config.source_db,
+            config.source_env
+        ));
+    }
+
+    run_batch(params, configs, "mapped").await
+}
+
+/// Show, confirm and execute a batch of synchronizations, failing if any of them failed
+async fn run_batch(params: &SyncParams, configs: Vec<SyncConfig>, kind: &str) -> Result<()> {
+    if params.dry_run && params.output == OutputFormat::Json {
+        return print_json(true, &configs, &[], &[]);
+    }
     if params.dry_run {
-        print_dry_run_summary(&config);
+        plan::batch_plan(&configs).print_dry_run();
+        println!("\n{}", "No changes were made.".yellow());
+        return Ok(());
+    }
+
+    if params.interactive
+        && !confirm(
+            &format!("Synchronize {} {} databases?", configs.len(), kind),
+            params.yes,
+        )?
+    {
+        println!("Operation cancelled.");
         return Ok(());
     }
 
-    perform_sync(config).await
+    let total = configs.len();
+    let plan = configs.clone();
+    let outcomes = perform_sync_batch(configs).await?;
+    match params.output {
+        OutputFormat::Text => plan::print_batch_summary(&outcomes),
+        OutputFormat::Json => print_json(false, &plan, &[], &outcomes)?,
+    }
+    let failed = outcomes.iter().filter(|o| !o.is_success()).count();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} {} syncs failed", failed, total, kind));
+    }
+
+    Ok(())
+}
+
+/// Run a sync and print what it did, failing if the sync failed
+async fn finish_sync(config: SyncConfig, output: OutputFormat) -> Result<()> {
+    let plan = [config.clone()];
+    let report = perform_sync(config).await;
+    match output {
+        OutputFormat::Text => print_report(&report),
+        OutputFormat::Json => print_json(false, &plan, &[], std::slice::from_ref(&report))?,
+    }
+    report.into_result().map(|_| ())
+}
+
+/// Print the plan and the reports of a sync as the single JSON document of
+/// `--output json`. Estimates, when given, are added to the plan entries in
+/// the same order.
+fn print_json(
+    dry_run: bool,
+    plan: &[SyncConfig],
+    estimates: &[SyncEstimate],
+    results: &[SyncReport],
+) -> Result<()> {
+    let plan: Vec<serde_json::Value> = plan
+        .iter()
+        .enumerate()
+        .map(|(i, config)| {
+            let mut entry = plan::sync_plan(config).to_json();
+            if let Some(estimate) = estimates.get(i) {
+                entry["estimate"] = serde_json::json!(estimate);
+            }
+            entry
+        })
+        .collect();
+    let succeeded = results.iter().filter(|r| r.is_success()).count();
+    let json = serde_json::json!({
+        "run_id": crate::utils::run_id::get(),
+        "dry_run": dry_run,
+        "plan": plan,
+        "results": results,
+        "succeeded": succeeded,
+        "failed": results.len() - succeeded,
+    });
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
+/// Print the collections, phase durations and warnings of a sync report
+fn print_report(report: &SyncReport) {
+    let count = |status| {
+        report
+            .collections
+            .iter()
+            .filter(|c| c.status == status)
+            .count()
+    };
+    if !report.collections.is_empty() {
+        println!(
+            "\n{} {} copied, {} unchanged, {} excluded",
+            "Collections:".bold(),
+            count(CollectionStatus::Copied),
+            count(CollectionStatus::Unchanged),
+            count(CollectionStatus::Excluded)
+        );
+    }
+    if !report.phases.is_empty() {
+        let phases: Vec<String> = report
+            .phases
+            .iter()
+            .map(|p| format!("{} {}", p.phase, units::format_duration(p.duration)))
+            .collect();
+        println!("{} {}", "Timing:".bold(), phases.join(", "));
+    }
+    print_transfer(report);
+    print_count_checks(&report.count_checks);
+    print_checksum_checks(&report.checksum_checks);
+    warnings::print_summary(&report.warnings);
+}
+
+/// Print the bytes the copied collections took on disk and on the wire, and
+/// how well the dump compressed them
+fn print_transfer(report: &SyncReport) {
+    let measured: Vec<(u64, TransferSize)> = report
+        .collections
+        .iter()
+        .filter_map(|c| Some((c.size, c.transfer?)))
+        .collect();
+    if measured.is_empty() {
+        return;
+    }
+    let read: u64 = measured.iter().map(|(size, _)| size).sum();
+    let total = TransferSize {
+        on_disk: measured.iter().map(|(_, t)| t.on_disk).sum(),
+        sent: measured.iter().map(|(_, t)| t.sent).sum(),
+    };
+    println!(
+        "{} {} read, {} on disk ({:.1}x compression), {} sent",
+        "Transfer:".bold(),
+        units::format_bytes(read),
+        units::format_bytes(total.on_disk),
+        total.compression_ratio(),
+        units::format_bytes(total.sent)
+    );
+}
+
+/// Print how many collections have the same document count in the source and
+/// the target, and the counts of those that don't
+fn print_count_checks(checks: &[CountCheck]) {
+    if checks.is_empty() {
+        return;
+    }
+    let mismatched: Vec<&CountCheck> = checks.iter().filter(|c| !c.matches).collect();
+    println!(
+        "{} {} of {} collection(s) match",
+        "Document counts:".bold(),
+        checks.len() - mismatched.len(),
+        checks.len()
+    );
+    for check in mismatched {
+        println!(
+            "  {} {}: {} in source, {} in target",
+            theme::cross().red(),
+            check.collection,
+            check.source,
+            check.target
+        );
+    }
+}
+
+/// Print how many collections have the same `dbHash` in the source and the
+/// target, and the ones that don't
+fn print_checksum_checks(checks: &[ChecksumCheck]) {
+    if checks.is_empty() {
+        return;
+    }
+    let mismatched: Vec<&ChecksumCheck> = checks.iter().filter(|c| !c.matches).collect();
+    println!(
+        "{} {} of {} collection(s) match",
+        "Checksums:".bold(),
+        checks.len() - mismatched.len(),
+        checks.len()
+    );
+    for check in mismatched {
+        let reason = match check.target {
+            Some(_) => "content differs",
+            None => "missing in target",
+        };
+        println!(
+            "  {} {}: {}",
+            theme::cross().red(),
+            check.collection,
+            reason
+        );
+    }
+}
+
+/// Console output of sync progress
+pub struct ConsoleRenderer;
+
+/// Progress still shown with `--quiet`: failures, what they left behind, and
+/// the final result
+fn is_outcome(progress: &SyncProgress) -> bool {
+    matches!(
+        progress,
+        SyncProgress::ExportFailed { .. }
+            | SyncProgress::ImportFailed { .. }
+            | SyncProgress::SchemaCopyFailed { .. }
+            | SyncProgress::BackupRestored
+            | SyncProgress::BackupRestoreFailed { .. }
+            | SyncProgress::BackupRestoreSkipped { .. }
+            | SyncProgress::ResumeSaved { .. }
+            | SyncProgress::TimedOut { .. }
+            | SyncProgress::Completed
+    )
+}
+
+impl ProgressRenderer for ConsoleRenderer {
+    fn render(&self, database: &str, progress: &SyncProgress) {
+        if is_quiet() && !is_outcome(progress) {
+            return;
+        }
+        match progress {
+            SyncProgress::DatabaseStarted => println!("\nProcessing database: {}", database),
+            SyncProgress::PhaseStarted(_) | SyncProgress::PhaseFinished(_) => {}
+            SyncProgress::BackupCreated { path, role } => match role {
+                BackupRole::Source => {
+                    println!("{} {}", "Source backup created:".green(), path.display())
+                }
+                BackupRole::Target => {
+                    println!("{} {}", "Backup created:".green(), path.display())
+                }
+            },
+            SyncProgress::SchemaCopied { target_db, count } => println!(
+                "{} {} ({} collections and views)",
+                "Schema copied:".green(),
+                target_db,
+                count
+            ),
+            SyncProgress::Resumed { collections } => println!(
+                "{} {} collection(s) already copied: {}",
+                "Resuming:".green(),
+                collections.len(),
+                collections.join(", ")
+            ),
+            SyncProgress::HashesUnavailable => println!(
+                "{} content hashes are unavailable on the source, copying all collections",
+                "Delta:".yellow()
+            ),
+            SyncProgress::OversizedSkipped { collections, limit } => println!(
+                "{} {} ({} collection(s) larger than {})",
+                "Too large, skipped:".green(),
+                collections.join(", "),
+                collections.len(),
+                units::format_bytes(*limit)
+            ),
+            SyncProgress::OversizedTrimmed {
+                collections,
+                limit,
+                newest,
+            } => println!(
+                "{} {} (larger than {}, newest {} documents only)",
+                "Too large, trimmed:".green(),
+                collections.join(", "),
+                units::format_bytes(*limit),
+                newest
+            ),
+            SyncProgress::UnchangedSkipped { collections } => println!(
+                "{} {} ({} collection(s))",
+                "Unchanged, skipped:".green(),
+                collections.join(", "),
+                collections.len()
+            ),
+            SyncProgress::WaitingForTarget { error, max_wait } => println!(
+                "{} target doesn't accept connections yet ({}), retrying for up to {}",
+                "Waiting:".yellow().bold(),
+                error,
+                units::format_duration(*max_wait)
+            ),
+            SyncProgress::TargetAvailable { waited } => println!(
+                "{} target accepts connections after {}",
+                "Resuming:".green(),
+                units::format_duration(*waited)
+            ),
+            SyncProgress::WaitingForBusyTarget { reason, max_wait } => println!(
+                "{} target is busy ({}), waiting for up to {}",
+                "Waiting:".yellow().bold(),
+                reason,
+                units::format_duration(*max_wait)
+            ),
+            SyncProgress::BusyTargetIdle { waited } => println!(
+                "{} target is idle after {}",
+                "Resuming:".green(),
+                units::format_duration(*waited)
+            ),
+            SyncProgress::Retrying {
+                operation,
+                attempt,
+                attempts,
+                delay,
+                error,
+            } => println!(
+                "{} {} failed (attempt {} of {}), retrying in {}: {}",
+                "Retrying:".yellow().bold(),
+                operation,
+                attempt,
+                attempts,
+                units::format_duration(*delay),
+                error
+            ),
+            SyncProgress::IncrementalCutoff { field, since } => println!(
+                "{} documents with {} after {}",
+                "Incremental:".green(),
+                field,
+                since.to_rfc3339()
+            ),
+            SyncProgress::AlreadyInSync { target_db } => println!(
+                "\n{} {} already matches the source",
+                "Synchronization completed:".green().bold(),
+                target_db
+            ),
+            SyncProgress::Exported => println!("{} {}", "Export completed:".green(), database),
+            SyncProgress::ExportFailed { error } => {
+                println!("{} Export failed: {}", "Error:".red().bold(), error)
+            }
+            SyncProgress::PipelineApplied {
+                collection,
+                documents,
+                ..
+            } => println!(
+                "{} {} ({} documents)",
+                "Pipeline applied:".green(),
+                collection,
+                documents
+            ),
+            SyncProgress::FieldsDropped {
+                collection,
+                fields,
+                documents,
+                ..
+            } => println!(
+                "{} {} from {} ({} documents)",
+                "Fields dropped:".green(),
+                fields.join(", "),
+                collection,
+                documents
+            ),
+            SyncProgress::RulesApplied {
+                collection,
+                rules,
+                documents,
+                ..
+            } => println!(
+                "{} {} rules to {} ({} documents changed)",
+                "Transformed:".green(),
+                rules,
+                collection,
+                documents
+            ),
+            SyncProgress::DumpRenamed { from, to } => {
+                println!("{} {} -> {}", "Renamed export directory:".green(), from, to)
+            }
+            SyncProgress::DumpKept { path } => {
+                println!("{} {}", "Dump kept in:".green(), path.display())
+            }
+            SyncProgress::CollectionCopied {
+                collection,
+                documents,
+                ..
+            } => println!(
+                "{} {} ({} documents)",
+                "Copied:".green(),
+                collection,
+                documents
+            ),
+            SyncProgress::Imported { target_db } => {
+                println!("{} {}", "Import completed:".green(), target_db)
+            }
+            SyncProgress::ImportFailed { error } => {
+                println!("{} Import failed: {}", "Error:".red().bold(), error)
+            }
+            SyncProgress::SchemaCopyFailed { error } => {
+                println!("{} Schema copy failed: {}", "Error:".red().bold(), error)
+            }
+            SyncProgress::NewerTargetDocuments { collections } => {
+                for (collection, documents) in collections {
+                    println!("  {} ({} documents)", collection, documents);
+                }
+            }
+            SyncProgress::SourceVersionsApplied { documents } => println!(
+                "{} {} newer target documents",
+                "Overwritten with source:".green(),
+                documents
+            ),
+            SyncProgress::VerifyStarted { sample_size } => println!(
+                "\n{} {} documents per collection",
+                "Verifying samples:".bold(),
+                sample_size
+            ),
+            SyncProgress::CollectionVerified {
+                collection,
+                comparison,
+                ..
+            } => {
+                if comparison.is_match() {
+                    println!(
+                        "  {} {} ({} documents)",
+                        theme::check().green(),
+                        collection,
+                        comparison.checked
+                    );
+                } else {
+                    println!(
+                        "  {} {} ({} checked, {} missing, {} different)",
+                        theme::cross().red(),
+                        collection,
+                        comparison.checked,
+                        comparison.missing,
+                        comparison.mismatched
+                    );
+                }
+            }
+            SyncProgress::ScriptStarted { script } => println!(
+                "{} {}",
+                "Running post-sync script:".green(),
+                script.display()
+            ),
+            SyncProgress::ScriptOutput { output } => {
+                println!("{}", "Post-sync script output:".green());
+                for line in output.lines() {
+                    println!("  {}", line);
+                }
+            }
+            SyncProgress::BackupRestoreStarted { path } => {
+                println!("{} {}", "Restoring backup:".yellow(), path.display())
+            }
+            SyncProgress::BackupRestored => {
+                println!("{}", "Backup restored successfully".green())
+            }
+            SyncProgress::BackupRestoreFailed { error } => println!(
+                "{} Backup restoration failed: {}",
+                "Error:".red().bold(),
+                error
+            ),
+            SyncProgress::BackupRestoreSkipped { path, target_db } => println!(
+                "{} Backup not restored, the target is left as the import left it ({})\n  Restore it with: arcula restore --from-run {} --db {}",
+                "Note:".yellow().bold(),
+                path.display(),
+                crate::utils::run_id::get(),
+                target_db
+            ),
+            SyncProgress::ResumeSaved { path, collections } => println!(
+                "{} {} collection(s) were copied completely ({})\n  Continue with the same sync and --resume",
+                "Note:".yellow().bold(),
+                collections,
+                path.display()
+            ),
+            SyncProgress::TimedOut { max_duration } => println!(
+                "{} Maximum duration of {} exceeded, aborting sync of {}",
+                "Error:".red().bold(),
+                units::format_duration(*max_duration),
+                database
+            ),
+            SyncProgress::Completed => {
+                println!("\n{}", "Synchronization completed".green().bold())
+            }
+        }
+    }
 }
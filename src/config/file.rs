@@ -0,0 +1,420 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use super::{AuthMechanism, ConfigError, ReadPreference};
+
+const CONFIG_FILE_NAME: &str = "arcula.toml";
+
+static FILE_CONFIG: OnceLock<FileConfig> = OnceLock::new();
+static SOURCE: OnceLock<ConfigSource> = OnceLock::new();
+
+/// Where the loaded configuration came from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigSource {
+    /// The config file, if one was found
+    pub path: Option<PathBuf>,
+    /// The `--profile` applied to it
+    pub profile: Option<String>,
+}
+
+/// Settings loaded from the arcula config file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    /// Default values for sync options, used when a flag is not given
+    pub defaults: SyncDefaults,
+
+    /// Named groups of environments, usable as `--to @<group>`
+    pub groups: BTreeMap<String, Vec<String>>,
+
+    /// Named sync presets, selected with `sync --preset <name>`
+    pub presets: BTreeMap<String, Preset>,
+
+    /// Optional StatsD/Datadog reporting of sync runs
+    pub metrics: MetricsConfig,
+
+    /// Rendering of prompts and terminal output
+    pub ui: UiConfig,
+
+    /// MongoDB environments, as an alternative to `MONGO_<ENV>_URI` variables
+    pub environments: BTreeMap<String, EnvironmentConfig>,
+
+    /// Named environment sets, selected with `--profile <name>`
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Caching of database lists for interactive prompts
+    pub cache: CacheConfig,
+}
+
+/// Clients allowed to use `arcula rpc` and `arcula mcp`, read from
+/// [`crate::core::access::SERVER_CONFIG_PATH`] rather than the config file,
+/// which the clients starting a server can choose
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Client tokens by name; when any are set, servers refuse clients without one
+    pub tokens: BTreeMap<String, ServerToken>,
+}
+
+/// A client token from a `[tokens.<name>]` table of the server config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerToken {
+    pub role: crate::core::access::Role,
+    /// Hex SHA-256 of the token, e.g. from `printf %s "$TOKEN" | sha256sum`
+    pub sha256: String,
+}
+
+/// Cache settings from the `[cache]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Set to `false` to always fetch database lists from the cluster
+    pub enabled: Option<bool>,
+    /// How long a cached database list is shown without refreshing it first, e.g. `"10m"`
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub databases_ttl: Option<std::time::Duration>,
+}
+
+/// A named set of environments from a `[profiles.<name>]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Environments added to (or replacing) the top-level `[environments]`
+    pub environments: BTreeMap<String, EnvironmentConfig>,
+}
+
+/// A MongoDB environment from an `[environments.<NAME>]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvironmentConfig {
+    /// Connection string
+    pub uri: Option<String>,
+    /// Read the connection string from the OS keyring instead of the file
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub keyring: bool,
+    /// Mark an environment as production, e.g. to exclude it from two-way syncs
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub production: bool,
+    /// Members exports read from when this environment is the source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_preference: Option<ReadPreference>,
+    /// Database holding the credentials (`authSource`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_source: Option<String>,
+    /// How to authenticate (`authMechanism`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_mechanism: Option<AuthMechanism>,
+    /// PEM file with the client certificate and key, e.g. for MONGODB-X509
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_certificate_key_file: Option<PathBuf>,
+    /// Prefix of the databases that belong to us on a shared cluster, left
+    /// out of database names in listings and added to names given with `--db`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db_prefix: Option<String>,
+}
+
+impl EnvironmentConfig {
+    /// Authentication arguments for the MongoDB Database Tools (mongodump,
+    /// mongorestore, mongoexport)
+    pub fn tool_args(&self) -> Vec<String> {
+        self.auth_args("--ssl", "--sslPEMKeyFile")
+    }
+
+    /// Authentication arguments for mongosh
+    pub fn shell_args(&self) -> Vec<String> {
+        self.auth_args("--tls", "--tlsCertificateKeyFile")
+    }
+
+    fn auth_args(&self, tls_flag: &str, key_file_flag: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(source) = &self.auth_source {
+            args.extend(["--authenticationDatabase".to_string(), source.clone()]);
+        }
+        if let Some(mechanism) = self.auth_mechanism {
+            args.extend([
+                "--authenticationMechanism".to_string(),
+                mechanism.as_str().to_string(),
+            ]);
+        }
+        if let Some(path) = &self.tls_certificate_key_file {
+            args.extend([
+                tls_flag.to_string(),
+                key_file_flag.to_string(),
+                path.display().to_string(),
+            ]);
+        }
+        args
+    }
+}
+
+/// Default sync options from the `[defaults]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncDefaults {
+    pub backup: Option<bool>,
+    pub drop: Option<bool>,
+    pub clear: Option<bool>,
+    /// Largest source database to sync, e.g. `"50GB"`
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_source_size: Option<u64>,
+    /// What to do when the target has index builds or balancing in progress
+    pub on_busy_target: Option<crate::core::preflight::BusyTargetAction>,
+    /// Longest wait of `on_busy_target = "wait"` for an idle target, e.g. `"2h"`
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub busy_target_timeout: Option<std::time::Duration>,
+    /// What to do when the target likely lacks the disk space for an import
+    pub on_low_space: Option<crate::core::preflight::LowSpaceAction>,
+    /// Compare document counts after each sync, warning or failing on a mismatch
+    pub verify_counts: Option<crate::core::verify::CountMismatchAction>,
+    /// Collections verified at the same time after a sync or restore
+    pub verify_concurrency: Option<usize>,
+    /// Compress sync exports and backups with `--gzip`
+    pub gzip: Option<bool>,
+    /// Kill mongodump and mongorestore runs taking longer than this, e.g. `"30m"`
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Option<std::time::Duration>,
+    /// Timeout of mongodump runs, instead of `timeout`
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub export_timeout: Option<std::time::Duration>,
+    /// Timeout of mongorestore runs, instead of `timeout`
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub import_timeout: Option<std::time::Duration>,
+    /// Retries of exports and imports failing on network errors
+    pub retries: Option<u32>,
+    /// Wait before the first retry, doubled for every further one, e.g. `"10s"`
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub retry_backoff: Option<std::time::Duration>,
+    /// Fields removed from the synced documents by collection, `"*"` for every collection
+    pub drop_fields: crate::core::redact::DroppedFields,
+    /// Rules changing the synced documents by collection, `"*"` for every collection
+    pub transforms: crate::core::transform::CollectionTransforms,
+}
+
+/// Deserialize an optional duration given as a string such as `"10m"`
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<std::time::Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    crate::utils::units::parse_duration(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Deserialize an optional byte size given as a string such as `"50GB"`
+fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    crate::utils::units::parse_size(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// StatsD settings from the `[metrics]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// StatsD/DogStatsD agent address (`host:port`); metrics are disabled when unset
+    pub statsd: Option<String>,
+    /// Metric name prefix, `arcula` by default
+    pub prefix: Option<String>,
+    /// Also send Datadog events for sync start, finish and failure
+    pub datadog_events: bool,
+}
+
+/// Accessibility settings from the `[ui]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// Plain ASCII instead of symbols such as ✓, ✗ and →
+    pub ascii: bool,
+    /// Bright, bold prompt colors
+    pub high_contrast: bool,
+    /// Set to `false` to disable colors in prompts and output
+    pub color: Option<bool>,
+    /// Glob patterns of databases left out of interactive database lists
+    pub hide_databases: Vec<String>,
+}
+
+/// A named set of sync parameters from a `[presets.<name>]` table.
+///
+/// Values given on the command line take precedence over the preset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub db: Option<String>,
+    pub target_db: Option<String>,
+    pub backup: Option<bool>,
+    pub drop: Option<bool>,
+    pub clear: Option<bool>,
+    pub post_sync_script: Option<PathBuf>,
+    pub pipelines: Option<PathBuf>,
+    pub collection_map: Option<PathBuf>,
+    pub verify_sample: Option<u64>,
+}
+
+/// Locate the config file.
+///
+/// `ARCULA_CONFIG` takes precedence, followed by `arcula.toml` in the current
+/// directory and finally `$XDG_CONFIG_HOME/arcula/config.toml` (or `~/.config`).
+pub fn find_config_file() -> Option<PathBuf> {
+    if let Ok(path) = env::var("ARCULA_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let local = PathBuf::from(CONFIG_FILE_NAME);
+    if local.exists() {
+        return Some(local);
+    }
+
+    user_config_path().filter(|user| user.exists())
+}
+
+/// `$XDG_CONFIG_HOME/arcula/config.toml`, falling back to `~/.config`
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok()?;
+    Some(config_home.join("arcula").join("config.toml"))
+}
+
+/// Add `[environments.<NAME>]` tables to a parsed config document, leaving
+/// the rest of it, including comments and formatting, as it is.
+///
+/// Environments already present are left untouched unless `overwrite` is set.
+/// Returns the names of the environments that were written.
+pub fn add_environments(
+    document: &mut toml_edit::DocumentMut,
+    environments: &BTreeMap<String, EnvironmentConfig>,
+    overwrite: bool,
+) -> Result<Vec<String>, ConfigError> {
+    let invalid =
+        |name: &str, error: String| ConfigError::InvalidConfigFile(name.to_string(), error);
+    let table = document
+        .entry("environments")
+        .or_insert_with(|| {
+            // Written as `[environments.<NAME>]` headers only
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            toml_edit::Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| invalid("environments", "expected a table".to_string()))?;
+
+    let mut written = Vec::new();
+    for (name, environment) in environments {
+        if table.contains_key(name) && !overwrite {
+            continue;
+        }
+        let content = toml::to_string(environment).map_err(|e| invalid(name, e.to_string()))?;
+        let value = content
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|e| invalid(name, e.to_string()))?;
+        table.insert(name, toml_edit::Item::Table(value.as_table().clone()));
+        written.push(name.clone());
+    }
+
+    Ok(written)
+}
+
+/// Write a config file readable only by its owner, as it may hold
+/// connection strings with credentials
+pub fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(content.as_bytes())
+}
+
+/// Parse a config file from disk
+pub fn load(path: &Path) -> Result<FileConfig, ConfigError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError::InvalidConfigFile(path.display().to_string(), e.to_string()))?;
+
+    parse(&content)
+        .map_err(|e| ConfigError::InvalidConfigFile(path.display().to_string(), e.to_string()))
+}
+
+/// Parse config file content
+pub fn parse(content: &str) -> Result<FileConfig, toml::de::Error> {
+    toml::from_str(content)
+}
+
+/// Merge the environments of a named profile over the top-level ones.
+///
+/// Profile entries replace top-level environments of the same name, compared
+/// case-insensitively.
+pub fn apply_profile(config: &mut FileConfig, name: &str) -> Result<(), ConfigError> {
+    let profile = config
+        .profiles
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ConfigError::UnknownProfile(name.to_string()))?;
+
+    for (env_name, environment) in profile.environments {
+        config
+            .environments
+            .retain(|existing, _| !existing.eq_ignore_ascii_case(&env_name));
+        config.environments.insert(env_name, environment);
+    }
+
+    Ok(())
+}
+
+/// Load the config file and make it available through [`get`].
+///
+/// `path` overrides the config file search of [`find_config_file`] and
+/// `profile` selects a `[profiles.<name>]` table. Should be called once at
+/// startup; later calls keep the first configuration.
+pub fn init(path: Option<&Path>, profile: Option<&str>) -> Result<(), ConfigError> {
+    let path = path.map(Path::to_path_buf).or_else(find_config_file);
+    let mut config = match &path {
+        Some(path) => load(path)?,
+        None => FileConfig::default(),
+    };
+
+    if let Some(profile) = profile {
+        apply_profile(&mut config, profile)?;
+    }
+
+    if FILE_CONFIG.set(config).is_ok() {
+        let _ = SOURCE.set(ConfigSource {
+            path,
+            profile: profile.map(String::from),
+        });
+    }
+    Ok(())
+}
+
+/// Config file and profile of the loaded configuration
+pub fn source() -> &'static ConfigSource {
+    SOURCE.get_or_init(ConfigSource::default)
+}
+
+/// The loaded config file, or an empty configuration if none was loaded
+pub fn get() -> &'static FileConfig {
+    FILE_CONFIG.get_or_init(FileConfig::default)
+}
@@ -1,10 +1,15 @@
+use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
-use mongodb::options::ClientOptions;
+use mongodb::options::{ClientOptions, Tls, TlsOptions};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod file;
+pub mod secrets;
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("Environment variable not found: {0}")]
@@ -21,6 +26,29 @@ pub enum ConfigError {
 
     #[error("MongoDB binary not found")]
     BinaryNotFound,
+
+    #[error("Invalid config file {0}: {1}")]
+    InvalidConfigFile(String, String),
+
+    #[error("Unknown environment group: {0}")]
+    UnknownGroup(String),
+
+    #[error("Unknown preset: {0}")]
+    UnknownPreset(String),
+
+    #[error("Unknown profile: {0}")]
+    UnknownProfile(String),
+
+    #[error("Keyring error: {0}")]
+    Keyring(String),
+
+    #[error("Failed to read secret file {0}: {1}")]
+    SecretFile(String, String),
+
+    #[error(
+        "{0} authenticates with MONGODB-AWS, which needs arcula built with the aws-auth feature"
+    )]
+    AwsAuthUnavailable(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -62,10 +90,37 @@ pub struct MongoConfig {
 }
 
 impl MongoConfig {
+    /// Resolve the connection string of an environment.
+    ///
+    /// `MONGO_<ENV>_URI` takes precedence, followed by `MONGO_<ENV>_URI_FILE`
+    /// naming a file that holds the URI (Docker/Kubernetes secret mounts), and
+    /// finally an `[environments.<ENV>]` table in the config file, which holds
+    /// either the URI or a keyring reference. Environments registered for the
+    /// run with [`register_environment`] come first.
     pub fn from_env(env: Environment) -> Result<Self, ConfigError> {
+        let registered = RUN_ENVIRONMENTS
+            .get()
+            .and_then(|uris| uris.lock().unwrap().get(env.name()).cloned());
+        if let Some(connection_string) = registered {
+            return Ok(Self {
+                connection_string,
+                environment: env,
+            });
+        }
+
         let var_name = format!("MONGO_{}_URI", env);
-        let connection_string =
-            env::var(&var_name).map_err(|_| ConfigError::EnvVarNotFound(var_name))?;
+        let file_var_name = format!("{}_FILE", var_name);
+        let connection_string = match (env::var(&var_name), env::var(&file_var_name)) {
+            (Ok(uri), _) => uri,
+            (Err(_), Ok(path)) => read_secret_file(Path::new(&path))?,
+            (Err(_), Err(_)) => match file_environment(&env) {
+                Some(file::EnvironmentConfig { uri: Some(uri), .. }) => uri.clone(),
+                Some(file::EnvironmentConfig { keyring: true, .. }) => {
+                    secrets::get_uri(env.name())?
+                }
+                _ => return Err(ConfigError::EnvVarNotFound(var_name)),
+            },
+        };
 
         Ok(Self {
             connection_string,
@@ -74,9 +129,90 @@ impl MongoConfig {
     }
 
     pub async fn get_client_options(&self) -> Result<ClientOptions, ConfigError> {
-        let options = ClientOptions::parse(&self.connection_string).await?;
+        if self.uses_aws_auth() && !cfg!(feature = "aws-auth") {
+            return Err(ConfigError::AwsAuthUnavailable(
+                self.environment.to_string(),
+            ));
+        }
+        let mut options = ClientOptions::parse(&self.connection_string).await?;
+        if let Some(config) = file_environment(&self.environment) {
+            apply_auth(&mut options, config)?;
+        }
         Ok(options)
     }
+
+    /// Authentication arguments from the config file for the MongoDB
+    /// Database Tools, for settings the connection string doesn't hold
+    pub fn tool_args(&self) -> Vec<String> {
+        let mut args = file_environment(&self.environment)
+            .map(|config| config.tool_args())
+            .unwrap_or_default();
+        args.extend(self.aws_session_token_args());
+        args
+    }
+
+    /// Authentication arguments from the config file for mongosh
+    pub fn shell_args(&self) -> Vec<String> {
+        let mut args = file_environment(&self.environment)
+            .map(|config| config.shell_args())
+            .unwrap_or_default();
+        args.extend(self.aws_session_token_args());
+        args
+    }
+
+    /// Whether the environment authenticates with MONGODB-AWS, set in the
+    /// config file or the connection string
+    pub fn uses_aws_auth(&self) -> bool {
+        file_environment(&self.environment)
+            .is_some_and(|config| config.auth_mechanism == Some(AuthMechanism::MongodbAws))
+            || uri_option(&self.connection_string, "authMechanism")
+                .is_some_and(|mechanism| mechanism.eq_ignore_ascii_case("MONGODB-AWS"))
+    }
+
+    fn aws_session_token_args(&self) -> Vec<String> {
+        if !self.uses_aws_auth() {
+            return Vec::new();
+        }
+        let env_token = env::var("AWS_SESSION_TOKEN").ok();
+        match aws_session_token_for_tools(&self.connection_string, env_token.as_deref()) {
+            Some(token) => vec!["--awsSessionToken".to_string(), token.to_string()],
+            None => Vec::new(),
+        }
+    }
+
+    /// The driver client for this connection string, created on first use and
+    /// shared afterwards, so every phase of a run reuses its connection pool
+    /// instead of resolving SRV records and doing TLS handshakes again
+    pub async fn client(&self) -> Result<mongodb::Client, ConfigError> {
+        let clients = CLIENTS.get_or_init(Default::default);
+        if let Some(client) = clients.lock().unwrap().get(&self.connection_string) {
+            return Ok(client.clone());
+        }
+
+        let client = mongodb::Client::with_options(self.get_client_options().await?)?;
+        Ok(clients
+            .lock()
+            .unwrap()
+            .entry(self.connection_string.clone())
+            .or_insert(client)
+            .clone())
+    }
+}
+
+/// Driver clients by connection string, see `MongoConfig::client`
+static CLIENTS: OnceLock<Mutex<HashMap<String, mongodb::Client>>> = OnceLock::new();
+
+/// Connection strings by environment name, see `register_environment`
+static RUN_ENVIRONMENTS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+/// Make an environment that only exists for this run resolvable, e.g. the
+/// container started for `sync --to docker:<image>`
+pub fn register_environment(env: &Environment, connection_string: &str) {
+    RUN_ENVIRONMENTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .insert(env.name().to_string(), connection_string.to_string());
 }
 
 pub fn get_mongodb_bin_path() -> Result<PathBuf, ConfigError> {
@@ -118,40 +254,376 @@ pub fn get_mongodb_bin_path() -> Result<PathBuf, ConfigError> {
     Err(ConfigError::BinaryNotFound)
 }
 
+/// Locate an optional MongoDB tool that is only needed by some commands.
+///
+/// Looks in `MONGODB_BIN_PATH` first and falls back to `PATH`.
+pub fn get_tool_path(name: &str) -> Result<PathBuf, ConfigError> {
+    if let Ok(path) = env::var("MONGODB_BIN_PATH") {
+        let tool = PathBuf::from(path).join(name);
+        if tool.exists() {
+            return Ok(tool);
+        }
+    }
+
+    Ok(which::which(name)?)
+}
+
+/// Locate the `mongosh` shell, which is only required for post-sync scripts.
+pub fn get_mongosh_path() -> Result<PathBuf, ConfigError> {
+    get_tool_path("mongosh")
+}
+
 /// Checks if MongoDB tools (mongodump and mongorestore) are available
 pub fn check_mongodb_tools() -> Result<(), ConfigError> {
     // This will return an error if it can't find both mongodump and mongorestore
     get_mongodb_bin_path().map(|_| ())
 }
 
-/// Get all available MongoDB environments from environment variables
-pub fn get_available_environments() -> Vec<Environment> {
-    let prefix = "MONGO_";
-    let suffix = "_URI";
-
-    // Get all environment variables
-    let mut environments = Vec::new();
-
-    for (key, _) in env::vars() {
-        if key.starts_with(prefix) && key.ends_with(suffix) {
-            // Extract the environment name (between MONGO_ and _URI)
-            if let Some(env_name) = key
-                .strip_prefix(prefix)
-                .and_then(|s| s.strip_suffix(suffix))
-            {
-                if !env_name.is_empty() {
-                    environments.push(Environment::new(env_name));
-                }
-            }
+/// Look up the `[environments.<ENV>]` table of the config file, ignoring case
+fn file_environment(env: &Environment) -> Option<&'static file::EnvironmentConfig> {
+    file::get()
+        .environments
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(env.name()))
+        .map(|(_, config)| config)
+}
+
+/// Whether an environment holds production data: named `PROD` or `PRODUCTION`,
+/// or marked with `production = true` in the config file
+pub fn is_production(env: &Environment) -> bool {
+    ["PROD", "PRODUCTION"]
+        .iter()
+        .any(|name| env.name().eq_ignore_ascii_case(name))
+        || file_environment(env).is_some_and(|config| config.production)
+}
+
+/// Authentication mechanisms that can be set per environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthMechanism {
+    #[serde(rename = "SCRAM-SHA-1")]
+    ScramSha1,
+    #[serde(rename = "SCRAM-SHA-256")]
+    ScramSha256,
+    /// IAM credentials from the AWS environment; driver connections need the
+    /// `aws-auth` feature
+    #[serde(rename = "MONGODB-AWS")]
+    MongodbAws,
+    #[serde(rename = "MONGODB-X509")]
+    MongodbX509,
+}
+
+impl AuthMechanism {
+    /// Name as in connection strings and tool arguments
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMechanism::ScramSha1 => "SCRAM-SHA-1",
+            AuthMechanism::ScramSha256 => "SCRAM-SHA-256",
+            AuthMechanism::MongodbAws => "MONGODB-AWS",
+            AuthMechanism::MongodbX509 => "MONGODB-X509",
+        }
+    }
+}
+
+/// The `AWS_SESSION_TOKEN` the MongoDB tools need passed explicitly for a
+/// MONGODB-AWS connection string.
+///
+/// The driver falls back to the `AWS_SESSION_TOKEN` variable for an access key
+/// in the connection string, while the tools then authenticate without a
+/// token, which fails for temporary credentials (e.g. from an assumed role).
+/// Without an access key in the connection string both read all credentials
+/// from the environment themselves.
+pub fn aws_session_token_for_tools<'a>(uri: &str, env_token: Option<&'a str>) -> Option<&'a str> {
+    let has_access_key = uri
+        .split_once("://")
+        .map(|(_, rest)| rest.split(['/', '?']).next().unwrap_or_default())
+        .is_some_and(|authority| authority.contains('@'));
+    let has_token = uri_option(uri, "authMechanismProperties")
+        .is_some_and(|properties| properties.contains("AWS_SESSION_TOKEN:"));
+    env_token.filter(|token| has_access_key && !has_token && !token.is_empty())
+}
+
+/// Value of a query option of a connection string, with the option name
+/// matched case-insensitively as the driver does
+fn uri_option<'a>(uri: &'a str, name: &str) -> Option<&'a str> {
+    let (_, query) = uri.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|option| option.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Apply the authentication settings of an `[environments.<ENV>]` table to
+/// driver options parsed from its connection string
+pub fn apply_auth(
+    options: &mut ClientOptions,
+    config: &file::EnvironmentConfig,
+) -> Result<(), ConfigError> {
+    if config.auth_source.is_some() || config.auth_mechanism.is_some() {
+        let credential = options.credential.get_or_insert_with(Default::default);
+        if let Some(source) = &config.auth_source {
+            credential.source = Some(source.clone());
+        }
+        if let Some(mechanism) = config.auth_mechanism {
+            credential.mechanism = Some(mechanism.as_str().parse()?);
+        }
+    }
+
+    if let Some(path) = &config.tls_certificate_key_file {
+        let mut tls = match options.tls.take() {
+            Some(Tls::Enabled(tls)) => tls,
+            _ => TlsOptions::default(),
+        };
+        tls.cert_key_file_path = Some(path.clone());
+        options.tls = Some(Tls::Enabled(tls));
+    }
+    Ok(())
+}
+
+/// Replica set members a sync reads the source from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+#[value(rename_all = "camelCase")]
+pub enum ReadPreference {
+    Primary,
+    PrimaryPreferred,
+    Secondary,
+    SecondaryPreferred,
+    Nearest,
+}
+
+impl ReadPreference {
+    /// Mode name as mongodump's `--readPreference` takes it
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadPreference::Primary => "primary",
+            ReadPreference::PrimaryPreferred => "primaryPreferred",
+            ReadPreference::Secondary => "secondary",
+            ReadPreference::SecondaryPreferred => "secondaryPreferred",
+            ReadPreference::Nearest => "nearest",
+        }
+    }
+
+    pub fn to_driver(self) -> mongodb::options::ReadPreference {
+        use mongodb::options::ReadPreference as Driver;
+        match self {
+            ReadPreference::Primary => Driver::Primary,
+            ReadPreference::PrimaryPreferred => Driver::PrimaryPreferred { options: None },
+            ReadPreference::Secondary => Driver::Secondary { options: None },
+            ReadPreference::SecondaryPreferred => Driver::SecondaryPreferred { options: None },
+            ReadPreference::Nearest => Driver::Nearest { options: None },
+        }
+    }
+}
+
+impl std::fmt::Display for ReadPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Read preference of an environment from `read_preference` in its
+/// `[environments.<ENV>]` table
+pub fn read_preference(env: &Environment) -> Option<ReadPreference> {
+    file_environment(env).and_then(|config| config.read_preference)
+}
+
+/// Database prefix of an environment from `db_prefix` in its
+/// `[environments.<ENV>]` table
+pub fn db_prefix(env: &Environment) -> Option<&'static str> {
+    file_environment(env)
+        .and_then(|config| config.db_prefix.as_deref())
+        .filter(|prefix| !prefix.is_empty())
+}
+
+/// Full name of a database given without the `db_prefix` of its environment
+pub fn scoped_database(env: &Environment, database: &str) -> String {
+    format!("{}{}", db_prefix(env).unwrap_or_default(), database)
+}
+
+/// Name of a database as shown for an environment, without its `db_prefix`,
+/// or `None` for databases outside the prefix
+pub fn unscoped_database<'a>(env: &Environment, database: &'a str) -> Option<&'a str> {
+    match db_prefix(env) {
+        Some(prefix) => database
+            .strip_prefix(prefix)
+            .filter(|name| !name.is_empty()),
+        None => Some(database),
+    }
+}
+
+/// Read a connection string from a secret file, ignoring surrounding whitespace
+pub fn read_secret_file(path: &Path) -> Result<String, ConfigError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError::SecretFile(path.display().to_string(), e.to_string()))?;
+    let uri = content.trim();
+    if uri.is_empty() {
+        return Err(ConfigError::SecretFile(
+            path.display().to_string(),
+            "file is empty".to_string(),
+        ));
+    }
+    Ok(uri.to_string())
+}
+
+/// Extract the environment name from a `MONGO_<ENV>_URI` variable name
+pub fn environment_from_var(key: &str) -> Option<&str> {
+    key.strip_prefix("MONGO_")
+        .and_then(|s| s.strip_suffix("_URI"))
+        .filter(|name| !name.is_empty())
+}
+
+/// Variables PaaS platforms and app templates keep a MongoDB connection
+/// string in (Railway, Heroku add-ons, Render, Fly.io), by priority
+pub const PAAS_VARS: &[&str] = &[
+    "MONGODB_URI",
+    "MONGODB_URL",
+    "MONGO_URL",
+    "MONGO_URI",
+    "MONGOLAB_URI",
+    "MONGOHQ_URL",
+    "ORMONGO_URL",
+    "DATABASE_URL",
+];
+
+/// Connection strings held by the [`PAAS_VARS`] among `vars`, as
+/// `(variable, uri)` pairs by priority. Variables such as `DATABASE_URL`
+/// holding another kind of URL are left out, as are repeated connection strings.
+pub fn detect_paas_uris(vars: &[(String, String)]) -> Vec<(String, String)> {
+    let mut detected: Vec<(String, String)> = Vec::new();
+    for name in PAAS_VARS {
+        let Some((_, uri)) = vars.iter().find(|(key, _)| key == name) else {
+            continue;
+        };
+        let uri = uri.trim();
+        let is_mongodb = uri.starts_with("mongodb://") || uri.starts_with("mongodb+srv://");
+        if is_mongodb && !detected.iter().any(|(_, seen)| seen == uri) {
+            detected.push((name.to_string(), uri.to_string()));
         }
     }
+    detected
+}
+
+/// Environment name offered for a detected connection string: `LOCAL` for
+/// servers on this machine, `APP` otherwise
+pub fn suggest_environment_name(uri: &str) -> &'static str {
+    let hosts = uri
+        .split_once("://")
+        .map_or(uri, |(_, rest)| rest)
+        .split(['/', '?'])
+        .next()
+        .unwrap_or_default();
+    let hosts = hosts.rsplit_once('@').map_or(hosts, |(_, hosts)| hosts);
+    let local = hosts.split(',').all(|host| {
+        let host = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+            None => host.split(':').next().unwrap_or_default(),
+        };
+        matches!(host, "localhost" | "127.0.0.1" | "::1")
+    });
+    if local {
+        "LOCAL"
+    } else {
+        "APP"
+    }
+}
+
+/// Extract the environment name from a `MONGO_<ENV>_URI_FILE` variable name
+fn environment_from_file_var(key: &str) -> Option<&str> {
+    key.strip_suffix("_FILE").and_then(environment_from_var)
+}
+
+/// Get all available MongoDB environments from environment variables and the config file
+pub fn get_available_environments() -> Vec<Environment> {
+    let mut environments: Vec<Environment> = env::vars()
+        .filter_map(|(key, _)| {
+            environment_from_var(&key)
+                .or_else(|| environment_from_file_var(&key))
+                .map(Environment::new)
+        })
+        .chain(
+            file::get()
+                .environments
+                .keys()
+                .map(|name| Environment::new(name)),
+        )
+        .collect();
 
     // Sort environments alphabetically for consistent display
     environments.sort_by(|a, b| a.name().cmp(b.name()));
+    environments.dedup();
 
     environments
 }
 
+/// Resolve the members of a named environment group from the config file.
+///
+/// The name may be given with or without its leading `@`.
+pub fn get_environment_group(name: &str) -> Result<Vec<Environment>, ConfigError> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    let members = file::get()
+        .groups
+        .get(name)
+        .filter(|members| !members.is_empty())
+        .ok_or_else(|| ConfigError::UnknownGroup(name.to_string()))?;
+
+    members.iter().map(|m| m.parse()).collect()
+}
+
+/// Get a named sync preset from the config file
+pub fn get_preset(name: &str) -> Result<file::Preset, ConfigError> {
+    file::get()
+        .presets
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ConfigError::UnknownPreset(name.to_string()))
+}
+
+/// Directory for state kept between runs, such as caches.
+///
+/// `ARCULA_STATE_DIR` takes precedence, followed by `$XDG_STATE_HOME/arcula`
+/// and `~/.local/state/arcula`.
+pub fn get_state_dir() -> PathBuf {
+    if let Ok(path) = env::var("ARCULA_STATE_DIR") {
+        return PathBuf::from(path);
+    }
+
+    env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))
+        .unwrap_or_else(|_| env::temp_dir())
+        .join("arcula")
+}
+
+/// Prefix of the temporary directories arcula creates, so `prune-temp` can
+/// find those left behind by killed runs
+pub const TEMP_DIR_PREFIX: &str = "arcula-";
+
+/// Overrides the system temp directory for staging dumps and scripts
+pub const TEMP_DIR_VAR: &str = "ARCULA_TEMP_DIR";
+
+/// Directory temporary files are created in: `ARCULA_TEMP_DIR`, or the
+/// system temp directory
+pub fn get_temp_dir() -> PathBuf {
+    env::var(TEMP_DIR_VAR)
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+}
+
+pub fn create_temp_dir() -> std::io::Result<tempfile::TempDir> {
+    create_temp_dir_in(&get_temp_dir())
+}
+
+/// Create a temporary directory in `dir`, creating `dir` first if needed
+pub fn create_temp_dir_in(dir: &Path) -> std::io::Result<tempfile::TempDir> {
+    std::fs::create_dir_all(dir)?;
+    tempfile::Builder::new()
+        .prefix(TEMP_DIR_PREFIX)
+        .tempdir_in(dir)
+}
+
 pub fn get_backup_dir() -> PathBuf {
     env::var("BACKUP_DIR")
         .map(PathBuf::from)
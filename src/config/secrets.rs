@@ -0,0 +1,48 @@
+//! Connection strings stored in the OS keyring.
+//!
+//! Entries use the service name `arcula` and the environment name as the user.
+//! Keyring support is behind the `keyring` cargo feature.
+
+use super::ConfigError;
+
+#[cfg(feature = "keyring")]
+const SERVICE: &str = "arcula";
+
+/// Read the connection string of an environment from the keyring
+#[cfg(feature = "keyring")]
+pub fn get_uri(env: &str) -> Result<String, ConfigError> {
+    entry(env)?
+        .get_password()
+        .map_err(|e| ConfigError::Keyring(format!("{}: {}", env, e)))
+}
+
+/// Store the connection string of an environment in the keyring
+#[cfg(feature = "keyring")]
+pub fn set_uri(env: &str, uri: &str) -> Result<(), ConfigError> {
+    entry(env)?
+        .set_password(uri)
+        .map_err(|e| ConfigError::Keyring(format!("{}: {}", env, e)))
+}
+
+#[cfg(feature = "keyring")]
+fn entry(env: &str) -> Result<::keyring::Entry, ConfigError> {
+    ::keyring::Entry::new(SERVICE, env).map_err(|e| ConfigError::Keyring(e.to_string()))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn get_uri(env: &str) -> Result<String, ConfigError> {
+    Err(unavailable(env))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn set_uri(env: &str, _uri: &str) -> Result<(), ConfigError> {
+    Err(unavailable(env))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn unavailable(env: &str) -> ConfigError {
+    ConfigError::Keyring(format!(
+        "{}: arcula was built without keyring support (enable the 'keyring' feature)",
+        env
+    ))
+}
@@ -0,0 +1,121 @@
+//! Roles of the clients of `arcula rpc` and `arcula mcp`, granted by the
+//! tokens of the server config at [`SERVER_CONFIG_PATH`].
+//!
+//! Clients start the servers they talk to and so choose their environment,
+//! arguments and config file; the tokens therefore live in a fixed file
+//! outside the client's reach. It only holds SHA-256 hashes of the tokens;
+//! a server reads the token of its client from `ARCULA_TOKEN`, which MCP
+//! clients set in the environment of the servers they start.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::file::{ServerConfig, ServerToken};
+
+/// Environment variable holding the token of the client of a server
+pub const TOKEN_VAR: &str = "ARCULA_TOKEN";
+
+/// The server config with the client tokens, deliberately not configurable
+pub const SERVER_CONFIG_PATH: &str = "/etc/arcula/server.toml";
+
+/// What a client may do, each role allowing everything of the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// List environments, databases, collections and backups, and dry-run syncs
+    Viewer,
+    /// Take backups and sync into environments that aren't production
+    Operator,
+    /// Also sync into production environments
+    Admin,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Hex SHA-256 of a token, as stored in the config file
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Name and role of the configured token matching `token`
+pub fn find_token<'a>(
+    tokens: &'a BTreeMap<String, ServerToken>,
+    token: &str,
+) -> Option<(&'a str, Role)> {
+    let hash = hash_token(token);
+    tokens
+        .iter()
+        .find(|(_, configured)| configured.sha256.eq_ignore_ascii_case(&hash))
+        .map(|(name, configured)| (name.as_str(), configured.role))
+}
+
+/// Read a server config, treating a missing file as one without tokens
+pub fn load_server_config(path: &Path) -> Result<ServerConfig> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => {
+            toml::from_str(&content).context(format!("Failed to parse {}", path.display()))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ServerConfig::default()),
+        Err(e) => Err(e).context(format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Role of a client presenting `token`: the role of its token when tokens
+/// are configured, which it must present, otherwise viewer, unless the
+/// server was started with `--allow-unauthenticated` and so gets `None` to
+/// decide on its own
+pub fn authorize(
+    server: &ServerConfig,
+    token: Option<&str>,
+    allow_unauthenticated: bool,
+) -> Result<Option<Role>> {
+    if server.tokens.is_empty() {
+        if allow_unauthenticated {
+            return Ok(None);
+        }
+        log::info!(
+            "No client tokens in {}, serving the client as {}",
+            SERVER_CONFIG_PATH,
+            Role::Viewer
+        );
+        return Ok(Some(Role::Viewer));
+    }
+    let token = match token {
+        Some(token) if !token.is_empty() => token,
+        _ => anyhow::bail!(
+            "{} must be set, as {} restricts servers to clients with a token",
+            TOKEN_VAR,
+            SERVER_CONFIG_PATH
+        ),
+    };
+    match find_token(&server.tokens, token) {
+        Some((name, role)) => {
+            log::info!("Serving client with token '{}' as {}", name, role);
+            Ok(Some(role))
+        }
+        None => anyhow::bail!(
+            "The token in {} is not in {}",
+            TOKEN_VAR,
+            SERVER_CONFIG_PATH
+        ),
+    }
+}
+
+/// Role of the client of this server, see [`authorize`]
+pub fn authenticate(allow_unauthenticated: bool) -> Result<Option<Role>> {
+    let server = load_server_config(Path::new(SERVER_CONFIG_PATH))?;
+    let token = std::env::var(TOKEN_VAR).ok();
+    authorize(&server, token.as_deref(), allow_unauthenticated)
+}
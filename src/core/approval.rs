@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Environment variable holding the secret shared by requesters and approvers
+pub const APPROVAL_KEY_VAR: &str = "ARCULA_APPROVAL_KEY";
+
+/// How long a requested plan can be approved
+pub const PLAN_VALIDITY: chrono::Duration = chrono::Duration::hours(24);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A sync requested with `sync --request`, to be executed by `approve`.
+///
+/// The signature is an HMAC-SHA256 over the other fields, so a plan edited
+/// after it was requested is rejected, and so is one whose referenced files
+/// changed or that expired.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncPlan {
+    pub requested_by: String,
+    pub requested_at: String,
+    /// The plan can't be approved after this time (RFC 3339)
+    pub expires_at: String,
+    /// The sync parameters, as given to `sync`
+    pub sync: serde_json::Value,
+    /// SHA-256 of the files the sync parameters reference, by path
+    pub files: BTreeMap<String, String>,
+    pub signature: String,
+}
+
+/// Read the shared approval key from the environment
+pub fn approval_key() -> Result<Vec<u8>> {
+    match std::env::var(APPROVAL_KEY_VAR) {
+        Ok(key) if !key.is_empty() => Ok(key.into_bytes()),
+        _ => anyhow::bail!(
+            "{} must be set to sign and approve sync plans",
+            APPROVAL_KEY_VAR
+        ),
+    }
+}
+
+/// The current user, as recorded in requested plans
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// SHA-256 of the contents of each file, by path as given
+pub fn hash_files(paths: &[&Path]) -> Result<BTreeMap<String, String>> {
+    paths
+        .iter()
+        .map(|path| {
+            let content =
+                std::fs::read(path).context(format!("Failed to read {}", path.display()))?;
+            Ok((
+                path.display().to_string(),
+                hex::encode(Sha256::digest(content)),
+            ))
+        })
+        .collect()
+}
+
+fn mac(key: &[u8], plan: &SyncPlan) -> HmacSha256 {
+    // serde_json maps are sorted, so the payload is canonical
+    let payload = json!({
+        "requested_by": plan.requested_by,
+        "requested_at": plan.requested_at,
+        "expires_at": plan.expires_at,
+        "sync": plan.sync,
+        "files": plan.files,
+    });
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(payload.to_string().as_bytes());
+    mac
+}
+
+/// Create a signed plan for the given sync parameters and the hashes of the
+/// files they reference, approvable until [`PLAN_VALIDITY`] after it was
+/// requested
+pub fn sign_plan(
+    sync: serde_json::Value,
+    files: BTreeMap<String, String>,
+    requested_by: &str,
+    requested_at: chrono::DateTime<chrono::Utc>,
+    key: &[u8],
+) -> SyncPlan {
+    let mut plan = SyncPlan {
+        requested_by: requested_by.to_string(),
+        requested_at: requested_at.to_rfc3339(),
+        expires_at: (requested_at + PLAN_VALIDITY).to_rfc3339(),
+        sync,
+        files,
+        signature: String::new(),
+    };
+    plan.signature = hex::encode(mac(key, &plan).finalize().into_bytes());
+    plan
+}
+
+/// Check that a plan was signed with `key`, was not modified since, has not
+/// expired at `now`, and that the files it references still have the
+/// contents they had when it was requested
+pub fn verify_plan(plan: &SyncPlan, key: &[u8], now: chrono::DateTime<chrono::Utc>) -> Result<()> {
+    let signature = hex::decode(&plan.signature).context("Plan signature is not valid hex")?;
+
+    mac(key, plan).verify_slice(&signature).map_err(|_| {
+        anyhow::anyhow!("Plan signature does not match; it was modified or signed with another key")
+    })?;
+
+    let expires_at = chrono::DateTime::parse_from_rfc3339(&plan.expires_at)
+        .context("Plan expiry is not a valid time")?;
+    if now > expires_at {
+        anyhow::bail!(
+            "Plan expired at {}; request the sync again",
+            plan.expires_at
+        );
+    }
+
+    let paths: Vec<&Path> = plan.files.keys().map(Path::new).collect();
+    let current = hash_files(&paths)?;
+    for (path, hash) in &plan.files {
+        if current.get(path) != Some(hash) {
+            anyhow::bail!(
+                "{} changed since the sync was requested; request it again",
+                path
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Write a plan file
+pub fn write_plan(path: &Path, plan: &SyncPlan) -> Result<()> {
+    let content = serde_json::to_string_pretty(plan)?;
+    std::fs::write(path, content).context(format!("Failed to write plan {}", path.display()))
+}
+
+/// Read a plan file
+pub fn read_plan(path: &Path) -> Result<SyncPlan> {
+    let content =
+        std::fs::read_to_string(path).context(format!("Failed to read plan {}", path.display()))?;
+    serde_json::from_str(&content).context(format!("Invalid plan file {}", path.display()))
+}
@@ -0,0 +1,422 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use futures::{StreamExt, TryStreamExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::{get_backup_dir, MongoConfig};
+use crate::core::preflight::CollectionEstimate;
+use crate::core::verify;
+use crate::utils::dump::DumpLayout;
+use crate::utils::run_id;
+use crate::utils::theme;
+use crate::utils::{disk, mongodb};
+
+/// Document count and content hash of a collection at backup time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionChecksum {
+    pub name: String,
+    pub documents: u64,
+    /// Content MD5, absent where it couldn't be computed
+    pub hash: Option<String>,
+    /// How `hash` was computed; older versions only recorded `dbHash` hashes
+    #[serde(default)]
+    pub hash_method: HashMethod,
+}
+
+impl CollectionChecksum {
+    /// Whether the hashes of both checksums exist and were computed the same
+    /// way, as hashes of different methods never match
+    pub fn hash_comparable(&self, other: &CollectionChecksum) -> bool {
+        self.hash.is_some() && other.hash.is_some() && self.hash_method == other.hash_method
+    }
+}
+
+/// How the content hash of a collection was computed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashMethod {
+    /// The `dbHash` command of the server
+    #[default]
+    DbHash,
+    /// MD5 of the raw documents in `_id` order, where `dbHash` is unavailable
+    Scan,
+}
+
+impl std::fmt::Display for HashMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashMethod::DbHash => write!(f, "dbHash"),
+            HashMethod::Scan => write!(f, "scan"),
+        }
+    }
+}
+
+/// Which side of a sync a backup was taken of
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupRole {
+    /// The source, taken with `--backup-source`
+    Source,
+    /// The target, taken before the import replaces it
+    #[default]
+    Target,
+}
+
+impl std::fmt::Display for BackupRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupRole::Source => write!(f, "source"),
+            BackupRole::Target => write!(f, "target"),
+        }
+    }
+}
+
+/// Describes what a backup contains so a restore can be validated without the source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub environment: String,
+    pub database: String,
+    pub created_at: String,
+    /// Run that created the manifest; missing in manifests of older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// Older versions only backed up targets
+    #[serde(default)]
+    pub role: BackupRole,
+    pub collections: Vec<CollectionChecksum>,
+}
+
+/// Gather per-collection document counts and content hashes of a database.
+///
+/// Hashes come from `dbHash`. Where it is unavailable they are computed by
+/// reading every document in `_id` order if `scan_fallback` is set, several
+/// collections at a time, and left out otherwise.
+pub async fn collect_checksums(
+    config: &MongoConfig,
+    database: &str,
+    scan_fallback: bool,
+) -> Result<Vec<CollectionChecksum>> {
+    let (hashes, dbhash_available) = match mongodb::collection_hashes(config, database).await {
+        Ok(hashes) => (hashes, true),
+        Err(e) => {
+            warn!("Content hashes unavailable for {}: {}", database, e);
+            (Default::default(), false)
+        }
+    };
+
+    let collections = mongodb::list_collection_stats(config, database)
+        .await?
+        .into_iter()
+        .filter(|collection| collection.kind != "view");
+
+    futures::stream::iter(collections)
+        .map(|collection| {
+            let hashes = &hashes;
+            async move {
+                let (hash, hash_method) = match hashes.get(&collection.name) {
+                    Some(hash) => (Some(hash.clone()), HashMethod::DbHash),
+                    None if scan_fallback && !dbhash_available => (
+                        Some(
+                            mongodb::scan_collection_hash(config, database, &collection.name)
+                                .await?,
+                        ),
+                        HashMethod::Scan,
+                    ),
+                    None => (None, HashMethod::DbHash),
+                };
+                Ok::<_, anyhow::Error>(CollectionChecksum {
+                    name: collection.name,
+                    documents: collection.documents,
+                    hash,
+                    hash_method,
+                })
+            }
+        })
+        .buffered(verify::concurrency())
+        .try_collect()
+        .await
+}
+
+/// Record the checksums of the backed-up database in the backup directory
+pub async fn write_manifest(
+    config: &MongoConfig,
+    database: &str,
+    backup_path: &Path,
+    role: BackupRole,
+) -> Result<BackupManifest> {
+    let manifest = BackupManifest {
+        environment: config.environment.to_string(),
+        database: database.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        run_id: Some(run_id::get().to_string()),
+        role,
+        collections: collect_checksums(config, database, false).await?,
+    };
+
+    std::fs::write(
+        DumpLayout::new(backup_path).manifest_file(),
+        serde_json::to_string_pretty(&manifest)?,
+    )
+    .context("Failed to write backup manifest")?;
+
+    Ok(manifest)
+}
+
+pub fn read_manifest(backup_path: &Path) -> Result<BackupManifest> {
+    let path = DumpLayout::new(backup_path).manifest_file();
+    let content = std::fs::read_to_string(&path).context(format!(
+        "Failed to read backup manifest: {}",
+        path.display()
+    ))?;
+    serde_json::from_str(&content).context(format!("Invalid backup manifest: {}", path.display()))
+}
+
+/// Backups taken during a run, found through the run IDs recorded in the
+/// manifests of a backup directory and sorted by path
+pub fn find_run_backups(backup_dir: &Path, run_id: &str) -> Result<Vec<(PathBuf, BackupManifest)>> {
+    let mut backups = list_backups(backup_dir)?;
+    backups.retain(|(_, manifest)| manifest.run_id.as_deref() == Some(run_id));
+    Ok(backups)
+}
+
+/// Backups with a manifest in a backup directory, sorted by path; unreadable
+/// manifests are skipped
+pub fn list_backups(backup_dir: &Path) -> Result<Vec<(PathBuf, BackupManifest)>> {
+    let entries = match std::fs::read_dir(backup_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(format!("Failed to read {}", backup_dir.display())),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if !DumpLayout::new(&path).manifest_file().is_file() {
+            continue;
+        }
+        match read_manifest(&path) {
+            Ok(manifest) => backups.push((path, manifest)),
+            Err(e) => warn!("Skipping {}: {}", path.display(), e),
+        }
+    }
+    backups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(backups)
+}
+
+/// What a backup would write, from the statistics of the database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackupEstimate {
+    pub environment: String,
+    pub database: String,
+    /// Directory the backup would be written to
+    pub destination: PathBuf,
+    pub gzip: bool,
+    /// Collections the backup would hold, without views, sorted by name
+    pub collections: Vec<CollectionEstimate>,
+    /// Free bytes in the backup directory, absent when unknown
+    pub free: Option<u64>,
+}
+
+impl BackupEstimate {
+    /// BSON bytes written before any compression
+    pub fn size(&self) -> u64 {
+        self.collections.iter().map(|c| c.size).sum()
+    }
+
+    pub fn documents(&self) -> u64 {
+        self.collections.iter().map(|c| c.documents).sum()
+    }
+}
+
+/// Estimate the backup of a database without writing anything
+pub async fn estimate_backup(
+    config: &MongoConfig,
+    database: &str,
+    gzip: bool,
+) -> Result<BackupEstimate> {
+    let mut collections: Vec<CollectionEstimate> = mongodb::list_collection_stats(config, database)
+        .await?
+        .into_iter()
+        .filter(|c| c.kind != "view")
+        .map(|c| CollectionEstimate {
+            name: c.name,
+            documents: c.documents,
+            size: c.size,
+        })
+        .collect();
+    collections.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(BackupEstimate {
+        environment: config.environment.to_string(),
+        database: database.to_string(),
+        destination: mongodb::backup_path(config, database),
+        gzip,
+        collections,
+        free: disk::free_space(&get_backup_dir()).await.ok(),
+    })
+}
+
+/// A collection a restore would replace with its backed-up version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RestoredCollection {
+    pub name: String,
+    /// Documents recorded in the manifest
+    pub backup_documents: u64,
+    /// Documents in the target now, absent when the restore creates the collection
+    pub target_documents: Option<u64>,
+}
+
+/// What a restore of a backup would change in its target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RestorePlan {
+    /// Collections dropped and restored, sorted by name
+    pub restored: Vec<RestoredCollection>,
+    /// Target collections the restore leaves untouched, sorted by name
+    pub untouched: Vec<String>,
+}
+
+/// Compare the collections of a manifest with those of the target, restoring
+/// only `collections` unless empty
+pub fn plan_restore(
+    manifest: &BackupManifest,
+    collections: &[String],
+    target: &[mongodb::CollectionStats],
+) -> RestorePlan {
+    let mut restored: Vec<RestoredCollection> = manifest
+        .collections
+        .iter()
+        .filter(|c| collections.is_empty() || collections.contains(&c.name))
+        .map(|c| RestoredCollection {
+            name: c.name.clone(),
+            backup_documents: c.documents,
+            target_documents: target
+                .iter()
+                .find(|t| t.name == c.name)
+                .map(|t| t.documents),
+        })
+        .collect();
+    restored.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut untouched: Vec<String> = target
+        .iter()
+        .filter(|t| t.kind != "view" && !restored.iter().any(|r| r.name == t.name))
+        .map(|t| t.name.clone())
+        .collect();
+    untouched.sort();
+
+    RestorePlan {
+        restored,
+        untouched,
+    }
+}
+
+/// List the differences between expected and actual checksums.
+///
+/// Hashes are only compared when both sides have one computed the same way,
+/// see [`incomparable_hashes`] for the others.
+pub fn compare_checksums(
+    expected: &[CollectionChecksum],
+    actual: &[CollectionChecksum],
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for collection in expected {
+        match actual.iter().find(|c| c.name == collection.name) {
+            None => problems.push(format!("{}: missing", collection.name)),
+            Some(current) if current.documents != collection.documents => problems.push(format!(
+                "{}: {} documents, expected {}",
+                collection.name, current.documents, collection.documents
+            )),
+            Some(current) => {
+                if collection.hash_comparable(current) && collection.hash != current.hash {
+                    problems.push(format!("{}: content hash differs", collection.name));
+                }
+            }
+        }
+    }
+
+    for collection in actual {
+        if !expected.iter().any(|c| c.name == collection.name) {
+            problems.push(format!("{}: not in backup", collection.name));
+        }
+    }
+
+    problems
+}
+
+/// Collections hashed on both sides, but with different methods, so their
+/// content couldn't be compared, e.g. `users: dbHash and scan hashes`
+pub fn incomparable_hashes(
+    expected: &[CollectionChecksum],
+    actual: &[CollectionChecksum],
+) -> Vec<String> {
+    expected
+        .iter()
+        .filter_map(|collection| {
+            let current = actual.iter().find(|c| c.name == collection.name)?;
+            (collection.hash.is_some()
+                && current.hash.is_some()
+                && collection.hash_method != current.hash_method)
+                .then(|| {
+                    format!(
+                        "{}: {} and {} hashes",
+                        collection.name, collection.hash_method, current.hash_method
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Names of source collections identical to the baseline: same document count
+/// and same content hash.
+///
+/// Collections without a hash on either side, or hashed with different
+/// methods, are never considered identical.
+pub fn unchanged_collections(
+    source: &[CollectionChecksum],
+    baseline: &[CollectionChecksum],
+) -> Vec<String> {
+    source
+        .iter()
+        .filter(|collection| {
+            baseline.iter().any(|base| {
+                base.name == collection.name
+                    && base.documents == collection.documents
+                    && base.hash_comparable(collection)
+                    && base.hash == collection.hash
+            })
+        })
+        .map(|collection| collection.name.clone())
+        .collect()
+}
+
+/// Check a live database against a backup manifest, failing on any difference
+pub async fn verify_against_manifest(
+    config: &MongoConfig,
+    database: &str,
+    manifest: &BackupManifest,
+) -> Result<()> {
+    let actual = collect_checksums(config, database, false).await?;
+    let problems = compare_checksums(&manifest.collections, &actual);
+
+    if problems.is_empty() {
+        println!(
+            "{} {} collection(s) match the backup manifest",
+            "Verified:".green().bold(),
+            manifest.collections.len()
+        );
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("  {} {}", theme::cross().red(), problem);
+    }
+    anyhow::bail!(
+        "{} collection(s) of {} differ from the backup manifest",
+        problems.len(),
+        database
+    );
+}
@@ -0,0 +1,155 @@
+//! Database lists cached per environment so interactive prompts render
+//! without waiting for the cluster.
+
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{self, get_state_dir, Environment};
+use crate::core::sync::get_databases;
+
+const CACHE_DIR: &str = "databases";
+
+/// How long cached database lists are used without refreshing them first
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The databases of one environment and when they were fetched, stored in a
+/// file of its own so refreshes of different environments never overwrite
+/// each other
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedDatabases {
+    /// What the list was fetched for, see [`cache_key`]
+    pub key: String,
+    /// Unix timestamp in seconds
+    pub fetched_at: i64,
+    pub databases: Vec<String>,
+}
+
+impl CachedDatabases {
+    /// Read a cache entry, treating a missing or unreadable one as absent
+    pub fn load(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    /// Write the entry atomically, so concurrent runs never read a partial file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let parent = path.parent().unwrap_or(Path::new("."));
+        std::fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+        let mut temp = tempfile::NamedTempFile::new_in(parent)?;
+        temp.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        temp.persist(path)
+            .context(format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Whether the entry is younger than `ttl`
+    pub fn is_fresh(&self, ttl: Duration, now: i64) -> bool {
+        now - self.fetched_at < ttl.as_secs() as i64
+    }
+}
+
+/// Identifies the database list of an environment: the same environment
+/// name can point elsewhere in another config file or profile, and its
+/// `db_prefix` decides which databases are listed
+pub fn cache_key(
+    env: &str,
+    config_file: Option<&Path>,
+    profile: Option<&str>,
+    db_prefix: Option<&str>,
+) -> String {
+    format!(
+        "{}|config={}|profile={}|prefix={}",
+        env,
+        config_file
+            .map(|p| p.display().to_string())
+            .unwrap_or_default(),
+        profile.unwrap_or_default(),
+        db_prefix.unwrap_or_default()
+    )
+}
+
+/// File of the cache entry with the given key in `dir`
+pub fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    // Keys hold paths, so they are hashed into a file name
+    let hash = hex::encode(Sha256::digest(key.as_bytes()));
+    dir.join(format!("{}.json", &hash[..16]))
+}
+
+fn env_key(env: &Environment) -> String {
+    let source = config::file::source();
+    // `arcula.toml` is found in the current directory, so its name alone
+    // doesn't tell config files apart
+    let config_file = source
+        .path
+        .as_ref()
+        .map(|path| std::fs::canonicalize(path).unwrap_or_else(|_| path.clone()));
+    cache_key(
+        env.name(),
+        config_file.as_deref(),
+        source.profile.as_deref(),
+        config::db_prefix(env),
+    )
+}
+
+fn cache_enabled() -> bool {
+    config::file::get().cache.enabled != Some(false)
+}
+
+/// Fetch the databases of an environment and store them in the cache
+pub async fn refresh(env: &Environment) -> Result<Vec<String>> {
+    let databases = get_databases(env).await?;
+    if !cache_enabled() {
+        return Ok(databases);
+    }
+
+    let key = env_key(env);
+    let entry = CachedDatabases {
+        key: key.clone(),
+        fetched_at: chrono::Utc::now().timestamp(),
+        databases: databases.clone(),
+    };
+    if let Err(e) = entry.save(&entry_path(&get_state_dir().join(CACHE_DIR), &key)) {
+        warn!("Failed to update the database cache: {}", e);
+    }
+
+    Ok(databases)
+}
+
+/// The databases of an environment for interactive prompts.
+///
+/// A fresh cached list is returned immediately while it is refreshed in the
+/// background; otherwise the list is fetched and cached first. Lists can
+/// lag behind the cluster by up to the TTL, so callers looking for a
+/// database the list lacks should [`refresh`] it before giving up.
+pub async fn get_databases_cached(env: &Environment) -> Result<Vec<String>> {
+    if !cache_enabled() {
+        return get_databases(env).await;
+    }
+
+    let ttl = config::file::get()
+        .cache
+        .databases_ttl
+        .unwrap_or(DEFAULT_TTL);
+    let key = env_key(env);
+    let entry = CachedDatabases::load(&entry_path(&get_state_dir().join(CACHE_DIR), &key))
+        .filter(|entry| entry.key == key && entry.is_fresh(ttl, chrono::Utc::now().timestamp()));
+    match entry {
+        Some(entry) => {
+            let env = env.clone();
+            tokio::spawn(async move {
+                if let Err(e) = refresh(&env).await {
+                    warn!("Failed to refresh the databases of {}: {}", env, e);
+                }
+            });
+            Ok(entry.databases)
+        }
+        None => refresh(env).await,
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use inquire::Select;
+use serde::{Deserialize, Serialize};
+use std::io::IsTerminal;
+
+use ::mongodb::bson::{Bson, DateTime};
+
+use crate::config::MongoConfig;
+use crate::utils::mongodb;
+
+/// Which version wins when target documents changed after the source export started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Prefer {
+    /// Replace the fresher target documents with their source version
+    Source,
+    /// Keep the fresher target documents
+    Target,
+}
+
+/// Target documents of a collection modified after the source export started
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub collection: String,
+    pub ids: Vec<Bson>,
+}
+
+/// Find target documents whose `field` is later than the start of the export
+pub async fn find_conflicts(
+    target_config: &MongoConfig,
+    target_db: &str,
+    collections: &[String],
+    field: &str,
+    export_started: DateTime,
+) -> Result<Vec<Conflict>> {
+    let mut conflicts = Vec::new();
+    for collection in collections {
+        let ids = mongodb::find_ids_modified_since(
+            target_config,
+            target_db,
+            collection,
+            field,
+            export_started,
+        )
+        .await?;
+        if !ids.is_empty() {
+            conflicts.push(Conflict {
+                collection: collection.clone(),
+                ids,
+            });
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Decide how to handle the conflicting collections, prompting when no
+/// preference was given.
+///
+/// Without a preference and without a terminal to prompt on, the sync is aborted.
+pub fn resolve_preference(prefer: Option<Prefer>) -> Result<Prefer> {
+    if let Some(prefer) = prefer {
+        return Ok(prefer);
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Target documents are newer than the source export; use --prefer source|target to decide"
+        );
+    }
+
+    const KEEP: &str = "Keep the target versions";
+    const OVERWRITE: &str = "Overwrite them with the source versions";
+    const ABORT: &str = "Abort the sync";
+    match Select::new(
+        "How should these documents be handled?",
+        vec![KEEP, OVERWRITE, ABORT],
+    )
+    .prompt()?
+    {
+        KEEP => Ok(Prefer::Target),
+        OVERWRITE => Ok(Prefer::Source),
+        _ => anyhow::bail!("Sync aborted because of newer target documents"),
+    }
+}
+
+/// Overwrite conflicting target documents with their current source version.
+///
+/// Returns the number of documents replaced.
+pub async fn apply_source_versions(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    conflicts: &[Conflict],
+) -> Result<u64> {
+    let mut replaced = 0;
+    for conflict in conflicts {
+        let documents = mongodb::find_documents_by_id(
+            source_config,
+            source_db,
+            &conflict.collection,
+            conflict.ids.clone(),
+        )
+        .await?;
+        replaced +=
+            mongodb::replace_documents(target_config, target_db, &conflict.collection, documents)
+                .await?;
+    }
+    Ok(replaced)
+}
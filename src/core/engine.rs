@@ -0,0 +1,660 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use tempfile::TempDir;
+
+use crate::config::{self, MongoConfig, ReadPreference};
+use crate::core::incremental;
+use crate::core::progress::{self, SyncPhase, SyncProgress};
+use crate::core::sync::{SyncOptions, TransferSize};
+use crate::core::transform::{self, Transform};
+use crate::core::{redact, retry, sampling};
+use crate::utils::dump::DumpLayout;
+use crate::utils::mongodb::{self, ExportOptions, ImportError, ImportOptions, IndexTimer};
+
+/// How a sync moves the documents of a database from the source to the target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    /// mongodump into a temporary directory, then mongorestore
+    #[default]
+    Tools,
+    /// Read from the source and write to the target through the driver,
+    /// without MongoDB tools or a temporary directory
+    Driver,
+    /// Pipe `mongodump --archive` into `mongorestore --archive`, without a
+    /// temporary directory
+    Stream,
+}
+
+impl std::fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineKind::Tools => write!(f, "tools"),
+            EngineKind::Driver => write!(f, "driver"),
+            EngineKind::Stream => write!(f, "stream"),
+        }
+    }
+}
+
+/// The database an engine copies and what to copy of it
+pub struct Transfer<'a> {
+    pub source_config: &'a MongoConfig,
+    pub source_db: &'a str,
+    pub target_config: &'a MongoConfig,
+    pub target_db: &'a str,
+    pub options: &'a SyncOptions,
+    /// Source collections neither exported nor touched on the target
+    pub skipped: &'a [String],
+    /// Cutoff of an incremental sync, which copies only documents modified
+    /// after it and upserts them
+    pub modified_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Time the import spends restoring indexes
+    pub index_timer: IndexTimer,
+}
+
+impl Transfer<'_> {
+    fn export_options<'b>(&'b self, exclude: &'b [String]) -> ExportOptions<'b> {
+        ExportOptions {
+            collections: &self.options.collections,
+            exclude,
+            parallel_collections: self.options.parallel_collections,
+            read_preference: self.read_preference(),
+            oplog: self.options.consistent_snapshot,
+            without_documents: false,
+            timeout: self.options.export_timeout,
+        }
+    }
+
+    /// Read preference of the export, from the options or else the config of
+    /// the source environment
+    fn read_preference(&self) -> Option<ReadPreference> {
+        self.options
+            .read_preference
+            .or_else(|| config::read_preference(&self.source_config.environment))
+    }
+
+    fn import_options<'b>(
+        &'b self,
+        namespace_renames: &'b [(String, String)],
+    ) -> ImportOptions<'b> {
+        ImportOptions {
+            drop: self.options.drop_collections,
+            clear: self.options.clear_collections,
+            namespace_renames,
+            data_only: self.options.data_only,
+            index_timer: Some(&self.index_timer),
+            transform: None,
+            keep: self.skipped,
+            collections: &self.options.collections,
+            parallel_collections: self.options.parallel_collections,
+            insertion_workers: self.options.insertion_workers,
+            oplog_replay: self.options.consistent_snapshot,
+            upsert: self.modified_since.is_some(),
+            timeout: self.options.import_timeout,
+        }
+    }
+
+    /// Run a step of the transfer, retrying it on transient errors as the
+    /// retry policy of the sync allows
+    async fn retry<T, Fut>(&self, operation: &str, run: impl FnMut() -> Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        let on_retry = |retry: &retry::RetryAttempt| {
+            progress::report(
+                self.source_db,
+                SyncProgress::Retrying {
+                    operation: operation.to_string(),
+                    attempt: retry.attempt,
+                    attempts: retry.attempts,
+                    delay: retry.delay,
+                    error: format!("{:#}", retry.error),
+                },
+            )
+        };
+        retry::retry(self.options.retry, operation, on_retry, run).await
+    }
+
+    /// Pipeline whose results are copied instead of a whole collection: its
+    /// `--pipelines` entry followed by the selection of its `--limit`
+    fn export_pipeline(&self, collection: &str) -> Option<Vec<::mongodb::bson::Document>> {
+        let mut pipeline = self.options.pipelines.get(collection).cloned();
+        if let Some(count) = self.options.document_limit(collection) {
+            pipeline
+                .get_or_insert_with(Vec::new)
+                .extend(sampling::sampling_stages(count, self.options.sampling));
+        }
+        pipeline
+    }
+
+    /// Source query of a collection copied through the driver: its export
+    /// pipeline, preceded by the document selection of an incremental sync
+    /// and followed by the removal of dropped fields
+    fn source_pipeline(&self, collection: &str) -> Option<Vec<::mongodb::bson::Document>> {
+        let mut pipeline = self.export_pipeline(collection);
+        if let (Some(field), Some(since)) = (&self.options.incremental_field, self.modified_since) {
+            let mut stages = vec![incremental::modified_since_stage(field, since)];
+            stages.extend(pipeline.unwrap_or_default());
+            pipeline = Some(stages);
+        }
+        let dropped = self.options.dropped_fields(collection);
+        if !dropped.is_empty() {
+            pipeline
+                .get_or_insert_with(Vec::new)
+                .push(redact::unset_stage(&dropped));
+        }
+        pipeline
+    }
+
+    /// Target database and collection a source collection is imported into
+    pub fn target_namespace<'b>(&'b self, collection: &'b str) -> (&'b str, &'b str) {
+        match self
+            .options
+            .collection_map
+            .iter()
+            .find(|mapping| mapping.source_collection == collection)
+        {
+            Some(mapping) => (
+                mapping.target_db.as_deref().unwrap_or(self.target_db),
+                &mapping.target_collection,
+            ),
+            None => (self.target_db, collection),
+        }
+    }
+}
+
+/// A way of copying the documents of a database, driven by `sync_database`.
+///
+/// Backups, delta detection, conflict checks, verification and post-sync
+/// scripts are shared by all engines; an engine only reads the source and
+/// writes the target.
+// Engines are only used as concrete types, so the futures need no `Send` bound
+#[allow(async_fn_in_trait)]
+pub trait Engine {
+    /// Read the data to copy from the source
+    async fn export(&mut self, transfer: &Transfer<'_>) -> Result<()>;
+
+    /// Apply the `--pipelines` of the sync to the exported data. Returns
+    /// whether the exported data was changed, so the sync only reports a
+    /// transform phase when there was one.
+    async fn transform(&mut self, transfer: &Transfer<'_>) -> Result<bool>;
+
+    /// Source collections whose documents the import writes, sorted
+    fn exported_collections(&self, transfer: &Transfer<'_>) -> Result<Vec<String>>;
+
+    /// Write the exported data to the target
+    async fn import(&mut self, transfer: &Transfer<'_>) -> Result<()>;
+
+    /// Dump sizes of the exported collections by source name, empty for
+    /// engines that don't write the data to disk
+    fn transfer_sizes(&self, _transfer: &Transfer<'_>) -> Result<BTreeMap<String, TransferSize>> {
+        Ok(BTreeMap::new())
+    }
+}
+
+/// Copies through a mongodump directory restored with mongorestore.
+///
+/// A temporary directory is removed when the engine is dropped; one given
+/// with `--keep-dump` is left in place.
+pub struct ToolsEngine {
+    dump: DumpLayout,
+    _temp_dir: Option<TempDir>,
+    /// Collections with a `--limit`, dumped without documents and filled
+    /// in by the transform
+    limited: Vec<String>,
+}
+
+impl ToolsEngine {
+    /// Dump into a new temporary directory in `staging_dir`
+    pub fn new(staging_dir: &Path, compressed: bool) -> Result<Self> {
+        let temp_dir = crate::config::create_temp_dir_in(staging_dir).context(format!(
+            "Failed to create a temporary directory in {}",
+            staging_dir.display()
+        ))?;
+        Ok(Self {
+            dump: DumpLayout::new(temp_dir.path()).compressed(compressed),
+            _temp_dir: Some(temp_dir),
+            limited: Vec::new(),
+        })
+    }
+
+    /// Dump into a directory that outlives the engine
+    pub fn in_dir(dir: &Path, compressed: bool) -> Result<Self> {
+        std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        Ok(Self {
+            dump: DumpLayout::new(dir).compressed(compressed),
+            _temp_dir: None,
+            limited: Vec::new(),
+        })
+    }
+}
+
+impl Engine for ToolsEngine {
+    async fn export(&mut self, transfer: &Transfer<'_>) -> Result<()> {
+        // The oplog holds source namespaces, which mongorestore replays as they are
+        if transfer.options.consistent_snapshot && transfer.source_db != transfer.target_db {
+            anyhow::bail!(
+                "A consistent snapshot can't be restored into another database ({} -> {})",
+                transfer.source_db,
+                transfer.target_db
+            );
+        }
+        // A kept dump would mix in collections of an earlier one
+        for database in [transfer.source_db, transfer.target_db] {
+            if self.dump.has_database(database) {
+                anyhow::bail!(
+                    "{} already holds a dump of {}; remove it or use another --keep-dump directory",
+                    self.dump.root().display(),
+                    database
+                );
+            }
+        }
+        // Dumping limited collections in full would take as long as copying
+        // them, so only their indexes and options are dumped
+        if !transfer.options.document_limits.is_empty() {
+            let collections = transfer
+                .retry("Listing source collections", || {
+                    mongodb::list_collection_stats(transfer.source_config, transfer.source_db)
+                })
+                .await?;
+            self.limited = collections
+                .into_iter()
+                .filter(|c| {
+                    c.kind != "view"
+                        && transfer.options.includes(&c.name)
+                        && !transfer.skipped.contains(&c.name)
+                        && transfer.options.document_limit(&c.name).is_some()
+                })
+                .map(|c| c.name)
+                .collect();
+        }
+        let mut exclude = transfer.skipped.to_vec();
+        exclude.extend(self.limited.iter().cloned());
+
+        // mongodump rewrites the files a failed attempt left behind
+        transfer
+            .retry("Export", || {
+                mongodb::export_database(
+                    transfer.source_config,
+                    transfer.source_db,
+                    &self.dump,
+                    transfer.export_options(&exclude),
+                )
+            })
+            .await?;
+        if !self.limited.is_empty() {
+            transfer
+                .retry("Export", || {
+                    mongodb::export_database(
+                        transfer.source_config,
+                        transfer.source_db,
+                        &self.dump,
+                        ExportOptions {
+                            collections: &self.limited,
+                            exclude: &[],
+                            without_documents: true,
+                            ..transfer.export_options(&[])
+                        },
+                    )
+                })
+                .await?;
+        }
+
+        if !self.dump.has_database(transfer.source_db) {
+            let export_db_path = self.dump.database_dir(transfer.source_db);
+            anyhow::bail!(
+                "Export directory not found at: {}. The database may be empty.",
+                export_db_path.display()
+            );
+        }
+        if transfer.options.consistent_snapshot && !self.dump.has_oplog() {
+            anyhow::bail!("mongodump recorded no oplog; --oplog needs a replica set source");
+        }
+        Ok(())
+    }
+
+    async fn transform(&mut self, transfer: &Transfer<'_>) -> Result<bool> {
+        let (source_db, target_db) = (transfer.source_db, transfer.target_db);
+        let mut collections: Vec<&String> = transfer
+            .options
+            .pipelines
+            .keys()
+            .filter(|collection| transfer.options.includes(collection))
+            .chain(&self.limited)
+            .collect();
+        collections.sort();
+        collections.dedup();
+        let pipelines: Vec<_> = collections
+            .into_iter()
+            .map(|collection| {
+                let pipeline = transfer.export_pipeline(collection).unwrap_or_default();
+                (collection, pipeline)
+            })
+            .collect();
+        let dumped = self.dump.collections(source_db)?;
+        let redacted: Vec<(&String, Vec<&str>)> = dumped
+            .iter()
+            .map(|collection| (collection, transfer.options.dropped_fields(collection)))
+            .filter(|(_, fields)| !fields.is_empty())
+            .collect();
+        let ruled: Vec<(&String, Vec<_>)> = dumped
+            .iter()
+            .map(|collection| (collection, transfer.options.transform_rules(collection)))
+            .filter(|(_, rules)| !rules.is_empty())
+            .collect();
+        // Renaming the dump directory is part of the phase too
+        let transformed = !pipelines.is_empty()
+            || !redacted.is_empty()
+            || !ruled.is_empty()
+            || source_db != target_db;
+        if transformed {
+            progress::report(source_db, SyncProgress::PhaseStarted(SyncPhase::Transform));
+        }
+        let total = pipelines.len() as u64;
+        for (done, (collection, pipeline)) in pipelines.iter().enumerate() {
+            let count = mongodb::export_pipeline_results(
+                transfer.source_config,
+                source_db,
+                collection,
+                pipeline.clone(),
+                &self.dump,
+            )
+            .await?;
+            progress::report(
+                source_db,
+                SyncProgress::PipelineApplied {
+                    collection: collection.to_string(),
+                    documents: count,
+                    done: done as u64 + 1,
+                    total,
+                },
+            );
+        }
+        // After the pipelines, whose results hold the fields again
+        let total = redacted.len() as u64;
+        for (done, (collection, fields)) in redacted.iter().enumerate() {
+            let documents = redact::drop_fields_in_file(
+                &self.dump.collection_file(source_db, collection),
+                self.dump.is_compressed(),
+                fields,
+            )?;
+            progress::report(
+                source_db,
+                SyncProgress::FieldsDropped {
+                    collection: collection.to_string(),
+                    fields: fields.iter().map(|f| f.to_string()).collect(),
+                    documents,
+                    done: done as u64 + 1,
+                    total,
+                },
+            );
+        }
+        // Rules see the documents without their dropped fields, as with the
+        // driver engine
+        let total = ruled.len() as u64;
+        for (done, (collection, rules)) in ruled.iter().enumerate() {
+            let documents = transform::rewrite_file(
+                &self.dump.collection_file(source_db, collection),
+                self.dump.is_compressed(),
+                rules,
+            )?;
+            progress::report(
+                source_db,
+                SyncProgress::RulesApplied {
+                    collection: collection.to_string(),
+                    rules: rules.len(),
+                    documents,
+                    done: done as u64 + 1,
+                    total,
+                },
+            );
+        }
+        if source_db != target_db {
+            self.dump.rename_database(source_db, target_db)?;
+            progress::report(
+                source_db,
+                SyncProgress::DumpRenamed {
+                    from: source_db.to_string(),
+                    to: target_db.to_string(),
+                },
+            );
+        }
+        if transformed {
+            progress::report(source_db, SyncProgress::PhaseFinished(SyncPhase::Transform));
+        }
+        Ok(transformed)
+    }
+
+    fn exported_collections(&self, transfer: &Transfer<'_>) -> Result<Vec<String>> {
+        self.dump.collections(transfer.target_db)
+    }
+
+    async fn import(&mut self, transfer: &Transfer<'_>) -> Result<()> {
+        let namespace_renames: Vec<(String, String)> = transfer
+            .options
+            .collection_map
+            .iter()
+            .map(|mapping| mapping.namespaces(transfer.target_db))
+            .collect();
+
+        transfer
+            .retry("Import", || {
+                mongodb::import_database(
+                    transfer.target_config,
+                    transfer.target_db,
+                    &self.dump,
+                    transfer.import_options(&namespace_renames),
+                )
+            })
+            .await
+    }
+
+    fn transfer_sizes(&self, transfer: &Transfer<'_>) -> Result<BTreeMap<String, TransferSize>> {
+        let mut sizes = BTreeMap::new();
+        for collection in self.exported_collections(transfer)? {
+            let file = self.dump.collection_file(transfer.target_db, &collection);
+            let size = TransferSize {
+                on_disk: std::fs::metadata(&file)?.len(),
+                sent: self.dump.data_size(transfer.target_db, &collection)?,
+            };
+            sizes.insert(collection, size);
+        }
+        Ok(sizes)
+    }
+}
+
+/// Copies collection by collection through the driver.
+///
+/// Nothing is read before the import, so pipelines run as the source query
+/// of their collection during the import.
+#[derive(Default)]
+pub struct DriverEngine {
+    /// Collections and views to copy, views last as they may be defined on
+    /// the collections
+    collections: Vec<mongodb::CollectionStats>,
+}
+
+impl Engine for DriverEngine {
+    async fn export(&mut self, transfer: &Transfer<'_>) -> Result<()> {
+        let mut collections = transfer
+            .retry("Listing source collections", || {
+                mongodb::list_collection_stats(transfer.source_config, transfer.source_db)
+            })
+            .await?;
+        collections
+            .retain(|c| transfer.options.includes(&c.name) && !transfer.skipped.contains(&c.name));
+        collections.sort_by(|a, b| (a.kind == "view", &a.name).cmp(&(b.kind == "view", &b.name)));
+        if collections.is_empty() {
+            anyhow::bail!("No collections to copy from {}", transfer.source_db);
+        }
+        self.collections = collections;
+        Ok(())
+    }
+
+    async fn transform(&mut self, _transfer: &Transfer<'_>) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn exported_collections(&self, _transfer: &Transfer<'_>) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self
+            .collections
+            .iter()
+            .filter(|c| c.kind != "view")
+            .map(|c| c.name.clone())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    async fn import(&mut self, transfer: &Transfer<'_>) -> Result<()> {
+        let options = transfer.import_options(&[]);
+        // Clear the same collections as mongorestore would, not only the
+        // copied ones, so `--clear` means the same with every engine
+        transfer
+            .retry("Clearing target collections", || {
+                mongodb::clear_before_import(transfer.target_config, transfer.target_db, &options)
+            })
+            .await?;
+        let total = self.collections.len() as u64;
+        let mut restored = Vec::new();
+        for (done, collection) in self.collections.iter().enumerate() {
+            let (target_db, target_collection) = transfer.target_namespace(&collection.name);
+            let rules = transfer.options.transform_rules(&collection.name);
+            let options = ImportOptions {
+                transform: (!rules.is_empty()).then_some(&rules as &dyn Transform),
+                ..options
+            };
+            let copied = transfer
+                .retry(&format!("Copy of {}", collection.name), || {
+                    mongodb::copy_collection(
+                        transfer.source_config,
+                        (transfer.source_db, &collection.name),
+                        transfer.target_config,
+                        (target_db, target_collection),
+                        transfer.source_pipeline(&collection.name),
+                        transfer.read_preference(),
+                        options,
+                    )
+                })
+                .await
+                .map_err(|e| ImportError {
+                    restored: restored.clone(),
+                    message: format!("{:#}", e),
+                })?;
+            restored.push(format!("{}.{}", target_db, target_collection));
+            progress::report(
+                transfer.source_db,
+                SyncProgress::CollectionCopied {
+                    collection: collection.name.clone(),
+                    documents: copied,
+                    done: done as u64 + 1,
+                    total,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Copies through a mongodump archive piped straight into mongorestore.
+///
+/// Both tools run during the import, so nothing can be transformed on the
+/// way; `--pipelines` are rejected with this engine.
+#[derive(Default)]
+pub struct StreamEngine {
+    /// Source collections and views in the archive
+    collections: Vec<String>,
+    /// Source collections and views left out of the archive
+    excluded: Vec<String>,
+}
+
+impl Engine for StreamEngine {
+    async fn export(&mut self, transfer: &Transfer<'_>) -> Result<()> {
+        let names = transfer
+            .retry("Listing source collections", || {
+                mongodb::list_collection_names(transfer.source_config, transfer.source_db)
+            })
+            .await?;
+        (self.collections, self.excluded) = names
+            .into_iter()
+            .partition(|name| transfer.options.includes(name) && !transfer.skipped.contains(name));
+        if self.collections.is_empty() {
+            anyhow::bail!("No collections to copy from {}", transfer.source_db);
+        }
+        self.collections.sort();
+        Ok(())
+    }
+
+    async fn transform(&mut self, transfer: &Transfer<'_>) -> Result<bool> {
+        if transfer
+            .options
+            .pipelines
+            .keys()
+            .any(|collection| self.collections.contains(collection))
+        {
+            anyhow::bail!("Pipelines can't be applied while streaming; use --engine tools");
+        }
+        if self
+            .collections
+            .iter()
+            .any(|collection| transfer.options.document_limit(collection).is_some())
+        {
+            anyhow::bail!("Collections can't be limited while streaming; use --engine tools");
+        }
+        if self
+            .collections
+            .iter()
+            .any(|collection| !transfer.options.dropped_fields(collection).is_empty())
+        {
+            anyhow::bail!("Fields can't be dropped while streaming; use --engine tools");
+        }
+        if self
+            .collections
+            .iter()
+            .any(|collection| !transfer.options.transform_rules(collection).is_empty())
+        {
+            anyhow::bail!(
+                "Transform rules can't be applied while streaming; use --engine driver or tools"
+            );
+        }
+        Ok(false)
+    }
+
+    fn exported_collections(&self, _transfer: &Transfer<'_>) -> Result<Vec<String>> {
+        Ok(self.collections.clone())
+    }
+
+    async fn import(&mut self, transfer: &Transfer<'_>) -> Result<()> {
+        // The archive holds the source namespaces, so every collection that
+        // ends up elsewhere gets its own rename
+        let namespace_renames: Vec<(String, String)> = self
+            .collections
+            .iter()
+            .map(|collection| {
+                let (db, target) = transfer.target_namespace(collection);
+                (
+                    format!("{}.{}", transfer.source_db, collection),
+                    format!("{}.{}", db, target),
+                )
+            })
+            .filter(|(from, to)| from != to)
+            .collect();
+
+        transfer
+            .retry("Streaming", || {
+                mongodb::stream_database(
+                    transfer.source_config,
+                    transfer.source_db,
+                    transfer.target_config,
+                    transfer.target_db,
+                    transfer.export_options(&self.excluded),
+                    transfer.options.gzip,
+                    transfer.import_options(&namespace_renames),
+                )
+            })
+            .await
+    }
+}
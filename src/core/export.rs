@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Environment, MongoConfig};
+use crate::utils::mongodb;
+use crate::utils::theme;
+
+/// File format for analytics exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Flat CSV written by mongoexport
+    Csv,
+    /// Columnar Parquet with types inferred from the data
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// Parse a comma-separated field projection such as `_id,name,address.city`
+pub fn parse_fields(fields: &str) -> Result<Vec<String>> {
+    let mut parsed: Vec<String> = Vec::new();
+    for field in fields.split(',').map(str::trim) {
+        if field.is_empty() {
+            continue;
+        }
+        if field.starts_with('.') || field.ends_with('.') || field.contains("..") {
+            anyhow::bail!("Invalid field path '{}'", field);
+        }
+        if !parsed.iter().any(|f| f == field) {
+            parsed.push(field.to_string());
+        }
+    }
+
+    if parsed.is_empty() {
+        anyhow::bail!("At least one field is required");
+    }
+    Ok(parsed)
+}
+
+pub struct ExportConfig {
+    pub env: Environment,
+    pub database: String,
+    /// Collections to export; all non-system collections when empty
+    pub collections: Vec<String>,
+    pub fields: Vec<String>,
+    pub format: ExportFormat,
+    pub output_dir: PathBuf,
+}
+
+/// Export the projected fields of each collection to `<output_dir>/<collection>.<ext>`
+pub async fn perform_export(config: ExportConfig) -> Result<Vec<PathBuf>> {
+    let mongo_config = MongoConfig::from_env(config.env.clone())
+        .context(format!("Failed to get configuration for {}", config.env))?;
+
+    let collections = if config.collections.is_empty() {
+        mongodb::list_collection_stats(&mongo_config, &config.database)
+            .await?
+            .into_iter()
+            .map(|c| c.name)
+            .collect()
+    } else {
+        config.collections.clone()
+    };
+
+    if collections.is_empty() {
+        println!(
+            "{}",
+            format!("No collections found in {}:{}", config.env, config.database).yellow()
+        );
+        return Ok(Vec::new());
+    }
+
+    std::fs::create_dir_all(&config.output_dir).context(format!(
+        "Failed to create output directory {}",
+        config.output_dir.display()
+    ))?;
+
+    println!("\n{}", "Export plan:".bold().underline());
+    println!(
+        "{} {}:{}",
+        "Database:".green().bold(),
+        config.env,
+        config.database
+    );
+    println!("{} {}", "Fields:".green().bold(), config.fields.join(", "));
+    println!(
+        "{} {}",
+        "Format:".green().bold(),
+        config.format.extension().to_uppercase()
+    );
+    println!(
+        "{} {}",
+        "Output directory:".green().bold(),
+        config.output_dir.display()
+    );
+
+    let mut written = Vec::new();
+    for collection in &collections {
+        let output_file = export_file_path(&config.output_dir, collection, config.format);
+        match config.format {
+            ExportFormat::Csv => {
+                mongodb::export_collection_csv(
+                    &mongo_config,
+                    &config.database,
+                    collection,
+                    &config.fields,
+                    &output_file,
+                )
+                .await?;
+                println!("  {} {}", theme::check().green(), output_file.display());
+            }
+            ExportFormat::Parquet => {
+                let rows = mongodb::export_collection_parquet(
+                    &mongo_config,
+                    &config.database,
+                    collection,
+                    &config.fields,
+                    &output_file,
+                )
+                .await?;
+                println!(
+                    "  {} {} ({} rows)",
+                    theme::check().green(),
+                    output_file.display(),
+                    rows
+                );
+            }
+        }
+        written.push(output_file);
+    }
+
+    println!(
+        "\n{} {} collection(s) exported",
+        "Export completed:".green().bold(),
+        written.len()
+    );
+
+    Ok(written)
+}
+
+fn export_file_path(output_dir: &Path, collection: &str, format: ExportFormat) -> PathBuf {
+    output_dir.join(format!("{}.{}", collection, format.extension()))
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use ::mongodb::bson::{doc, Document};
+
+use crate::config::get_state_dir;
+
+/// File in the state directory holding the cutoff of the next incremental
+/// sync of a database into a target database
+pub fn state_file(source_env: &str, source_db: &str, target_env: &str, target_db: &str) -> PathBuf {
+    get_state_dir().join(format!(
+        "incremental-{}-{}-{}-{}.json",
+        source_env, source_db, target_env, target_db
+    ))
+}
+
+/// Where an incremental sync left off, so the next one without `--since`
+/// copies only what changed after it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncrementalState {
+    /// Date field the documents were selected by
+    pub field: String,
+    /// When the last successful incremental sync started reading the source
+    pub synced_at: DateTime<Utc>,
+}
+
+impl IncrementalState {
+    /// Read the saved state, or `None` if no incremental sync has finished yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).map(Some).context(format!(
+                "Invalid incremental sync state in {}",
+                path.display()
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Write the state atomically, so an interrupted write never loses the cutoff
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp, path).context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// The cutoff of an incremental sync: `--since` if given, else the start of
+/// the last incremental sync by the same field
+pub fn resolve_since(
+    field: &str,
+    since: Option<DateTime<Utc>>,
+    state: Option<&IncrementalState>,
+) -> Result<DateTime<Utc>> {
+    if let Some(since) = since {
+        return Ok(since);
+    }
+    match state {
+        Some(state) if state.field == field => Ok(state.synced_at),
+        Some(state) => anyhow::bail!(
+            "The last incremental sync selected documents by '{}', not '{}'; pass --since",
+            state.field,
+            field
+        ),
+        None => anyhow::bail!("No earlier incremental sync to continue from; pass --since"),
+    }
+}
+
+/// `$match` stage selecting the documents whose `field` is later than `since`.
+///
+/// Documents without the field are never selected.
+pub fn modified_since_stage(field: &str, since: DateTime<Utc>) -> Document {
+    let since = ::mongodb::bson::DateTime::from_millis(since.timestamp_millis());
+    doc! { "$match": { field: { "$gt": since } } }
+}
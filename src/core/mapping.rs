@@ -0,0 +1,234 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::utils::mongodb::validate_db_name;
+
+/// A single source → target database pair from a mapping file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseMapping {
+    pub source_db: String,
+    pub target_db: String,
+}
+
+/// A collection rename applied when importing into the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectionMapping {
+    /// Source database the rename is limited to; applies to every synced database when `None`
+    pub source_db: Option<String>,
+    pub source_collection: String,
+    /// Database to redirect the collection into; the sync target database when `None`
+    pub target_db: Option<String>,
+    pub target_collection: String,
+}
+
+impl CollectionMapping {
+    /// The `(from, to)` namespaces passed to `mongorestore --nsFrom/--nsTo`
+    /// when restoring a dump of `database`
+    pub fn namespaces(&self, database: &str) -> (String, String) {
+        (
+            format!("{}.{}", database, self.source_collection),
+            format!(
+                "{}.{}",
+                self.target_db.as_deref().unwrap_or(database),
+                self.target_collection
+            ),
+        )
+    }
+}
+
+/// Load database pairs from a mapping file.
+///
+/// YAML files (`.yaml`/`.yml`) map source database names to target names:
+///
+/// ```yaml
+/// orders: orders_stg
+/// users: users_stg
+/// ```
+///
+/// CSV files contain `source_db,target_db` rows, with an optional header row.
+/// Pairs are returned in file order.
+pub fn load_database_mapping(path: &Path) -> Result<Vec<DatabaseMapping>> {
+    load_mapping_file(path, parse_yaml_mapping, parse_csv_mapping)
+}
+
+/// Load collection renames from a mapping file.
+///
+/// Uses the same formats as database mapping files. A target of the form
+/// `database.collection` redirects the collection into another database:
+///
+/// ```yaml
+/// users: accounts          # renamed within the target database
+/// invoices: billing.invoices
+/// ```
+pub fn load_collection_mapping(path: &Path) -> Result<Vec<CollectionMapping>> {
+    load_mapping_file(
+        path,
+        parse_yaml_collection_mapping,
+        parse_csv_collection_mapping,
+    )
+}
+
+fn load_mapping_file<T>(
+    path: &Path,
+    parse_yaml: fn(&str) -> Result<Vec<T>>,
+    parse_csv: fn(&str) -> Result<Vec<T>>,
+) -> Result<Vec<T>> {
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read mapping file: {}", path.display()))?;
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    let mappings = match extension.as_deref() {
+        Some("yaml") | Some("yml") => parse_yaml(&content),
+        Some("csv") => parse_csv(&content),
+        _ => anyhow::bail!(
+            "Unsupported mapping file format: {} (expected .yaml, .yml or .csv)",
+            path.display()
+        ),
+    }
+    .context(format!("Invalid mapping file: {}", path.display()))?;
+
+    if mappings.is_empty() {
+        anyhow::bail!("Mapping file contains no entries: {}", path.display());
+    }
+
+    Ok(mappings)
+}
+
+pub fn parse_yaml_mapping(content: &str) -> Result<Vec<DatabaseMapping>> {
+    parse_yaml_pairs(content)?
+        .into_iter()
+        .map(|(source_db, target_db)| {
+            Ok(DatabaseMapping {
+                source_db,
+                target_db,
+            })
+        })
+        .collect()
+}
+
+pub fn parse_csv_mapping(content: &str) -> Result<Vec<DatabaseMapping>> {
+    parse_csv_pairs(content, ("source_db", "target_db"))?
+        .into_iter()
+        .map(|(source_db, target_db)| {
+            Ok(DatabaseMapping {
+                source_db,
+                target_db,
+            })
+        })
+        .collect()
+}
+
+pub fn parse_yaml_collection_mapping(content: &str) -> Result<Vec<CollectionMapping>> {
+    parse_yaml_pairs(content)?
+        .into_iter()
+        .map(|(source, target)| collection_mapping(source, &target))
+        .collect()
+}
+
+pub fn parse_csv_collection_mapping(content: &str) -> Result<Vec<CollectionMapping>> {
+    parse_csv_pairs(content, ("source_collection", "target"))?
+        .into_iter()
+        .map(|(source, target)| collection_mapping(source, &target))
+        .collect()
+}
+
+fn collection_mapping(source_collection: String, target: &str) -> Result<CollectionMapping> {
+    // Database names can't contain dots, so the first dot separates the database
+    let (target_db, target_collection) = match target.split_once('.') {
+        Some((db, collection)) => {
+            validate_db_name(db)?;
+            (Some(db.to_string()), collection.to_string())
+        }
+        None => (None, target.to_string()),
+    };
+
+    if target_collection.is_empty() {
+        anyhow::bail!("Missing target collection for '{}'", source_collection);
+    }
+
+    Ok(CollectionMapping {
+        source_db: None,
+        source_collection,
+        target_db,
+        target_collection,
+    })
+}
+
+/// Parse a `--ns-map` value of the form `source.users=target.customers`.
+///
+/// The database parts are optional: a source without one renames the
+/// collection in every synced database, and a target without one keeps it in
+/// the sync target database.
+pub fn parse_namespace_mapping(value: &str) -> Result<CollectionMapping> {
+    let (source, target) = value
+        .split_once('=')
+        .context(format!("Expected 'source=target', got '{}'", value))?;
+    let (source, target) = (source.trim(), target.trim());
+
+    let (source_db, source_collection) = match source.split_once('.') {
+        Some((db, collection)) => {
+            validate_db_name(db)?;
+            (Some(db.to_string()), collection)
+        }
+        None => (None, source),
+    };
+    if source_collection.is_empty() {
+        anyhow::bail!("Missing source collection in '{}'", value);
+    }
+
+    Ok(CollectionMapping {
+        source_db,
+        ..collection_mapping(source_collection.to_string(), target)?
+    })
+}
+
+fn parse_yaml_pairs(content: &str) -> Result<Vec<(String, String)>> {
+    let mapping: serde_yaml::Mapping = serde_yaml::from_str(content)?;
+
+    mapping
+        .into_iter()
+        .map(|(source, target)| {
+            let source = source.as_str().context("Source names must be strings")?;
+            let target = target
+                .as_str()
+                .context(format!("Target for '{}' must be a string", source))?;
+
+            Ok((source.to_string(), target.to_string()))
+        })
+        .collect()
+}
+
+fn parse_csv_pairs(content: &str, header: (&str, &str)) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != 2 || fields.iter().any(|f| f.is_empty()) {
+            anyhow::bail!(
+                "Line {}: expected '{},{}', got '{}'",
+                index + 1,
+                header.0,
+                header.1,
+                line
+            );
+        }
+
+        // Skip an optional header row
+        if pairs.is_empty() && fields[0] == header.0 && fields[1] == header.1 {
+            continue;
+        }
+
+        pairs.push((fields[0].to_string(), fields[1].to_string()));
+    }
+
+    Ok(pairs)
+}
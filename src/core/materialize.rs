@@ -0,0 +1,117 @@
+use ::mongodb::bson::{Bson, Document};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::str::FromStr;
+
+use crate::config::{self, Environment, MongoConfig};
+use crate::utils::mongodb;
+
+/// A collection in a given environment, written as `ENV:database.collection`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectionRef {
+    pub env: Environment,
+    pub database: String,
+    pub collection: String,
+}
+
+impl FromStr for CollectionRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (env, namespace) = s
+            .split_once(':')
+            .context(format!("Expected ENV:database.collection, got '{}'", s))?;
+        // Collection names may contain dots, database names may not
+        let (database, collection) = namespace
+            .split_once('.')
+            .context(format!("Expected ENV:database.collection, got '{}'", s))?;
+
+        if collection.is_empty() {
+            anyhow::bail!("Missing collection name in '{}'", s);
+        }
+        mongodb::validate_db_name(database)?;
+
+        Ok(Self {
+            env: env.parse()?,
+            database: database.to_string(),
+            collection: collection.to_string(),
+        })
+    }
+}
+
+impl CollectionRef {
+    /// The reference with the `db_prefix` of its environment added to the
+    /// database, as database names are given without it
+    pub fn scoped(self) -> Self {
+        Self {
+            database: config::scoped_database(&self.env, &self.database),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for CollectionRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}.{}", self.env, self.database, self.collection)
+    }
+}
+
+/// Parse a query filter written in MongoDB Extended JSON
+pub fn parse_query(query: &str) -> Result<Document> {
+    let json: serde_json::Value = serde_json::from_str(query).context("Query is not valid JSON")?;
+
+    match Bson::try_from(json)? {
+        Bson::Document(doc) => Ok(doc),
+        _ => anyhow::bail!("Query must be a JSON object"),
+    }
+}
+
+pub struct MaterializeConfig {
+    pub source: CollectionRef,
+    pub target: CollectionRef,
+    pub query: Document,
+    /// Drop the target collection before copying
+    pub drop_target: bool,
+}
+
+/// Copy documents matching a query from one collection into another via the driver,
+/// leaving all other target data untouched
+pub async fn perform_materialize(config: MaterializeConfig) -> Result<u64> {
+    let source_config = MongoConfig::from_env(config.source.env.clone()).context(format!(
+        "Failed to get configuration for {}",
+        config.source.env
+    ))?;
+    let target_config = MongoConfig::from_env(config.target.env.clone()).context(format!(
+        "Failed to get configuration for {}",
+        config.target.env
+    ))?;
+
+    println!("\n{}", "Materialization plan:".bold().underline());
+    println!("{} {}", "From:".green().bold(), config.source);
+    println!("{} {}", "To:".green().bold(), config.target);
+    println!("{} {}", "Query:".green().bold(), config.query);
+    println!(
+        "{} {}",
+        "Drop target collection:".green().bold(),
+        if config.drop_target { "Yes" } else { "No" }
+    );
+
+    let copied = mongodb::copy_documents(
+        &source_config,
+        (&config.source.database, &config.source.collection),
+        &target_config,
+        (&config.target.database, &config.target.collection),
+        config.query,
+        config.drop_target,
+    )
+    .await?;
+
+    println!(
+        "\n{} {} documents copied to {}",
+        "Materialization completed:".green().bold(),
+        copied,
+        config.target
+    );
+
+    Ok(copied)
+}
@@ -0,0 +1,397 @@
+use ::mongodb::bson::{self, Document};
+use ::mongodb::change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken};
+use ::mongodb::change_stream::ChangeStream;
+use ::mongodb::options::FullDocumentType;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use futures::StreamExt;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::{get_state_dir, Environment, MongoConfig};
+use crate::core::sync::{perform_sync, SyncConfig, SyncOptions};
+use crate::core::warnings;
+
+/// How many applied changes between progress log lines
+const LOG_INTERVAL: u64 = 1000;
+
+/// Server error codes meaning the change stream can't be resumed from a token:
+/// InvalidResumeToken, ChangeStreamFatalError and ChangeStreamHistoryLost
+const RESUME_LOST_CODES: &[i32] = &[260, 280, 286];
+
+pub struct MirrorConfig {
+    pub source_env: Environment,
+    pub target_env: Environment,
+    pub source_db: String,
+    pub target_db: String,
+    /// Start with an initial sync even if a saved position exists
+    pub resync: bool,
+}
+
+/// Why tailing the change stream ended
+#[derive(Debug, PartialEq, Eq)]
+pub enum TailEnd {
+    /// Interrupted by the user
+    Stopped,
+    /// The target can't be kept in step from the stream alone
+    ResyncNeeded(String),
+}
+
+/// What to do with a change event on the target
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChangeAction {
+    /// Write the full document, or delete it if it no longer exists
+    Upsert,
+    Delete,
+    DropCollection,
+    Resync,
+    Ignore,
+}
+
+pub fn change_action(operation: &OperationType) -> ChangeAction {
+    match operation {
+        OperationType::Insert | OperationType::Update | OperationType::Replace => {
+            ChangeAction::Upsert
+        }
+        OperationType::Delete => ChangeAction::Delete,
+        OperationType::Drop => ChangeAction::DropCollection,
+        // Renames and dropped databases end the stream with an invalidate event
+        OperationType::Rename | OperationType::DropDatabase | OperationType::Invalidate => {
+            ChangeAction::Resync
+        }
+        _ => ChangeAction::Ignore,
+    }
+}
+
+/// Whether an error means the saved position is no longer in the oplog
+pub fn is_resume_lost(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<::mongodb::error::Error>()
+            .is_some_and(|e| match e.kind.as_ref() {
+                ::mongodb::error::ErrorKind::Command(command) => {
+                    RESUME_LOST_CODES.contains(&command.code)
+                }
+                _ => false,
+            })
+    })
+}
+
+/// File in the state directory holding the change stream position of a mirror
+pub fn state_file(config: &MirrorConfig) -> PathBuf {
+    get_state_dir().join(format!(
+        "mirror-{}-{}-{}-{}.json",
+        config.source_env, config.source_db, config.target_env, config.target_db
+    ))
+}
+
+/// Position of a mirror in the source change stream, kept in the state
+/// directory so a restarted mirror continues with the next change
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MirrorState {
+    /// Resume token of the last change applied to the target
+    pub resume_token: Option<Document>,
+    /// Last change applied per collection
+    pub collections: BTreeMap<String, CollectionPosition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionPosition {
+    pub resume_token: Document,
+    /// Changes applied since the last initial sync
+    pub applied: u64,
+    pub updated_at: String,
+}
+
+impl MirrorState {
+    /// Read the saved state, or an empty one if the mirror hasn't run yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .context(format!("Invalid mirror state in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).context(format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Write the state atomically, so an interrupted write never loses the position
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp, path).context(format!("Failed to write {}", path.display()))
+    }
+
+    pub fn stream_position(&self) -> Result<Option<ResumeToken>> {
+        self.resume_token
+            .clone()
+            .map(|token| Ok(bson::from_document(token)?))
+            .transpose()
+    }
+
+    /// Start over after an initial sync consistent with the given position
+    pub fn reset(&mut self, token: &ResumeToken) -> Result<()> {
+        self.resume_token = Some(bson::to_document(token)?);
+        self.collections.clear();
+        Ok(())
+    }
+
+    /// Record a change applied to a collection
+    pub fn record(&mut self, collection: &str, token: &ResumeToken) -> Result<()> {
+        let token = bson::to_document(token)?;
+        let position = self
+            .collections
+            .entry(collection.to_string())
+            .or_insert_with(|| CollectionPosition {
+                resume_token: token.clone(),
+                applied: 0,
+                updated_at: String::new(),
+            });
+        position.resume_token = token.clone();
+        position.applied += 1;
+        position.updated_at = chrono::Utc::now().to_rfc3339();
+        self.resume_token = Some(token);
+        Ok(())
+    }
+}
+
+/// Mirror a source database into the target until interrupted: an initial
+/// sync followed by applying the changes of the source as they happen.
+///
+/// A new initial sync runs whenever the change stream can't be resumed.
+pub async fn run_mirror(config: MirrorConfig) -> Result<()> {
+    let source_config = MongoConfig::from_env(config.source_env.clone())?;
+    let target_config = MongoConfig::from_env(config.target_env.clone())?;
+    let state_path = state_file(&config);
+
+    let mut state = if config.resync {
+        MirrorState::default()
+    } else {
+        MirrorState::load(&state_path)?
+    };
+    if state.resume_token.is_some() {
+        println!(
+            "{} from the position saved in {}",
+            "Resuming mirror".green(),
+            state_path.display()
+        );
+        for (collection, position) in &state.collections {
+            println!(
+                "  {}: {} changes applied, last at {}",
+                collection, position.applied, position.updated_at
+            );
+        }
+    }
+
+    loop {
+        let resume_token = match state.stream_position()? {
+            Some(token) => token,
+            None => initial_sync(&config, &source_config, &mut state, &state_path).await?,
+        };
+
+        match tail(
+            &config,
+            &source_config,
+            &target_config,
+            resume_token,
+            &mut state,
+            &state_path,
+        )
+        .await?
+        {
+            TailEnd::Stopped => {
+                println!("{}", "Mirror stopped".yellow());
+                return Ok(());
+            }
+            TailEnd::ResyncNeeded(reason) => {
+                println!(
+                    "{} {}; running a new initial sync",
+                    "Warning:".yellow().bold(),
+                    reason
+                );
+                state = MirrorState::default();
+            }
+        }
+    }
+}
+
+/// Open a change stream on a database, looking up the full document of updates
+pub async fn open_stream(
+    config: &MongoConfig,
+    database: &str,
+    resume_after: Option<ResumeToken>,
+) -> Result<ChangeStream<ChangeStreamEvent<Document>>> {
+    let client = config.client().await?;
+    let stream = client
+        .database(database)
+        .watch()
+        .full_document(FullDocumentType::UpdateLookup)
+        .resume_after(resume_after)
+        .await
+        .context(format!("Failed to open a change stream on {}", database))?;
+    Ok(stream)
+}
+
+/// Copy the whole database and return the stream position it is consistent with.
+///
+/// The stream is opened before the copy, so changes made during it are
+/// replayed afterwards; replaying them is idempotent.
+async fn initial_sync(
+    config: &MirrorConfig,
+    source_config: &MongoConfig,
+    state: &mut MirrorState,
+    state_path: &Path,
+) -> Result<ResumeToken> {
+    let stream = open_stream(source_config, &config.source_db, None).await?;
+    let token = stream
+        .resume_token()
+        .context("The change stream returned no resume token")?;
+    drop(stream);
+
+    println!("{}", "Initial sync".bold().underline());
+    let report = perform_sync(SyncConfig {
+        source_env: config.source_env.clone(),
+        target_env: config.target_env.clone(),
+        source_db: config.source_db.clone(),
+        target_db: config.target_db.clone(),
+        options: SyncOptions {
+            drop_collections: true,
+            clear_collections: false,
+            ..SyncOptions::default()
+        },
+    })
+    .await;
+    warnings::print_summary(&report.warnings);
+    report.into_result()?;
+
+    state.reset(&token)?;
+    state.save(state_path)?;
+    Ok(token)
+}
+
+/// Apply source changes to the target until interrupted or a resync is needed.
+///
+/// The state is saved after every applied change, so a restart neither skips
+/// nor repeats changes.
+async fn tail(
+    config: &MirrorConfig,
+    source_config: &MongoConfig,
+    target_config: &MongoConfig,
+    resume_token: ResumeToken,
+    state: &mut MirrorState,
+    state_path: &Path,
+) -> Result<TailEnd> {
+    let mut stream = match open_stream(source_config, &config.source_db, Some(resume_token)).await {
+        Ok(stream) => stream,
+        Err(e) if is_resume_lost(&e) => {
+            return Ok(TailEnd::ResyncNeeded(
+                "The saved position is no longer in the source oplog".to_string(),
+            ))
+        }
+        Err(e) => return Err(e),
+    };
+
+    let client = target_config.client().await?;
+    let target = client.database(&config.target_db);
+
+    println!(
+        "{} changes of {}:{} to {}:{} (Ctrl+C to stop)",
+        "Mirroring".green().bold(),
+        config.source_env,
+        config.source_db,
+        config.target_env,
+        config.target_db
+    );
+
+    let mut applied: u64 = 0;
+    let end = loop {
+        let event = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break TailEnd::Stopped,
+            event = stream.next() => event,
+        };
+
+        let event = match event {
+            Some(Ok(event)) => event,
+            Some(Err(e)) => {
+                let e = anyhow::Error::from(e);
+                if is_resume_lost(&e) {
+                    break TailEnd::ResyncNeeded(
+                        "The change stream fell behind the source oplog".to_string(),
+                    );
+                }
+                return Err(e.context("Change stream failed"));
+            }
+            None => break TailEnd::ResyncNeeded("The change stream was closed".to_string()),
+        };
+
+        if change_action(&event.operation_type) == ChangeAction::Resync {
+            break TailEnd::ResyncNeeded(format!(
+                "The source database changed in a way that can't be replayed ({:?})",
+                event.operation_type
+            ));
+        }
+        apply_change(&target, &event).await?;
+
+        if let Some(collection) = event.ns.as_ref().and_then(|ns| ns.coll.as_deref()) {
+            state.record(collection, &event.id)?;
+            state.save(state_path)?;
+        }
+        applied += 1;
+        if applied.is_multiple_of(LOG_INTERVAL) {
+            info!("Applied {} changes", applied);
+        }
+    };
+
+    println!("{} {} changes", "Applied:".green(), applied);
+
+    Ok(end)
+}
+
+async fn apply_change(
+    target: &::mongodb::Database,
+    event: &ChangeStreamEvent<Document>,
+) -> Result<()> {
+    let Some(collection) = event.ns.as_ref().and_then(|ns| ns.coll.as_deref()) else {
+        return Ok(());
+    };
+    let collection = target.collection::<Document>(collection);
+    // Never fall back to an empty filter, which would match any document
+    let key = || {
+        event
+            .document_key
+            .clone()
+            .context("Change event without a document key")
+    };
+
+    match change_action(&event.operation_type) {
+        ChangeAction::Upsert => match &event.full_document {
+            Some(document) => {
+                collection
+                    .replace_one(key()?, document.clone())
+                    .upsert(true)
+                    .await?;
+            }
+            // The document was deleted again before the update could be looked up
+            None => {
+                collection.delete_one(key()?).await?;
+            }
+        },
+        ChangeAction::Delete => {
+            collection.delete_one(key()?).await?;
+        }
+        ChangeAction::DropCollection => {
+            collection.drop().await?;
+        }
+        ChangeAction::Resync => {}
+        ChangeAction::Ignore => {
+            warn!("Ignoring {:?} event", event.operation_type);
+        }
+    }
+
+    Ok(())
+}
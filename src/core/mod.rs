@@ -1 +1,26 @@
+pub mod access;
+pub mod approval;
+pub mod backup;
+pub mod cache;
+pub mod conflicts;
+pub mod engine;
+pub mod export;
+pub mod incremental;
+pub mod mapping;
+pub mod materialize;
+pub mod mirror;
+pub mod pipeline;
+pub mod plan;
+pub mod preflight;
+pub mod progress;
+pub mod prune;
+pub mod redact;
+pub mod resume;
+pub mod retry;
+pub mod sampling;
 pub mod sync;
+pub mod transform;
+pub mod two_way;
+pub mod variables;
+pub mod verify;
+pub mod warnings;
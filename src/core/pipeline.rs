@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use mongodb::bson::{Bson, Document};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::core::variables::{self, Variables};
+
+/// Aggregation pipelines keyed by collection name
+pub type CollectionPipelines = BTreeMap<String, Vec<Document>>;
+
+/// Stages selecting the `count` documents with the highest `field`, e.g. the
+/// newest ones by `_id`
+pub fn newest_stages(field: &str, count: u64) -> Vec<Document> {
+    vec![
+        mongodb::bson::doc! { "$sort": { field: -1 } },
+        mongodb::bson::doc! { "$limit": count as i64 },
+    ]
+}
+
+/// Load per-collection aggregation pipelines from a JSON file.
+///
+/// The file maps collection names to pipelines in MongoDB Extended JSON:
+///
+/// ```json
+/// {
+///   "events": [
+///     { "$match": { "createdAt": { "$gte": { "$date": "2024-01-01T00:00:00Z" } } } },
+///     { "$project": { "payload": 0 } }
+///   ]
+/// }
+/// ```
+pub fn load_pipelines(path: &Path, variables: &Variables) -> Result<CollectionPipelines> {
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read pipelines file: {}", path.display()))?;
+
+    parse_pipelines(&content, variables)
+        .context(format!("Invalid pipelines file: {}", path.display()))
+}
+
+/// Parse pipelines, substituting `{name}` variables in their string values
+pub fn parse_pipelines(content: &str, variables: &Variables) -> Result<CollectionPipelines> {
+    let mut json: serde_json::Map<String, serde_json::Value> = serde_json::from_str(content)?;
+    json.values_mut()
+        .for_each(|pipeline| variables::substitute_json(pipeline, variables));
+
+    json.into_iter()
+        .map(|(collection, value)| {
+            let stages = match Bson::try_from(value)? {
+                Bson::Array(stages) => stages,
+                _ => anyhow::bail!("Pipeline for '{}' must be an array of stages", collection),
+            };
+
+            let pipeline = stages
+                .into_iter()
+                .map(|stage| match stage {
+                    Bson::Document(doc) if doc.len() == 1 => Ok(doc),
+                    _ => anyhow::bail!(
+                        "Each stage in the pipeline for '{}' must be a single-key document",
+                        collection
+                    ),
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok((collection, pipeline))
+        })
+        .collect()
+}
@@ -0,0 +1,661 @@
+//! Plans of the operations arcula is about to run.
+//!
+//! A plan is built once per sync, batch of syncs, restore or backup and
+//! rendered as text for dry runs and confirmations, as JSON for `--output
+//! json` and RPC clients, and as markdown for reports such as GitHub step
+//! summaries, so all of them show the same settings. Dropping and clearing
+//! target collections are settings of a sync and listed in its plan. The
+//! outcomes of syncs are summarized here too.
+
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+use crate::config::Environment;
+use crate::core::backup::{BackupEstimate, BackupManifest, RestorePlan};
+use crate::core::engine::EngineKind;
+use crate::core::redact;
+use crate::core::retry::RetryPolicy;
+use crate::core::sync::{
+    delta_baseline, describe_limits, incremental_cutoff, SyncConfig, SyncReport, TargetOverlap,
+};
+use crate::utils::dump::DumpLayout;
+use crate::utils::{theme, units};
+
+/// What a plan does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Sync,
+    Restore,
+    Backup,
+}
+
+impl Operation {
+    /// Heading of the plan, e.g. `Synchronization plan`
+    pub fn title(self) -> &'static str {
+        match self {
+            Operation::Sync => "Synchronization plan",
+            Operation::Restore => "Restore plan",
+            Operation::Backup => "Backup plan",
+        }
+    }
+
+    fn noun(self) -> &'static str {
+        match self {
+            Operation::Sync => "synchronization",
+            Operation::Restore => "restore",
+            Operation::Backup => "backup",
+        }
+    }
+}
+
+/// A line of a plan, e.g. `Drop collections: Yes`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanItem {
+    pub label: String,
+    pub value: String,
+    /// Highlighted as worth a second look, e.g. target collections a clear empties
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub warning: bool,
+}
+
+/// What an operation is about to do
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub operation: Operation,
+    pub items: Vec<PlanItem>,
+    /// Settings of the operation as JSON fields, for clients that read them
+    /// instead of the items
+    details: Map<String, Value>,
+}
+
+impl Plan {
+    pub fn new(operation: Operation) -> Self {
+        Self {
+            operation,
+            items: Vec::new(),
+            details: Map::new(),
+        }
+    }
+
+    /// Add a line
+    pub fn add(&mut self, label: &str, value: impl std::fmt::Display) -> &mut Self {
+        self.items.push(PlanItem {
+            label: label.to_string(),
+            value: value.to_string(),
+            warning: false,
+        });
+        self
+    }
+
+    /// Add a highlighted line
+    pub fn warn(&mut self, label: &str, value: impl std::fmt::Display) -> &mut Self {
+        self.items.push(PlanItem {
+            label: label.to_string(),
+            value: value.to_string(),
+            warning: true,
+        });
+        self
+    }
+
+    /// Set a field of the JSON plan
+    pub fn detail(&mut self, key: &str, value: Value) -> &mut Self {
+        self.details.insert(key.to_string(), value);
+        self
+    }
+
+    /// The lines of the plan, indented as printed under a heading
+    pub fn render_text(&self) -> String {
+        self.items
+            .iter()
+            .map(|item| {
+                let label = format!("{}:", item.label);
+                if item.warning {
+                    format!("  {} {}\n", label.yellow().bold(), item.value.yellow())
+                } else {
+                    format!("  {} {}\n", label.green(), item.value)
+                }
+            })
+            .collect()
+    }
+
+    /// The plan as a markdown table under a heading
+    pub fn render_markdown(&self) -> String {
+        let escape = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+        let mut markdown = format!(
+            "### Arcula {}\n\n| | |\n|---|---|\n",
+            self.operation.title().to_lowercase()
+        );
+        for item in &self.items {
+            let value = escape(&item.value);
+            markdown.push_str(&format!(
+                "| **{}** | {} |\n",
+                escape(&item.label),
+                if item.warning {
+                    format!("⚠️ {}", value)
+                } else {
+                    value
+                }
+            ));
+        }
+        markdown
+    }
+
+    /// The plan as JSON: its details, the operation and the lines
+    pub fn to_json(&self) -> Value {
+        let mut json = self.details.clone();
+        json.insert("operation".to_string(), json!(self.operation));
+        json.insert("items".to_string(), json!(self.items));
+        Value::Object(json)
+    }
+
+    /// Print the plan under its heading before the operation runs
+    pub fn print(&self) {
+        println!(
+            "\n{}",
+            format!("{}:", self.operation.title()).bold().underline()
+        );
+        print!("{}", self.render_text());
+    }
+
+    /// Print the plan as the one of a dry run, which changes nothing
+    pub fn print_dry_run(&self) {
+        println!("\n{}", "=== DRY RUN MODE ===".yellow().bold());
+        println!(
+            "The following {} would be performed:\n",
+            self.operation.noun()
+        );
+        print!("{}", self.render_text());
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "Yes"
+    } else {
+        "No"
+    }
+}
+
+/// What a sync is about to do
+pub fn sync_plan(config: &SyncConfig) -> Plan {
+    let options = &config.options;
+    let mut plan = Plan::new(Operation::Sync);
+    plan.detail("source_env", json!(config.source_env))
+        .detail("source_db", json!(config.source_db))
+        .detail("target_env", json!(config.target_env))
+        .detail("target_db", json!(config.target_db))
+        .detail("create_backup", json!(options.create_backup))
+        .detail("drop_collections", json!(options.drop_collections))
+        .detail("clear_collections", json!(options.clear_collections))
+        .detail("schema_only", json!(options.schema_only))
+        .detail("collections", json!(options.collections))
+        .detail("exclude_collections", json!(options.exclude_collections))
+        .detail("drop_fields", json!(options.drop_fields))
+        .detail("transforms", json!(options.transforms))
+        .detail("document_limits", json!(options.document_limits))
+        .detail("sampling", json!(options.sampling))
+        .detail("engine", json!(options.engine.to_string()));
+
+    plan.add(
+        "Environments",
+        format!(
+            "{} {} {}",
+            config.source_env,
+            theme::arrow(),
+            config.target_env
+        ),
+    )
+    .add(
+        "Databases",
+        format!(
+            "{} {} {}",
+            config.source_db,
+            theme::arrow(),
+            config.target_db
+        ),
+    )
+    .add("Create backup", yes_no(options.create_backup));
+    if options.backup_source {
+        plan.add("Back up source", "Yes");
+    }
+    plan.add("Drop collections", yes_no(options.drop_collections))
+        .add("Clear collections", yes_no(options.clear_collections));
+    if options.schema_only {
+        plan.add("Schema only", "collections, indexes and views only");
+    }
+    if options.data_only {
+        plan.add("Data only", "target indexes and validators are kept");
+    }
+    if !options.collections.is_empty() {
+        plan.add("Collections", options.collections.join(", "));
+    }
+    if !options.exclude_collections.is_empty() {
+        plan.add(
+            "Excluded collections",
+            options.exclude_collections.join(", "),
+        );
+    }
+    match (options.skip_larger_than, options.keep_newest) {
+        (Some(limit), Some(count)) => {
+            plan.add(
+                "Trim",
+                format!(
+                    "newest {} documents by {} of collections larger than {}",
+                    count,
+                    options.newest_field(),
+                    units::format_bytes(limit)
+                ),
+            );
+        }
+        (Some(limit), None) => {
+            plan.add(
+                "Skip",
+                format!("collections larger than {}", units::format_bytes(limit)),
+            );
+        }
+        _ => {}
+    }
+    if !options.document_limits.is_empty() {
+        plan.add("Limit", describe_limits(options));
+    }
+    if options.delta {
+        plan.add(
+            "Delta",
+            format!(
+                "only collections that differ from {}",
+                delta_baseline(options)
+            ),
+        );
+    }
+    if options.gzip {
+        plan.add("Compression", "gzip");
+    }
+    if let Some(dir) = &options.keep_dump {
+        plan.add("Keep dump", dir.display());
+    }
+    if let Some(dir) = &options.temp_dir {
+        plan.add("Temp directory", dir.display());
+    }
+    if options.engine != EngineKind::default() {
+        plan.add("Engine", options.engine);
+    }
+    if let Some(parallel) = options.parallel_collections {
+        plan.add("Parallel collections", parallel);
+    }
+    if let Some(workers) = options.insertion_workers {
+        plan.add("Insertion workers", workers);
+    }
+    if let Some(read_preference) = options.read_preference {
+        plan.add("Read preference", read_preference);
+    }
+    if options.consistent_snapshot {
+        plan.add(
+            "Consistent snapshot",
+            "full dump with oplog, replayed on import",
+        );
+    }
+    if options.resume {
+        plan.add(
+            "Resume",
+            "skipping collections a failed attempt already copied",
+        );
+    }
+    if let Some(field) = &options.incremental_field {
+        plan.add(
+            "Incremental",
+            format!(
+                "documents with {} after {}",
+                field,
+                incremental_cutoff(options)
+            ),
+        );
+    }
+    if let Some(script) = &options.post_sync_script {
+        plan.add("Post-sync script", script.display());
+    }
+    for (collection, pipeline) in &options.pipelines {
+        plan.add(
+            "Pipeline",
+            format!("{} ({} stages)", collection, pipeline.len()),
+        );
+    }
+    for mapping in &options.collection_map {
+        let (from, to) = mapping.namespaces(&config.target_db);
+        plan.add("Collection mapping", format!("{} -> {}", from, to));
+    }
+    for (collection, fields) in &options.drop_fields {
+        plan.add(
+            "Drop fields",
+            format!(
+                "{} from {}",
+                fields.join(", "),
+                redact::describe_collection(collection)
+            ),
+        );
+    }
+    for (collection, rules) in &options.transforms {
+        let rules: Vec<String> = rules.iter().map(|rule| rule.to_string()).collect();
+        plan.add(
+            "Transform",
+            format!(
+                "{} on {}",
+                rules.join(", "),
+                redact::describe_collection(collection)
+            ),
+        );
+    }
+    if let Some(sample_size) = options.verify_sample {
+        plan.add(
+            "Verify sample",
+            format!("{} documents per collection", sample_size),
+        );
+    }
+    if let Some(action) = options.verify_counts {
+        plan.add("Verify counts", format!("{} on a mismatch", action));
+    }
+    if let Some(mode) = options.verify {
+        plan.add("Verify", mode);
+    }
+    if let Some(max_size) = options.max_source_size {
+        plan.add("Maximum source size", units::format_bytes(max_size));
+    }
+    if let Some(max_wait) = options.wait_for_target {
+        plan.add(
+            "Wait for target",
+            format!("up to {}", units::format_duration(max_wait)),
+        );
+    }
+    if let Some(max_duration) = options.max_duration {
+        plan.add(
+            "Maximum duration",
+            format!(
+                "{}{}",
+                units::format_duration(max_duration),
+                if options.restore_on_timeout {
+                    " (restore backup on timeout)"
+                } else {
+                    ""
+                }
+            ),
+        );
+    }
+    if options.retry != RetryPolicy::default() {
+        plan.add(
+            "Retries",
+            format!(
+                "{} (backoff {})",
+                options.retry.retries,
+                units::format_duration(options.retry.backoff)
+            ),
+        );
+    }
+    if options.engine != EngineKind::Driver {
+        if let Some(timeout) = options.export_timeout {
+            plan.add("Export timeout", units::format_duration(timeout));
+        }
+        if let Some(timeout) = options.import_timeout {
+            plan.add("Import timeout", units::format_duration(timeout));
+        }
+    }
+    if options.create_backup && !options.auto_restore {
+        plan.add(
+            "Restore on failure",
+            "No, the backup is kept for 'arcula restore'",
+        );
+    }
+    plan
+}
+
+/// What a batch of syncs is about to do. The options are shared by all of
+/// them, so they are listed once.
+pub fn batch_plan(configs: &[SyncConfig]) -> Plan {
+    let mut plan = Plan::new(Operation::Sync);
+    let databases: Vec<Value> = configs
+        .iter()
+        .map(|config| {
+            json!({
+                "source_env": config.source_env,
+                "source_db": config.source_db,
+                "target_env": config.target_env,
+                "target_db": config.target_db,
+            })
+        })
+        .collect();
+    plan.detail("databases", json!(databases));
+
+    plan.add("Databases", configs.len());
+    for config in configs {
+        plan.add(
+            "Sync",
+            format!(
+                "{}:{} {} {}:{}",
+                config.source_env,
+                config.source_db,
+                theme::arrow(),
+                config.target_env,
+                config.target_db
+            ),
+        );
+    }
+
+    let Some(first) = configs.first() else {
+        return plan;
+    };
+    let options = &first.options;
+    plan.detail("create_backup", json!(options.create_backup))
+        .detail("drop_collections", json!(options.drop_collections))
+        .detail("clear_collections", json!(options.clear_collections))
+        .detail("schema_only", json!(options.schema_only));
+    if options.schema_only {
+        plan.add("Schema only", "collections, indexes and views only");
+    }
+    if !options.collection_map.is_empty() {
+        plan.add(
+            "Collection mapping",
+            format!(
+                "{} collection(s) renamed on import",
+                options.collection_map.len()
+            ),
+        );
+    }
+    if let Some(script) = &options.post_sync_script {
+        plan.add("Post-sync script", script.display());
+    }
+    plan.add("Create backup", yes_no(options.create_backup))
+        .add("Drop collections", yes_no(options.drop_collections))
+        .add("Clear collections", yes_no(options.clear_collections));
+    plan
+}
+
+/// Add the target collections an import with drop or clear replaces, and
+/// those only on the target, to the plan of a sync
+pub fn add_target_overlap(plan: &mut Plan, overlap: &TargetOverlap, drop: bool) {
+    let (replaced, target_only) = if drop {
+        (
+            "Replaced on target (dropped)",
+            "Only on target (left untouched)",
+        )
+    } else {
+        (
+            "Replaced on target (cleared)",
+            "Only on target (emptied by clear)",
+        )
+    };
+    if !overlap.replaced.is_empty() {
+        plan.add(replaced, overlap.replaced.join(", "));
+    }
+    if !overlap.created.is_empty() {
+        plan.add("New on target", overlap.created.join(", "));
+    }
+    if !overlap.target_only.is_empty() {
+        plan.warn(target_only, overlap.target_only.join(", "));
+    }
+    plan.detail("target_overlap", json!(overlap));
+}
+
+/// What a restore of a backup into an environment is about to do
+pub fn restore_plan(
+    env: &Environment,
+    backup: &Path,
+    manifest: &BackupManifest,
+    restore: &RestorePlan,
+) -> Plan {
+    let manifest_file = DumpLayout::new(backup).manifest_file();
+    let mut plan = Plan::new(Operation::Restore);
+    plan.detail("environment", json!(env))
+        .detail("database", json!(manifest.database))
+        .detail("backup", json!(backup))
+        .detail("manifest", json!(manifest_file))
+        .detail("run_id", json!(manifest.run_id))
+        .detail("role", json!(manifest.role))
+        .detail("created_at", json!(manifest.created_at))
+        .detail("restore", json!(restore));
+
+    plan.add("Target", format!("{}:{}", env, manifest.database))
+        .add(
+            "Backup",
+            format!(
+                "{} backup of {}:{} taken {}",
+                manifest.role, manifest.environment, manifest.database, manifest.created_at
+            ),
+        )
+        .add("Manifest", manifest_file.display());
+
+    let mut replaced = Vec::new();
+    let mut created = Vec::new();
+    for collection in &restore.restored {
+        match collection.target_documents {
+            Some(documents) => replaced.push(format!(
+                "{} ({} {} {} documents)",
+                collection.name,
+                documents,
+                theme::arrow(),
+                collection.backup_documents
+            )),
+            None => created.push(format!(
+                "{} ({} documents)",
+                collection.name, collection.backup_documents
+            )),
+        }
+    }
+    if !replaced.is_empty() {
+        plan.add("Replaced on target (dropped)", replaced.join(", "));
+    }
+    if !created.is_empty() {
+        plan.add("New on target", created.join(", "));
+    }
+    if !restore.untouched.is_empty() {
+        plan.add(
+            "Only on target (left untouched)",
+            restore.untouched.join(", "),
+        );
+    }
+    plan
+}
+
+/// Where a backup is about to be written
+pub fn backup_plan(estimate: &BackupEstimate) -> Plan {
+    let mut plan = Plan::new(Operation::Backup);
+    plan.detail("backup", json!(estimate));
+    plan.add(
+        "Database",
+        format!("{}:{}", estimate.environment, estimate.database),
+    )
+    .add("Destination", estimate.destination.display());
+    if estimate.gzip {
+        plan.add("Compression", "gzip");
+    }
+    plan
+}
+
+/// Render sync outcomes as a markdown table, e.g. for a CI step summary
+pub fn render_markdown_summary(outcomes: &[SyncReport]) -> String {
+    let succeeded = outcomes.iter().filter(|o| o.is_success()).count();
+    let mut markdown = format!(
+        "### Arcula sync: {} succeeded, {} failed\n\n\
+         | | Source | Target | Duration | Phases | Warnings | Error |\n\
+         |---|---|---|---|---|---|---|\n",
+        succeeded,
+        outcomes.len() - succeeded
+    );
+
+    for outcome in outcomes {
+        let error = outcome
+            .error
+            .as_deref()
+            .unwrap_or_default()
+            .replace('|', "\\|")
+            .replace('\n', " ");
+        let warnings = outcome
+            .warnings
+            .iter()
+            .map(|w| format!("`{}`", w.code))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let phases = outcome
+            .phases
+            .iter()
+            .map(|p| format!("{} {}", p.phase, units::format_duration(p.duration)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        markdown.push_str(&format!(
+            "| {} | `{}:{}` | `{}:{}` | {}s | {} | {} | {} |\n",
+            if outcome.is_success() { "✅" } else { "❌" },
+            outcome.source_env,
+            outcome.source_db,
+            outcome.target_env,
+            outcome.target_db,
+            outcome.duration.as_secs(),
+            phases,
+            warnings,
+            error
+        ));
+    }
+
+    markdown
+}
+
+/// Print the per-database results of a batch run
+pub fn print_batch_summary(outcomes: &[SyncReport]) {
+    println!("\n{}", "Synchronization summary:".bold().underline());
+
+    for outcome in outcomes {
+        let target = format!("{}:{}", outcome.target_env, outcome.target_db);
+        match &outcome.error {
+            None => println!(
+                "  {} {} {} {}",
+                theme::check().green(),
+                outcome.source_db,
+                theme::arrow(),
+                target
+            ),
+            Some(e) => println!(
+                "  {} {} {} {} ({})",
+                theme::cross().red(),
+                outcome.source_db,
+                theme::arrow(),
+                target,
+                e
+            ),
+        }
+        for warning in &outcome.warnings {
+            println!(
+                "      {} [{}] {}",
+                "Warning:".yellow(),
+                warning.code,
+                warning.message
+            );
+        }
+    }
+
+    let succeeded = outcomes.iter().filter(|o| o.is_success()).count();
+    println!(
+        "\n{} {} succeeded, {} failed",
+        "Result:".bold(),
+        succeeded,
+        outcomes.len() - succeeded
+    );
+}
@@ -0,0 +1,368 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::config::MongoConfig;
+use crate::core::progress::{self, SyncProgress};
+use crate::core::sync::SyncOptions;
+use crate::core::warnings::{self, WarningCode};
+use crate::utils::mongodb::{self, CollectionStats, TargetActivity};
+use crate::utils::units;
+
+/// How long to wait between checks while waiting for a busy or unavailable target
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long `--on-busy-target wait` waits for a busy target by default
+pub const DEFAULT_BUSY_TARGET_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// What to do when the target has index builds or balancing in progress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BusyTargetAction {
+    /// Print a warning and restore anyway
+    #[default]
+    Warn,
+    /// Wait until the activity has finished
+    Wait,
+    /// Abort the sync
+    Abort,
+}
+
+/// What to do when an import likely needs more space than the target has free
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LowSpaceAction {
+    /// Print a warning and sync anyway
+    Warn,
+    /// Abort the sync before anything is written
+    #[default]
+    Abort,
+}
+
+/// A source collection a sync would copy, with its size after `--keep-newest`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CollectionEstimate {
+    pub name: String,
+    pub documents: u64,
+    /// Data size in bytes
+    pub size: u64,
+}
+
+/// What a sync is expected to move, from the statistics of the source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncEstimate {
+    /// Collections the sync would copy, sorted by name
+    pub collections: Vec<CollectionEstimate>,
+    /// Bytes of the dump written to the temporary directory before any
+    /// compression, 0 for engines that don't write one
+    pub dump_size: u64,
+    /// Directory the dump is written to, temporary unless `--keep-dump` names one
+    pub temp_dir: PathBuf,
+    /// Free bytes in that directory, absent when unknown
+    pub temp_free: Option<u64>,
+}
+
+impl SyncEstimate {
+    pub fn documents(&self) -> u64 {
+        self.collections.iter().map(|c| c.documents).sum()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.collections.iter().map(|c| c.size).sum()
+    }
+
+    /// Whether the dump fits in the temporary directory, if its free space is known
+    pub fn fits_temp_dir(&self) -> Option<bool> {
+        self.temp_free.map(|free| self.dump_size <= free)
+    }
+}
+
+/// The source collections a sync would copy, sorted by name. Collections
+/// over `--skip-larger-than` are left out, or with `--keep-newest` counted
+/// with that many documents of their average size, as are collections with
+/// a `--limit`.
+pub fn estimate_collections(
+    stats: &[CollectionStats],
+    options: &SyncOptions,
+) -> Vec<CollectionEstimate> {
+    let mut collections: Vec<CollectionEstimate> = stats
+        .iter()
+        .filter(|c| c.kind != "view" && options.includes(&c.name))
+        .filter_map(|c| {
+            let oversized = options.skip_larger_than.is_some_and(|limit| c.size > limit);
+            let newest = match (oversized, options.keep_newest) {
+                (false, _) => None,
+                (true, Some(count)) => Some(count),
+                (true, None) => return None,
+            };
+            let documents = newest
+                .into_iter()
+                .chain(options.document_limit(&c.name))
+                .fold(c.documents, u64::min);
+            Some(CollectionEstimate {
+                name: c.name.clone(),
+                documents,
+                size: limited_size(c.size, c.documents, documents),
+            })
+        })
+        .collect();
+    collections.sort_by(|a, b| a.name.cmp(&b.name));
+    collections
+}
+
+/// Share of `size` taken by `kept` of a collection's `documents`, assuming
+/// documents of the average size
+fn limited_size(size: u64, documents: u64, kept: u64) -> u64 {
+    if kept >= documents {
+        size
+    } else {
+        size / documents * kept
+    }
+}
+
+/// Estimate the bytes an import needs on the target: the storage and index
+/// files of the synced source collections, plus their index size again for
+/// the temporary files of index builds, less the files of the target
+/// collections it drops first. Collections trimmed by `--keep-newest` are
+/// left out, as their size after trimming is unknown, and collections with
+/// a `--limit` count with the share of their documents copied.
+pub fn required_space(
+    source: &[CollectionStats],
+    target: &[CollectionStats],
+    options: &SyncOptions,
+) -> u64 {
+    let synced: Vec<&CollectionStats> = source
+        .iter()
+        .filter(|c| options.includes(&c.name) && !options.trims(c))
+        .collect();
+    let incoming: u64 = synced
+        .iter()
+        .map(|c| {
+            let size = c.storage_size + 2 * c.index_size;
+            match options.document_limit(&c.name) {
+                Some(limit) => limited_size(size, c.documents, limit),
+                None => size,
+            }
+        })
+        .sum();
+    let freed: u64 = if options.drop_collections {
+        target
+            .iter()
+            .filter(|t| synced.iter().any(|s| s.name == t.name))
+            .map(|t| t.storage_size + t.index_size)
+            .sum()
+    } else {
+        0
+    };
+    incoming.saturating_sub(freed)
+}
+
+/// Compare the estimated size of an import with the free space on the target
+/// before anything is exported, and warn or abort as configured.
+///
+/// Failing to check (e.g. through a server that doesn't report filesystem
+/// sizes) only warns.
+pub async fn check_target_space(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    options: &SyncOptions,
+) -> Result<()> {
+    let space = async {
+        let source = mongodb::list_collection_stats(source_config, source_db).await?;
+        let target = mongodb::list_collection_stats(target_config, target_db).await?;
+        let free = mongodb::get_free_storage(target_config, target_db).await?;
+        anyhow::Ok(free.map(|free| (required_space(&source, &target, options), free)))
+    };
+    let (required, free) = match space.await {
+        Ok(Some(space)) => space,
+        Ok(None) => {
+            warnings::record(
+                WarningCode::TargetSpaceUnknown,
+                source_db,
+                format!(
+                    "{} doesn't report its free disk space; it wasn't checked",
+                    target_config.environment
+                ),
+            );
+            return Ok(());
+        }
+        Err(e) => {
+            warnings::record(
+                WarningCode::TargetSpaceUnknown,
+                source_db,
+                format!(
+                    "Could not check the free disk space of {}: {}",
+                    target_config.environment, e
+                ),
+            );
+            return Ok(());
+        }
+    };
+    if required <= free {
+        return Ok(());
+    }
+
+    let message = format!(
+        "{} needs about {} on {}, which has {} free",
+        target_db,
+        units::format_bytes(required),
+        target_config.environment,
+        units::format_bytes(free)
+    );
+    match options.on_low_space {
+        LowSpaceAction::Warn => {
+            warnings::record(
+                WarningCode::TargetSpaceLow,
+                source_db,
+                format!("{}; the restore may fail", message),
+            );
+            Ok(())
+        }
+        LowSpaceAction::Abort => {
+            anyhow::bail!("{}. Use --on-low-space warn to sync anyway", message)
+        }
+    }
+}
+
+/// Describe the activity that makes a target busy, or `None` when it is idle
+pub fn describe_activity(activity: &TargetActivity) -> Option<String> {
+    let mut reasons = Vec::new();
+    if !activity.index_builds.is_empty() {
+        reasons.push(format!(
+            "index builds in progress on {}",
+            activity.index_builds.join(", ")
+        ));
+    }
+    if activity.balancer_running {
+        reasons.push("the shard balancer is migrating chunks".to_string());
+    }
+
+    (!reasons.is_empty()).then(|| reasons.join("; "))
+}
+
+/// Check the target for index builds and balancing before a restore and
+/// warn, wait for at most `max_wait` or abort as configured.
+///
+/// Failing to check (e.g. missing privileges for `currentOp`) only warns.
+/// Warnings are recorded for the sync of `database`.
+pub async fn check_target_activity(
+    target_config: &MongoConfig,
+    database: &str,
+    action: BusyTargetAction,
+    max_wait: Duration,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut waiting = false;
+    loop {
+        let activity = match mongodb::get_target_activity(target_config).await {
+            Ok(activity) => activity,
+            Err(e) => {
+                warnings::record(
+                    WarningCode::TargetActivityUnknown,
+                    database,
+                    format!(
+                        "Could not check {} for running operations: {}",
+                        target_config.environment, e
+                    ),
+                );
+                return Ok(());
+            }
+        };
+
+        let Some(reason) = describe_activity(&activity) else {
+            if waiting {
+                progress::report(
+                    database,
+                    SyncProgress::BusyTargetIdle {
+                        waited: started.elapsed(),
+                    },
+                );
+            }
+            return Ok(());
+        };
+
+        match action {
+            BusyTargetAction::Warn => {
+                warnings::record(
+                    WarningCode::TargetBusy,
+                    database,
+                    format!(
+                        "{}: {}; the restore may fail",
+                        target_config.environment, reason
+                    ),
+                );
+                return Ok(());
+            }
+            BusyTargetAction::Abort => {
+                anyhow::bail!("Target {} is busy: {}", target_config.environment, reason);
+            }
+            BusyTargetAction::Wait => {
+                if started.elapsed() + POLL_INTERVAL > max_wait {
+                    anyhow::bail!(
+                        "Target {} is still busy after waiting {}: {}. Raise busy_target_timeout \
+                         in the config file or use --on-busy-target warn",
+                        target_config.environment,
+                        units::format_duration(max_wait),
+                        reason
+                    );
+                }
+                if !waiting {
+                    progress::report(
+                        database,
+                        SyncProgress::WaitingForBusyTarget { reason, max_wait },
+                    );
+                    waiting = true;
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Poll the target until it accepts connections, for at most `max_wait`,
+/// e.g. while a container or cluster that was just created starts up
+pub async fn wait_for_target(
+    target_config: &MongoConfig,
+    database: &str,
+    max_wait: Duration,
+) -> Result<()> {
+    let started = Instant::now();
+    let deadline = started + max_wait;
+    let mut waiting = false;
+    loop {
+        // Each attempt waits for server selection, but never past the deadline
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let error = match tokio::time::timeout(remaining, mongodb::ping(target_config)).await {
+            Ok(Ok(())) => {
+                if waiting {
+                    progress::report(
+                        database,
+                        SyncProgress::TargetAvailable {
+                            waited: started.elapsed(),
+                        },
+                    );
+                }
+                return Ok(());
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "no response".to_string(),
+        };
+
+        if Instant::now() + POLL_INTERVAL >= deadline {
+            anyhow::bail!(
+                "Target {} didn't accept connections within {}: {}",
+                target_config.environment,
+                units::format_duration(max_wait),
+                error
+            );
+        }
+        if !waiting {
+            progress::report(database, SyncProgress::WaitingForTarget { error, max_wait });
+            waiting = true;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,286 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::core::backup::BackupRole;
+use crate::core::verify::SampleComparison;
+use crate::utils::events::{self, EventKind, ProgressEvent};
+
+static RENDERER: OnceLock<Box<dyn ProgressRenderer>> = OnceLock::new();
+
+/// Phases of a single-database sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncPhase {
+    Backup,
+    Export,
+    Transform,
+    Import,
+    /// Restoring the indexes of the imported collections, timed within the
+    /// import and only listed in its timings
+    Indexes,
+    Verify,
+    Script,
+}
+
+impl std::fmt::Display for SyncPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncPhase::Backup => write!(f, "backup"),
+            SyncPhase::Export => write!(f, "export"),
+            SyncPhase::Transform => write!(f, "transform"),
+            SyncPhase::Import => write!(f, "import"),
+            SyncPhase::Indexes => write!(f, "indexes"),
+            SyncPhase::Verify => write!(f, "verify"),
+            SyncPhase::Script => write!(f, "script"),
+        }
+    }
+}
+
+/// What the sync of a database reports while it runs, in the order it happens
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncProgress {
+    DatabaseStarted,
+    PhaseStarted(SyncPhase),
+    PhaseFinished(SyncPhase),
+    BackupCreated {
+        path: PathBuf,
+        role: BackupRole,
+    },
+    SchemaCopied {
+        target_db: String,
+        /// Collections and views created
+        count: usize,
+    },
+    /// `--resume` skips the collections a failed attempt already copied
+    Resumed {
+        collections: Vec<String>,
+    },
+    /// `--delta` can't compare collection contents and copies everything
+    HashesUnavailable,
+    /// Collections `--skip-larger-than` leaves out
+    OversizedSkipped {
+        collections: Vec<String>,
+        limit: u64,
+    },
+    /// Collections over `--skip-larger-than` of which `--keep-newest` copies
+    /// only the newest documents
+    OversizedTrimmed {
+        collections: Vec<String>,
+        limit: u64,
+        newest: u64,
+    },
+    /// Collections `--delta` leaves out because the target already matches
+    UnchangedSkipped {
+        collections: Vec<String>,
+    },
+    /// `--wait-for-target` polls a target that doesn't accept connections yet
+    WaitingForTarget {
+        error: String,
+        max_wait: Duration,
+    },
+    /// The target accepts connections after `--wait-for-target` waited for it
+    TargetAvailable {
+        waited: Duration,
+    },
+    /// `--on-busy-target wait` polls a target with index builds or balancing
+    /// in progress
+    WaitingForBusyTarget {
+        reason: String,
+        max_wait: Duration,
+    },
+    /// The target is idle after `--on-busy-target wait` waited for it
+    BusyTargetIdle {
+        waited: Duration,
+    },
+    /// A step failed on a transient error and is retried after `delay`
+    Retrying {
+        /// What is retried, e.g. `Export` or `Copy of users`
+        operation: String,
+        /// The failed attempt, counting from 1
+        attempt: u32,
+        attempts: u32,
+        delay: Duration,
+        error: String,
+    },
+    /// Only documents whose `field` is later than `since` are copied
+    IncrementalCutoff {
+        field: String,
+        since: chrono::DateTime<chrono::Utc>,
+    },
+    /// Every collection of the target already matches, nothing is copied
+    AlreadyInSync {
+        target_db: String,
+    },
+    Exported,
+    ExportFailed {
+        error: String,
+    },
+    PipelineApplied {
+        collection: String,
+        documents: u64,
+        done: u64,
+        total: u64,
+    },
+    /// Dropped fields were removed from the dump of a collection
+    FieldsDropped {
+        collection: String,
+        fields: Vec<String>,
+        /// Documents that had any of the fields
+        documents: u64,
+        done: u64,
+        total: u64,
+    },
+    /// The transform rules of a collection were applied to its dump
+    RulesApplied {
+        collection: String,
+        rules: usize,
+        /// Documents the rules changed
+        documents: u64,
+        done: u64,
+        total: u64,
+    },
+    DumpRenamed {
+        from: String,
+        to: String,
+    },
+    CollectionCopied {
+        collection: String,
+        documents: u64,
+        done: u64,
+        total: u64,
+    },
+    Imported {
+        target_db: String,
+    },
+    ImportFailed {
+        error: String,
+    },
+    SchemaCopyFailed {
+        error: String,
+    },
+    /// Target documents changed after the source export started, per collection
+    NewerTargetDocuments {
+        collections: Vec<(String, usize)>,
+    },
+    /// Newer target documents replaced with their source version (`--prefer source`)
+    SourceVersionsApplied {
+        documents: u64,
+    },
+    /// `--keep-dump` left the dump of the database in place
+    DumpKept {
+        path: PathBuf,
+    },
+    VerifyStarted {
+        sample_size: u64,
+    },
+    CollectionVerified {
+        collection: String,
+        comparison: SampleComparison,
+        done: u64,
+        total: u64,
+    },
+    ScriptStarted {
+        script: PathBuf,
+    },
+    ScriptOutput {
+        output: String,
+    },
+    BackupRestoreStarted {
+        path: PathBuf,
+    },
+    BackupRestored,
+    BackupRestoreFailed {
+        error: String,
+    },
+    /// The import failed and `--no-auto-restore` leaves the backup unrestored
+    BackupRestoreSkipped {
+        path: PathBuf,
+        target_db: String,
+    },
+    /// The failed import left the target as it was; `--resume` continues
+    /// after the collections it copied
+    ResumeSaved {
+        path: PathBuf,
+        collections: usize,
+    },
+    /// `--max-duration` passed and the sync is being aborted
+    TimedOut {
+        max_duration: Duration,
+    },
+    Completed,
+}
+
+impl SyncProgress {
+    /// The `--progress-events` event for this progress of the sync of a
+    /// source database, if it has one
+    pub fn event(&self, database: &str) -> Option<ProgressEvent> {
+        let collection = |phase, collection: &str, done, total| {
+            ProgressEvent::new(EventKind::Progress, database)
+                .phase(phase)
+                .collection(collection, done, total)
+        };
+        match self {
+            SyncProgress::PhaseStarted(phase) => {
+                Some(ProgressEvent::new(EventKind::PhaseStarted, database).phase(*phase))
+            }
+            SyncProgress::PhaseFinished(phase) => {
+                Some(ProgressEvent::new(EventKind::PhaseFinished, database).phase(*phase))
+            }
+            SyncProgress::PipelineApplied {
+                collection: name,
+                done,
+                total,
+                ..
+            } => Some(collection(SyncPhase::Transform, name, *done, *total)),
+            SyncProgress::FieldsDropped {
+                collection: name,
+                done,
+                total,
+                ..
+            } => Some(collection(SyncPhase::Transform, name, *done, *total)),
+            SyncProgress::RulesApplied {
+                collection: name,
+                done,
+                total,
+                ..
+            } => Some(collection(SyncPhase::Transform, name, *done, *total)),
+            SyncProgress::CollectionCopied {
+                collection: name,
+                done,
+                total,
+                ..
+            } => Some(collection(SyncPhase::Import, name, *done, *total)),
+            SyncProgress::CollectionVerified {
+                collection: name,
+                done,
+                total,
+                ..
+            } => Some(collection(SyncPhase::Verify, name, *done, *total)),
+            _ => None,
+        }
+    }
+}
+
+/// Frontend showing the progress of syncs, e.g. the console output of the CLI
+pub trait ProgressRenderer: Send + Sync {
+    fn render(&self, database: &str, progress: &SyncProgress);
+}
+
+/// Show sync progress with a renderer; only the first renderer set is used.
+/// Without one, progress is only sent as progress events.
+pub fn set_renderer(renderer: impl ProgressRenderer + 'static) {
+    let _ = RENDERER.set(Box::new(renderer));
+}
+
+/// Report progress of the sync of a source database to the progress event
+/// sink and the renderer
+pub fn report(database: &str, progress: SyncProgress) {
+    if let Some(event) = progress.event(database) {
+        events::emit(event);
+    }
+    if let Some(renderer) = RENDERER.get() {
+        renderer.render(database, &progress);
+    }
+}
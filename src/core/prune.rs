@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::TEMP_DIR_PREFIX;
+use crate::core::backup::read_manifest;
+use crate::utils::dump::DumpLayout;
+
+/// Kinds of files arcula leaves behind that `prune-temp` cleans up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// Export directory of a run that was killed before it could clean up
+    TempDump,
+    /// Backup whose dump doesn't match its manifest
+    BrokenBackup,
+    /// Mirror position that hasn't moved for a long time
+    MirrorState,
+    /// Half-written state file of an interrupted save
+    PartialStateFile,
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ArtifactKind::TempDump => "temp dump",
+            ArtifactKind::BrokenBackup => "broken backup",
+            ArtifactKind::MirrorState => "mirror checkpoint",
+            ArtifactKind::PartialStateFile => "partial state file",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleArtifact {
+    pub path: PathBuf,
+    pub kind: ArtifactKind,
+    pub reason: String,
+    /// Bytes freed by removing it
+    pub size: u64,
+}
+
+impl StaleArtifact {
+    pub fn remove(&self) -> Result<()> {
+        let result = if self.path.is_dir() {
+            std::fs::remove_dir_all(&self.path)
+        } else {
+            std::fs::remove_file(&self.path)
+        };
+        result.context(format!("Failed to remove {}", self.path.display()))
+    }
+}
+
+/// Directories searched for stale artifacts
+pub struct PruneLocations {
+    pub temp_dir: PathBuf,
+    pub backup_dir: PathBuf,
+    pub state_dir: PathBuf,
+}
+
+/// Find artifacts that are safe to remove.
+///
+/// Temp dumps, mirror checkpoints and partial state files count once they are
+/// older than `older_than`; broken backups regardless of their age.
+pub fn find_stale_artifacts(
+    locations: &PruneLocations,
+    older_than: Duration,
+    now: SystemTime,
+) -> Result<Vec<StaleArtifact>> {
+    let is_old = |path: &Path| -> bool {
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= older_than)
+    };
+    let mut artifacts = Vec::new();
+
+    for path in list_dir(&locations.temp_dir)? {
+        if file_name(&path).starts_with(TEMP_DIR_PREFIX) && path.is_dir() && is_old(&path) {
+            artifacts.push(artifact(
+                path,
+                ArtifactKind::TempDump,
+                "left by an aborted run",
+            ));
+        }
+    }
+
+    for path in list_dir(&locations.backup_dir)? {
+        if path.is_dir() {
+            if let Some(reason) = backup_problem(&path) {
+                artifacts.push(artifact(path, ArtifactKind::BrokenBackup, &reason));
+            }
+        }
+    }
+
+    for path in list_dir(&locations.state_dir)? {
+        let name = file_name(&path);
+        if !path.is_file() || !is_old(&path) {
+            continue;
+        }
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if extension.starts_with("tmp") {
+            artifacts.push(artifact(
+                path,
+                ArtifactKind::PartialStateFile,
+                "left by an interrupted save",
+            ));
+        } else if name.starts_with("mirror-") && extension == "json" {
+            artifacts.push(artifact(
+                path,
+                ArtifactKind::MirrorState,
+                "no changes applied recently",
+            ));
+        }
+    }
+
+    Ok(artifacts)
+}
+
+/// Why a backup can't be restored as recorded, if it can't.
+///
+/// Backups without a manifest are not checked, as older versions didn't
+/// write one and a running backup writes it last.
+pub fn backup_problem(path: &Path) -> Option<String> {
+    let dump = DumpLayout::open(path);
+    if !dump.manifest_file().exists() {
+        return None;
+    }
+    let manifest = match read_manifest(path) {
+        Ok(manifest) => manifest,
+        Err(e) => return Some(format!("{:#}", e)),
+    };
+
+    let missing: Vec<&str> = manifest
+        .collections
+        .iter()
+        .filter(|c| {
+            !dump.collection_file(&manifest.database, &c.name).exists()
+                || !dump.metadata_file(&manifest.database, &c.name).exists()
+        })
+        .map(|c| c.name.as_str())
+        .collect();
+    if missing.is_empty() {
+        None
+    } else {
+        Some(format!("missing dump of {}", missing.join(", ")))
+    }
+}
+
+fn artifact(path: PathBuf, kind: ArtifactKind, reason: &str) -> StaleArtifact {
+    StaleArtifact {
+        size: disk_usage(&path),
+        path,
+        kind,
+        reason: reason.to_string(),
+    }
+}
+
+fn list_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => {
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .collect();
+            paths.sort();
+            Ok(paths)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).context(format!("Failed to read {}", dir.display())),
+    }
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().and_then(|n| n.to_str()).unwrap_or("")
+}
+
+/// Total size of the files below a path
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
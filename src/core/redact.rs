@@ -0,0 +1,109 @@
+//! Fields dropped from documents on the way to the target, e.g. password
+//! hashes and personal data when pulling production data into development.
+//!
+//! mongodump can't project, so the tools engine rewrites the collection files
+//! of the dump before the import, and the driver engine drops the fields with
+//! an `$unset` stage of its source query.
+
+use anyhow::Result;
+use mongodb::bson::{doc, Bson, Document};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::core::transform::{self, Transform};
+
+/// Key of the fields dropped from every collection
+pub const ALL_COLLECTIONS: &str = "*";
+
+/// Dotted field paths to drop by collection name, or by [`ALL_COLLECTIONS`]
+pub type DroppedFields = BTreeMap<String, Vec<String>>;
+
+/// Parse a `--drop-field` value: `collection:field.path`, or a field path
+/// dropped from every collection
+pub fn parse_drop_field(value: &str) -> Result<(String, String)> {
+    let (collection, field) = match value.split_once(':') {
+        Some((collection, field)) if !collection.is_empty() => (collection, field),
+        Some(_) => anyhow::bail!("Missing collection in '{}'", value),
+        None => (ALL_COLLECTIONS, value),
+    };
+    validate_field(field)?;
+    Ok((collection.to_string(), field.to_string()))
+}
+
+/// Check a field path the config file or the command line drops
+pub fn validate_field(field: &str) -> Result<()> {
+    if field.is_empty() || field.split('.').any(str::is_empty) {
+        anyhow::bail!("Invalid field path '{}'", field);
+    }
+    if field.starts_with('$') {
+        anyhow::bail!("Field paths can't start with '$': '{}'", field);
+    }
+    // Documents without an _id would get new ones in the target
+    if field == "_id" {
+        anyhow::bail!("The _id field can't be dropped");
+    }
+    Ok(())
+}
+
+/// Fields dropped from a collection, those of every collection first
+pub fn fields_for<'a>(fields: &'a DroppedFields, collection: &str) -> Vec<&'a str> {
+    let mut dropped: Vec<&str> = Vec::new();
+    for key in [ALL_COLLECTIONS, collection] {
+        for field in fields.get(key).into_iter().flatten() {
+            if !dropped.contains(&field.as_str()) {
+                dropped.push(field);
+            }
+        }
+    }
+    dropped
+}
+
+/// A key of [`DroppedFields`] as shown in plans
+pub fn describe_collection(key: &str) -> &str {
+    if key == ALL_COLLECTIONS {
+        "every collection"
+    } else {
+        key
+    }
+}
+
+/// Aggregation stage dropping fields, appended to source queries
+pub fn unset_stage(fields: &[&str]) -> Document {
+    doc! { "$unset": fields }
+}
+
+/// Remove a dotted field path from a document, descending into arrays of
+/// embedded documents like `$unset` does. Returns whether anything was removed.
+pub fn remove_field(document: &mut Document, path: &str) -> bool {
+    let Some((head, rest)) = path.split_once('.') else {
+        return document.remove(path).is_some();
+    };
+    match document.get_mut(head) {
+        Some(Bson::Document(inner)) => remove_field(inner, rest),
+        Some(Bson::Array(items)) => items.iter_mut().fold(false, |removed, item| match item {
+            Bson::Document(inner) => remove_field(inner, rest) || removed,
+            _ => removed,
+        }),
+        _ => false,
+    }
+}
+
+/// Fields removed from every document
+#[derive(Debug)]
+struct DroppedFieldList<'a>(&'a [&'a str]);
+
+impl Transform for DroppedFieldList<'_> {
+    fn apply(&self, document: &mut Document) -> Result<bool> {
+        let mut removed = false;
+        for field in self.0 {
+            removed |= remove_field(document, field);
+        }
+        Ok(removed)
+    }
+}
+
+/// Drop fields from every document of a mongodump collection file in place.
+/// Returns the number of documents that had any of the fields.
+pub fn drop_fields_in_file(path: &Path, compressed: bool, fields: &[&str]) -> Result<u64> {
+    transform::rewrite_file(path, compressed, &DroppedFieldList(fields))
+}
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::{get_backup_dir, Environment};
+
+/// File in the backup directory recording what a failed sync of a database
+/// already copied into the target
+pub fn state_file(
+    source_env: &Environment,
+    source_db: &str,
+    target_env: &Environment,
+    target_db: &str,
+) -> PathBuf {
+    get_backup_dir().join(format!(
+        "resume_{}_{}_{}_{}.json",
+        source_env.to_string().to_lowercase(),
+        source_db,
+        target_env.to_string().to_lowercase(),
+        target_db
+    ))
+}
+
+/// Progress of a sync whose import failed with the target left as it was,
+/// so `--resume` can skip the collections it already copied
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResumeState {
+    /// Run that started the sync
+    pub run_id: String,
+    /// Backup of the target taken before the first attempt, if any
+    pub backup_path: Option<PathBuf>,
+    /// Source collections completely copied to the target
+    pub completed: Vec<String>,
+}
+
+impl ResumeState {
+    /// Read the saved state, or `None` if there is no failed sync to resume
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .context(format!("Invalid resume state in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Write the state atomically, so an interrupted write never loses progress
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp, path).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Add collections copied by another attempt, keeping the list sorted
+    pub fn record(&mut self, collections: impl IntoIterator<Item = String>) {
+        self.completed.extend(collections);
+        self.completed.sort();
+        self.completed.dedup();
+    }
+}
+
+/// Remove the state of a sync that no longer needs resuming
+pub fn clear(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).context(format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,137 @@
+//! Retries of sync operations failing on network blips, e.g. a mongodump
+//! losing its connection to Atlas in the middle of an export.
+
+use anyhow::Result;
+use log::warn;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::utils::mongodb::ToolTimeout;
+use crate::utils::units;
+
+/// Retries after the first attempt unless configured otherwise
+pub const DEFAULT_RETRIES: u32 = 2;
+/// Wait before the first retry unless configured otherwise
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(10);
+/// Longest wait between two attempts, however often the backoff was doubled
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Parts of MongoDB tool output that point at the network rather than at
+/// the data, matched case-insensitively
+const TRANSIENT_MESSAGES: &[&str] = &[
+    "connection reset",
+    "connection refused",
+    "connection closed",
+    "connection pool",
+    "broken pipe",
+    "server selection",
+    "no reachable servers",
+    "i/o timeout",
+    "network is unreachable",
+    "socket was unexpectedly closed",
+];
+
+/// How often and how far apart operations failing on transient errors are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts after the first one; 0 disables retries
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every further one
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry`, counting from 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+/// A failed attempt that is retried after `delay`
+#[derive(Debug)]
+pub struct RetryAttempt<'a> {
+    /// The failed attempt, counting from 1
+    pub attempt: u32,
+    /// All attempts the policy allows
+    pub attempts: u32,
+    pub delay: Duration,
+    pub error: &'a anyhow::Error,
+}
+
+/// Whether an error is likely to go away when the operation is retried:
+/// network and server selection errors of the driver and MongoDB tools losing
+/// their connection. A tool killed by `--timeout` is reported, not retried.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    use ::mongodb::error::{ErrorKind, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR};
+
+    if error.chain().any(|cause| cause.is::<ToolTimeout>()) {
+        return false;
+    }
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<::mongodb::error::Error>() {
+            return matches!(
+                e.kind.as_ref(),
+                ErrorKind::Io(_)
+                    | ErrorKind::ConnectionPoolCleared { .. }
+                    | ErrorKind::ServerSelection { .. }
+                    | ErrorKind::DnsResolve { .. }
+            ) || e.contains_label(RETRYABLE_WRITE_ERROR)
+                || e.contains_label(TRANSIENT_TRANSACTION_ERROR);
+        }
+        let message = cause.to_string().to_lowercase();
+        TRANSIENT_MESSAGES
+            .iter()
+            .any(|pattern| message.contains(pattern))
+    })
+}
+
+/// Run an operation, retrying it with backoff as long as it fails with
+/// transient errors and the policy allows. Every retry is logged and passed
+/// to `on_retry` first.
+pub async fn retry<T, F, Fut>(
+    policy: RetryPolicy,
+    operation: &str,
+    mut on_retry: impl FnMut(&RetryAttempt),
+    mut run: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let attempts = policy.retries.saturating_add(1);
+    let mut attempt = 1;
+    loop {
+        let error = match run().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < attempts && is_transient(&e) => e,
+            Err(e) => return Err(e),
+        };
+        let delay = policy.delay(attempt);
+        warn!(
+            "{} failed (attempt {} of {}), retrying in {}: {:#}",
+            operation,
+            attempt,
+            attempts,
+            units::format_duration(delay),
+            error
+        );
+        on_retry(&RetryAttempt {
+            attempt,
+            attempts,
+            delay,
+            error: &error,
+        });
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
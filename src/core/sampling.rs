@@ -0,0 +1,72 @@
+//! Copying a sample of the documents of collections instead of all of them,
+//! e.g. a thousand documents per collection for a local development database.
+//!
+//! A limited collection is copied through an aggregation selecting its first
+//! or random documents: the driver engine runs it as its source query, and
+//! the tools engine dumps only the metadata of the collection and writes the
+//! selected documents next to it.
+
+use anyhow::{Context, Result};
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::core::redact::ALL_COLLECTIONS;
+
+/// Most documents copied by collection name, or by [`ALL_COLLECTIONS`]
+pub type DocumentLimits = BTreeMap<String, u64>;
+
+/// Which documents of a limited collection are copied
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Sampling {
+    /// The first documents in natural order, which is cheap to read
+    #[default]
+    First,
+    /// Random documents, read with `$sample`
+    Random,
+}
+
+impl std::fmt::Display for Sampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sampling::First => write!(f, "first"),
+            Sampling::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// Parse a `--limit` value: `collection=N`, or a number of documents copied
+/// of every collection
+pub fn parse_limit(value: &str) -> Result<(String, u64)> {
+    let (collection, count) = match value.rsplit_once('=') {
+        Some((collection, count)) if !collection.is_empty() => (collection, count),
+        Some(_) => anyhow::bail!("Missing collection in '{}'", value),
+        None => (ALL_COLLECTIONS, value),
+    };
+    let count: u64 = count
+        .trim()
+        .parse()
+        .context(format!("Invalid number of documents '{}'", count))?;
+    if count == 0 {
+        anyhow::bail!("The number of documents must be at least 1");
+    }
+    Ok((collection.to_string(), count))
+}
+
+/// Most documents copied of a collection: its own limit, else the one of
+/// every collection
+pub fn limit_for(limits: &DocumentLimits, collection: &str) -> Option<u64> {
+    limits
+        .get(collection)
+        .or_else(|| limits.get(ALL_COLLECTIONS))
+        .copied()
+}
+
+/// Stages selecting `count` documents of a collection
+pub fn sampling_stages(count: u64, sampling: Sampling) -> Vec<Document> {
+    match sampling {
+        Sampling::First => vec![doc! { "$limit": count as i64 }],
+        Sampling::Random => vec![doc! { "$sample": { "size": count as i64 } }],
+    }
+}
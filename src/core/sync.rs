@@ -1,30 +1,272 @@
 use anyhow::{Context, Result};
-use colored::Colorize;
-use log::error;
-use std::path::PathBuf;
+use log::{error, info};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use crate::config::{Environment, MongoConfig};
-use crate::utils::mongodb;
+use crate::config::{Environment, MongoConfig, ReadPreference};
+use crate::core::backup::{self, BackupManifest, BackupRole};
+use crate::core::conflicts::{self, Prefer};
+use crate::core::engine::{DriverEngine, Engine, EngineKind, StreamEngine, ToolsEngine, Transfer};
+use crate::core::incremental::{self, IncrementalState};
+use crate::core::mapping::CollectionMapping;
+use crate::core::pipeline::{newest_stages, CollectionPipelines};
+use crate::core::plan;
+use crate::core::preflight::{self, BusyTargetAction, LowSpaceAction, SyncEstimate};
+use crate::core::progress::{self, SyncPhase, SyncProgress};
+use crate::core::redact::{self, DroppedFields};
+use crate::core::resume::{self, ResumeState};
+use crate::core::retry::{self, RetryPolicy};
+use crate::core::sampling::{self, DocumentLimits, Sampling};
+use crate::core::transform::{self, CollectionTransforms, TransformRule};
+use crate::core::verify::{self, ChecksumCheck, CountCheck, CountMismatchAction, VerifyMode};
+use crate::core::warnings::{self, SyncWarning, WarningCode};
+use crate::utils::disk;
+use crate::utils::dump::DumpLayout;
+use crate::utils::events::{self, EventKind, ProgressEvent};
+use crate::utils::github;
+use crate::utils::mongodb::{self, DatabaseStats, IndexTimer};
+use crate::utils::pattern;
+use crate::utils::progress::is_quiet;
+use crate::utils::run_id;
+use crate::utils::statsd::{AlertType, StatsdClient};
+use crate::utils::units;
 
+#[derive(Debug, Clone)]
 pub struct SyncOptions {
     pub create_backup: bool,
     pub drop_collections: bool,
     pub clear_collections: bool,
+    /// JavaScript file run with mongosh against the target after a successful import
+    pub post_sync_script: Option<PathBuf>,
+    /// Aggregation pipelines whose results replace the exported data of a collection
+    pub pipelines: CollectionPipelines,
+    /// Collection renames and redirects applied when importing into the target
+    pub collection_map: Vec<CollectionMapping>,
+    /// Fields removed from the documents before they reach the target
+    pub drop_fields: DroppedFields,
+    /// Rules changing the documents before they reach the target, applied
+    /// after the fields are dropped
+    pub transforms: CollectionTransforms,
+    /// Number of random documents per collection to check byte-for-byte after import
+    pub verify_sample: Option<u64>,
+    /// Compare the document counts of source and target after the import
+    pub verify_counts: Option<CountMismatchAction>,
+    /// Deeper verification after the import
+    pub verify: Option<VerifyMode>,
+    /// How long to wait for a target that doesn't accept connections yet
+    pub wait_for_target: Option<Duration>,
+    /// Abort the run once it has taken longer than this
+    pub max_duration: Option<Duration>,
+    /// Kill a mongodump run of the export that takes longer than this
+    pub export_timeout: Option<Duration>,
+    /// Kill a mongorestore run of the import that takes longer than this
+    pub import_timeout: Option<Duration>,
+    /// How often the export and import are retried after transient errors
+    pub retry: RetryPolicy,
+    /// Restore the backup when the run is aborted after the import has started
+    pub restore_on_timeout: bool,
+    /// Restore the backup when the import fails; when off, the target is
+    /// left as the failed import left it
+    pub auto_restore: bool,
+    /// Also back up the source database before exporting it
+    pub backup_source: bool,
+    /// Refuse to sync source databases whose data size exceeds this many bytes
+    pub max_source_size: Option<u64>,
+    /// Sync source databases without any documents, wiping the target
+    pub allow_empty_source: bool,
+    /// Copy collection definitions, indexes, validators and views but no documents
+    pub schema_only: bool,
+    /// Restore documents only, keeping the indexes and validators of the target
+    pub data_only: bool,
+    /// How to handle target documents modified after the export started when merging
+    pub prefer: Option<Prefer>,
+    /// Date field holding the last modification time of a document
+    pub updated_field: String,
+    /// What to do when the target has index builds or balancing in progress
+    pub on_busy_target: BusyTargetAction,
+    /// How long `on_busy_target` wait waits for the target to become idle
+    pub busy_target_timeout: Duration,
+    /// What to do when the import likely needs more disk space than the target has free
+    pub on_low_space: LowSpaceAction,
+    /// Only copy collections whose document count or content hash differs from the target
+    pub delta: bool,
+    /// Backup of the target to compare against instead of its current state
+    pub delta_from: Option<PathBuf>,
+    /// Collections to sync; the whole database when empty
+    pub collections: Vec<String>,
+    /// Collections neither exported nor touched on the target
+    pub exclude_collections: Vec<String>,
+    /// Collections whose data size exceeds this are excluded as well
+    pub skip_larger_than: Option<u64>,
+    /// Copy this many of the newest documents of collections over
+    /// `skip_larger_than` instead of excluding them
+    pub keep_newest: Option<u64>,
+    /// Field ordering documents for `keep_newest`; `_id` when not set
+    pub newest_by: Option<String>,
+    /// Most documents copied per collection
+    pub document_limits: DocumentLimits,
+    /// Which documents of collections with a limit are copied
+    pub sampling: Sampling,
+    /// Compress the export and the backup with `--gzip`
+    pub gzip: bool,
+    /// Directory the tools engine exports into and leaves the dump in,
+    /// instead of a temporary directory
+    pub keep_dump: Option<PathBuf>,
+    /// Directory the temporary dump is created in instead of `ARCULA_TEMP_DIR`
+    /// or the system temp directory
+    pub temp_dir: Option<PathBuf>,
+    /// How the documents are copied
+    pub engine: EngineKind,
+    /// Collections mongodump and mongorestore process at the same time
+    pub parallel_collections: Option<u64>,
+    /// Insertion workers mongorestore runs per collection, or `_id` ranges
+    /// the driver engine copies at the same time
+    pub insertion_workers: Option<u64>,
+    /// Replica set members the export reads from; the source environment's
+    /// `read_preference` from the config file when unset
+    pub read_preference: Option<ReadPreference>,
+    /// Dump with `--oplog` and restore with `--oplogReplay`, so databases
+    /// written during the export are copied as of a single point in time
+    pub consistent_snapshot: bool,
+    /// Continue a sync whose import failed, skipping the collections it
+    /// already copied and reusing its backup
+    pub resume: bool,
+    /// Date field selecting the documents of an incremental sync, which only
+    /// upserts documents modified after the cutoff
+    pub incremental_field: Option<String>,
+    /// Cutoff of the incremental sync; the start of the last one when unset
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl Default for SyncOptions {
+    /// Built-in defaults, overridden by the `[defaults]` table of the config file
     fn default() -> Self {
+        let defaults = &crate::config::file::get().defaults;
         Self {
-            create_backup: true,
-            drop_collections: true,
-            clear_collections: false,
+            create_backup: defaults.backup.unwrap_or(true),
+            drop_collections: defaults.drop.unwrap_or(true),
+            clear_collections: defaults.clear.unwrap_or(false),
+            post_sync_script: None,
+            pipelines: CollectionPipelines::new(),
+            collection_map: Vec::new(),
+            drop_fields: defaults.drop_fields.clone(),
+            transforms: defaults.transforms.clone(),
+            verify_sample: None,
+            verify_counts: defaults.verify_counts,
+            verify: None,
+            wait_for_target: None,
+            max_duration: None,
+            export_timeout: defaults.export_timeout.or(defaults.timeout),
+            import_timeout: defaults.import_timeout.or(defaults.timeout),
+            retry: RetryPolicy {
+                retries: defaults.retries.unwrap_or(retry::DEFAULT_RETRIES),
+                backoff: defaults.retry_backoff.unwrap_or(retry::DEFAULT_BACKOFF),
+            },
+            restore_on_timeout: false,
+            auto_restore: true,
+            backup_source: false,
+            max_source_size: defaults.max_source_size,
+            allow_empty_source: false,
+            schema_only: false,
+            data_only: false,
+            prefer: None,
+            updated_field: "updatedAt".to_string(),
+            on_busy_target: defaults.on_busy_target.unwrap_or_default(),
+            busy_target_timeout: defaults
+                .busy_target_timeout
+                .unwrap_or(preflight::DEFAULT_BUSY_TARGET_TIMEOUT),
+            on_low_space: defaults.on_low_space.unwrap_or_default(),
+            delta: false,
+            delta_from: None,
+            collections: Vec::new(),
+            exclude_collections: Vec::new(),
+            skip_larger_than: None,
+            keep_newest: None,
+            newest_by: None,
+            document_limits: DocumentLimits::new(),
+            sampling: Sampling::default(),
+            gzip: defaults.gzip.unwrap_or(false),
+            keep_dump: None,
+            temp_dir: None,
+            engine: EngineKind::default(),
+            parallel_collections: None,
+            insertion_workers: None,
+            read_preference: None,
+            consistent_snapshot: false,
+            resume: false,
+            incremental_field: None,
+            since: None,
         }
     }
 }
 
 impl SyncOptions {
+    /// Whether a source collection is part of the sync
+    pub fn includes(&self, collection: &str) -> bool {
+        (self.collections.is_empty() || self.collections.iter().any(|c| c == collection))
+            && !self.exclude_collections.iter().any(|c| c == collection)
+    }
+
+    /// Field whose highest values are the newest documents for `keep_newest`
+    pub fn newest_field(&self) -> &str {
+        self.newest_by.as_deref().unwrap_or("_id")
+    }
+
+    /// Directory the tools engine stages its dump in
+    pub fn staging_dir(&self) -> PathBuf {
+        self.temp_dir
+            .clone()
+            .unwrap_or_else(crate::config::get_temp_dir)
+    }
+
+    /// Whether a source collection is over `skip_larger_than` and trimmed to
+    /// its newest documents rather than left out
+    pub fn trims(&self, collection: &mongodb::CollectionStats) -> bool {
+        self.keep_newest.is_some()
+            && self
+                .skip_larger_than
+                .is_some_and(|limit| collection.size > limit)
+    }
+
+    /// Most documents copied of a source collection
+    pub fn document_limit(&self, collection: &str) -> Option<u64> {
+        sampling::limit_for(&self.document_limits, collection)
+    }
+
+    /// Fields dropped from the documents of a source collection
+    pub fn dropped_fields(&self, collection: &str) -> Vec<&str> {
+        redact::fields_for(&self.drop_fields, collection)
+    }
+
+    /// Rules applied to the documents of a source collection
+    pub fn transform_rules(&self, collection: &str) -> Vec<TransformRule> {
+        transform::rules_for(&self.transforms, collection)
+    }
+
+    /// Source collections that are transformed or renamed on the way to the
+    /// target, so their target copy is expected to differ
+    pub fn transformed_collections(&self) -> Vec<String> {
+        self.pipelines
+            .keys()
+            .cloned()
+            .chain(
+                self.collection_map
+                    .iter()
+                    .map(|m| m.source_collection.clone()),
+            )
+            .collect()
+    }
+
     pub fn update_collection_settings(&mut self) {
+        // Dropping would discard the target schema, so data-only syncs clear instead
+        if self.data_only && self.drop_collections {
+            self.drop_collections = false;
+            self.clear_collections = true;
+        }
+
         // If drop is enabled, automatically disable clear as it's redundant
         if self.drop_collections {
             self.clear_collections = false;
@@ -32,6 +274,7 @@ impl SyncOptions {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct SyncConfig {
     pub source_env: Environment,
     pub target_env: Environment,
@@ -40,12 +283,286 @@ pub struct SyncConfig {
     pub options: SyncOptions,
 }
 
+/// Result of a single database synchronization.
+///
+/// Serializes to JSON for consumers of the library; durations are seconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    pub run_id: String,
+    pub source_env: Environment,
+    pub source_db: String,
+    pub target_env: Environment,
+    pub target_db: String,
+    #[serde(rename = "duration_seconds", serialize_with = "serialize_seconds")]
+    pub duration: Duration,
+    pub error: Option<String>,
+    pub warnings: Vec<SyncWarning>,
+    /// Backup of the target taken before the import
+    pub backup_path: Option<PathBuf>,
+    /// Backup of the source taken with `backup_source`
+    pub source_backup_path: Option<PathBuf>,
+    /// Dump of the database left in place by `keep_dump`
+    pub dump_path: Option<PathBuf>,
+    /// Source collections and what the sync did with them, sorted by name
+    pub collections: Vec<CollectionReport>,
+    /// Phases in the order they finished
+    pub phases: Vec<PhaseReport>,
+    /// Document counts compared with `verify_counts`, sorted by collection
+    pub count_checks: Vec<CountCheck>,
+    /// Collection hashes compared with `verify`, sorted by collection
+    pub checksum_checks: Vec<ChecksumCheck>,
+}
+
+impl SyncReport {
+    pub fn new(config: &SyncConfig) -> Self {
+        Self {
+            run_id: run_id::get().to_string(),
+            source_env: config.source_env.clone(),
+            source_db: config.source_db.clone(),
+            target_env: config.target_env.clone(),
+            target_db: config.target_db.clone(),
+            duration: Duration::ZERO,
+            error: None,
+            warnings: Vec::new(),
+            backup_path: None,
+            source_backup_path: None,
+            dump_path: None,
+            collections: Vec::new(),
+            phases: Vec::new(),
+            count_checks: Vec::new(),
+            checksum_checks: Vec::new(),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The report of a successful sync, or its error
+    pub fn into_result(self) -> Result<Self> {
+        match &self.error {
+            Some(e) => Err(anyhow::anyhow!("{}", e)),
+            None => Ok(self),
+        }
+    }
+
+    fn record_phase(&mut self, phase: SyncPhase, started: Instant) {
+        self.record_phase_duration(phase, started.elapsed());
+    }
+
+    fn record_phase_duration(&mut self, phase: SyncPhase, duration: Duration) {
+        info!(
+            "{} phase of {} took {:.1}s",
+            phase,
+            self.source_db,
+            duration.as_secs_f64()
+        );
+        self.phases.push(PhaseReport { phase, duration });
+    }
+}
+
+/// A source collection in a sync report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CollectionReport {
+    pub name: String,
+    pub status: CollectionStatus,
+    /// Documents in the source collection after the sync
+    pub documents: u64,
+    /// Data size of the source collection in bytes
+    pub size: u64,
+    /// Bytes the engine wrote to disk and sent to the target, for copied
+    /// collections of engines going through a dump
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer: Option<TransferSize>,
+}
+
+/// Bytes of a collection on its way from the dump to the target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TransferSize {
+    /// Size of the dump file, after compression
+    pub on_disk: u64,
+    /// BSON bytes restored into the target
+    pub sent: u64,
+}
+
+impl TransferSize {
+    /// How many times smaller the dump is than the data it holds
+    pub fn compression_ratio(&self) -> f64 {
+        if self.on_disk == 0 {
+            1.0
+        } else {
+            self.sent as f64 / self.on_disk as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectionStatus {
+    Copied,
+    /// Left out by `--delta` as the target already matched
+    Unchanged,
+    /// Left out by `--collections` or `--exclude-collections`
+    Excluded,
+}
+
+/// How long a phase of a sync took
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseReport {
+    pub phase: SyncPhase,
+    #[serde(rename = "duration_seconds", serialize_with = "serialize_seconds")]
+    pub duration: Duration,
+}
+
+fn serialize_seconds<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Report entries of the source collections of a database, views left out.
+/// `transfers` holds the dump sizes of the copied collections by name.
+pub fn collection_reports(
+    stats: &[mongodb::CollectionStats],
+    unchanged: &[String],
+    unselected: &[String],
+    transfers: &BTreeMap<String, TransferSize>,
+) -> Vec<CollectionReport> {
+    let mut collections: Vec<CollectionReport> = stats
+        .iter()
+        .filter(|c| c.kind != "view")
+        .map(|c| CollectionReport {
+            name: c.name.clone(),
+            status: if unselected.contains(&c.name) {
+                CollectionStatus::Excluded
+            } else if unchanged.contains(&c.name) {
+                CollectionStatus::Unchanged
+            } else {
+                CollectionStatus::Copied
+            },
+            documents: c.documents,
+            size: c.size,
+            transfer: transfers.get(&c.name).copied(),
+        })
+        .collect();
+    collections.sort_by(|a, b| a.name.cmp(&b.name));
+    collections
+}
+
+/// How the collections of a target database relate to the ones about to be imported
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TargetOverlap {
+    /// Target collections that the import replaces
+    pub replaced: Vec<String>,
+    /// Imported collections that don't exist on the target yet
+    pub created: Vec<String>,
+    /// Target collections without a source counterpart
+    pub target_only: Vec<String>,
+}
+
+/// Compare source and target collection names, applying collection renames.
+///
+/// Collections redirected into another database by the collection map are left out.
+pub fn compute_target_overlap(
+    source_collections: &[String],
+    target_collections: &[String],
+    target_db: &str,
+    collection_map: &[CollectionMapping],
+) -> TargetOverlap {
+    let imported: Vec<String> = source_collections
+        .iter()
+        .filter_map(
+            |name| match collection_map.iter().find(|m| &m.source_collection == name) {
+                Some(mapping)
+                    if mapping
+                        .target_db
+                        .as_deref()
+                        .is_some_and(|db| db != target_db) =>
+                {
+                    None
+                }
+                Some(mapping) => Some(mapping.target_collection.clone()),
+                None => Some(name.clone()),
+            },
+        )
+        .collect();
+
+    let mut overlap = TargetOverlap::default();
+    for name in &imported {
+        if target_collections.contains(name) {
+            overlap.replaced.push(name.clone());
+        } else {
+            overlap.created.push(name.clone());
+        }
+    }
+    overlap.target_only = target_collections
+        .iter()
+        .filter(|name| !imported.contains(name))
+        .cloned()
+        .collect();
+
+    overlap.replaced.sort();
+    overlap.created.sort();
+    overlap.target_only.sort();
+    overlap
+}
+
+/// Look up the source and target collections of a sync and compare them
+pub async fn preview_target_overlap(config: &SyncConfig) -> Result<TargetOverlap> {
+    let source_config = MongoConfig::from_env(config.source_env.clone())?;
+    let target_config = MongoConfig::from_env(config.target_env.clone())?;
+
+    let mut source = mongodb::list_collection_names(&source_config, &config.source_db).await?;
+    let mut target = mongodb::list_collection_names(&target_config, &config.target_db).await?;
+    // Collections outside --collections are left alone on both sides
+    source.retain(|c| config.options.includes(c));
+    target.retain(|c| config.options.includes(c));
+
+    Ok(compute_target_overlap(
+        &source,
+        &target,
+        &config.target_db,
+        &config.options.collection_map,
+    ))
+}
+
+/// Estimate the documents and bytes a sync would copy from the source
+/// statistics, and whether its dump fits in the temporary directory
+pub async fn estimate_sync(config: &SyncConfig) -> Result<SyncEstimate> {
+    let source_config = MongoConfig::from_env(config.source_env.clone())?;
+    let stats = mongodb::list_collection_stats(&source_config, &config.source_db).await?;
+    let collections = preflight::estimate_collections(&stats, &config.options);
+
+    // Only the tools engine writes the data to disk; schema-only syncs copy none
+    let writes_dump = config.options.engine == EngineKind::Tools && !config.options.schema_only;
+    let temp_dir = config
+        .options
+        .keep_dump
+        .clone()
+        .unwrap_or_else(|| config.options.staging_dir());
+    let temp_free = disk::free_space(&temp_dir).await.ok();
+    Ok(SyncEstimate {
+        dump_size: if writes_dump {
+            collections.iter().map(|c| c.size).sum()
+        } else {
+            0
+        },
+        collections,
+        temp_dir,
+        temp_free,
+    })
+}
+
 /// Parse environment string and return Environment enum
 pub fn parse_environment(env_str: &str) -> Result<Environment> {
     Environment::from_str(env_str).context(format!("Invalid environment: {}", env_str))
 }
 
-/// Get list of databases for a given environment
+/// Get list of databases for a given environment.
+///
+/// With a `db_prefix` configured for the environment, only databases with the
+/// prefix are listed, and without it.
 pub async fn get_databases(env: &Environment) -> Result<Vec<String>> {
     let config = MongoConfig::from_env(env.clone())
         .context(format!("Failed to get configuration for {}", env))?;
@@ -54,179 +571,1315 @@ pub async fn get_databases(env: &Environment) -> Result<Vec<String>> {
 
     // Filter out system databases
     let dbs = all_dbs
-        .into_iter()
+        .iter()
         .filter(|db| !matches!(db.as_str(), "admin" | "local" | "config"))
+        .filter_map(|db| crate::config::unscoped_database(env, db))
+        .map(String::from)
         .collect();
 
     Ok(dbs)
 }
 
-/// Perform database synchronization with the given configuration
-pub async fn perform_sync(config: SyncConfig) -> Result<()> {
-    let source_config = MongoConfig::from_env(config.source_env.clone()).context(format!(
-        "Failed to get configuration for {}",
-        config.source_env
-    ))?;
-
-    let target_config = MongoConfig::from_env(config.target_env.clone()).context(format!(
-        "Failed to get configuration for {}",
-        config.target_env
-    ))?;
-
-    // Show summary before execution
-    println!("\n{}", "Synchronization plan:".bold().underline());
-    println!("{} {}", "From:".green().bold(), config.source_env);
-    println!("{} {}", "To:".green().bold(), config.target_env);
-    println!("{} {}", "Source database:".green().bold(), config.source_db);
-    println!("{} {}", "Target database:".green().bold(), config.target_db);
-    println!(
-        "{} {}",
-        "Create backup:".green().bold(),
-        if config.options.create_backup {
-            "Yes"
-        } else {
-            "No"
+/// Select the databases named by a list of names and glob patterns such as
+/// `tenant_*`, in the order given and without duplicates.
+///
+/// Fails when a name or pattern matches no database, as that is usually a typo.
+pub fn match_databases(databases: &[String], patterns: &[String]) -> Result<Vec<String>> {
+    let mut selected: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let matches: Vec<&String> = databases
+            .iter()
+            .filter(|db| pattern::glob_match(pattern, db))
+            .collect();
+        if matches.is_empty() {
+            anyhow::bail!("No database matches '{}'", pattern);
         }
-    );
-    println!(
-        "{} {}",
-        "Drop collections:".green().bold(),
-        if config.options.drop_collections {
-            "Yes"
-        } else {
-            "No"
+        for db in matches {
+            if !selected.contains(db) {
+                selected.push(db.clone());
+            }
         }
+    }
+    Ok(selected)
+}
+
+/// Find databases matching a tenant prefix such as `tenant_` or `tenant_*`.
+///
+/// Returns each matching database name together with its tenant id, which is
+/// the remainder of the name after the prefix.
+pub fn match_tenant_databases(databases: &[String], prefix: &str) -> Vec<(String, String)> {
+    let prefix = prefix.trim_end_matches('*');
+
+    databases
+        .iter()
+        .filter_map(|db| {
+            db.strip_prefix(prefix)
+                .filter(|id| !id.is_empty())
+                .map(|id| (db.clone(), id.to_string()))
+        })
+        .collect()
+}
+
+/// The first sync of a batch that would copy a database onto itself
+pub fn find_self_sync(configs: &[SyncConfig]) -> Option<&SyncConfig> {
+    configs
+        .iter()
+        .find(|c| c.source_env == c.target_env && c.source_db == c.target_db)
+}
+
+/// Apply a tenant rename rule such as `tenant_{id}_stg` for the given tenant id
+pub fn apply_tenant_rename(rule: &str, tenant_id: &str) -> String {
+    rule.replace("{id}", tenant_id)
+}
+
+/// Resolve a target database name template at the given point in time.
+///
+/// Supported placeholders are `{source_db}`, `{date}` (`YYYYMMDD`) and
+/// `{datetime}` (`YYYYMMDDHHMMSS`). Names without placeholders are returned as is.
+pub fn resolve_target_db_template(
+    template: &str,
+    source_db: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<String> {
+    let resolved = template
+        .replace("{source_db}", source_db)
+        .replace("{datetime}", &now.format("%Y%m%d%H%M%S").to_string())
+        .replace("{date}", &now.format("%Y%m%d").to_string());
+
+    if let Some(start) = resolved.find('{') {
+        let placeholder = resolved[start..]
+            .split_inclusive('}')
+            .next()
+            .unwrap_or(&resolved[start..]);
+        anyhow::bail!(
+            "Unknown placeholder '{}' in target database template '{}'",
+            placeholder,
+            template
+        );
+    }
+
+    mongodb::validate_db_name(&resolved)
+        .context(format!("Invalid target database name '{}'", resolved))?;
+
+    Ok(resolved)
+}
+
+/// Perform database synchronization with the given configuration.
+///
+/// Failures are part of the report; use [`SyncReport::into_result`] to treat
+/// them as errors.
+pub async fn perform_sync(config: SyncConfig) -> SyncReport {
+    let plan = plan::sync_plan(&config);
+    if !is_quiet() {
+        plan.print();
+    }
+
+    let deadline = config.options.max_duration.map(|d| Instant::now() + d);
+    let report = run_sync(&config, deadline).await;
+
+    if github::is_github_actions() {
+        github::append_step_summary(&format!(
+            "{}\n{}",
+            plan.render_markdown(),
+            plan::render_markdown_summary(std::slice::from_ref(&report))
+        ));
+    }
+
+    report
+}
+
+/// Sync a database and report the outcome with the warnings recorded meanwhile
+async fn run_sync(config: &SyncConfig, deadline: Option<Instant>) -> SyncReport {
+    let started = Instant::now();
+    let mut report = SyncReport::new(config);
+    let result =
+        async {
+            let source_config = MongoConfig::from_env(config.source_env.clone()).context(
+                format!("Failed to get configuration for {}", config.source_env),
+            )?;
+            let target_config = MongoConfig::from_env(config.target_env.clone()).context(
+                format!("Failed to get configuration for {}", config.target_env),
+            )?;
+
+            perform_sync_tracked(
+                &source_config,
+                &target_config,
+                &config.source_db,
+                &config.target_db,
+                &config.options,
+                deadline,
+                &mut report,
+            )
+            .await
+        }
+        .await;
+
+    report.duration = started.elapsed();
+    report.error = result.err().map(|e| format!("{:#}", e));
+    report.warnings = warnings::take();
+    report
+}
+
+/// Perform several synchronizations in sequence, continuing past failures.
+///
+/// A combined plan is printed up front; [`plan::print_batch_summary`] shows
+/// the outcomes.
+pub async fn perform_sync_batch(configs: Vec<SyncConfig>) -> Result<Vec<SyncReport>> {
+    let plan = plan::batch_plan(&configs);
+    if !is_quiet() {
+        plan.print();
+    }
+
+    let mut outcomes = Vec::with_capacity(configs.len());
+
+    // The maximum duration covers the whole batch, not each database
+    let run_started = Instant::now();
+
+    for config in configs {
+        let deadline = config.options.max_duration.map(|d| run_started + d);
+        outcomes.push(run_sync(&config, deadline).await);
+    }
+
+    if github::is_github_actions() {
+        github::append_step_summary(&format!(
+            "{}\n{}",
+            plan.render_markdown(),
+            plan::render_markdown_summary(&outcomes)
+        ));
+    }
+
+    Ok(outcomes)
+}
+
+/// Source collections whose data size exceeds `--skip-larger-than`, sorted,
+/// leaving out views and collections the sync excludes anyway
+pub fn oversized_collections(
+    stats: &[mongodb::CollectionStats],
+    options: &SyncOptions,
+) -> Vec<String> {
+    let Some(limit) = options.skip_larger_than else {
+        return Vec::new();
+    };
+    let mut oversized: Vec<String> = stats
+        .iter()
+        .filter(|c| c.kind != "view" && c.size > limit && options.includes(&c.name))
+        .map(|c| c.name.clone())
+        .collect();
+    oversized.sort();
+    oversized
+}
+
+/// The options of a sync with oversized collections excluded, or with
+/// `keep_newest` their pipelines preceded by the selection of their newest
+/// documents
+pub fn limit_oversized(options: &SyncOptions, oversized: Vec<String>) -> SyncOptions {
+    let Some(count) = options.keep_newest else {
+        let mut exclude_collections = options.exclude_collections.clone();
+        exclude_collections.extend(oversized);
+        return SyncOptions {
+            exclude_collections,
+            ..options.clone()
+        };
+    };
+
+    let mut pipelines = options.pipelines.clone();
+    for collection in oversized {
+        let mut stages = newest_stages(options.newest_field(), count);
+        stages.extend(pipelines.remove(&collection).unwrap_or_default());
+        pipelines.insert(collection, stages);
+    }
+    SyncOptions {
+        pipelines,
+        ..options.clone()
+    }
+}
+
+/// Refuse to sync a source database that is empty or larger than the maximum source size
+async fn check_source(
+    source_config: &MongoConfig,
+    source_db: &str,
+    options: &SyncOptions,
+) -> Result<()> {
+    if options.allow_empty_source && options.max_source_size.is_none() {
+        return Ok(());
+    }
+
+    let stats = mongodb::get_database_stats(source_config, source_db)
+        .await
+        .context(format!("Failed to read the size of {}", source_db))?;
+    check_source_stats(&stats, options)
+}
+
+/// Check source database statistics against the sync guards.
+///
+/// An empty source would otherwise drop every target collection and report success.
+pub fn check_source_stats(stats: &DatabaseStats, options: &SyncOptions) -> Result<()> {
+    let source_db = &stats.name;
+    // A schema-only sync moves no documents, so only missing collections matter
+    let empty = stats.collections == 0 || (stats.objects == 0 && !options.schema_only);
+    if !options.allow_empty_source && empty {
+        anyhow::bail!(
+            "Source database {} has no documents; syncing it would wipe the target. \
+             Use --allow-empty-source to sync it anyway",
+            source_db
+        );
+    }
+
+    if let (Some(max_size), false) = (options.max_source_size, options.schema_only) {
+        if stats.data_size > max_size {
+            anyhow::bail!(
+                "Source database {} is {}, larger than the maximum source size of {}",
+                source_db,
+                units::format_bytes(stats.data_size),
+                units::format_bytes(max_size)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore the backup after a failed import, unless the options keep the
+/// target as it is for inspection
+async fn recover_after_failure(
+    target_config: &MongoConfig,
+    source_db: &str,
+    target_db: &str,
+    backup_path: &Path,
+    options: &SyncOptions,
+) {
+    if options.auto_restore {
+        restore_after_failure(target_config, source_db, target_db, backup_path).await;
+    } else {
+        progress::report(
+            source_db,
+            SyncProgress::BackupRestoreSkipped {
+                path: backup_path.to_path_buf(),
+                target_db: target_db.to_string(),
+            },
+        );
+    }
+}
+
+/// Restore the backup of a target database after a failed or aborted sync and verify it
+async fn restore_after_failure(
+    target_config: &MongoConfig,
+    source_db: &str,
+    target_db: &str,
+    backup_path: &Path,
+) {
+    progress::report(
+        source_db,
+        SyncProgress::BackupRestoreStarted {
+            path: backup_path.to_path_buf(),
+        },
     );
-    println!(
-        "{} {}",
-        "Clear collections:".green().bold(),
-        if config.options.clear_collections {
-            "Yes"
-        } else {
-            "No"
+    match mongodb::restore_backup(target_config, target_db, backup_path).await {
+        Ok(()) => {
+            progress::report(source_db, SyncProgress::BackupRestored);
+            verify_restore(target_config, source_db, target_db, backup_path).await;
         }
+        Err(e) => {
+            error!("Failed to restore backup: {}", e);
+            progress::report(
+                source_db,
+                SyncProgress::BackupRestoreFailed {
+                    error: e.to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// Check a restored target against its backup manifest, reporting but not failing on mismatch
+async fn verify_restore(
+    target_config: &MongoConfig,
+    source_db: &str,
+    target_db: &str,
+    backup_path: &Path,
+) {
+    let result = match backup::read_manifest(backup_path) {
+        Ok(manifest) => backup::verify_against_manifest(target_config, target_db, &manifest).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        error!("Restore verification failed: {}", e);
+        warnings::record(
+            WarningCode::RestoreUnverified,
+            source_db,
+            format!("Restored data could not be verified: {}", e),
+        );
+    }
+}
+
+/// Run the post-sync mongosh script against the target database and show its output
+async fn run_post_sync_script(
+    target_config: &MongoConfig,
+    source_db: &str,
+    target_db: &str,
+    script: &std::path::Path,
+) -> Result<()> {
+    progress::report(
+        source_db,
+        SyncProgress::ScriptStarted {
+            script: script.to_path_buf(),
+        },
     );
 
-    perform_sync_single(
-        &source_config,
-        &target_config,
-        &config.source_db,
-        &config.target_db,
-        config.options.create_backup,
-        config.options.drop_collections,
-        config.options.clear_collections,
-    )
-    .await
+    let output = mongodb::run_mongosh_script(target_config, target_db, script)
+        .await
+        .context(format!("Post-sync script {} failed", script.display()))?;
+
+    let output = output.trim();
+    if !output.is_empty() {
+        progress::report(
+            source_db,
+            SyncProgress::ScriptOutput {
+                output: output.to_string(),
+            },
+        );
+    }
+
+    Ok(())
 }
 
-/// Perform synchronization between a single source and target database
-async fn perform_sync_single(
+/// Run a single-database sync, reporting its start and outcome to StatsD when configured
+async fn perform_sync_tracked(
     source_config: &MongoConfig,
     target_config: &MongoConfig,
     source_db: &str,
     target_db: &str,
-    should_backup: bool,
-    drop_collections: bool,
-    clear_collections: bool,
+    options: &SyncOptions,
+    deadline: Option<Instant>,
+    report: &mut SyncReport,
 ) -> Result<()> {
-    // Create temporary directory for export/import
-    let temp_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
-    let temp_path = temp_dir.path();
+    let statsd = StatsdClient::from_config(&crate::config::file::get().metrics);
+    let source = source_config.environment.to_string();
+    let target = target_config.environment.to_string();
+    let tags = [
+        ("source", source.as_str()),
+        ("target", target.as_str()),
+        ("db", source_db),
+    ];
+    let summary = format!("{}:{} → {}:{}", source, source_db, target, target_db);
+    // Event text only, as a tag per run would flood the metrics with tag values
+    let details = format!("{}\nRun ID: {}", summary, run_id::get());
 
-    println!("\nProcessing database: {}", source_db);
+    if let Some(statsd) = &statsd {
+        statsd.increment("sync.started", &tags);
+        statsd.event("Arcula sync started", &details, AlertType::Info, &tags);
+    }
 
-    // Backup target database if requested
-    let mut backup_path: Option<PathBuf> = None;
-    if should_backup {
-        match mongodb::create_backup(target_config, target_db).await {
-            Ok(path) => {
-                let path_display = path.display().to_string();
-                backup_path = Some(path);
-                println!("{} {}", "Backup created:".green(), path_display);
+    let github_actions = github::is_github_actions();
+    if github_actions {
+        github::group(&format!("Sync {}", summary));
+    }
+
+    events::emit(ProgressEvent::new(EventKind::SyncStarted, source_db));
+
+    let started = Instant::now();
+    let result = perform_sync_single(
+        source_config,
+        target_config,
+        source_db,
+        target_db,
+        options,
+        deadline,
+        report,
+    )
+    .await;
+
+    events::emit(match &result {
+        Ok(()) => ProgressEvent::new(EventKind::SyncFinished, source_db),
+        Err(e) => ProgressEvent::new(EventKind::SyncFailed, source_db).error(e),
+    });
+
+    if github_actions {
+        github::end_group();
+        if let Err(e) = &result {
+            github::error(&format!("Sync {} failed", summary), &e.to_string());
+        }
+    }
+
+    if let Some(statsd) = &statsd {
+        statsd.timing("sync.duration", started.elapsed(), &tags);
+        for phase in &report.phases {
+            let name = phase.phase.to_string();
+            let mut phase_tags = tags.to_vec();
+            phase_tags.push(("phase", name.as_str()));
+            statsd.timing("sync.phase.duration", phase.duration, &phase_tags);
+        }
+        match &result {
+            Ok(()) => {
+                statsd.increment("sync.completed", &tags);
+                statsd.event("Arcula sync finished", &details, AlertType::Success, &tags);
             }
             Err(e) => {
-                error!("Failed to create backup: {}", e);
-                println!(
-                    "{} Failed to create backup, proceeding without backup",
-                    "Warning:".yellow().bold()
+                statsd.increment("sync.failed", &tags);
+                statsd.event(
+                    "Arcula sync failed",
+                    &format!("{}\n{}", details, e),
+                    AlertType::Error,
+                    &tags,
                 );
             }
         }
     }
 
-    // Export database from source
-    match mongodb::export_database(source_config, source_db, temp_path).await {
-        Ok(_) => {
-            println!("{} {}", "Export completed:".green(), source_db);
-
-            // Verify the export directory structure
-            let export_db_path = temp_path.join(source_db);
-            if !export_db_path.exists() {
-                error!(
-                    "Export directory not found at expected path: {}",
-                    export_db_path.display()
-                );
-                anyhow::bail!(
-                    "Export directory not found at: {}. The database may be empty.",
-                    export_db_path.display()
-                );
+    result
+}
+
+/// Perform synchronization between a single source and target database,
+/// aborting it once the deadline has passed.
+///
+/// Aborting drops the running export or import, which kills the MongoDB tool
+/// processes and removes the temporary dump directory.
+async fn perform_sync_single(
+    source_config: &MongoConfig,
+    target_config: &MongoConfig,
+    source_db: &str,
+    target_db: &str,
+    options: &SyncOptions,
+    deadline: Option<Instant>,
+    report: &mut SyncReport,
+) -> Result<()> {
+    // Backup to restore if the run is aborted after the import has started
+    let mut restore_point: Option<PathBuf> = None;
+    let sync = sync_database(
+        source_config,
+        target_config,
+        source_db,
+        target_db,
+        options,
+        &mut restore_point,
+        report,
+    );
+
+    let Some(deadline) = deadline else {
+        return sync.await;
+    };
+    match tokio::time::timeout_at(deadline.into(), sync).await {
+        Ok(result) => result,
+        Err(_) => {
+            let max_duration = options.max_duration.unwrap_or_default();
+            progress::report(source_db, SyncProgress::TimedOut { max_duration });
+
+            if options.restore_on_timeout {
+                if let Some(path) = &restore_point {
+                    restore_after_failure(target_config, source_db, target_db, path).await;
+                }
             }
 
-            if source_db != target_db {
-                let target_db_path = temp_path.join(target_db);
-                let _ = std::fs::remove_dir_all(&target_db_path);
-                std::fs::rename(&export_db_path, &target_db_path)?;
-                println!(
-                    "{} {} -> {}",
-                    "Renamed export directory:".green(),
+            anyhow::bail!(
+                "Sync of {} aborted after exceeding the maximum duration of {}",
+                source_db,
+                units::format_duration(max_duration)
+            )
+        }
+    }
+}
+
+/// Source collections left out by `--collections` or `--exclude-collections`,
+/// failing when a selected collection doesn't exist in the source
+async fn unselected_collections(
+    source_config: &MongoConfig,
+    source_db: &str,
+    options: &SyncOptions,
+) -> Result<Vec<String>> {
+    if options.collections.is_empty() && options.exclude_collections.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let names = mongodb::list_collection_names(source_config, source_db).await?;
+    let missing: Vec<&str> = options
+        .collections
+        .iter()
+        .filter(|c| !names.contains(c))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Collection(s) not found in {}: {}",
+            source_db,
+            missing.join(", ")
+        );
+    }
+
+    Ok(names
+        .into_iter()
+        .filter(|name| !options.includes(name))
+        .collect())
+}
+
+/// What `--delta` compares the source with, for plans
+pub fn delta_baseline(options: &SyncOptions) -> String {
+    match &options.delta_from {
+        Some(path) => path.display().to_string(),
+        None if options.create_backup => "the target backup".to_string(),
+        None => "the target".to_string(),
+    }
+}
+
+/// The cutoff of an incremental sync, for plans
+pub fn incremental_cutoff(options: &SyncOptions) -> String {
+    match options.since {
+        Some(since) => since.to_rfc3339(),
+        None => "the last incremental sync".to_string(),
+    }
+}
+
+/// The documents `--limit` copies, for plans, e.g. `first 1000 documents of every collection`
+pub fn describe_limits(options: &SyncOptions) -> String {
+    options
+        .document_limits
+        .iter()
+        .map(|(collection, count)| {
+            format!(
+                "{} {} documents of {}",
+                options.sampling,
+                count,
+                redact::describe_collection(collection)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Source collections whose copy on the target is already identical, for `--delta`.
+///
+/// The target side comes from `--delta-from`, else the backup taken by this run,
+/// else the target itself. Transformed and renamed collections are always copied.
+async fn unchanged_collections(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    options: &SyncOptions,
+    backup_manifest: Option<&BackupManifest>,
+) -> Result<Vec<String>> {
+    let baseline = match (&options.delta_from, backup_manifest) {
+        (Some(path), _) => backup::read_manifest(path)?.collections,
+        (None, Some(manifest)) => manifest.collections.clone(),
+        (None, None) => backup::collect_checksums(target_config, target_db, false).await?,
+    };
+    let source = backup::collect_checksums(source_config, source_db, false).await?;
+    if source.iter().all(|c| c.hash.is_none()) {
+        progress::report(source_db, SyncProgress::HashesUnavailable);
+    }
+
+    let transformed = options.transformed_collections();
+    let mut unchanged = backup::unchanged_collections(&source, &baseline);
+    unchanged.retain(|name| !transformed.contains(name));
+    Ok(unchanged)
+}
+
+/// Back up one side of a sync and record its manifest, warning instead of
+/// failing when either can't be written
+async fn back_up(
+    config: &MongoConfig,
+    database: &str,
+    source_db: &str,
+    role: BackupRole,
+    options: &SyncOptions,
+) -> Option<(PathBuf, Option<BackupManifest>)> {
+    let path = match mongodb::create_backup(config, database, options.gzip).await {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to create {} backup: {}", role, e);
+            warnings::record(
+                WarningCode::BackupFailed,
+                source_db,
+                match role {
+                    BackupRole::Source => "Failed to back up the source, proceeding without it",
+                    BackupRole::Target => "Failed to create backup, proceeding without backup",
+                },
+            );
+            return None;
+        }
+    };
+    progress::report(
+        source_db,
+        SyncProgress::BackupCreated {
+            path: path.clone(),
+            role,
+        },
+    );
+
+    let manifest = match backup::write_manifest(config, database, &path, role).await {
+        Ok(manifest) => Some(manifest),
+        Err(e) => {
+            error!("Failed to write backup manifest: {}", e);
+            warnings::record(
+                WarningCode::BackupManifestFailed,
+                source_db,
+                "Failed to record backup checksums, the restore can't be verified",
+            );
+            None
+        }
+    };
+    Some((path, manifest))
+}
+
+/// The phases of a single-database sync: backup, export, transform, import,
+/// verification and post-sync script
+async fn sync_database(
+    source_config: &MongoConfig,
+    target_config: &MongoConfig,
+    source_db: &str,
+    target_db: &str,
+    options: &SyncOptions,
+    restore_point: &mut Option<PathBuf>,
+    report: &mut SyncReport,
+) -> Result<()> {
+    check_source(source_config, source_db, options).await?;
+
+    progress::report(source_db, SyncProgress::DatabaseStarted);
+
+    if let Some(max_wait) = options.wait_for_target {
+        preflight::wait_for_target(target_config, source_db, max_wait).await?;
+    }
+
+    // Collections over --skip-larger-than are left out like excluded ones,
+    // or trimmed to their newest documents
+    let sized_options;
+    let options = match options.skip_larger_than {
+        Some(limit) => {
+            let stats = mongodb::list_collection_stats(source_config, source_db).await?;
+            let oversized = oversized_collections(&stats, options);
+            if oversized.is_empty() {
+                options
+            } else {
+                let collections = oversized.clone();
+                progress::report(
                     source_db,
-                    target_db
+                    match options.keep_newest {
+                        Some(newest) => SyncProgress::OversizedTrimmed {
+                            collections,
+                            limit,
+                            newest,
+                        },
+                        None => SyncProgress::OversizedSkipped { collections, limit },
+                    },
                 );
+                sized_options = limit_oversized(options, oversized);
+                &sized_options
             }
+        }
+        None => options,
+    };
 
-            // Import database to target
-            match mongodb::import_database(
+    // Incremental syncs copy a fraction of the source, which can't be sized
+    // up front, and schemas take no space worth checking
+    if !options.schema_only && options.incremental_field.is_none() {
+        preflight::check_target_space(source_config, source_db, target_config, target_db, options)
+            .await?;
+    }
+
+    // Resolved before the backup, so a missing cutoff fails early. Taking the
+    // start time before anything is read makes consecutive runs overlap
+    // rather than miss documents.
+    let incremental = match &options.incremental_field {
+        Some(field) => {
+            let path = incremental::state_file(
+                source_config.environment.name(),
+                source_db,
+                target_config.environment.name(),
+                target_db,
+            );
+            let started = chrono::Utc::now();
+            let state = IncrementalState::load(&path)?;
+            let since = incremental::resolve_since(field, options.since, state.as_ref())?;
+            progress::report(
+                source_db,
+                SyncProgress::IncrementalCutoff {
+                    field: field.clone(),
+                    since,
+                },
+            );
+            let state = IncrementalState {
+                field: field.clone(),
+                synced_at: started,
+            };
+            Some((path, state, since))
+        }
+        None => None,
+    };
+
+    let resume_file = resume::state_file(
+        &source_config.environment,
+        source_db,
+        &target_config.environment,
+        target_db,
+    );
+    // Without --resume the sync starts over, so earlier progress is void
+    let resumed = if options.resume {
+        ResumeState::load(&resume_file)?
+    } else {
+        resume::clear(&resume_file)?;
+        None
+    };
+
+    // Back up the source and the target database if requested. A resumed
+    // sync keeps the backup of its first attempt, as the target has been
+    // partly overwritten since.
+    let mut backup_path: Option<PathBuf> = None;
+    let mut backup_manifest: Option<BackupManifest> = None;
+    if let Some(state) = &resumed {
+        progress::report(
+            source_db,
+            SyncProgress::Resumed {
+                collections: state.completed.clone(),
+            },
+        );
+        report.backup_path = state.backup_path.clone();
+        backup_path = state.backup_path.clone();
+    } else if options.backup_source || options.create_backup {
+        let started = Instant::now();
+        progress::report(source_db, SyncProgress::PhaseStarted(SyncPhase::Backup));
+        if options.backup_source {
+            let backup = back_up(
+                source_config,
+                source_db,
+                source_db,
+                BackupRole::Source,
+                options,
+            )
+            .await;
+            report.source_backup_path = backup.map(|(path, _)| path);
+        }
+        if options.create_backup {
+            if let Some((path, manifest)) = back_up(
                 target_config,
                 target_db,
-                temp_path,
-                drop_collections,
-                clear_collections,
+                source_db,
+                BackupRole::Target,
+                options,
             )
             .await
             {
-                Ok(_) => {
-                    println!("{} {}", "Import completed:".green(), target_db);
-                }
-                Err(e) => {
-                    error!("Failed to import database: {}", e);
-                    println!("{} Import failed: {}", "Error:".red().bold(), e);
-
-                    // Restore backup if available
-                    if let Some(path) = &backup_path {
-                        println!("{} {}", "Restoring backup:".yellow(), path.display());
-                        if let Err(restore_err) =
-                            mongodb::restore_backup(target_config, target_db, path).await
-                        {
-                            error!("Failed to restore backup: {}", restore_err);
-                            println!(
-                                "{} Backup restoration failed: {}",
-                                "Error:".red().bold(),
-                                restore_err
-                            );
-                        } else {
-                            println!("{}", "Backup restored successfully".green());
-                        }
-                    }
+                report.backup_path = Some(path.clone());
+                backup_path = Some(path);
+                backup_manifest = manifest;
+            }
+        }
+        report.record_phase(SyncPhase::Backup, started);
+        progress::report(source_db, SyncProgress::PhaseFinished(SyncPhase::Backup));
+    }
+
+    if options.schema_only {
+        preflight::check_target_activity(
+            target_config,
+            source_db,
+            options.on_busy_target,
+            options.busy_target_timeout,
+        )
+        .await?;
+        *restore_point = backup_path.clone();
+        let started = Instant::now();
+        progress::report(source_db, SyncProgress::PhaseStarted(SyncPhase::Import));
+        match mongodb::copy_schema(
+            source_config,
+            source_db,
+            target_config,
+            target_db,
+            options.drop_collections,
+        )
+        .await
+        {
+            Ok(count) => {
+                progress::report(
+                    source_db,
+                    SyncProgress::SchemaCopied {
+                        target_db: target_db.to_string(),
+                        count,
+                    },
+                );
+                report.record_phase(SyncPhase::Import, started);
+                progress::report(source_db, SyncProgress::PhaseFinished(SyncPhase::Import));
+            }
+            Err(e) => {
+                error!("Failed to copy schema: {}", e);
+                progress::report(
+                    source_db,
+                    SyncProgress::SchemaCopyFailed {
+                        error: e.to_string(),
+                    },
+                );
+                if let Some(path) = &backup_path {
+                    recover_after_failure(target_config, source_db, target_db, path, options).await;
                 }
+                anyhow::bail!("Schema copy of {} failed: {}", target_db, e);
             }
         }
-        Err(e) => {
-            error!("Failed to export database: {}", e);
-            println!("{} Export failed: {}", "Error:".red().bold(), e);
+
+        run_script_phase(target_config, source_db, target_db, options, report).await?;
+
+        progress::report(source_db, SyncProgress::Completed);
+        return Ok(());
+    }
+
+    let unselected = unselected_collections(source_config, source_db, options).await?;
+
+    let unchanged = if options.delta {
+        let unchanged = unchanged_collections(
+            source_config,
+            source_db,
+            target_config,
+            target_db,
+            options,
+            backup_manifest.as_ref(),
+        )
+        .await?;
+        if !unchanged.is_empty() {
+            progress::report(
+                source_db,
+                SyncProgress::UnchangedSkipped {
+                    collections: unchanged.clone(),
+                },
+            );
+        }
+        let stats = mongodb::list_collection_stats(source_config, source_db).await?;
+        if stats
+            .iter()
+            .all(|c| c.kind == "view" || unchanged.contains(&c.name) || !options.includes(&c.name))
+        {
+            report.collections =
+                collection_reports(&stats, &unchanged, &unselected, &BTreeMap::new());
+            progress::report(
+                source_db,
+                SyncProgress::AlreadyInSync {
+                    target_db: target_db.to_string(),
+                },
+            );
+            return Ok(());
+        }
+        unchanged
+    } else {
+        Vec::new()
+    };
+
+    let mut resume_state = resumed.unwrap_or_else(|| ResumeState {
+        run_id: run_id::get().to_string(),
+        backup_path: backup_path.clone(),
+        completed: Vec::new(),
+    });
+
+    // Excluded collections and those a failed attempt already copied are
+    // treated like unchanged ones: not exported and not cleared on the target
+    let skipped: Vec<String> = unchanged
+        .iter()
+        .chain(&options.exclude_collections)
+        .chain(&resume_state.completed)
+        .cloned()
+        .collect();
+
+    let transfer = Transfer {
+        source_config,
+        source_db,
+        target_config,
+        target_db,
+        options,
+        skipped: &skipped,
+        modified_since: incremental.as_ref().map(|(_, _, since)| *since),
+        index_timer: IndexTimer::default(),
+    };
+    let backup = backup_path.as_deref();
+    let resume = (resume_file.as_path(), &mut resume_state);
+    let transfers = match options.engine {
+        EngineKind::Tools => {
+            let mut engine = match &options.keep_dump {
+                Some(dir) => ToolsEngine::in_dir(dir, options.gzip)?,
+                None => ToolsEngine::new(&options.staging_dir(), options.gzip)?,
+            };
+            let transfers = copy_data(
+                &mut engine,
+                &transfer,
+                backup,
+                resume,
+                restore_point,
+                report,
+            )
+            .await?;
+            if let Some(dir) = &options.keep_dump {
+                let path = DumpLayout::new(dir).database_dir(target_db);
+                progress::report(source_db, SyncProgress::DumpKept { path: path.clone() });
+                report.dump_path = Some(path);
+            }
+            transfers
+        }
+        EngineKind::Driver => {
+            let mut engine = DriverEngine::default();
+            copy_data(
+                &mut engine,
+                &transfer,
+                backup,
+                resume,
+                restore_point,
+                report,
+            )
+            .await?
+        }
+        EngineKind::Stream => {
+            let mut engine = StreamEngine::default();
+            copy_data(
+                &mut engine,
+                &transfer,
+                backup,
+                resume,
+                restore_point,
+                report,
+            )
+            .await?
+        }
+    };
+    if let Err(e) = resume::clear(&resume_file) {
+        error!("{:#}", e);
+    }
+
+    match mongodb::list_collection_stats(source_config, source_db).await {
+        Ok(stats) => {
+            report.collections = collection_reports(&stats, &unchanged, &unselected, &transfers)
+        }
+        Err(e) => error!("Failed to list the collections of {}: {}", source_db, e),
+    }
+
+    // An incremental sync copies only part of the source, so its counts
+    // can't be compared
+    let verify_counts = options
+        .verify_counts
+        .filter(|_| options.incremental_field.is_none());
+    let verify = options
+        .verify
+        .filter(|_| options.incremental_field.is_none());
+    if options.verify_sample.is_some() || verify_counts.is_some() || verify.is_some() {
+        let mut skip = options.transformed_collections();
+        skip.extend(unselected.iter().cloned());
+        // Limited collections only have some of the source documents
+        skip.extend(
+            report
+                .collections
+                .iter()
+                .filter(|c| options.document_limit(&c.name).is_some())
+                .map(|c| c.name.clone()),
+        );
+        // Documents without their dropped fields or changed by rules still
+        // count, but differ in content
+        let mut content_skip = skip.clone();
+        content_skip.extend(
+            report
+                .collections
+                .iter()
+                .filter(|c| {
+                    !options.dropped_fields(&c.name).is_empty()
+                        || !options.transform_rules(&c.name).is_empty()
+                })
+                .map(|c| c.name.clone()),
+        );
+        let started = Instant::now();
+        progress::report(source_db, SyncProgress::PhaseStarted(SyncPhase::Verify));
+        if let Some(sample_size) = options.verify_sample {
+            verify::verify_sample(
+                source_config,
+                source_db,
+                target_config,
+                target_db,
+                sample_size,
+                &content_skip,
+            )
+            .await?;
+        }
+        if let Some(action) = verify_counts {
+            // Without drop or clear the target keeps documents of its own
+            let exact = options.drop_collections || options.clear_collections;
+            report.count_checks = verify::verify_counts(
+                source_config,
+                source_db,
+                target_config,
+                target_db,
+                &skip,
+                exact,
+            )
+            .await?;
+            check_counts(&report.count_checks, action, source_db)?;
+        }
+        if verify == Some(VerifyMode::Checksum) {
+            report.checksum_checks = verify::verify_checksums(
+                source_config,
+                source_db,
+                target_config,
+                target_db,
+                &content_skip,
+            )
+            .await?;
+            let mismatched: Vec<&str> = report
+                .checksum_checks
+                .iter()
+                .filter(|c| !c.matches)
+                .map(|c| c.collection.as_str())
+                .collect();
+            if !mismatched.is_empty() {
+                anyhow::bail!(
+                    "Checksum verification failed for collection(s): {}",
+                    mismatched.join(", ")
+                );
+            }
         }
+        report.record_phase(SyncPhase::Verify, started);
+        progress::report(source_db, SyncProgress::PhaseFinished(SyncPhase::Verify));
     }
 
-    println!("\n{}", "Synchronization completed".green().bold());
+    run_script_phase(target_config, source_db, target_db, options, report).await?;
+
+    if let Some((path, state, _)) = &incremental {
+        state.save(path)?;
+    }
+
+    progress::report(source_db, SyncProgress::Completed);
 
     Ok(())
 }
+
+/// Warn or fail about collections whose document counts differ
+fn check_counts(checks: &[CountCheck], action: CountMismatchAction, source_db: &str) -> Result<()> {
+    let mismatched: Vec<String> = checks
+        .iter()
+        .filter(|c| !c.matches)
+        .map(|c| {
+            format!(
+                "{} ({} in source, {} in target)",
+                c.collection, c.source, c.target
+            )
+        })
+        .collect();
+    if mismatched.is_empty() {
+        return Ok(());
+    }
+    match action {
+        CountMismatchAction::Warn => {
+            warnings::record(
+                WarningCode::CountMismatch,
+                source_db,
+                format!("Document counts differ: {}", mismatched.join(", ")),
+            );
+            Ok(())
+        }
+        CountMismatchAction::Fail => anyhow::bail!(
+            "Document counts differ for collection(s): {}",
+            mismatched.join(", ")
+        ),
+    }
+}
+
+/// Record the collections a failed import left completely copied on the
+/// target, so `--resume` continues with the rest
+fn save_resume_state<E: Engine>(
+    transfer: &Transfer<'_>,
+    engine: &E,
+    error: &anyhow::Error,
+    resume_file: &Path,
+    resume_state: &mut ResumeState,
+) {
+    let restored = error
+        .downcast_ref::<mongodb::ImportError>()
+        .map(|e| e.restored.as_slice())
+        .unwrap_or_default();
+    let completed = engine
+        .exported_collections(transfer)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|collection| {
+            let (db, target) = transfer.target_namespace(collection);
+            restored.contains(&format!("{}.{}", db, target))
+        });
+    resume_state.record(completed);
+    match resume_state.save(resume_file) {
+        Ok(()) => progress::report(
+            transfer.source_db,
+            SyncProgress::ResumeSaved {
+                path: resume_file.to_path_buf(),
+                collections: resume_state.completed.len(),
+            },
+        ),
+        Err(e) => error!("Failed to save the resume state: {:#}", e),
+    }
+}
+
+/// Run the post-sync script of the options, if any
+async fn run_script_phase(
+    target_config: &MongoConfig,
+    source_db: &str,
+    target_db: &str,
+    options: &SyncOptions,
+    report: &mut SyncReport,
+) -> Result<()> {
+    if let Some(script) = &options.post_sync_script {
+        let started = Instant::now();
+        progress::report(source_db, SyncProgress::PhaseStarted(SyncPhase::Script));
+        run_post_sync_script(target_config, source_db, target_db, script).await?;
+        report.record_phase(SyncPhase::Script, started);
+        progress::report(source_db, SyncProgress::PhaseFinished(SyncPhase::Script));
+    }
+    Ok(())
+}
+
+/// Export, transform and import the data of a database with an engine,
+/// restoring the backup when the import fails, or else recording the
+/// collections it copied for `--resume`. Returns the dump sizes of the
+/// copied collections.
+async fn copy_data<E: Engine>(
+    engine: &mut E,
+    transfer: &Transfer<'_>,
+    backup_path: Option<&Path>,
+    (resume_file, resume_state): (&Path, &mut ResumeState),
+    restore_point: &mut Option<PathBuf>,
+    report: &mut SyncReport,
+) -> Result<BTreeMap<String, TransferSize>> {
+    let Transfer {
+        source_config,
+        source_db,
+        target_config,
+        target_db,
+        options,
+        ..
+    } = *transfer;
+
+    // Export database from source
+    let export_started = ::mongodb::bson::DateTime::now();
+    let started = Instant::now();
+    progress::report(source_db, SyncProgress::PhaseStarted(SyncPhase::Export));
+    if let Err(e) = engine.export(transfer).await {
+        error!("Failed to export database: {}", e);
+        progress::report(
+            source_db,
+            SyncProgress::ExportFailed {
+                error: e.to_string(),
+            },
+        );
+        anyhow::bail!("Export of {} failed: {}", source_db, e);
+    }
+    progress::report(source_db, SyncProgress::Exported);
+    report.record_phase(SyncPhase::Export, started);
+    progress::report(source_db, SyncProgress::PhaseFinished(SyncPhase::Export));
+
+    let started = Instant::now();
+    if engine.transform(transfer).await? {
+        report.record_phase(SyncPhase::Transform, started);
+    }
+
+    // Merging keeps existing target documents, so check whether any of
+    // them changed after the export started. Transformed and renamed
+    // collections have no source version to compare with, and incremental
+    // syncs overwrite target documents by design.
+    let mut conflicts = Vec::new();
+    let mut prefer = Prefer::Target;
+    if !options.drop_collections
+        && !options.clear_collections
+        && options.incremental_field.is_none()
+    {
+        let transformed = options.transformed_collections();
+        let collections: Vec<String> = engine
+            .exported_collections(transfer)?
+            .into_iter()
+            .filter(|c| !transformed.contains(c))
+            .collect();
+        conflicts = conflicts::find_conflicts(
+            target_config,
+            target_db,
+            &collections,
+            &options.updated_field,
+            export_started,
+        )
+        .await?;
+        if !conflicts.is_empty() {
+            let documents: usize = conflicts.iter().map(|c| c.ids.len()).sum();
+            warnings::record(
+                WarningCode::NewerTargetDocuments,
+                source_db,
+                format!(
+                    "{} target documents changed after the source export started",
+                    documents
+                ),
+            );
+            progress::report(
+                source_db,
+                SyncProgress::NewerTargetDocuments {
+                    collections: conflicts
+                        .iter()
+                        .map(|c| (c.collection.clone(), c.ids.len()))
+                        .collect(),
+                },
+            );
+            prefer = conflicts::resolve_preference(options.prefer)?;
+        }
+    }
+
+    // Import database to target
+    preflight::check_target_activity(
+        target_config,
+        source_db,
+        options.on_busy_target,
+        options.busy_target_timeout,
+    )
+    .await?;
+    *restore_point = backup_path.map(Path::to_path_buf);
+    let started = Instant::now();
+    progress::report(source_db, SyncProgress::PhaseStarted(SyncPhase::Import));
+    if let Err(e) = engine.import(transfer).await {
+        error!("Failed to import database: {}", e);
+        progress::report(
+            source_db,
+            SyncProgress::ImportFailed {
+                error: e.to_string(),
+            },
+        );
+
+        // Restore backup if available
+        if let Some(path) = backup_path {
+            recover_after_failure(target_config, source_db, target_db, path, options).await;
+        }
+        if backup_path.is_none() || !options.auto_restore {
+            save_resume_state(transfer, engine, &e, resume_file, resume_state);
+        }
+
+        anyhow::bail!("Import of {} failed: {}", target_db, e);
+    }
+
+    progress::report(
+        source_db,
+        SyncProgress::Imported {
+            target_db: target_db.to_string(),
+        },
+    );
+    if prefer == Prefer::Source && !conflicts.is_empty() {
+        let replaced = conflicts::apply_source_versions(
+            source_config,
+            source_db,
+            target_config,
+            target_db,
+            &conflicts,
+        )
+        .await?;
+        progress::report(
+            source_db,
+            SyncProgress::SourceVersionsApplied {
+                documents: replaced,
+            },
+        );
+    }
+    report.record_phase(SyncPhase::Import, started);
+    progress::report(source_db, SyncProgress::PhaseFinished(SyncPhase::Import));
+
+    // Indexes are restored during the import; their share of it is listed
+    // on its own
+    let indexes = transfer.index_timer.elapsed();
+    if !indexes.is_zero() {
+        report.record_phase_duration(SyncPhase::Indexes, indexes);
+    }
+
+    // Sizes only feed the report, so failing to read them doesn't fail the sync
+    Ok(engine.transfer_sizes(transfer).unwrap_or_else(|e| {
+        error!("Failed to measure the dump of {}: {:#}", source_db, e);
+        BTreeMap::new()
+    }))
+}
@@ -0,0 +1,263 @@
+//! Rules changing documents on their way to the target, e.g. renaming a field
+//! the target schema calls differently or replacing e-mail addresses with ones
+//! built from the document.
+//!
+//! Rules are configured by collection in the `[defaults.transforms]` table of
+//! the config file and applied in order. The driver engine applies them to
+//! each document between reading and writing it; the tools engine rewrites
+//! the collection files of the dump, like it does for dropped fields.
+
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use mongodb::bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::core::redact::{self, ALL_COLLECTIONS};
+
+/// A change made to every document of a collection
+pub trait Transform: std::fmt::Debug + Send + Sync {
+    /// Change a document in place, returning whether anything changed
+    fn apply(&self, document: &mut Document) -> Result<bool>;
+}
+
+/// Rules by collection name, or by [`ALL_COLLECTIONS`]
+pub type CollectionTransforms = BTreeMap<String, Vec<TransformRule>>;
+
+/// A configured change of a field, e.g. `{ op = "rename", field = "name", to = "full_name" }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TransformRule {
+    /// Move a field to another path
+    Rename { field: String, to: String },
+    /// Set a field to a constant
+    Set { field: String, value: Bson },
+    /// Remove a field
+    Drop { field: String },
+    /// Set a field to a string built from the document, where `{path}`
+    /// stands for the value at that path, e.g. `"user-{_id}@example.com"`
+    Template { field: String, template: String },
+}
+
+impl TransformRule {
+    /// Dotted path of the field the rule reads
+    pub fn field(&self) -> &str {
+        match self {
+            TransformRule::Rename { field, .. }
+            | TransformRule::Set { field, .. }
+            | TransformRule::Drop { field }
+            | TransformRule::Template { field, .. } => field,
+        }
+    }
+
+    /// Check the field paths and template of a rule from the config file
+    pub fn validate(&self) -> Result<()> {
+        redact::validate_field(self.field())?;
+        match self {
+            TransformRule::Rename { field, to } => {
+                redact::validate_field(to)?;
+                if field == to {
+                    anyhow::bail!("'{}' is renamed to itself", field);
+                }
+            }
+            TransformRule::Template { template, .. } => {
+                template_paths(template)?;
+            }
+            TransformRule::Set { .. } | TransformRule::Drop { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for TransformRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformRule::Rename { field, to } => write!(f, "rename {} -> {}", field, to),
+            TransformRule::Set { field, value } => write!(f, "set {} = {}", field, value),
+            TransformRule::Drop { field } => write!(f, "drop {}", field),
+            TransformRule::Template { field, template } => {
+                write!(f, "template {} = \"{}\"", field, template)
+            }
+        }
+    }
+}
+
+impl Transform for TransformRule {
+    fn apply(&self, document: &mut Document) -> Result<bool> {
+        Ok(match self {
+            TransformRule::Rename { field, to } => match take_path(document, field) {
+                Some(value) => match set_path(document, to, value) {
+                    Ok(()) => true,
+                    // The target path runs through a value that isn't a
+                    // document, so the field stays where it was
+                    Err(value) => {
+                        let _ = set_path(document, field, value);
+                        false
+                    }
+                },
+                None => false,
+            },
+            TransformRule::Set { field, value } => set_path(document, field, value.clone()).is_ok(),
+            TransformRule::Drop { field } => redact::remove_field(document, field),
+            TransformRule::Template { field, template } => {
+                let value = render_template(template, document)?;
+                set_path(document, field, Bson::String(value)).is_ok()
+            }
+        })
+    }
+}
+
+impl<T: Transform> Transform for Vec<T> {
+    fn apply(&self, document: &mut Document) -> Result<bool> {
+        let mut changed = false;
+        for transform in self {
+            changed |= transform.apply(document)?;
+        }
+        Ok(changed)
+    }
+}
+
+/// Rules applied to a collection, those of every collection first
+pub fn rules_for(transforms: &CollectionTransforms, collection: &str) -> Vec<TransformRule> {
+    [ALL_COLLECTIONS, collection]
+        .into_iter()
+        .flat_map(|key| transforms.get(key).into_iter().flatten())
+        .cloned()
+        .collect()
+}
+
+/// Dotted paths a template refers to, in order
+pub fn template_paths(template: &str) -> Result<Vec<&str>> {
+    let mut paths = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            anyhow::bail!("Unmatched '}}' in template '{}'", template);
+        }
+        let end = rest[start..]
+            .find('}')
+            .context(format!("Unclosed '{{' in template '{}'", template))?;
+        let path = &rest[start + 1..start + end];
+        if path.is_empty() || path.split('.').any(str::is_empty) || path.contains('{') {
+            anyhow::bail!("Invalid field path '{}' in template '{}'", path, template);
+        }
+        paths.push(path);
+        rest = &rest[start + end + 1..];
+    }
+    Ok(paths)
+}
+
+/// Fill in the `{path}` placeholders of a template from a document; missing
+/// fields become empty strings
+pub fn render_template(template: &str, document: &Document) -> Result<String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    for path in template_paths(template)? {
+        // Checked by `template_paths`: the next brace opens this placeholder
+        let start = rest.find('{').unwrap_or_default();
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(&get_path(document, path).map(display).unwrap_or_default());
+        rest = &rest[start + path.len() + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// A value as it reads in a string, e.g. an ObjectId as its hex digits
+fn display(value: &Bson) -> String {
+    match value {
+        Bson::String(s) => s.clone(),
+        Bson::ObjectId(id) => id.to_hex(),
+        Bson::DateTime(date) => date
+            .try_to_rfc3339_string()
+            .unwrap_or_else(|_| date.to_string()),
+        Bson::Null | Bson::Undefined => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Value at a dotted path through embedded documents
+fn get_path<'a>(document: &'a Document, path: &str) -> Option<&'a Bson> {
+    match path.split_once('.') {
+        Some((head, rest)) => get_path(document.get_document(head).ok()?, rest),
+        None => document.get(path),
+    }
+}
+
+/// Remove and return the value at a dotted path through embedded documents
+fn take_path(document: &mut Document, path: &str) -> Option<Bson> {
+    match path.split_once('.') {
+        Some((head, rest)) => take_path(document.get_document_mut(head).ok()?, rest),
+        None => document.remove(path),
+    }
+}
+
+/// Set the value at a dotted path, creating missing embedded documents. Gives
+/// the value back when the path runs through a value that isn't a document.
+fn set_path(document: &mut Document, path: &str, value: Bson) -> Result<(), Bson> {
+    let Some((head, rest)) = path.split_once('.') else {
+        document.insert(path, value);
+        return Ok(());
+    };
+    if !document.contains_key(head) {
+        document.insert(head, Document::new());
+    }
+    match document.get_document_mut(head) {
+        Ok(inner) => set_path(inner, rest, value),
+        Err(_) => Err(value),
+    }
+}
+
+/// Apply a transform to every document of a mongodump collection file in
+/// place. Returns the number of documents it changed.
+pub fn rewrite_file(path: &Path, compressed: bool, transform: &dyn Transform) -> Result<u64> {
+    let file = std::fs::File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut reader: Box<dyn BufRead> = if compressed {
+        Box::new(BufReader::new(MultiGzDecoder::new(BufReader::new(file))))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    // Written next to the dump file so the rename can't cross file systems
+    let rewritten = path.with_extension("rewriting");
+    let mut output = BufWriter::new(
+        std::fs::File::create(&rewritten)
+            .context(format!("Failed to create {}", rewritten.display()))?,
+    );
+    let changed = if compressed {
+        let mut encoder = GzEncoder::new(&mut output, Compression::default());
+        let changed = rewrite_documents(&mut reader, &mut encoder, transform)
+            .context(format!("Failed to rewrite {}", path.display()))?;
+        encoder.finish()?;
+        changed
+    } else {
+        rewrite_documents(&mut reader, &mut output, transform)
+            .context(format!("Failed to rewrite {}", path.display()))?
+    };
+    output.flush()?;
+    drop(output);
+
+    std::fs::rename(&rewritten, path).context(format!("Failed to replace {}", path.display()))?;
+    Ok(changed)
+}
+
+/// Copy BSON documents through a transform, counting those it changed
+fn rewrite_documents(
+    reader: &mut dyn BufRead,
+    writer: &mut impl Write,
+    transform: &dyn Transform,
+) -> Result<u64> {
+    let mut changed = 0;
+    while !reader.fill_buf()?.is_empty() {
+        let mut document = Document::from_reader(&mut *reader)?;
+        if transform.apply(&mut document)? {
+            changed += 1;
+        }
+        document.to_writer(&mut *writer)?;
+    }
+    Ok(changed)
+}
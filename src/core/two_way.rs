@@ -0,0 +1,412 @@
+//! Experimental two-way sync between two non-production environments.
+//!
+//! Changes are read from the change streams of both databases and applied to
+//! the other side. Writes made by the sync itself run in a transaction on a
+//! known session, so their echoes are recognized by the session id of the
+//! change event. A change that was overwritten by a write from the other side
+//! before it could be replicated is a conflict: it is logged, not resolved.
+
+use ::mongodb::bson::{self, Bson, Document, Timestamp};
+use ::mongodb::change_stream::event::ChangeStreamEvent;
+use ::mongodb::change_stream::ChangeStream;
+use ::mongodb::{ClientSession, Database};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use futures::StreamExt;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::{get_state_dir, is_production, Environment, MongoConfig};
+use crate::core::mirror::{change_action, is_resume_lost, open_stream, ChangeAction, MirrorState};
+use crate::utils::run_id;
+
+/// One of the two synchronized databases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    From,
+    To,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::From => Side::To,
+            Side::To => Side::From,
+        }
+    }
+}
+
+/// How a change event observed on one side relates to the sync's own writes
+#[derive(Debug, PartialEq, Eq)]
+pub enum EventOrigin {
+    /// Caused by the sync replicating a change from the other side
+    Echo,
+    /// Made on this side but overwritten by a replicated change before it was seen
+    Conflict,
+    /// Made on this side; replicate it
+    Change,
+}
+
+/// Remembers the documents the sync wrote to each side until their echoes arrive
+#[derive(Debug, Default)]
+pub struct ConflictTracker {
+    /// Time of the latest write and number of echoes still expected, per document
+    writes: HashMap<(Side, String, Vec<u8>), (Timestamp, u32)>,
+}
+
+impl ConflictTracker {
+    fn key(
+        side: Side,
+        collection: &str,
+        document_key: &Document,
+    ) -> Result<(Side, String, Vec<u8>)> {
+        Ok((side, collection.to_string(), bson::to_vec(document_key)?))
+    }
+
+    pub fn record_write(
+        &mut self,
+        side: Side,
+        collection: &str,
+        document_key: &Document,
+        at: Timestamp,
+    ) -> Result<()> {
+        let entry = self
+            .writes
+            .entry(Self::key(side, collection, document_key)?)
+            .or_insert((at, 0));
+        entry.0 = entry.0.max(at);
+        entry.1 += 1;
+        Ok(())
+    }
+
+    /// Classify a change event observed on `side`
+    pub fn classify(
+        &mut self,
+        side: Side,
+        collection: &str,
+        document_key: &Document,
+        cluster_time: Option<Timestamp>,
+        echo: bool,
+    ) -> Result<EventOrigin> {
+        let key = Self::key(side, collection, document_key)?;
+
+        if echo {
+            // Changes are streamed in order, so everything before the echo has been seen
+            if let Some(entry) = self.writes.get_mut(&key) {
+                entry.1 = entry.1.saturating_sub(1);
+                if entry.1 == 0 {
+                    self.writes.remove(&key);
+                }
+            }
+            return Ok(EventOrigin::Echo);
+        }
+
+        match (self.writes.get(&key), cluster_time) {
+            (Some((written_at, _)), Some(time)) if time < *written_at => Ok(EventOrigin::Conflict),
+            _ => Ok(EventOrigin::Change),
+        }
+    }
+}
+
+/// A conflicting change written to the conflict log for manual resolution
+#[derive(Debug, Serialize)]
+pub struct ConflictRecord {
+    pub run_id: String,
+    pub detected_at: String,
+    pub collection: String,
+    pub document_key: serde_json::Value,
+    /// Environment whose change was overwritten
+    pub overwritten_env: String,
+    pub operation: String,
+    /// Document after the overwritten change, or the fields it updated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<serde_json::Value>,
+    /// Environment whose version both sides now have
+    pub kept_env: String,
+}
+
+pub struct TwoWayConfig {
+    pub from_env: Environment,
+    pub to_env: Environment,
+    pub from_db: String,
+    pub to_db: String,
+    pub conflict_log: PathBuf,
+}
+
+/// Positions of both change streams
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TwoWayState {
+    pub from: MirrorState,
+    pub to: MirrorState,
+}
+
+impl TwoWayState {
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .context(format!("Invalid two-way state in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).context(format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp, path).context(format!("Failed to write {}", path.display()))
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut MirrorState {
+        match side {
+            Side::From => &mut self.from,
+            Side::To => &mut self.to,
+        }
+    }
+}
+
+/// Refuse production environments, which must never receive writes from another environment
+pub fn check_environments(from: &Environment, to: &Environment) -> Result<()> {
+    if from == to {
+        anyhow::bail!("Two-way sync needs two different environments");
+    }
+    for env in [from, to] {
+        if is_production(env) {
+            anyhow::bail!(
+                "Two-way sync is limited to non-production environments, but {} is production",
+                env
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A database being synchronized, with the session its writes are made on
+struct Endpoint {
+    env: Environment,
+    database: Database,
+    session: ClientSession,
+}
+
+impl Endpoint {
+    async fn connect(env: &Environment, database: &str) -> Result<Self> {
+        let config = MongoConfig::from_env(env.clone())?;
+        let client = config.client().await?;
+        let session = client.start_session().await?;
+        Ok(Self {
+            env: env.clone(),
+            database: client.database(database),
+            session,
+        })
+    }
+
+    /// Apply a change in a transaction, so its echo carries this session's id.
+    ///
+    /// Returns the time of the write.
+    async fn apply(
+        &mut self,
+        event: &ChangeStreamEvent<Document>,
+        collection: &str,
+        key: Document,
+    ) -> Result<Option<Timestamp>> {
+        let collection = self.database.collection::<Document>(collection);
+        self.session.start_transaction().await?;
+        let result = match (&change_action(&event.operation_type), &event.full_document) {
+            (ChangeAction::Upsert, Some(document)) => collection
+                .replace_one(key, document.clone())
+                .upsert(true)
+                .session(&mut self.session)
+                .await
+                .map(|_| ()),
+            _ => collection
+                .delete_one(key)
+                .session(&mut self.session)
+                .await
+                .map(|_| ()),
+        };
+        match result {
+            Ok(()) => self.session.commit_transaction().await?,
+            Err(e) => {
+                self.session.abort_transaction().await?;
+                return Err(e.into());
+            }
+        }
+        Ok(self.session.operation_time())
+    }
+}
+
+fn state_file(config: &TwoWayConfig) -> PathBuf {
+    get_state_dir().join(format!(
+        "two-way-{}-{}-{}-{}.json",
+        config.from_env, config.from_db, config.to_env, config.to_db
+    ))
+}
+
+/// Open the change stream of one side at its saved position, or at the current time
+async fn open_side(
+    config: &MongoConfig,
+    database: &str,
+    state: &MirrorState,
+) -> Result<ChangeStream<ChangeStreamEvent<Document>>> {
+    match open_stream(config, database, state.stream_position()?).await {
+        Err(e) if is_resume_lost(&e) => Err(e.context(
+            "The saved position is no longer in the oplog; bring both databases in step \
+             with a one-way sync and remove the two-way state file to start over",
+        )),
+        result => result,
+    }
+}
+
+/// Whether a change was made on the given session
+fn made_by(event: &ChangeStreamEvent<Document>, session: &ClientSession) -> bool {
+    // The event's session id also carries a user hash, so compare the ids only
+    event
+        .lsid
+        .as_ref()
+        .and_then(|lsid| lsid.get("id"))
+        .is_some_and(|id| session.id().get("id") == Some(id))
+}
+
+fn append_conflict(path: &Path, record: &ConflictRecord) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open conflict log {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Keep two databases in step in both directions until interrupted.
+///
+/// Both databases are expected to hold the same data when the sync first starts.
+pub async fn run_two_way(config: TwoWayConfig) -> Result<()> {
+    check_environments(&config.from_env, &config.to_env)?;
+
+    let from_config = MongoConfig::from_env(config.from_env.clone())?;
+    let to_config = MongoConfig::from_env(config.to_env.clone())?;
+    let state_path = state_file(&config);
+    let mut state = TwoWayState::load(&state_path)?;
+
+    let mut from_stream = open_side(&from_config, &config.from_db, &state.from).await?;
+    let mut to_stream = open_side(&to_config, &config.to_db, &state.to).await?;
+
+    let mut from = Endpoint::connect(&config.from_env, &config.from_db).await?;
+    let mut to = Endpoint::connect(&config.to_env, &config.to_db).await?;
+    let mut tracker = ConflictTracker::default();
+
+    println!(
+        "{} {}:{} <-> {}:{} (experimental, Ctrl+C to stop)",
+        "Two-way sync".green().bold(),
+        config.from_env,
+        config.from_db,
+        config.to_env,
+        config.to_db
+    );
+    println!(
+        "{} {}",
+        "Conflict log:".green(),
+        config.conflict_log.display()
+    );
+
+    let (mut applied, mut conflicts) = (0u64, 0u64);
+    loop {
+        let (side, event) = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            event = from_stream.next() => (Side::From, event),
+            event = to_stream.next() => (Side::To, event),
+        };
+        let event = match event {
+            Some(event) => event.context("Change stream failed")?,
+            None => anyhow::bail!("The change stream of {:?} side was closed", side),
+        };
+
+        let (source, target) = match side {
+            Side::From => (&from, &mut to),
+            Side::To => (&to, &mut from),
+        };
+        let Some(collection) = event.ns.as_ref().and_then(|ns| ns.coll.clone()) else {
+            continue;
+        };
+
+        match change_action(&event.operation_type) {
+            ChangeAction::Upsert | ChangeAction::Delete => {}
+            ChangeAction::Ignore => {
+                warn!("Ignoring {:?} event", event.operation_type);
+                continue;
+            }
+            ChangeAction::DropCollection | ChangeAction::Resync => anyhow::bail!(
+                "{:?} of {}.{} in {} can't be replicated in two-way mode; stopping",
+                event.operation_type,
+                source.database.name(),
+                collection,
+                source.env
+            ),
+        }
+
+        let key = event
+            .document_key
+            .clone()
+            .context("Change event without a document key")?;
+        let echo = made_by(&event, &source.session);
+        match tracker.classify(side, &collection, &key, event.cluster_time, echo)? {
+            EventOrigin::Echo => {}
+            EventOrigin::Conflict => {
+                conflicts += 1;
+                println!(
+                    "{} {} {} changed on both sides, the change in {} was overwritten",
+                    "Conflict:".yellow().bold(),
+                    collection,
+                    Bson::Document(key.clone()).into_relaxed_extjson(),
+                    source.env
+                );
+                append_conflict(
+                    &config.conflict_log,
+                    &ConflictRecord {
+                        run_id: run_id::get().to_string(),
+                        detected_at: chrono::Utc::now().to_rfc3339(),
+                        collection: collection.clone(),
+                        document_key: Bson::Document(key).into_relaxed_extjson(),
+                        overwritten_env: source.env.to_string(),
+                        operation: format!("{:?}", event.operation_type),
+                        change: event
+                            .full_document
+                            .clone()
+                            .map(|doc| Bson::Document(doc).into_relaxed_extjson())
+                            .or_else(|| {
+                                event
+                                    .update_description
+                                    .as_ref()
+                                    .and_then(|d| bson::to_bson(d).ok())
+                                    .map(Bson::into_relaxed_extjson)
+                            }),
+                        kept_env: target.env.to_string(),
+                    },
+                )?;
+            }
+            EventOrigin::Change => {
+                if let Some(at) = target.apply(&event, &collection, key.clone()).await? {
+                    tracker.record_write(side.other(), &collection, &key, at)?;
+                }
+                applied += 1;
+            }
+        }
+
+        state.side_mut(side).record(&collection, &event.id)?;
+        state.save(&state_path)?;
+    }
+
+    println!(
+        "{} {} changes, {} conflicts",
+        "Applied:".green(),
+        applied,
+        conflicts
+    );
+    Ok(())
+}
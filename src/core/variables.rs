@@ -0,0 +1,109 @@
+//! `{name}` variables given with `sync -v name=value`, substituted into
+//! preset values and pipeline files so one preset serves many tenants.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use crate::config::file::Preset;
+
+/// Variable values keyed by name
+pub type Variables = BTreeMap<String, String>;
+
+/// Placeholders resolved later by the target database template and tenant syncs
+const BUILTIN_PLACEHOLDERS: &[&str] = &["source_db", "date", "datetime", "id"];
+
+/// Parse a `name=value` command-line argument
+pub fn parse_variable(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", arg))?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "invalid variable name '{}' (use letters, digits and '_')",
+            name
+        ));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Replace every `{name}` of a known variable in the text
+pub fn substitute(text: &str, variables: &Variables) -> String {
+    variables
+        .iter()
+        .fold(text.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// Substitute variables into every string value of a JSON document
+pub fn substitute_json(value: &mut serde_json::Value, variables: &Variables) {
+    match value {
+        serde_json::Value::String(text) => *text = substitute(text, variables),
+        serde_json::Value::Array(items) => {
+            items
+                .iter_mut()
+                .for_each(|item| substitute_json(item, variables));
+        }
+        serde_json::Value::Object(map) => {
+            map.values_mut()
+                .for_each(|item| substitute_json(item, variables));
+        }
+        _ => {}
+    }
+}
+
+/// Placeholders in the text that are neither variables nor built-in placeholders
+fn unresolved(text: &str) -> Vec<String> {
+    text.split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+        .filter(|name| !BUILTIN_PLACEHOLDERS.contains(name))
+        .map(String::from)
+        .collect()
+}
+
+/// Substitute variables into the text values of a preset.
+///
+/// Fails when a value still refers to a variable that wasn't given.
+pub fn apply_to_preset(name: &str, preset: Preset, variables: &Variables) -> Result<Preset> {
+    let mut missing = Vec::new();
+    let mut text = |value: String| {
+        let value = substitute(&value, variables);
+        missing.extend(unresolved(&value));
+        value
+    };
+
+    let preset = Preset {
+        from: preset.from.map(&mut text),
+        to: preset.to.map(&mut text),
+        db: preset.db.map(&mut text),
+        target_db: preset.target_db.map(&mut text),
+        post_sync_script: preset
+            .post_sync_script
+            .map(|path| text(path.to_string_lossy().into_owned()).into()),
+        pipelines: preset
+            .pipelines
+            .map(|path| text(path.to_string_lossy().into_owned()).into()),
+        collection_map: preset
+            .collection_map
+            .map(|path| text(path.to_string_lossy().into_owned()).into()),
+        ..preset
+    };
+
+    if !missing.is_empty() {
+        missing.sort();
+        missing.dedup();
+        anyhow::bail!(
+            "Preset '{}' needs values for {}; pass them with -v NAME=VALUE",
+            name,
+            missing
+                .iter()
+                .map(|name| format!("'{}'", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    Ok(preset)
+}
@@ -0,0 +1,302 @@
+use ::mongodb::bson::{Bson, RawDocumentBuf};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config::MongoConfig;
+use crate::core::progress::{self, SyncProgress};
+use crate::utils::mongodb;
+
+/// Collections verified at the same time unless `verify_concurrency` is configured
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Number of collections to verify at the same time
+pub fn concurrency() -> usize {
+    crate::config::file::get()
+        .defaults
+        .verify_concurrency
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY)
+}
+
+/// Outcome of comparing sampled source documents with the target
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SampleComparison {
+    pub checked: usize,
+    /// Sampled documents with no document of the same `_id` in the target
+    pub missing: usize,
+    /// Sampled documents whose target copy differs byte-for-byte
+    pub mismatched: usize,
+}
+
+impl SampleComparison {
+    pub fn is_match(&self) -> bool {
+        self.missing == 0 && self.mismatched == 0
+    }
+}
+
+/// What to do when the document counts of the source and the target differ
+/// after a sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CountMismatchAction {
+    /// Record a warning and keep the sync
+    Warn,
+    /// Fail the sync
+    #[default]
+    Fail,
+}
+
+impl std::fmt::Display for CountMismatchAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CountMismatchAction::Warn => write!(f, "warn"),
+            CountMismatchAction::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+/// Deeper verification run after a sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyMode {
+    /// Compare the `dbHash` of every collection in the source and the target
+    Checksum,
+}
+
+impl std::fmt::Display for VerifyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyMode::Checksum => write!(f, "checksum"),
+        }
+    }
+}
+
+/// `dbHash` of a collection in the source and the target after a sync
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChecksumCheck {
+    pub collection: String,
+    pub source: String,
+    /// Absent when the collection is missing from the target
+    pub target: Option<String>,
+    pub matches: bool,
+}
+
+/// Document counts of a collection in the source and the target after a sync
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CountCheck {
+    pub collection: String,
+    pub source: u64,
+    pub target: u64,
+    pub matches: bool,
+}
+
+impl CountCheck {
+    /// Compare the counts of a collection. Unless `exact`, the target may keep
+    /// documents of its own, so only fewer documents than the source is a mismatch.
+    pub fn new(collection: &str, source: u64, target: u64, exact: bool) -> Self {
+        Self {
+            collection: collection.to_string(),
+            source,
+            target,
+            matches: if exact {
+                source == target
+            } else {
+                target >= source
+            },
+        }
+    }
+}
+
+fn document_id(doc: &RawDocumentBuf) -> Result<Bson> {
+    let id = doc.get("_id")?.context("Sampled document has no _id")?;
+    Ok(Bson::try_from(id.to_raw_bson())?)
+}
+
+/// Compare source documents with their target counterparts, matched by `_id`
+pub fn compare_samples(
+    source: &[RawDocumentBuf],
+    target: &[RawDocumentBuf],
+) -> Result<SampleComparison> {
+    // Canonical Extended JSON gives a hashable key that keeps BSON types apart
+    let mut by_id = HashMap::new();
+    for doc in target {
+        by_id.insert(document_id(doc)?.into_canonical_extjson().to_string(), doc);
+    }
+
+    let mut comparison = SampleComparison::default();
+    for doc in source {
+        comparison.checked += 1;
+        let key = document_id(doc)?.into_canonical_extjson().to_string();
+        match by_id.get(&key) {
+            None => comparison.missing += 1,
+            Some(target_doc) if target_doc.as_bytes() != doc.as_bytes() => {
+                comparison.mismatched += 1
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(comparison)
+}
+
+/// Check that `sample_size` random documents of each collection exist
+/// byte-identical in the target.
+///
+/// Collections listed in `skip` (e.g. transformed by pipelines or renamed on
+/// import) are not sampled, as their target copies are expected to differ.
+pub async fn verify_sample(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    sample_size: u64,
+    skip: &[String],
+) -> Result<()> {
+    progress::report(source_db, SyncProgress::VerifyStarted { sample_size });
+
+    let collections: Vec<_> = mongodb::list_collection_stats(source_config, source_db)
+        .await?
+        .into_iter()
+        .filter(|c| c.kind != "view" && !skip.contains(&c.name))
+        .collect();
+    let total = collections.len() as u64;
+    let mut failed = Vec::new();
+
+    // Collections are compared concurrently, results reported in name order
+    let mut results = futures::stream::iter(collections)
+        .map(|collection| async move {
+            let source_docs =
+                mongodb::sample_documents(source_config, source_db, &collection.name, sample_size)
+                    .await?;
+            let ids = source_docs
+                .iter()
+                .map(document_id)
+                .collect::<Result<Vec<_>>>()?;
+            let target_docs =
+                mongodb::find_documents_by_id(target_config, target_db, &collection.name, ids)
+                    .await?;
+
+            let comparison = compare_samples(&source_docs, &target_docs)
+                .context(format!("Failed to compare samples of {}", collection.name))?;
+            Ok::<_, anyhow::Error>((collection, comparison))
+        })
+        .buffered(concurrency())
+        .enumerate();
+
+    while let Some((done, result)) = results.next().await {
+        let (collection, comparison) = result?;
+        let is_match = comparison.is_match();
+        progress::report(
+            source_db,
+            SyncProgress::CollectionVerified {
+                collection: collection.name.clone(),
+                comparison,
+                done: done as u64 + 1,
+                total,
+            },
+        );
+        if !is_match {
+            failed.push(collection.name);
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "Sample verification failed for collection(s): {}",
+            failed.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Count the documents of each collection in the source and the target,
+/// sorted by collection.
+///
+/// Collections listed in `skip` are not counted, like with [`verify_sample`].
+pub async fn verify_counts(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    skip: &[String],
+    exact: bool,
+) -> Result<Vec<CountCheck>> {
+    let collections: Vec<_> = mongodb::list_collection_stats(source_config, source_db)
+        .await?
+        .into_iter()
+        .filter(|c| c.kind != "view" && !skip.contains(&c.name))
+        .collect();
+
+    futures::stream::iter(collections)
+        .map(|collection| async move {
+            let filter = ::mongodb::bson::Document::new();
+            let source = mongodb::count_documents(
+                source_config,
+                source_db,
+                &collection.name,
+                filter.clone(),
+            )
+            .await?;
+            let target =
+                mongodb::count_documents(target_config, target_db, &collection.name, filter)
+                    .await
+                    .context(format!(
+                        "Failed to count the documents of {}",
+                        collection.name
+                    ))?;
+            Ok(CountCheck::new(&collection.name, source, target, exact))
+        })
+        .buffered(concurrency())
+        .collect::<Vec<Result<CountCheck>>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Pair the `dbHash` collection hashes of the source and the target, sorted
+/// by collection.
+///
+/// System collections and those listed in `skip` are left out.
+pub fn compare_checksums(
+    source: &BTreeMap<String, String>,
+    target: &BTreeMap<String, String>,
+    skip: &[String],
+) -> Vec<ChecksumCheck> {
+    source
+        .iter()
+        .filter(|(collection, _)| !collection.starts_with("system.") && !skip.contains(collection))
+        .map(|(collection, hash)| {
+            let target = target.get(collection).cloned();
+            ChecksumCheck {
+                collection: collection.clone(),
+                source: hash.clone(),
+                matches: target.as_ref() == Some(hash),
+                target,
+            }
+        })
+        .collect()
+}
+
+/// Compare the content hashes of every collection in the source and the
+/// target with `dbHash`, which catches changes that document counts miss.
+///
+/// `dbHash` locks each database while it reads all of its documents and isn't
+/// available through mongos. Collections listed in `skip` are not compared,
+/// like with [`verify_sample`].
+pub async fn verify_checksums(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    skip: &[String],
+) -> Result<Vec<ChecksumCheck>> {
+    let (source, target) = futures::try_join!(
+        mongodb::collection_hashes(source_config, source_db),
+        mongodb::collection_hashes(target_config, target_db),
+    )
+    .context("--verify checksum needs dbHash, which mongos doesn't support")?;
+    Ok(compare_checksums(&source, &target, skip))
+}
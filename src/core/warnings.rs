@@ -0,0 +1,95 @@
+//! Non-fatal problems of a sync, collected for the final summary and sent as
+//! progress events with stable codes so scripts can detect them.
+
+use colored::Colorize;
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::utils::events::{self, EventKind, ProgressEvent};
+use crate::utils::progress::is_quiet;
+
+/// Stable identifiers of warnings; existing codes are never renamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+    /// The target backup failed and the sync continued without one
+    BackupFailed,
+    /// Backup checksums couldn't be recorded, so a restore can't be verified
+    BackupManifestFailed,
+    /// A restored backup couldn't be verified against its manifest
+    RestoreUnverified,
+    /// Index builds or shard balancing were running on the target before the restore
+    TargetBusy,
+    /// The target couldn't be checked for running operations
+    TargetActivityUnknown,
+    /// Target documents changed after the source export started
+    NewerTargetDocuments,
+    /// The import likely needs more space than the target has free
+    TargetSpaceLow,
+    /// The free space of the target couldn't be checked
+    TargetSpaceUnknown,
+    /// Collections whose document counts differ between source and target
+    CountMismatch,
+}
+
+impl WarningCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningCode::BackupFailed => "backup_failed",
+            WarningCode::BackupManifestFailed => "backup_manifest_failed",
+            WarningCode::RestoreUnverified => "restore_unverified",
+            WarningCode::TargetBusy => "target_busy",
+            WarningCode::TargetActivityUnknown => "target_activity_unknown",
+            WarningCode::NewerTargetDocuments => "newer_target_documents",
+            WarningCode::TargetSpaceLow => "target_space_low",
+            WarningCode::TargetSpaceUnknown => "target_space_unknown",
+            WarningCode::CountMismatch => "count_mismatch",
+        }
+    }
+}
+
+impl std::fmt::Display for WarningCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncWarning {
+    pub code: WarningCode,
+    pub message: String,
+}
+
+static WARNINGS: Mutex<Vec<SyncWarning>> = Mutex::new(Vec::new());
+
+/// Print a warning, send it as a progress event and keep it for the summary
+pub fn record(code: WarningCode, database: &str, message: impl Into<String>) {
+    let message = message.into();
+    // Quiet runs only list warnings in the final summary
+    if !is_quiet() {
+        println!("{} {}", "Warning:".yellow().bold(), message);
+    }
+    events::emit(ProgressEvent::new(EventKind::Warning, database).warning(code.as_str(), &message));
+
+    WARNINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(SyncWarning { code, message });
+}
+
+/// Remove and return the warnings recorded so far
+pub fn take() -> Vec<SyncWarning> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Print the warnings of a finished sync again, so they aren't lost in its output
+pub fn print_summary(warnings: &[SyncWarning]) {
+    if warnings.is_empty() {
+        return;
+    }
+
+    println!("\n{}", "Warnings:".yellow().bold());
+    for warning in warnings {
+        println!("  [{}] {}", warning.code, warning.message);
+    }
+}
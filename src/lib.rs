@@ -2,4 +2,6 @@
 pub mod commands;
 pub mod config;
 pub mod core;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod utils;
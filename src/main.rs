@@ -2,6 +2,7 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use env_logger::Env;
+use std::io::Write;
 
 mod commands;
 mod config;
@@ -14,9 +15,40 @@ mod utils;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Emit newline-delimited JSON progress events to 'stderr' or an inherited 'fd:N'
+    #[arg(long, global = true, value_name = "TARGET")]
+    progress_events: Option<String>,
+
+    /// How to render progress: animated spinners, plain log lines or nothing
+    #[arg(long, global = true, value_enum, default_value_t)]
+    progress: utils::progress::ProgressMode,
+
+    /// Config file to use instead of $ARCULA_CONFIG, ./arcula.toml or ~/.config/arcula/config.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<std::path::PathBuf>,
+
+    /// Named profile from the config file whose environments to use
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Answer yes to confirmation prompts, for scripts and cron jobs
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Print only results, warnings and errors: no plan, progress or info logging
+    #[arg(long, global = true)]
+    quiet: bool,
+
+    /// Output format; 'json' prints a single JSON document with the results
+    /// and nothing else on stdout
+    #[arg(short, long, global = true, value_enum, default_value_t)]
+    output: commands::OutputFormat,
 }
 
 #[derive(Subcommand)]
+// Parsed once at startup, so the size of the sync variant doesn't matter
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Synchronize data between MongoDB environments
     Sync {
@@ -24,28 +56,89 @@ enum Commands {
         #[arg(short, long)]
         from: Option<String>,
 
-        /// Target environment (LOCAL, DEV, STG, PROD)
+        /// Target environment (LOCAL, DEV, STG, PROD), @<group> to sync to
+        /// every member of an environment group from the config file, or
+        /// docker:<image> to sync into a throwaway container, e.g. 'docker:mongo:7'
         #[arg(short, long)]
         to: Option<String>,
 
-        /// Database to synchronize
-        #[arg(short, long)]
-        db: Option<String>,
+        /// Leave the container of a docker:<image> target running after the sync
+        #[arg(long)]
+        keep_container: bool,
+
+        /// Database to synchronize. Repeat it or use a glob such as 'tenant_*' to
+        /// sync several databases in one run
+        #[arg(short, long, value_name = "DB")]
+        db: Vec<String>,
 
-        /// Target database name (defaults to source database name)
+        /// Target database name (defaults to source database name). Supports the
+        /// placeholders {source_db}, {date} and {datetime}, e.g. '{source_db}_copy_{date}'
         #[arg(short = 'n', long)]
         target_db: Option<String>,
 
-        /// Create backup before import
-        #[arg(short, long, default_value = "true")]
+        /// Only sync these collections, comma-separated [default: all collections]
+        #[arg(
+            long,
+            value_name = "NAMES",
+            value_delimiter = ',',
+            conflicts_with = "schema_only"
+        )]
+        collections: Vec<String>,
+
+        /// Leave these collections out of the sync, comma-separated; the target
+        /// keeps its own copy of them
+        #[arg(
+            long,
+            value_name = "NAMES",
+            value_delimiter = ',',
+            conflicts_with_all = ["collections", "schema_only"]
+        )]
+        exclude_collections: Vec<String>,
+
+        /// Leave out collections whose data size exceeds this, e.g. '5GB'; the
+        /// target keeps its own copy of them
+        #[arg(
+            long,
+            value_name = "SIZE",
+            value_parser = utils::units::parse_size,
+            conflicts_with_all = ["collections", "schema_only"]
+        )]
+        skip_larger_than: Option<u64>,
+
+        /// Copy the newest N documents of collections over --skip-larger-than
+        /// instead of leaving them out
+        #[arg(long, value_name = "N", requires = "skip_larger_than")]
+        keep_newest: Option<u64>,
+
+        /// Field whose highest values are the newest documents for --keep-newest
+        /// [default: _id]
+        #[arg(long, value_name = "FIELD", requires = "keep_newest")]
+        newest_by: Option<String>,
+
+        /// Copy at most N documents of every collection, or with 'events=N' of one;
+        /// a collection's own limit wins over the one of every collection. Can be repeated
+        #[arg(long = "limit", value_name = "[COLLECTION=]N")]
+        limits: Vec<String>,
+
+        /// Which documents --limit copies: the first ones in natural order, or random
+        /// ones read with $sample [default: first]
+        #[arg(long, value_enum, value_name = "SAMPLING", requires = "limits")]
+        sampling: Option<core::sampling::Sampling>,
+
+        /// Create backup before import [default: true, or config file value]
+        #[arg(short, long)]
         backup: Option<bool>,
 
-        /// Drop collections during import
-        #[arg(short = 'D', long, default_value = "true")]
+        /// Also back up the source database before exporting it
+        #[arg(long)]
+        backup_source: bool,
+
+        /// Drop collections during import [default: true, or config file value]
+        #[arg(short = 'D', long)]
         drop: Option<bool>,
 
-        /// Clear collections during import (ignored if drop is enabled)
-        #[arg(short = 'c', long, default_value = "false")]
+        /// Clear collections during import (ignored if drop is enabled) [default: false, or config file value]
+        #[arg(short = 'c', long)]
         clear: Option<bool>,
 
         /// Interactive mode - prompt for values not provided on command line
@@ -55,9 +148,547 @@ enum Commands {
         /// Dry-run mode - show what would be done without executing
         #[arg(long)]
         dry_run: bool,
+
+        /// Sync every database matching a tenant prefix (e.g. 'tenant_*');
+        /// --target-db then acts as a rename rule where {id} is the tenant id
+        #[arg(long, conflicts_with = "db")]
+        tenant_prefix: Option<String>,
+
+        /// Mapping file (.yaml or .csv) of source_db → target_db pairs to sync in one run
+        #[arg(long = "map", value_name = "FILE", conflicts_with_all = ["db", "target_db", "tenant_prefix"])]
+        map_file: Option<std::path::PathBuf>,
+
+        /// JavaScript file to run with mongosh against the target database after import
+        #[arg(long, value_name = "FILE")]
+        post_sync_script: Option<std::path::PathBuf>,
+
+        /// JSON file mapping collection names to aggregation pipelines whose results
+        /// are copied instead of the full collection
+        #[arg(long, value_name = "FILE")]
+        pipelines: Option<std::path::PathBuf>,
+
+        /// Mapping file (.yaml or .csv) renaming collections on import; a target of
+        /// 'database.collection' redirects the collection into another database
+        #[arg(long, value_name = "FILE")]
+        collection_map: Option<std::path::PathBuf>,
+
+        /// Rename a collection on import, e.g. 'app.users=app.customers'; the database
+        /// parts are optional and a target in another database redirects the collection.
+        /// Can be repeated
+        #[arg(long, value_name = "FROM=TO")]
+        ns_map: Vec<String>,
+
+        /// Remove a field from the synced documents, e.g. 'ssn' from every collection
+        /// or 'users:profile.phone' from one; added to 'drop_fields' of the config file.
+        /// Can be repeated
+        #[arg(long = "drop-field", value_name = "[COLLECTION:]FIELD")]
+        drop_fields: Vec<String>,
+
+        /// After import, check that N random documents per collection exist
+        /// byte-identical in the target
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        verify_sample: Option<u64>,
+
+        /// After import, compare the document counts of every collection and
+        /// warn or fail on a mismatch [default: fail when given, or config file value]
+        #[arg(
+            long,
+            value_enum,
+            value_name = "ACTION",
+            num_args = 0..=1,
+            default_missing_value = "fail"
+        )]
+        verify_counts: Option<core::verify::CountMismatchAction>,
+
+        /// After import, run a deeper verification: 'checksum' compares the dbHash
+        /// of every collection, needs --drop or --clear and isn't supported through mongos
+        #[arg(long, value_enum, value_name = "MODE")]
+        verify: Option<core::verify::VerifyMode>,
+
+        /// Keep polling a target that doesn't accept connections yet for up to
+        /// this long before syncing, e.g. '10m' right after it was started
+        #[arg(long, value_name = "DURATION", value_parser = utils::units::parse_duration)]
+        wait_for_target: Option<std::time::Duration>,
+
+        /// Abort the sync when it runs longer than this, e.g. '90m' or '2h'
+        #[arg(long, value_name = "DURATION", value_parser = utils::units::parse_duration)]
+        max_duration: Option<std::time::Duration>,
+
+        /// Kill a mongodump or mongorestore run that takes longer than this, e.g. '30m',
+        /// instead of waiting for a hung tool forever; overrides the config timeouts
+        #[arg(long, value_name = "DURATION", value_parser = utils::units::parse_duration)]
+        timeout: Option<std::time::Duration>,
+
+        /// Retry the export and import this often when they fail on network errors,
+        /// e.g. a connection reset by Atlas; 0 disables retries (default: 2)
+        #[arg(long, value_name = "N")]
+        retries: Option<u32>,
+
+        /// Wait this long before the first retry, doubling it for every further one
+        /// (default: 10s)
+        #[arg(long, value_name = "DURATION", value_parser = utils::units::parse_duration)]
+        retry_backoff: Option<std::time::Duration>,
+
+        /// Restore the backup when --max-duration aborts the sync during or after the import
+        #[arg(long, requires = "max_duration")]
+        restore_on_timeout: bool,
+
+        /// Leave the target as it is when the import fails instead of restoring
+        /// the backup, which can still be restored with 'arcula restore'
+        #[arg(long)]
+        no_auto_restore: bool,
+
+        /// Refuse to sync a source database whose data size exceeds this, e.g. '50GB'
+        /// [default: config file value]
+        #[arg(long, value_name = "SIZE", value_parser = utils::units::parse_size)]
+        max_source_size: Option<u64>,
+
+        /// Sync a source database without any documents, which empties the target
+        #[arg(long)]
+        allow_empty_source: bool,
+
+        /// Copy collection definitions, indexes, validators and views without any documents
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "pipelines",
+                "collection_map",
+                "ns_map",
+                "drop_fields",
+                "limits",
+                "verify_sample",
+                "verify_counts",
+                "verify"
+            ]
+        )]
+        schema_only: bool,
+
+        /// Restore documents only, leaving target indexes and validators untouched;
+        /// collections are cleared instead of dropped
+        #[arg(long, conflicts_with = "schema_only")]
+        data_only: bool,
+
+        /// When merging (no drop or clear), which version wins for target documents
+        /// modified after the export started [default: prompt]
+        #[arg(long, value_enum)]
+        prefer: Option<core::conflicts::Prefer>,
+
+        /// Date field holding the last modification time of documents [default: updatedAt]
+        #[arg(long, value_name = "FIELD")]
+        updated_field: Option<String>,
+
+        /// What to do when the target has index builds or shard balancing in progress
+        /// before the restore [default: warn, or config file value]
+        #[arg(long, value_enum)]
+        on_busy_target: Option<core::preflight::BusyTargetAction>,
+
+        /// What to do when the import likely needs more disk space than the target
+        /// has free [default: abort, or config file value]
+        #[arg(long, value_enum)]
+        on_low_space: Option<core::preflight::LowSpaceAction>,
+
+        /// Only copy collections whose document count or content hash differs from
+        /// the target, compared with the backup taken before the import if any
+        #[arg(long, conflicts_with = "schema_only")]
+        delta: bool,
+
+        /// Compare with the manifest of an earlier backup of the target instead;
+        /// the target must not have changed since (implies --delta)
+        #[arg(long, value_name = "BACKUP_DIR", conflicts_with = "schema_only")]
+        delta_from: Option<std::path::PathBuf>,
+
+        /// Compress the export and the backup with gzip [default: false, or config file value]
+        #[arg(long)]
+        gzip: bool,
+
+        /// Export into DIR instead of a temporary directory and keep the dump
+        /// there after the import, e.g. to restore it again with mongorestore
+        #[arg(long, value_name = "DIR", conflicts_with = "stream")]
+        keep_dump: Option<std::path::PathBuf>,
+
+        /// Directory to stage the temporary dump in [default: $ARCULA_TEMP_DIR,
+        /// or the system temp directory]
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["stream", "keep_dump"])]
+        temp_dir: Option<std::path::PathBuf>,
+
+        /// How to copy the documents [default: tools]
+        #[arg(long, value_enum)]
+        engine: Option<core::engine::EngineKind>,
+
+        /// Pipe mongodump straight into mongorestore without a temporary
+        /// directory (same as --engine stream)
+        #[arg(long, conflicts_with_all = ["engine", "pipelines"])]
+        stream: bool,
+
+        /// Number of collections mongodump and mongorestore process at the same time
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        parallel_collections: Option<u64>,
+
+        /// Number of insertion workers mongorestore runs per collection, or with
+        /// --engine driver the number of _id ranges of a large collection copied
+        /// at the same time
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        insertion_workers: Option<u64>,
+
+        /// Replica set members to export from, e.g. 'secondary' to keep the load
+        /// off the primary [default: the source environment's config file value]
+        #[arg(long, value_name = "MODE")]
+        read_preference: Option<config::ReadPreference>,
+
+        /// Copy a point-in-time snapshot of a database that is written to during
+        /// the export: dump the whole source deployment with --oplog and replay
+        /// the oplog on import
+        #[arg(long, conflicts_with_all = ["stream", "engine"])]
+        oplog: bool,
+
+        /// Continue a sync whose import failed and left the target as it was
+        /// (without a backup or with --no-auto-restore), skipping the collections it
+        /// already copied
+        #[arg(long, conflicts_with = "oplog")]
+        resume: bool,
+
+        /// Only copy documents modified after DATE (e.g. 2024-01-01 or an RFC 3339
+        /// timestamp) and upsert them into the target through the driver
+        #[arg(long, value_name = "DATE", value_parser = utils::units::parse_date, conflicts_with_all = ["stream", "oplog", "schema_only"])]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Date field selecting the documents of an incremental sync; without --since,
+        /// copies what changed after the last incremental sync [default: --updated-field]
+        #[arg(long, value_name = "FIELD", conflicts_with_all = ["stream", "oplog", "schema_only"])]
+        incremental_field: Option<String>,
+
+        /// Don't sync; write the validated plan to a signed FILE to be run with 'arcula approve'
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["interactive", "dry_run"])]
+        request: Option<std::path::PathBuf>,
+
+        /// Leave databases matching a glob PATTERN out of interactive database lists
+        /// (repeatable, in addition to `hide_databases` from the config file)
+        #[arg(long = "hide-db", value_name = "PATTERN")]
+        hide_db: Vec<String>,
+
+        /// Named preset from the config file providing defaults for these options
+        #[arg(short, long)]
+        preset: Option<String>,
+
+        /// Value for a {NAME} variable in the preset and pipelines file (repeatable)
+        #[arg(short = 'v', long = "var", value_name = "NAME=VALUE", value_parser = core::variables::parse_variable)]
+        vars: Vec<(String, String)>,
     },
     /// Show information about available MongoDB environments
-    Info,
+    Info {
+        /// Show extra columns (documents, data size, index size)
+        #[arg(short, long)]
+        wide: bool,
+
+        /// Only show this environment
+        #[arg(short, long)]
+        env: Option<String>,
+
+        /// Only show databases matching a glob pattern (e.g. 'analytics*')
+        #[arg(short, long)]
+        db: Option<String>,
+
+        /// Per-environment query timeout in seconds
+        #[arg(short, long, default_value_t = 10)]
+        timeout: u64,
+    },
+    /// List the databases of an environment (system databases excluded)
+    ListDbs {
+        /// Environment to list
+        #[arg(short, long)]
+        env: String,
+    },
+    /// List the collections of a database with document counts and sizes
+    ListCollections {
+        /// Environment to inspect
+        #[arg(short, long)]
+        env: String,
+
+        /// Database to inspect
+        #[arg(short, long)]
+        db: String,
+    },
+    /// Copy documents matching a query into a (possibly differently named) target collection
+    Materialize {
+        /// Source collection as ENV:database.collection
+        #[arg(short, long)]
+        from: String,
+
+        /// Target collection as ENV:database.collection
+        #[arg(short, long)]
+        to: String,
+
+        /// Query filter in MongoDB Extended JSON
+        #[arg(short, long, default_value = "{}")]
+        query: String,
+
+        /// Drop the target collection before copying
+        #[arg(long)]
+        drop: bool,
+
+        /// Dry-run mode - count matching documents without copying
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Keep a target database in step with a source: an initial sync followed
+    /// by applying source changes from a change stream until interrupted
+    Mirror {
+        /// Source environment (needs a replica set or sharded cluster)
+        #[arg(short, long)]
+        from: String,
+
+        /// Target environment
+        #[arg(short, long)]
+        to: String,
+
+        /// Database to mirror
+        #[arg(short, long)]
+        db: String,
+
+        /// Target database name (defaults to the source database name)
+        #[arg(short = 'n', long)]
+        target_db: Option<String>,
+
+        /// Start with a new initial sync instead of resuming from the saved position
+        #[arg(long)]
+        resync: bool,
+
+        /// Experimental: also apply target changes to the source, logging conflicting
+        /// writes instead of resolving them (non-production environments only)
+        #[arg(long, conflicts_with = "resync")]
+        two_way: bool,
+
+        /// JSON Lines file conflicts of a two-way sync are appended to
+        /// [default: arcula-conflicts-<run id>.jsonl]
+        #[arg(long, value_name = "FILE", requires = "two_way")]
+        conflict_log: Option<std::path::PathBuf>,
+    },
+    /// Find which environments contain a database
+    FindDb {
+        /// Database name or glob pattern (e.g. 'tenant_acme*')
+        pattern: String,
+
+        /// Per-environment query timeout in seconds
+        #[arg(short, long, default_value_t = 10)]
+        timeout: u64,
+    },
+    /// Export selected fields of each collection to CSV or Parquet files
+    Export {
+        /// Source environment
+        #[arg(short, long)]
+        env: String,
+
+        /// Database to export
+        #[arg(short, long)]
+        db: String,
+
+        /// Output file format
+        #[arg(long, value_enum)]
+        format: core::export::ExportFormat,
+
+        /// Comma-separated fields to export, dotted paths allowed (e.g. '_id,name,address.city')
+        #[arg(long)]
+        fields: String,
+
+        /// Directory to write one file per collection into
+        #[arg(long)]
+        out: std::path::PathBuf,
+
+        /// Comma-separated collections to export (defaults to all)
+        #[arg(short, long)]
+        collections: Option<String>,
+    },
+    /// Print per-collection document counts and content hashes of a database
+    Checksum {
+        /// Environment to inspect
+        #[arg(short, long)]
+        env: String,
+
+        /// Database to inspect
+        #[arg(short, long)]
+        db: String,
+
+        /// Compare against an earlier JSON output or backup manifest and fail on differences
+        #[arg(long, value_name = "FILE")]
+        compare: Option<std::path::PathBuf>,
+    },
+    /// Back up a database into the backup directory, with a manifest of its checksums
+    Backup {
+        /// Environment to back up
+        #[arg(short, long)]
+        env: String,
+
+        /// Database to back up
+        #[arg(short, long)]
+        db: String,
+
+        /// Compress the backup with gzip [default: false, or config file value]
+        #[arg(long)]
+        gzip: bool,
+
+        /// Show the destination and size estimate without backing up
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Inspect and validate backups
+    Backups {
+        #[command(subcommand)]
+        command: BackupsCommands,
+    },
+    /// Restore a database or some of its collections from the backup taken during a run
+    Restore {
+        /// Run ID of the sync whose backup to restore
+        #[arg(long, value_name = "RUN_ID")]
+        from_run: String,
+
+        /// Backed-up database (needed when the run backed up several)
+        #[arg(short, long)]
+        db: Option<String>,
+
+        /// Environment to restore into (defaults to the backup's environment)
+        #[arg(short, long)]
+        to: Option<String>,
+
+        /// Side of the sync whose backup to restore
+        #[arg(long, value_enum, default_value_t = core::backup::BackupRole::Target)]
+        role: core::backup::BackupRole,
+
+        /// Collection to restore (repeatable) [default: all collections]
+        #[arg(short, long = "collection", value_name = "NAME")]
+        collections: Vec<String>,
+
+        /// Show which collections would be replaced without restoring
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Sync every database of an environment into another one
+    Clone {
+        /// Source environment
+        #[arg(short, long)]
+        from: String,
+
+        /// Target environment
+        #[arg(short, long)]
+        to: String,
+
+        /// Only clone databases matching a glob PATTERN (repeatable) [default: all databases]
+        #[arg(long, value_name = "PATTERN")]
+        include: Vec<String>,
+
+        /// Leave out databases matching a glob PATTERN (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+
+        /// Create backups before import [default: true, or config file value]
+        #[arg(short, long)]
+        backup: Option<bool>,
+
+        /// Drop collections during import [default: true, or config file value]
+        #[arg(short = 'D', long)]
+        drop: Option<bool>,
+
+        /// Compress exports and backups with gzip [default: false, or config file value]
+        #[arg(long)]
+        gzip: bool,
+
+        /// Show the databases that would be cloned without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove temp dumps, broken backups and old mirror checkpoints left behind by earlier runs
+    PruneTemp {
+        /// Minimum age of temp dumps and checkpoints to remove, e.g. '24h'
+        #[arg(long, value_name = "DURATION", default_value = "7d", value_parser = utils::units::parse_duration)]
+        older_than: std::time::Duration,
+
+        /// List what would be removed without removing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Execute a sync plan written by 'sync --request' after reviewing it
+    Approve {
+        /// Plan file
+        plan: std::path::PathBuf,
+    },
+    /// Serve sync, backup and list operations as JSON-RPC 2.0 over stdin and
+    /// stdout, one message per line, with progress events as notifications
+    Rpc {
+        /// Serve clients without a token as the caller when no tokens are
+        /// configured, instead of as viewers
+        #[arg(long)]
+        allow_unauthenticated: bool,
+    },
+    /// Serve environment inspection and, with --allow-writes, syncs and backups
+    /// as a Model Context Protocol server over stdin and stdout
+    Mcp {
+        /// Offer the sync and backup tools; syncs into production environments
+        /// need a client with an admin token
+        #[arg(long)]
+        allow_writes: bool,
+        /// Serve clients without a token as operators when no tokens are
+        /// configured, instead of as viewers
+        #[arg(long)]
+        allow_unauthenticated: bool,
+    },
+    /// Manage the arcula config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupsCommands {
+    /// Check a database against the counts and hashes recorded in a backup manifest
+    Verify {
+        /// Backup directory
+        path: std::path::PathBuf,
+
+        /// Environment to check (defaults to the backup's environment)
+        #[arg(short, long)]
+        env: Option<String>,
+
+        /// Database to check (defaults to the backed-up database)
+        #[arg(short, long)]
+        db: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Write the current MONGO_<ENV>_URI variables (and .env) to the config file
+    ImportEnv {
+        /// Config file to write [default: the --config file or ~/.config/arcula/config.toml]
+        #[arg(long, value_name = "FILE")]
+        file: Option<std::path::PathBuf>,
+
+        /// Store connection strings in the OS keyring and only reference them in the file
+        #[arg(long)]
+        keyring: bool,
+
+        /// Replace environments already present in the config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Register the connection string of MONGODB_URI, MONGO_URL or a similar
+    /// PaaS variable (and .env) as an environment
+    Detect {
+        /// Environment name to register [default: asked for, LOCAL for local servers, else APP]
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Config file to write [default: the --config file or ~/.config/arcula/config.toml]
+        #[arg(long, value_name = "FILE")]
+        file: Option<std::path::PathBuf>,
+
+        /// Store the connection string in the OS keyring and only reference it in the file
+        #[arg(long)]
+        keyring: bool,
+
+        /// Replace an environment already present in the config file
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 #[tokio::main]
@@ -67,7 +698,30 @@ async fn main() -> Result<()> {
             eprintln!("Warning: Failed to parse .env file: {}", e);
         }
     }
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    // Parse CLI arguments
+    let cli = Cli::parse();
+
+    // JSON output leaves stdout to the JSON document, RPC mode to its messages
+    let rpc = matches!(cli.command, Commands::Rpc { .. } | Commands::Mcp { .. });
+    let quiet = cli.quiet || rpc || cli.output == commands::OutputFormat::Json;
+    let default_filter = if quiet { "warn" } else { "info" };
+    env_logger::Builder::from_env(Env::default().default_filter_or(default_filter))
+        .format(|buf, record| {
+            let style = buf.default_level_style(record.level());
+            writeln!(
+                buf,
+                "[{} {style}{:<5}{style:#} {} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                utils::run_id::get(),
+                record.target(),
+                record.args()
+            )
+        })
+        .init();
+
+    config::file::init(cli.config.as_deref(), cli.profile.as_deref())?;
+    utils::theme::init(&config::file::get().ui);
 
     if let Err(err) = config::check_mongodb_tools() {
         eprintln!("Error: MongoDB tools not found. Please install MongoDB tools (mongodump and mongorestore).");
@@ -76,38 +730,370 @@ async fn main() -> Result<()> {
         return Err(anyhow::anyhow!("MongoDB tools not found"));
     }
 
-    // Parse CLI arguments
-    let cli = Cli::parse();
+    utils::progress::set_quiet(quiet);
+    utils::progress::set_mode(if quiet {
+        utils::progress::ProgressMode::None
+    } else {
+        cli.progress
+    });
+    if cli.output == commands::OutputFormat::Text && !rpc {
+        core::progress::set_renderer(commands::sync::ConsoleRenderer);
+    }
+    if let Some(target) = &cli.progress_events {
+        utils::events::init(target)?;
+    }
 
     // Process commands
     match cli.command {
         Commands::Sync {
             from,
             to,
+            keep_container,
             db,
             target_db,
+            collections,
+            exclude_collections,
+            skip_larger_than,
+            keep_newest,
+            newest_by,
+            limits,
+            sampling,
             backup,
+            backup_source,
             drop,
             clear,
             interactive,
             dry_run,
+            tenant_prefix,
+            map_file,
+            post_sync_script,
+            preset,
+            pipelines,
+            collection_map,
+            ns_map,
+            drop_fields,
+            verify_sample,
+            verify_counts,
+            verify,
+            wait_for_target,
+            max_duration,
+            timeout,
+            retries,
+            retry_backoff,
+            restore_on_timeout,
+            no_auto_restore,
+            max_source_size,
+            allow_empty_source,
+            schema_only,
+            data_only,
+            prefer,
+            updated_field,
+            on_busy_target,
+            on_low_space,
+            delta,
+            delta_from,
+            gzip,
+            keep_dump,
+            temp_dir,
+            engine,
+            stream,
+            parallel_collections,
+            insertion_workers,
+            read_preference,
+            oplog,
+            resume,
+            since,
+            incremental_field,
+            request,
+            hide_db,
+            vars,
         } => {
             let params = commands::sync::SyncParams {
                 from,
                 to,
-                db,
+                keep_container,
+                db: None,
+                databases: db,
                 target_db,
                 backup,
+                backup_source,
                 drop,
                 clear,
                 interactive,
                 dry_run,
+                yes: cli.yes,
+                output: cli.output,
+                tenant_prefix,
+                map_file,
+                post_sync_script,
+                preset,
+                pipelines,
+                collection_map,
+                ns_map,
+                drop_fields,
+                verify_sample,
+                verify_counts,
+                verify,
+                wait_for_target,
+                max_duration,
+                timeout,
+                retries,
+                retry_backoff,
+                restore_on_timeout,
+                no_auto_restore,
+                max_source_size,
+                allow_empty_source,
+                schema_only,
+                data_only,
+                prefer,
+                updated_field,
+                on_busy_target,
+                on_low_space,
+                delta,
+                delta_from,
+                collections,
+                exclude_collections,
+                skip_larger_than,
+                keep_newest,
+                newest_by,
+                limits,
+                sampling,
+                gzip,
+                keep_dump,
+                temp_dir,
+                engine: if stream {
+                    Some(core::engine::EngineKind::Stream)
+                } else {
+                    engine
+                },
+                parallel_collections,
+                insertion_workers,
+                read_preference,
+                oplog,
+                resume,
+                incremental_field,
+                since,
+                request,
+                hide_db,
+                vars: vars.into_iter().collect(),
             };
             commands::sync::execute_with_params(params).await?;
         }
-        Commands::Info => {
-            commands::info::execute().await?;
+        Commands::Info {
+            wide,
+            env,
+            db,
+            timeout,
+        } => {
+            let params = commands::info::InfoParams {
+                wide,
+                env,
+                db,
+                timeout,
+                output: cli.output,
+            };
+            commands::info::execute(params).await?;
+        }
+        Commands::ListDbs { env } => {
+            let params = commands::list_dbs::ListDbsParams {
+                env,
+                output: cli.output,
+            };
+            commands::list_dbs::execute(params).await?;
+        }
+        Commands::ListCollections { env, db } => {
+            let params = commands::list_collections::ListCollectionsParams {
+                env,
+                db,
+                output: cli.output,
+            };
+            commands::list_collections::execute(params).await?;
+        }
+        Commands::Materialize {
+            from,
+            to,
+            query,
+            drop,
+            dry_run,
+        } => {
+            let params = commands::materialize::MaterializeParams {
+                from,
+                to,
+                query,
+                drop,
+                dry_run,
+            };
+            commands::materialize::execute(params).await?;
+        }
+        Commands::Mirror {
+            from,
+            to,
+            db,
+            target_db,
+            resync,
+            two_way,
+            conflict_log,
+        } => {
+            let params = commands::mirror::MirrorParams {
+                from,
+                to,
+                db,
+                target_db,
+                resync,
+                two_way,
+                conflict_log,
+            };
+            commands::mirror::execute(params).await?;
+        }
+        Commands::FindDb { pattern, timeout } => {
+            let params = commands::find_db::FindDbParams {
+                pattern,
+                timeout,
+                output: cli.output,
+            };
+            commands::find_db::execute(params).await?;
+        }
+        Commands::Export {
+            env,
+            db,
+            format,
+            fields,
+            out,
+            collections,
+        } => {
+            let params = commands::export::ExportParams {
+                env,
+                db,
+                format,
+                fields,
+                out,
+                collections,
+            };
+            commands::export::execute(params).await?;
+        }
+        Commands::Checksum { env, db, compare } => {
+            let params = commands::checksum::ChecksumParams {
+                env,
+                db,
+                output: cli.output,
+                compare,
+            };
+            commands::checksum::execute(params).await?;
+        }
+        Commands::Backup {
+            env,
+            db,
+            gzip,
+            dry_run,
+        } => {
+            let params = commands::backups::BackupParams {
+                env,
+                db,
+                gzip,
+                dry_run,
+                output: cli.output,
+            };
+            commands::backups::execute(params).await?;
+        }
+        Commands::Backups { command } => match command {
+            BackupsCommands::Verify { path, env, db } => {
+                let params = commands::backups::VerifyBackupParams { path, env, db };
+                commands::backups::verify(params).await?;
+            }
+        },
+        Commands::Restore {
+            from_run,
+            db,
+            to,
+            role,
+            collections,
+            dry_run,
+        } => {
+            let params = commands::restore::RestoreParams {
+                from_run,
+                db,
+                to,
+                role,
+                collections,
+                yes: cli.yes,
+                dry_run,
+                output: cli.output,
+            };
+            commands::restore::execute(params).await?;
+        }
+        Commands::Clone {
+            from,
+            to,
+            include,
+            exclude,
+            backup,
+            drop,
+            gzip,
+            dry_run,
+        } => {
+            let params = commands::clone::CloneParams {
+                from,
+                to,
+                include,
+                exclude,
+                backup,
+                drop,
+                gzip,
+                dry_run,
+                yes: cli.yes,
+            };
+            commands::clone::execute(params).await?;
+        }
+        Commands::PruneTemp {
+            older_than,
+            dry_run,
+        } => {
+            let params = commands::prune_temp::PruneTempParams {
+                older_than,
+                dry_run,
+            };
+            commands::prune_temp::execute(params).await?;
+        }
+        Commands::Approve { plan } => {
+            let params = commands::approve::ApproveParams { plan, yes: cli.yes };
+            commands::approve::execute(params).await?;
         }
+        Commands::Rpc {
+            allow_unauthenticated,
+        } => commands::rpc::execute(allow_unauthenticated).await?,
+        Commands::Mcp {
+            allow_writes,
+            allow_unauthenticated,
+        } => commands::mcp::execute(allow_writes, allow_unauthenticated).await?,
+        Commands::Config { command } => match command {
+            ConfigCommands::ImportEnv {
+                file,
+                keyring,
+                force,
+            } => {
+                let params = commands::config::ImportEnvParams {
+                    output: file.or(cli.config),
+                    keyring,
+                    force,
+                };
+                commands::config::import_env(params)?;
+            }
+            ConfigCommands::Detect {
+                name,
+                file,
+                keyring,
+                force,
+            } => {
+                let params = commands::config::DetectParams {
+                    name,
+                    output: file.or(cli.config),
+                    keyring,
+                    force,
+                    yes: cli.yes,
+                };
+                commands::config::detect(params)?;
+            }
+        },
     }
 
     Ok(())
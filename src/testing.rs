@@ -0,0 +1,93 @@
+//! MongoDB deployments for integration tests.
+//!
+//! [`TestMongo::start`] connects to the deployments named by
+//! `TEST_MONGO_SOURCE_URI` and `TEST_MONGO_TARGET_URI` when both are set, as
+//! in CI, or else starts a throwaway source and target container with Docker.
+//! Containers are removed when the `TestMongo` is dropped, also when a test
+//! panics.
+
+use anyhow::Result;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::config::{self, Environment, MongoConfig};
+use crate::core::preflight;
+use crate::utils::docker::MongoContainer;
+use crate::utils::run_id;
+
+/// Connection string of an existing source deployment to test against
+pub const SOURCE_URI_VAR: &str = "TEST_MONGO_SOURCE_URI";
+
+/// Connection string of an existing target deployment to test against
+pub const TARGET_URI_VAR: &str = "TEST_MONGO_TARGET_URI";
+
+/// Image the test containers are started from
+pub const IMAGE: &str = "mongo:latest";
+
+/// How long a new container may take to accept connections
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A source and a target deployment, registered as the `TEST_SOURCE` and
+/// `TEST_TARGET` environments so syncs between them resolve like configured ones
+pub struct TestMongo {
+    pub source: MongoConfig,
+    pub target: MongoConfig,
+    containers: Vec<String>,
+}
+
+impl TestMongo {
+    pub async fn start() -> Result<Self> {
+        let mongo = match (std::env::var(SOURCE_URI_VAR), std::env::var(TARGET_URI_VAR)) {
+            (Ok(source), Ok(target)) => Self {
+                source: test_config("TEST_SOURCE", source),
+                target: test_config("TEST_TARGET", target),
+                containers: Vec::new(),
+            },
+            _ => Self::start_containers().await?,
+        };
+        for config in [&mongo.source, &mongo.target] {
+            config::register_environment(&config.environment, &config.connection_string);
+        }
+        Ok(mongo)
+    }
+
+    async fn start_containers() -> Result<Self> {
+        let suffix = run_id::generate(chrono::Utc::now());
+        let source =
+            MongoContainer::start(IMAGE, &format!("arcula-test-source-{}", suffix), "test").await?;
+        let mut mongo = Self {
+            source: test_config("TEST_SOURCE", source.uri),
+            target: test_config("TEST_TARGET", String::new()),
+            containers: vec![source.name],
+        };
+        // Dropping `mongo` removes the source container if the target fails
+        let target =
+            MongoContainer::start(IMAGE, &format!("arcula-test-target-{}", suffix), "test").await?;
+        mongo.target.connection_string = target.uri;
+        mongo.containers.push(target.name);
+
+        for config in [&mongo.source, &mongo.target] {
+            preflight::wait_for_target(config, "test", STARTUP_TIMEOUT).await?;
+        }
+        Ok(mongo)
+    }
+}
+
+impl Drop for TestMongo {
+    fn drop(&mut self) {
+        for name in &self.containers {
+            let _ = Command::new("docker")
+                .args(["rm", "-f", name])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+}
+
+fn test_config(environment: &str, connection_string: String) -> MongoConfig {
+    MongoConfig {
+        connection_string,
+        environment: Environment::new(environment),
+    }
+}
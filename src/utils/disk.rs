@@ -0,0 +1,30 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tokio::process::Command;
+
+/// Free bytes on the filesystem holding a path, as reported by `df`
+pub async fn free_space(path: &Path) -> Result<u64> {
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(path)
+        .output()
+        .await
+        .context("Failed to run df")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "df failed for {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+        .context(format!("Unexpected df output for {}", path.display()))
+}
+
+/// Available bytes in the output of `df -Pk`: a header line, then one line per
+/// filesystem with the available 1024-byte blocks in the fourth column
+pub fn parse_df_available(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let blocks: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(blocks * 1024)
+}
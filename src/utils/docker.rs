@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Prefix of `--to` values naming an image to run a throwaway target from
+pub const TARGET_PREFIX: &str = "docker:";
+
+/// Image used for a bare `docker:` target
+pub const DEFAULT_IMAGE: &str = "mongo:latest";
+
+/// Port mongod listens on inside the official images
+const MONGO_PORT: &str = "27017/tcp";
+
+/// The image of a `docker:<image>` target, e.g. `mongo:7` for `docker:mongo:7`
+pub fn target_image(target: &str) -> Option<&str> {
+    let image = target.strip_prefix(TARGET_PREFIX)?;
+    Some(if image.is_empty() {
+        DEFAULT_IMAGE
+    } else {
+        image
+    })
+}
+
+/// A MongoDB container started for a single run
+#[derive(Debug, Clone)]
+pub struct MongoContainer {
+    pub name: String,
+    /// Connection string of the container's port published on localhost
+    pub uri: String,
+}
+
+impl MongoContainer {
+    /// Start a container from a MongoDB image with its port published on a
+    /// free port of localhost, labeled `arcula=<role>`. Containers are removed
+    /// once stopped.
+    pub async fn start(image: &str, name: &str, role: &str) -> Result<Self> {
+        let label = format!("arcula={}", role);
+        let output = Command::new("docker")
+            .args(["run", "-d", "--rm", "-p", "127.0.0.1::27017"])
+            .args(["--label", &label, "--name", name, image])
+            .output()
+            .await
+            .context("Failed to run docker; is Docker installed?")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to start a {} container: {}",
+                image,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let container = Self {
+            name: name.to_string(),
+            uri: String::new(),
+        };
+        match container.published_port().await {
+            Ok(port) => Ok(Self {
+                uri: format!("mongodb://127.0.0.1:{}/?directConnection=true", port),
+                ..container
+            }),
+            Err(e) => {
+                container.remove().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn published_port(&self) -> Result<u16> {
+        let output = Command::new("docker")
+            .args(["port", &self.name, MONGO_PORT])
+            .output()
+            .await
+            .context("Failed to run docker port")?;
+        parse_published_port(&String::from_utf8_lossy(&output.stdout)).context(format!(
+            "Container {} publishes no port for {}",
+            self.name, MONGO_PORT
+        ))
+    }
+
+    /// Stop and remove the container, logging rather than failing
+    pub async fn remove(&self) {
+        let status = Command::new("docker")
+            .args(["rm", "-f", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+        if !status.is_ok_and(|s| s.success()) {
+            log::error!("Failed to remove container {}", self.name);
+        }
+    }
+}
+
+/// Host port in the output of `docker port`, e.g. `127.0.0.1:49153`
+pub fn parse_published_port(output: &str) -> Option<u16> {
+    output
+        .lines()
+        .find_map(|line| line.trim().rsplit_once(':')?.1.parse().ok())
+}
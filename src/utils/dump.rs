@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// File written next to the dump in every backup directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Oplog written during the dump by `mongodump --oplog`
+pub const OPLOG_FILE: &str = "oplog.bson";
+
+/// The files of a mongodump output directory (`mongodump --out`).
+///
+/// Every database is a directory holding a `<collection>.bson` data file and
+/// a `<collection>.metadata.json` file with indexes and options per
+/// collection. Dumps taken with `--gzip` add a `.gz` suffix to both.
+/// Dumps taken with `--oplog` cover the whole deployment and hold the oplog
+/// entries written while dumping in an `oplog.bson` at the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpLayout {
+    root: PathBuf,
+    compressed: bool,
+}
+
+impl DumpLayout {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            compressed: false,
+        }
+    }
+
+    /// Layout of an existing dump, compressed if any of its files are
+    pub fn open(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let compressed = std::fs::read_dir(&root)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| std::fs::read_dir(entry.ok()?.path()).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().ends_with(".gz"));
+        Self { root, compressed }
+    }
+
+    /// Whether the dump is written and read with `--gzip`
+    pub fn compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Directory passed to `mongodump --out` and `mongorestore`
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn database_dir(&self, database: &str) -> PathBuf {
+        self.root.join(database)
+    }
+
+    pub fn has_database(&self, database: &str) -> bool {
+        self.database_dir(database).is_dir()
+    }
+
+    /// BSON data of a collection
+    pub fn collection_file(&self, database: &str, collection: &str) -> PathBuf {
+        self.database_dir(database)
+            .join(format!("{}.bson{}", collection, self.suffix()))
+    }
+
+    /// Indexes and collection options of a collection or view
+    pub fn metadata_file(&self, database: &str, collection: &str) -> PathBuf {
+        self.database_dir(database)
+            .join(format!("{}.metadata.json{}", collection, self.suffix()))
+    }
+
+    /// Oplog of a point-in-time dump, replayed by `mongorestore --oplogReplay`
+    pub fn oplog_file(&self) -> PathBuf {
+        self.root.join(format!("{}{}", OPLOG_FILE, self.suffix()))
+    }
+
+    pub fn has_oplog(&self) -> bool {
+        self.oplog_file().is_file()
+    }
+
+    /// Backup manifest written next to the dump
+    pub fn manifest_file(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE)
+    }
+
+    /// Names of the collections with data in the dump of a database, sorted.
+    /// Views only have a metadata file and are not included.
+    pub fn collections(&self, database: &str) -> Result<Vec<String>> {
+        let dir = self.database_dir(database);
+        let extension = format!(".bson{}", self.suffix());
+
+        let mut collections = Vec::new();
+        for entry in std::fs::read_dir(&dir).context(format!("Failed to read {}", dir.display()))? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if let Some(collection) = name.strip_suffix(extension.as_str()) {
+                collections.push(collection.to_string());
+            }
+        }
+        collections.sort();
+        Ok(collections)
+    }
+
+    /// Bytes of BSON data in the dump of a collection, decompressed if the
+    /// dump is compressed
+    pub fn data_size(&self, database: &str, collection: &str) -> Result<u64> {
+        let path = self.collection_file(database, collection);
+        let file =
+            std::fs::File::open(&path).context(format!("Failed to open {}", path.display()))?;
+        if !self.compressed {
+            return Ok(file.metadata()?.len());
+        }
+        let mut decoder = flate2::read::MultiGzDecoder::new(std::io::BufReader::new(file));
+        std::io::copy(&mut decoder, &mut std::io::sink())
+            .context(format!("Failed to decompress {}", path.display()))
+    }
+
+    /// Move the dump of a database to another name, so mongorestore restores it there
+    pub fn rename_database(&self, from: &str, to: &str) -> Result<()> {
+        let target = self.database_dir(to);
+        let _ = std::fs::remove_dir_all(&target);
+        std::fs::rename(self.database_dir(from), &target)
+            .context(format!("Failed to rename the dump of {} to {}", from, to))
+    }
+
+    fn suffix(&self) -> &'static str {
+        if self.compressed {
+            ".gz"
+        } else {
+            ""
+        }
+    }
+}
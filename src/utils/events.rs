@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+use crate::core::progress::SyncPhase;
+
+static SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+static HANDLER: OnceLock<Handler> = OnceLock::new();
+
+type Handler = Box<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    SyncStarted,
+    SyncFinished,
+    SyncFailed,
+    PhaseStarted,
+    PhaseFinished,
+    Progress,
+    Warning,
+}
+
+/// A single newline-delimited JSON progress event
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    pub event: EventKind,
+    pub run_id: String,
+    pub timestamp: String,
+    pub database: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<SyncPhase>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub done: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Stable code of a warning, e.g. `backup_failed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ProgressEvent {
+    pub fn new(event: EventKind, database: &str) -> Self {
+        Self {
+            event,
+            run_id: super::run_id::get().to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            database: database.to_string(),
+            phase: None,
+            collection: None,
+            done: None,
+            total: None,
+            error: None,
+            code: None,
+            message: None,
+        }
+    }
+
+    pub fn phase(mut self, phase: SyncPhase) -> Self {
+        self.phase = Some(phase);
+        self
+    }
+
+    /// Progress through the collections of a phase
+    pub fn collection(mut self, collection: &str, done: u64, total: u64) -> Self {
+        self.collection = Some(collection.to_string());
+        self.done = Some(done);
+        self.total = Some(total);
+        self
+    }
+
+    pub fn error(mut self, error: impl ToString) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+
+    pub fn warning(mut self, code: &str, message: &str) -> Self {
+        self.code = Some(code.to_string());
+        self.message = Some(message.to_string());
+        self
+    }
+}
+
+/// Send progress events to `stderr` or an inherited file descriptor (`fd:N`)
+pub fn init(target: &str) -> Result<()> {
+    let sink: Box<dyn Write + Send> = match target {
+        "stderr" => Box::new(std::io::stderr()),
+        _ => {
+            let fd: i32 = target
+                .strip_prefix("fd:")
+                .and_then(|fd| fd.parse().ok())
+                .context(format!(
+                    "Invalid progress event target '{}' (expected 'stderr' or 'fd:N')",
+                    target
+                ))?;
+            open_fd(fd)?
+        }
+    };
+
+    let _ = SINK.set(Mutex::new(sink));
+    Ok(())
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<Box<dyn Write + Send>> {
+    use std::os::fd::FromRawFd;
+
+    // Refuse descriptors that aren't open, as they could later be reused by a socket
+    if std::fs::symlink_metadata(format!("/dev/fd/{}", fd)).is_err() {
+        anyhow::bail!("File descriptor {} is not open", fd);
+    }
+
+    // SAFETY: the descriptor was inherited from the parent process for this purpose
+    // and is owned by the sink from here on
+    Ok(Box::new(unsafe { std::fs::File::from_raw_fd(fd) }))
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> Result<Box<dyn Write + Send>> {
+    anyhow::bail!("Progress events on file descriptors are only supported on Unix")
+}
+
+/// Also hand every event to a callback, e.g. to forward them as
+/// notifications of `arcula rpc`; only the first callback set is used
+pub fn set_handler(handler: impl Fn(&ProgressEvent) + Send + Sync + 'static) {
+    let _ = HANDLER.set(Box::new(handler));
+}
+
+/// Write an event if a progress event target was configured
+pub fn emit(event: ProgressEvent) {
+    if let Some(handler) = HANDLER.get() {
+        handler(&event);
+    }
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+
+    let result = serde_json::to_string(&event)
+        .map_err(std::io::Error::from)
+        .and_then(|line| {
+            let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+            writeln!(sink, "{}", line)?;
+            sink.flush()
+        });
+
+    if let Err(e) = result {
+        debug!("Failed to write progress event: {}", e);
+    }
+}
@@ -0,0 +1,51 @@
+use log::debug;
+use std::env;
+use std::io::Write;
+
+/// Whether arcula is running inside a GitHub Actions workflow
+pub fn is_github_actions() -> bool {
+    env::var("GITHUB_ACTIONS").is_ok_and(|v| v == "true")
+}
+
+/// Escape a workflow command message so newlines don't end the command
+pub fn escape_data(message: &str) -> String {
+    message
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Start a collapsible log group
+pub fn group(title: &str) {
+    println!("::group::{}", escape_data(title));
+}
+
+pub fn end_group() {
+    println!("::endgroup::");
+}
+
+/// Emit an error annotation shown on the workflow run
+pub fn error(title: &str, message: &str) {
+    println!(
+        "::error title={}::{}",
+        escape_data(title).replace(',', "%2C").replace(':', "%3A"),
+        escape_data(message)
+    );
+}
+
+/// Append markdown to the job's step summary, if the workflow provides one
+pub fn append_step_summary(markdown: &str) {
+    let Ok(path) = env::var("GITHUB_STEP_SUMMARY") else {
+        return;
+    };
+
+    let result = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", markdown));
+
+    if let Err(e) = result {
+        debug!("Failed to write step summary to {}: {}", path, e);
+    }
+}
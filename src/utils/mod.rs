@@ -1 +1,14 @@
+pub mod disk;
+pub mod docker;
+pub mod dump;
+pub mod events;
+pub mod github;
 pub mod mongodb;
+pub mod parquet;
+pub mod pattern;
+pub mod progress;
+pub mod run_id;
+pub mod statsd;
+pub mod table;
+pub mod theme;
+pub mod units;
@@ -1,11 +1,27 @@
 use anyhow::{Context, Result};
-use indicatif::{ProgressBar, ProgressStyle};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::{StreamExt, TryStreamExt};
 use log::{error, info};
+use mongodb::action::Action;
+use mongodb::options::SelectionCriteria;
+use serde::Serialize;
 use std::path::Path;
+use std::process::{Output, Stdio};
 use std::str;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tokio::process::Command;
 
-use crate::config::{get_backup_dir, get_mongodb_bin_path, MongoConfig};
+use crate::config::{
+    get_backup_dir, get_mongodb_bin_path, get_mongosh_path, get_tool_path, MongoConfig,
+    ReadPreference,
+};
+use crate::core::transform::Transform;
+use crate::utils::dump::DumpLayout;
+use crate::utils::parquet::ParquetWriter;
+use crate::utils::progress::create_progress_bar;
+use crate::utils::units::format_duration;
 
 pub fn validate_db_name(name: &str) -> Result<()> {
     if name.is_empty() {
@@ -35,20 +51,355 @@ pub fn mask_connection_string(uri: &str) -> String {
 }
 
 pub async fn list_databases(config: &MongoConfig) -> Result<Vec<String>> {
-    let client_options = config.get_client_options().await?;
-    let client = mongodb::Client::with_options(client_options)?;
+    let client = config.client().await?;
 
     let db_names = client.list_database_names().await?;
 
     Ok(db_names)
 }
 
+/// Size and content statistics for a single database
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatabaseStats {
+    pub name: String,
+    pub size_on_disk: u64,
+    pub collections: u64,
+    pub objects: u64,
+    pub data_size: u64,
+    pub index_size: u64,
+}
+
+/// Read a numeric field from a command response, whatever its BSON number type
+pub fn get_u64(doc: &mongodb::bson::Document, key: &str) -> u64 {
+    match doc.get(key) {
+        Some(mongodb::bson::Bson::Int32(v)) => (*v).max(0) as u64,
+        Some(mongodb::bson::Bson::Int64(v)) => (*v).max(0) as u64,
+        Some(mongodb::bson::Bson::Double(v)) => v.max(0.0) as u64,
+        _ => 0,
+    }
+}
+
+/// Get the MongoDB server version of an environment
+pub async fn get_server_version(config: &MongoConfig) -> Result<String> {
+    let client = config.client().await?;
+
+    let build_info = client
+        .database("admin")
+        .run_command(mongodb::bson::doc! { "buildInfo": 1 })
+        .await?;
+
+    Ok(build_info
+        .get_str("version")
+        .unwrap_or("unknown")
+        .to_string())
+}
+
+/// List databases accepted by `filter` together with their sizes and collection counts
+pub async fn list_database_stats(
+    config: &MongoConfig,
+    filter: impl Fn(&str) -> bool,
+) -> Result<Vec<DatabaseStats>> {
+    let client = config.client().await?;
+
+    let mut stats = Vec::new();
+    for spec in client.list_databases().await? {
+        if !filter(&spec.name) {
+            continue;
+        }
+
+        let db_stats = client
+            .database(&spec.name)
+            .run_command(mongodb::bson::doc! { "dbStats": 1 })
+            .await?;
+
+        stats.push(DatabaseStats {
+            size_on_disk: spec.size_on_disk,
+            collections: get_u64(&db_stats, "collections"),
+            objects: get_u64(&db_stats, "objects"),
+            data_size: get_u64(&db_stats, "dataSize"),
+            index_size: get_u64(&db_stats, "indexSize"),
+            name: spec.name,
+        });
+    }
+
+    Ok(stats)
+}
+
+/// Get the `dbStats` of a single database
+pub async fn get_database_stats(config: &MongoConfig, database: &str) -> Result<DatabaseStats> {
+    validate_db_name(database)?;
+
+    let client = config.client().await?;
+
+    let db_stats = client
+        .database(database)
+        .run_command(mongodb::bson::doc! { "dbStats": 1 })
+        .await?;
+
+    Ok(DatabaseStats {
+        name: database.to_string(),
+        size_on_disk: get_u64(&db_stats, "storageSize") + get_u64(&db_stats, "indexSize"),
+        collections: get_u64(&db_stats, "collections"),
+        objects: get_u64(&db_stats, "objects"),
+        data_size: get_u64(&db_stats, "dataSize"),
+        index_size: get_u64(&db_stats, "indexSize"),
+    })
+}
+
+/// Check that a deployment accepts connections and answers commands
+pub async fn ping(config: &MongoConfig) -> Result<()> {
+    let client = config.client().await?;
+    client
+        .database("admin")
+        .run_command(mongodb::bson::doc! { "ping": 1 })
+        .await?;
+    Ok(())
+}
+
+/// Free bytes on the filesystem holding the data files of a database, from
+/// `fsTotalSize` and `fsUsedSize` of `dbStats`.
+///
+/// `None` when the server doesn't report them, e.g. for a database that
+/// doesn't exist yet on some versions.
+pub async fn get_free_storage(config: &MongoConfig, database: &str) -> Result<Option<u64>> {
+    validate_db_name(database)?;
+
+    let client = config.client().await?;
+    let db_stats = client
+        .database(database)
+        .run_command(mongodb::bson::doc! { "dbStats": 1 })
+        .await?;
+
+    let total = get_u64(&db_stats, "fsTotalSize");
+    if total == 0 {
+        return Ok(None);
+    }
+    Ok(Some(total.saturating_sub(get_u64(&db_stats, "fsUsedSize"))))
+}
+
+/// Cluster activity that makes a restore likely to fail halfway through
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetActivity {
+    /// Namespaces with an index build in progress
+    pub index_builds: Vec<String>,
+    /// Whether the shard balancer is currently migrating chunks
+    pub balancer_running: bool,
+}
+
+/// Look for running index builds and an active shard balancer.
+///
+/// The balancer is only reported through mongos; on other deployments it is
+/// treated as not running.
+pub async fn get_target_activity(config: &MongoConfig) -> Result<TargetActivity> {
+    let client = config.client().await?;
+    let admin = client.database("admin");
+
+    let current_op = admin
+        .run_command(mongodb::bson::doc! {
+            "currentOp": true,
+            "$or": [
+                { "command.createIndexes": { "$exists": true } },
+                { "desc": { "$regex": "^IndexBuildsCoordinator" } },
+            ],
+        })
+        .await
+        .context("Failed to list running operations")?;
+
+    let mut index_builds: Vec<String> = current_op
+        .get_array("inprog")
+        .map(|ops| {
+            ops.iter()
+                .filter_map(|op| op.as_document())
+                .filter_map(|op| op.get_str("ns").ok())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    index_builds.sort();
+    index_builds.dedup();
+
+    let balancer_running = match admin
+        .run_command(mongodb::bson::doc! { "balancerStatus": 1 })
+        .await
+    {
+        Ok(status) => status.get_bool("inBalancerRound").unwrap_or(false),
+        // Not a sharded cluster
+        Err(_) => false,
+    };
+
+    Ok(TargetActivity {
+        index_builds,
+        balancer_running,
+    })
+}
+
+/// List the non-system collection names of a database, sorted
+pub async fn list_collection_names(config: &MongoConfig, database: &str) -> Result<Vec<String>> {
+    validate_db_name(database)?;
+
+    let client = config.client().await?;
+
+    let mut names = client.database(database).list_collection_names().await?;
+    names.retain(|name| !name.starts_with("system."));
+    names.sort();
+
+    Ok(names)
+}
+
+/// collStats commands running at the same time in `list_collection_stats`
+const STATS_CONCURRENCY: usize = 8;
+
+/// Document count and size statistics for a single collection
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CollectionStats {
+    pub name: String,
+    /// `collection`, `view` or `timeseries`
+    pub kind: String,
+    pub documents: u64,
+    pub size: u64,
+    pub storage_size: u64,
+    pub index_size: u64,
+}
+
+/// List the collections of a database with their document counts and sizes
+pub async fn list_collection_stats(
+    config: &MongoConfig,
+    database: &str,
+) -> Result<Vec<CollectionStats>> {
+    validate_db_name(database)?;
+
+    let client = config.client().await?;
+    let db = client.database(database);
+
+    let mut specs: Vec<_> = db.list_collections().await?.try_collect().await?;
+    specs.sort_by(|a, b| a.name.cmp(&b.name));
+    specs.retain(|spec| !spec.name.starts_with("system."));
+
+    // collStats is a round trip per collection, so several run at a time
+    futures::stream::iter(specs)
+        .map(|spec| collection_stats(&db, spec))
+        .buffered(STATS_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+/// Statistics of one collection, or just the name and kind of a view
+async fn collection_stats(
+    db: &mongodb::Database,
+    spec: mongodb::results::CollectionSpecification,
+) -> Result<CollectionStats> {
+    let kind = match spec.collection_type {
+        mongodb::results::CollectionType::View => "view",
+        mongodb::results::CollectionType::Timeseries => "timeseries",
+        _ => "collection",
+    };
+
+    let mut collection = CollectionStats {
+        name: spec.name.clone(),
+        kind: kind.to_string(),
+        ..Default::default()
+    };
+
+    // Views have no storage of their own
+    if kind != "view" {
+        let coll_stats = db
+            .run_command(mongodb::bson::doc! { "collStats": &spec.name })
+            .await?;
+        collection.documents = get_u64(&coll_stats, "count");
+        collection.size = get_u64(&coll_stats, "size");
+        collection.storage_size = get_u64(&coll_stats, "storageSize");
+        collection.index_size = get_u64(&coll_stats, "totalIndexSize");
+    }
+
+    Ok(collection)
+}
+
+/// What `export_database` and `stream_database` dump and how mongodump reads it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions<'a> {
+    /// Dump only these collections, one mongodump run each since
+    /// `--collection` takes a single name; all of them when empty
+    pub collections: &'a [String],
+    /// Collections left out of the dump
+    pub exclude: &'a [String],
+    /// Collections dumped at the same time instead of mongodump's default
+    pub parallel_collections: Option<u64>,
+    /// Replica set members to read from instead of the connection string's
+    pub read_preference: Option<ReadPreference>,
+    /// Dump the whole deployment with `--oplog` for a point-in-time
+    /// consistent copy; mongodump only records the oplog for full dumps
+    pub oplog: bool,
+    /// Dump only the indexes and options of the selected collections
+    pub without_documents: bool,
+    /// Kill a mongodump run that takes longer than this
+    pub timeout: Option<Duration>,
+}
+
+impl ExportOptions<'_> {
+    fn apply(&self, mongodump: &mut Command) {
+        if let Some(parallel) = self.parallel_collections {
+            mongodump
+                .arg("--numParallelCollections")
+                .arg(parallel.to_string());
+        }
+        if let Some(read_preference) = self.read_preference {
+            mongodump
+                .arg("--readPreference")
+                .arg(read_preference.as_str());
+        }
+    }
+}
+
+/// A MongoDB tool killed because it ran longer than its timeout
+#[derive(Debug, thiserror::Error)]
+#[error("{tool} timed out after {} and was killed", format_duration(*.timeout))]
+pub struct ToolTimeout {
+    pub tool: &'static str,
+    pub timeout: Duration,
+}
+
+/// Run a MongoDB tool to completion, killing it once it has run longer
+/// than `timeout`
+async fn run_tool(
+    command: &mut Command,
+    tool: &'static str,
+    timeout: Option<Duration>,
+) -> Result<Output> {
+    // Aborting the sync (e.g. on --max-duration) must not leave the tool
+    // running either, so the child is killed whenever its future is dropped
+    let output = command.kill_on_drop(true).output();
+    let output = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, output)
+            .await
+            .map_err(|_| ToolTimeout { tool, timeout })?,
+        None => output.await,
+    };
+    output.with_context(|| format!("Failed to execute {}", tool))
+}
+
+/// Dump a database with mongodump
 pub async fn export_database(
     config: &MongoConfig,
     database: &str,
-    output_dir: &Path,
+    dump: &DumpLayout,
+    options: ExportOptions<'_>,
 ) -> Result<()> {
+    let ExportOptions {
+        collections,
+        exclude,
+        oplog,
+        without_documents,
+        timeout,
+        ..
+    } = options;
     validate_db_name(database)?;
+    if oplog && !(collections.is_empty() && exclude.is_empty()) {
+        anyhow::bail!("A dump with the oplog can't select or exclude collections");
+    }
+    // mongodump only takes a query with --collection
+    if without_documents && collections.is_empty() {
+        anyhow::bail!("A dump without documents needs collections to select");
+    }
     info!(
         "Exporting database {} from {}",
         database, config.environment
@@ -68,59 +419,1078 @@ pub async fn export_database(
         mask_connection_string(&config.connection_string)
     );
 
-    // Use the traditional --db flag for mongodump (compatible with older versions)
-    let output = Command::new(mongodump_path)
+    let runs: Vec<Option<&String>> = if collections.is_empty() {
+        vec![None]
+    } else {
+        collections
+            .iter()
+            .filter(|c| !exclude.contains(c))
+            .map(Some)
+            .collect()
+    };
+
+    for collection in runs {
+        let mut command = Command::new(&mongodump_path);
+        command
+            .arg("--uri")
+            .arg(&config.connection_string)
+            .args(config.tool_args());
+        if oplog {
+            command.arg("--oplog");
+        } else {
+            // Use the traditional --db flag for mongodump (compatible with older versions)
+            command.arg("--db").arg(database);
+        }
+        command.arg("--out").arg(dump.root());
+        if dump.is_compressed() {
+            command.arg("--gzip");
+        }
+        options.apply(&mut command);
+        match collection {
+            Some(collection) => {
+                command.arg("--collection").arg(collection);
+                if without_documents {
+                    // Matches nothing without reading a document
+                    command.arg("--query").arg(r#"{"_id": {"$in": []}}"#);
+                }
+            }
+            None => {
+                for collection in exclude {
+                    command.arg("--excludeCollection").arg(collection);
+                }
+            }
+        }
+
+        let output = match run_tool(&mut command, "mongodump", timeout).await {
+            Ok(output) => output,
+            Err(e) => {
+                progress.finish_with_message("Export failed");
+                return Err(e);
+            }
+        };
+
+        if !output.status.success() {
+            progress.finish_with_message("Export failed");
+            let stderr = str::from_utf8(&output.stderr)?;
+            error!("Export failed: {}", stderr);
+            anyhow::bail!("Export failed: {}", stderr);
+        } else {
+            let stdout = str::from_utf8(&output.stdout)?;
+            info!("Export output: {}", stdout);
+        }
+    }
+
+    progress.finish_with_message("Export completed");
+
+    let db_path = dump.database_dir(database);
+    if !db_path.exists() {
+        info!(
+            "Database '{}' appears to be empty, creating placeholder directory",
+            database
+        );
+        std::fs::create_dir_all(&db_path)
+            .context("Failed to create placeholder for empty database")?;
+    }
+
+    Ok(())
+}
+
+/// Replace a collection in an export with the results of an aggregation pipeline.
+///
+/// The results replace the collection file of the dump in the format mongodump
+/// uses, so the collection's index metadata is kept.
+pub async fn export_pipeline_results(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    pipeline: Vec<mongodb::bson::Document>,
+    dump: &DumpLayout,
+) -> Result<u64> {
+    validate_db_name(database)?;
+    info!(
+        "Exporting {}.{} from {} through an aggregation pipeline",
+        database, collection, config.environment
+    );
+
+    let client = config.client().await?;
+
+    let mut cursor = client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection)
+        .aggregate(pipeline)
+        .allow_disk_use(true)
+        .await
+        .context(format!("Aggregation on {}.{} failed", database, collection))?;
+
+    std::fs::create_dir_all(dump.database_dir(database))?;
+    let file = std::fs::File::create(dump.collection_file(database, collection))?;
+    let mut writer = std::io::BufWriter::new(file);
+
+    let count = if dump.is_compressed() {
+        let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+        let count = write_documents(&mut cursor, &mut encoder).await?;
+        encoder.finish()?;
+        count
+    } else {
+        write_documents(&mut cursor, &mut writer).await?
+    };
+    std::io::Write::flush(&mut writer)?;
+
+    info!("Wrote {} documents for {}.{}", count, database, collection);
+    Ok(count)
+}
+
+/// Write the documents of a cursor in BSON, as in a mongodump collection file
+async fn write_documents(
+    cursor: &mut mongodb::Cursor<mongodb::bson::Document>,
+    writer: &mut impl std::io::Write,
+) -> Result<u64> {
+    let mut count = 0;
+    while let Some(doc) = cursor.try_next().await? {
+        doc.to_writer(&mut *writer)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Export selected fields of a collection to a flat CSV file via `mongoexport`
+pub async fn export_collection_csv(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    fields: &[String],
+    output_file: &Path,
+) -> Result<()> {
+    validate_db_name(database)?;
+    info!(
+        "Exporting {}.{} from {} to {}",
+        database,
+        collection,
+        config.environment,
+        output_file.display()
+    );
+
+    let mongoexport_path = get_tool_path("mongoexport")
+        .context("mongoexport is required for CSV exports but was not found")?;
+
+    let output = Command::new(mongoexport_path)
         .arg("--uri")
         .arg(&config.connection_string)
+        .args(config.tool_args())
         .arg("--db")
         .arg(database)
+        .arg("--collection")
+        .arg(collection)
+        .arg("--type=csv")
+        .arg("--fields")
+        .arg(fields.join(","))
         .arg("--out")
-        .arg(output_dir)
+        .arg(output_file)
+        .kill_on_drop(true)
         .output()
         .await
-        .context("Failed to execute mongodump")?;
-
-    progress.finish_with_message("Export completed");
+        .context("Failed to execute mongoexport")?;
 
     if !output.status.success() {
         let stderr = str::from_utf8(&output.stderr)?;
-        error!("Export failed: {}", stderr);
-        anyhow::bail!("Export failed: {}", stderr);
-    } else {
-        let stdout = str::from_utf8(&output.stdout)?;
-        info!("Export output: {}", stdout);
+        error!("CSV export failed: {}", stderr);
+        anyhow::bail!(
+            "CSV export of {}.{} failed: {}",
+            database,
+            collection,
+            stderr
+        );
     }
 
-    let db_path = output_dir.join(database);
-    if !db_path.exists() {
+    Ok(())
+}
+
+/// Export selected fields of a collection to a Parquet file, one row group per batch
+pub async fn export_collection_parquet(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    fields: &[String],
+    output_file: &Path,
+) -> Result<u64> {
+    const BATCH_SIZE: usize = 10_000;
+
+    validate_db_name(database)?;
+    info!(
+        "Exporting {}.{} from {} to {}",
+        database,
+        collection,
+        config.environment,
+        output_file.display()
+    );
+
+    let client = config.client().await?;
+
+    let mut projection = mongodb::bson::Document::new();
+    for field in fields {
+        projection.insert(field.clone(), 1);
+    }
+
+    let mut cursor = client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection)
+        .find(mongodb::bson::doc! {})
+        .projection(projection)
+        .await
+        .context(format!("Failed to read {}.{}", database, collection))?;
+
+    let mut writer: Option<ParquetWriter> = None;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut count = 0;
+
+    loop {
+        let next = cursor.try_next().await?;
+        let done = next.is_none();
+        if let Some(doc) = next {
+            batch.push(doc);
+        }
+
+        if batch.len() >= BATCH_SIZE || (done && (!batch.is_empty() || writer.is_none())) {
+            let writer = match writer.as_mut() {
+                Some(writer) => writer,
+                None => writer.insert(ParquetWriter::create(output_file, fields, &batch)?),
+            };
+            if !batch.is_empty() {
+                writer.write_batch(&batch)?;
+                count += batch.len() as u64;
+                batch.clear();
+            }
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    if let Some(writer) = writer {
+        writer.close()?;
+    }
+
+    info!("Wrote {} rows for {}.{}", count, database, collection);
+    Ok(count)
+}
+
+/// Pull up to `size` random documents of a collection as raw BSON
+pub async fn sample_documents(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    size: u64,
+) -> Result<Vec<mongodb::bson::RawDocumentBuf>> {
+    let client = config.client().await?;
+
+    let cursor = client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection)
+        .aggregate(vec![
+            mongodb::bson::doc! { "$sample": { "size": size as i64 } },
+        ])
+        .await
+        .context(format!("Failed to sample {}.{}", database, collection))?;
+
+    Ok(cursor
+        .with_type::<mongodb::bson::RawDocumentBuf>()
+        .try_collect()
+        .await?)
+}
+
+/// Fetch the documents with the given `_id` values as raw BSON
+pub async fn find_documents_by_id(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    ids: Vec<mongodb::bson::Bson>,
+) -> Result<Vec<mongodb::bson::RawDocumentBuf>> {
+    let client = config.client().await?;
+
+    let cursor = client
+        .database(database)
+        .collection::<mongodb::bson::RawDocumentBuf>(collection)
+        .find(mongodb::bson::doc! { "_id": { "$in": ids } })
+        .await
+        .context(format!("Failed to read {}.{}", database, collection))?;
+
+    Ok(cursor.try_collect().await?)
+}
+
+/// MD5 of all documents of a collection in `_id` order, for deployments where
+/// `dbHash` is unavailable (e.g. through mongos)
+pub async fn scan_collection_hash(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+) -> Result<String> {
+    use md5::Digest;
+
+    let client = config.client().await?;
+
+    let mut cursor = client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection)
+        .aggregate(vec![mongodb::bson::doc! { "$sort": { "_id": 1 } }])
+        .allow_disk_use(true)
+        .await
+        .context(format!("Failed to read {}.{}", database, collection))?
+        .with_type::<mongodb::bson::RawDocumentBuf>();
+
+    let mut hasher = md5::Md5::new();
+    while let Some(document) = cursor.try_next().await? {
+        hasher.update(document.as_bytes());
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// `_id`s of the documents whose date `field` is later than `since`
+pub async fn find_ids_modified_since(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    field: &str,
+    since: mongodb::bson::DateTime,
+) -> Result<Vec<mongodb::bson::Bson>> {
+    let client = config.client().await?;
+
+    let documents: Vec<mongodb::bson::Document> = client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection)
+        .find(mongodb::bson::doc! { field: { "$gt": since } })
+        .projection(mongodb::bson::doc! { "_id": 1 })
+        .await
+        .context(format!("Failed to read {}.{}", database, collection))?
+        .try_collect()
+        .await?;
+
+    Ok(documents
+        .into_iter()
+        .filter_map(|mut doc| doc.remove("_id"))
+        .collect())
+}
+
+/// Replace documents by `_id`, inserting the ones that don't exist yet
+pub async fn replace_documents(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    documents: Vec<mongodb::bson::RawDocumentBuf>,
+) -> Result<u64> {
+    let client = config.client().await?;
+    let target = client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection);
+
+    let documents = documents
+        .into_iter()
+        .map(|raw| raw.to_document())
+        .collect::<Result<Vec<_>, _>>()?;
+    upsert_documents(&target, documents).await
+}
+
+/// Replace documents by `_id`, inserting the ones that don't exist yet.
+/// Returns the number written.
+async fn upsert_documents(
+    target: &mongodb::Collection<mongodb::bson::Document>,
+    documents: Vec<mongodb::bson::Document>,
+) -> Result<u64> {
+    let mut written = 0;
+    for document in documents {
+        let id = document
+            .get("_id")
+            .cloned()
+            .context("Document without _id")?;
+        target
+            .replace_one(mongodb::bson::doc! { "_id": id }, document)
+            .upsert(true)
+            .await
+            .context(format!("Failed to write {}", target.namespace()))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Per-collection MD5 content hashes from the `dbHash` command.
+///
+/// Not supported through mongos, so callers should treat errors as "no hashes".
+pub async fn collection_hashes(
+    config: &MongoConfig,
+    database: &str,
+) -> Result<std::collections::BTreeMap<String, String>> {
+    let client = config.client().await?;
+
+    let result = client
+        .database(database)
+        .run_command(mongodb::bson::doc! { "dbHash": 1 })
+        .await
+        .context(format!("dbHash failed on {}", database))?;
+
+    let hashes = result
+        .get_document("collections")
+        .context("dbHash returned no collection hashes")?
+        .iter()
+        .filter_map(|(name, hash)| Some((name.clone(), hash.as_str()?.to_string())))
+        .collect();
+
+    Ok(hashes)
+}
+
+/// Count the documents of a collection matching a filter
+pub async fn count_documents(
+    config: &MongoConfig,
+    database: &str,
+    collection: &str,
+    filter: mongodb::bson::Document,
+) -> Result<u64> {
+    let client = config.client().await?;
+
+    Ok(client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection)
+        .count_documents(filter)
+        .await?)
+}
+
+/// Copy documents matching a filter between two collections using the driver.
+///
+/// Documents are inserted in batches; other data in the target is left untouched
+/// unless `drop_target` asks for the target collection to be dropped first.
+pub async fn copy_documents(
+    source_config: &MongoConfig,
+    (source_db, source_collection): (&str, &str),
+    target_config: &MongoConfig,
+    (target_db, target_collection): (&str, &str),
+    filter: mongodb::bson::Document,
+    drop_target: bool,
+) -> Result<u64> {
+    const BATCH_SIZE: usize = 1000;
+
+    validate_db_name(source_db)?;
+    validate_db_name(target_db)?;
+    info!(
+        "Copying documents from {}.{} on {} to {}.{} on {}",
+        source_db,
+        source_collection,
+        source_config.environment,
+        target_db,
+        target_collection,
+        target_config.environment
+    );
+
+    let source_client = source_config.client().await?;
+    let target_client = target_config.client().await?;
+
+    let source = source_client
+        .database(source_db)
+        .collection::<mongodb::bson::Document>(source_collection);
+    let target = target_client
+        .database(target_db)
+        .collection::<mongodb::bson::Document>(target_collection);
+
+    if drop_target {
+        target.drop().await?;
+    }
+
+    let mut progress = create_progress_bar("Copying documents");
+
+    let mut cursor = source.find(filter).await?;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut copied = 0;
+    while let Some(doc) = cursor.try_next().await? {
+        batch.push(doc);
+        if batch.len() == BATCH_SIZE {
+            target.insert_many(std::mem::take(&mut batch)).await?;
+            copied += BATCH_SIZE as u64;
+        }
+    }
+    if !batch.is_empty() {
+        copied += batch.len() as u64;
+        target.insert_many(batch).await?;
+    }
+
+    progress.finish_with_message("Copy completed");
+
+    info!("Copied {} documents", copied);
+    Ok(copied)
+}
+
+/// Copy collection definitions, validators, indexes and views without any documents.
+///
+/// Existing target collections are dropped first when `drop` is set and are
+/// otherwise kept, only gaining the missing indexes. Returns the number of
+/// collections and views copied.
+pub async fn copy_schema(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    drop: bool,
+) -> Result<usize> {
+    validate_db_name(source_db)?;
+    validate_db_name(target_db)?;
+
+    let mut progress = create_progress_bar("Copying schema");
+
+    let source_client = source_config.client().await?;
+    let target_client = target_config.client().await?;
+    let source = source_client.database(source_db);
+    let target = target_client.database(target_db);
+
+    let mut specs: Vec<_> = source.list_collections().await?.try_collect().await?;
+    specs.retain(|spec| !spec.name.starts_with("system."));
+    // Views are created last since they may be defined on other collections
+    specs.sort_by_key(|spec| {
+        (
+            matches!(spec.collection_type, mongodb::results::CollectionType::View),
+            spec.name.clone(),
+        )
+    });
+    let existing = target.list_collection_names().await?;
+
+    for spec in &specs {
+        let exists = existing.contains(&spec.name);
+        if exists && drop {
+            target
+                .collection::<mongodb::bson::Document>(&spec.name)
+                .drop()
+                .await?;
+        }
+        if !exists || drop {
+            target
+                .create_collection(&spec.name)
+                .with_options(spec.options.clone())
+                .await
+                .with_context(|| format!("Failed to create {}.{}", target_db, spec.name))?;
+        }
+
+        if matches!(spec.collection_type, mongodb::results::CollectionType::View) {
+            continue;
+        }
+
+        copy_indexes(
+            &source.collection(&spec.name),
+            &target.collection(&spec.name),
+        )
+        .await?;
+    }
+
+    progress.finish_with_message("Schema copied");
+
+    Ok(specs.len())
+}
+
+/// Create the indexes of a source collection other than `_id_` on a target collection
+async fn copy_indexes(
+    source: &mongodb::Collection<mongodb::bson::Document>,
+    target: &mongodb::Collection<mongodb::bson::Document>,
+) -> Result<()> {
+    let indexes: Vec<mongodb::IndexModel> = source.list_indexes().await?.try_collect().await?;
+    let indexes: Vec<_> = indexes
+        .into_iter()
+        .filter(|index| index.options.as_ref().and_then(|o| o.name.as_deref()) != Some("_id_"))
+        .collect();
+    if !indexes.is_empty() {
+        target
+            .create_indexes(indexes)
+            .await
+            .with_context(|| format!("Failed to create indexes on {}", target.namespace()))?;
+    }
+    Ok(())
+}
+
+/// Copy a collection or view with the driver, treating the target the way
+/// mongorestore does with the same `ImportOptions`.
+///
+/// `drop` recreates the target collection with the source options and
+/// indexes, `clear` removes its documents first, and otherwise documents whose
+/// `_id` already exists in the target are kept. With `data_only` the target's
+/// options and indexes are left as they are. With a pipeline its results are copied instead of
+/// the documents, and `transform` changes each document before it is
+/// written. With `insertion_workers`, collections of
+/// at least [`PARTITION_MIN_DOCUMENTS`] are split into `_id` ranges copied by
+/// that many workers at a time. Returns the number of documents written.
+pub async fn copy_collection(
+    source_config: &MongoConfig,
+    (source_db, source_collection): (&str, &str),
+    target_config: &MongoConfig,
+    (target_db, target_collection): (&str, &str),
+    pipeline: Option<Vec<mongodb::bson::Document>>,
+    read_preference: Option<ReadPreference>,
+    options: ImportOptions<'_>,
+) -> Result<u64> {
+    validate_db_name(source_db)?;
+    validate_db_name(target_db)?;
+    info!(
+        "Copying {}.{} from {} to {}.{} on {}",
+        source_db,
+        source_collection,
+        source_config.environment,
+        target_db,
+        target_collection,
+        target_config.environment
+    );
+
+    let source_client = source_config.client().await?;
+    let target_client = target_config.client().await?;
+    let source_database = source_client.database(source_db);
+    let target_database = target_client.database(target_db);
+
+    let spec = source_database
+        .list_collections()
+        .filter(mongodb::bson::doc! { "name": source_collection })
+        .await?
+        .try_next()
+        .await?
+        .context(format!(
+            "Collection {}.{} not found",
+            source_db, source_collection
+        ))?;
+    let is_view = matches!(spec.collection_type, mongodb::results::CollectionType::View);
+    if is_view && options.data_only {
+        return Ok(0);
+    }
+
+    let source = source_database.collection::<mongodb::bson::Document>(source_collection);
+    let target = target_database.collection::<mongodb::bson::Document>(target_collection);
+
+    let exists = target_database
+        .list_collection_names()
+        .filter(mongodb::bson::doc! { "name": target_collection })
+        .await?
+        .contains(&target_collection.to_string());
+    if exists && options.drop {
+        target.drop().await?;
+    }
+    if (!exists || options.drop) && !options.data_only {
+        target_database
+            .create_collection(target_collection)
+            .with_options(spec.options.clone())
+            .await
+            .with_context(|| format!("Failed to create {}.{}", target_db, target_collection))?;
+    }
+    if is_view {
+        return Ok(0);
+    }
+    if !options.data_only {
+        let started = Instant::now();
+        copy_indexes(&source, &target).await?;
+        if let Some(timer) = options.index_timer {
+            timer.add(started.elapsed());
+        }
+    }
+    if exists && options.clear && !options.drop {
+        target.delete_many(mongodb::bson::doc! {}).await?;
+    }
+
+    let criteria = read_preference
+        .map(|read_preference| SelectionCriteria::ReadPreference(read_preference.to_driver()));
+    if let Some(pipeline) = pipeline {
+        let cursor = source
+            .aggregate(pipeline)
+            .allow_disk_use(true)
+            .optional(criteria, |a, c| a.selection_criteria(c))
+            .await?;
+        return write_cursor(cursor, &target, options).await;
+    }
+
+    let workers = options.insertion_workers.unwrap_or(1).max(1) as usize;
+    let filters = if workers > 1
+        && source
+            .estimated_document_count()
+            .optional(criteria.clone(), |c, s| c.selection_criteria(s))
+            .await?
+            >= PARTITION_MIN_DOCUMENTS
+    {
+        let ranges = workers * RANGES_PER_WORKER;
+        let ids = sample_ids(&source, ranges * SAMPLES_PER_RANGE, criteria.clone()).await?;
+        id_range_filters(&range_boundaries(&ids, ranges))
+    } else {
+        vec![mongodb::bson::doc! {}]
+    };
+    if filters.len() > 1 {
         info!(
-            "Database '{}' appears to be empty, creating placeholder directory",
-            database
+            "Copying {}.{} in {} _id ranges with {} workers",
+            source_db,
+            source_collection,
+            filters.len(),
+            workers
         );
-        std::fs::create_dir_all(&db_path)
-            .context("Failed to create placeholder for empty database")?;
     }
 
-    Ok(())
+    // Each range has its own cursor; ranges are copied `workers` at a time
+    let copied: Vec<u64> = futures::stream::iter(filters)
+        .map(|filter| {
+            let (source, target, criteria) = (&source, &target, criteria.clone());
+            async move {
+                let cursor = source
+                    .find(filter)
+                    .optional(criteria, |f, c| f.selection_criteria(c))
+                    .await?;
+                write_cursor(cursor, target, options).await
+            }
+        })
+        .buffer_unordered(workers)
+        .try_collect()
+        .await?;
+
+    Ok(copied.into_iter().sum())
+}
+
+/// Smallest collection `copy_collection` splits into `_id` ranges
+pub const PARTITION_MIN_DOCUMENTS: u64 = 100_000;
+/// `_id` ranges per insertion worker, so workers that finish early pick up more
+const RANGES_PER_WORKER: usize = 4;
+/// Sampled `_id`s per range, spreading the range boundaries evenly
+const SAMPLES_PER_RANGE: usize = 20;
+
+/// Write the documents of a cursor to the target in batches, returning the
+/// number written
+async fn write_cursor(
+    mut cursor: mongodb::Cursor<mongodb::bson::Document>,
+    target: &mongodb::Collection<mongodb::bson::Document>,
+    options: ImportOptions<'_>,
+) -> Result<u64> {
+    const BATCH_SIZE: usize = 1000;
+
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut copied = 0;
+    let write = |batch| async {
+        if options.upsert {
+            upsert_documents(target, batch).await
+        } else {
+            insert_new_documents(target, batch).await
+        }
+    };
+    while let Some(mut doc) = cursor.try_next().await? {
+        if let Some(transform) = options.transform {
+            transform
+                .apply(&mut doc)
+                .with_context(|| format!("Failed to transform a document of {}", target.name()))?;
+        }
+        batch.push(doc);
+        if batch.len() == BATCH_SIZE {
+            copied += write(std::mem::take(&mut batch)).await?;
+        }
+    }
+    if !batch.is_empty() {
+        copied += write(batch).await?;
+    }
+
+    Ok(copied)
 }
 
+/// `_id`s of randomly sampled documents, sorted in BSON order
+async fn sample_ids(
+    source: &mongodb::Collection<mongodb::bson::Document>,
+    size: usize,
+    criteria: Option<SelectionCriteria>,
+) -> Result<Vec<mongodb::bson::Bson>> {
+    let pipeline = vec![
+        mongodb::bson::doc! { "$sample": { "size": size as i64 } },
+        mongodb::bson::doc! { "$project": { "_id": 1 } },
+        mongodb::bson::doc! { "$sort": { "_id": 1 } },
+    ];
+    let documents: Vec<mongodb::bson::Document> = source
+        .aggregate(pipeline)
+        .allow_disk_use(true)
+        .optional(criteria, |a, c| a.selection_criteria(c))
+        .await?
+        .try_collect()
+        .await?;
+    Ok(documents
+        .into_iter()
+        .filter_map(|mut doc| doc.remove("_id"))
+        .collect())
+}
+
+/// `$type` alias matching the `_id`s a range query on `id` can select:
+/// numbers compare with each other whatever their BSON type
+fn id_type(id: &mongodb::bson::Bson) -> mongodb::bson::Bson {
+    use mongodb::bson::Bson;
+    match id {
+        Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_) => {
+            Bson::String("number".to_string())
+        }
+        other => Bson::Int32(other.element_type() as i32),
+    }
+}
+
+/// Up to `ranges - 1` distinct boundaries splitting sorted `_id`s into ranges
+/// of about the same size, all of the type of the median `_id`
+pub fn range_boundaries(ids: &[mongodb::bson::Bson], ranges: usize) -> Vec<mongodb::bson::Bson> {
+    let Some(median) = ids.get(ids.len() / 2) else {
+        return Vec::new();
+    };
+    let kind = id_type(median);
+    let ids: Vec<&mongodb::bson::Bson> = ids.iter().filter(|id| id_type(id) == kind).collect();
+
+    let mut boundaries: Vec<mongodb::bson::Bson> = Vec::new();
+    for i in 1..ranges {
+        let id = ids[i * ids.len() / ranges];
+        if boundaries.last() != Some(id) {
+            boundaries.push(id.clone());
+        }
+    }
+    boundaries
+}
+
+/// Filters selecting every document exactly once: one `_id` range per gap
+/// between the sorted boundaries, and the `_id`s of other types, which range
+/// queries never select
+pub fn id_range_filters(boundaries: &[mongodb::bson::Bson]) -> Vec<mongodb::bson::Document> {
+    use mongodb::bson::doc;
+
+    let (Some(first), Some(last)) = (boundaries.first(), boundaries.last()) else {
+        return vec![doc! {}];
+    };
+    let mut filters = vec![doc! { "_id": { "$lt": first.clone() } }];
+    for pair in boundaries.windows(2) {
+        filters.push(doc! { "_id": { "$gte": pair[0].clone(), "$lt": pair[1].clone() } });
+    }
+    filters.push(doc! { "_id": { "$gte": last.clone() } });
+    filters.push(doc! { "_id": { "$not": { "$type": id_type(first) } } });
+    filters
+}
+
+/// Insert documents, skipping those whose `_id` already exists in the target
+/// as mongorestore does. Returns the number inserted.
+async fn insert_new_documents(
+    target: &mongodb::Collection<mongodb::bson::Document>,
+    documents: Vec<mongodb::bson::Document>,
+) -> Result<u64> {
+    const DUPLICATE_KEY: i32 = 11000;
+
+    let total = documents.len() as u64;
+    match target.insert_many(documents).ordered(false).await {
+        Ok(_) => Ok(total),
+        Err(e) => match e.kind.as_ref() {
+            mongodb::error::ErrorKind::InsertMany(mongodb::error::InsertManyError {
+                write_errors: Some(errors),
+                write_concern_error: None,
+                ..
+            }) if errors.iter().all(|error| error.code == DUPLICATE_KEY) => {
+                Ok(total - errors.len() as u64)
+            }
+            _ => Err(e.into()),
+        },
+    }
+}
+
+/// The `--nsInclude` arguments restoring the given collections of a database,
+/// or all of them when none are given
+pub fn namespace_filters(database: &str, collections: &[String]) -> Vec<String> {
+    if collections.is_empty() {
+        return vec!["--nsInclude".to_string(), format!("{}.*", database)];
+    }
+    collections
+        .iter()
+        .flat_map(|collection| {
+            [
+                "--nsInclude".to_string(),
+                format!("{}.{}", database, collection),
+            ]
+        })
+        .collect()
+}
+
+/// How `import_database` treats the data already in the target
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions<'a> {
+    /// Drop the target collections being restored
+    pub drop: bool,
+    /// Delete all documents of the target database first, ignored with `drop`
+    pub clear: bool,
+    /// `(nsFrom, nsTo)` pairs renaming collections while restoring
+    pub namespace_renames: &'a [(String, String)],
+    /// Leave indexes and collection options of the target as they are
+    pub data_only: bool,
+    /// Adds up the time spent creating indexes
+    pub index_timer: Option<&'a IndexTimer>,
+    /// Change every document between reading and writing it; only supported
+    /// when copying through the driver
+    pub transform: Option<&'a dyn Transform>,
+    /// Collections not cleared, because the dump doesn't contain them
+    pub keep: &'a [String],
+    /// Restore only these collections of the dump; all of them when empty
+    pub collections: &'a [String],
+    /// Collections restored at the same time instead of mongorestore's default
+    pub parallel_collections: Option<u64>,
+    /// Insertion workers per collection instead of mongorestore's default;
+    /// `_id` ranges copied at the same time through the driver
+    pub insertion_workers: Option<u64>,
+    /// Replay the oplog of a dump taken with `--oplog`, bringing the
+    /// restored collections to the moment the dump finished
+    pub oplog_replay: bool,
+    /// Replace target documents with the same `_id` instead of keeping them;
+    /// only supported when copying through the driver
+    pub upsert: bool,
+    /// Kill a mongorestore run that takes longer than this
+    pub timeout: Option<Duration>,
+}
+
+/// Time imports spent creating indexes, across collections and retries
+#[derive(Debug, Default)]
+pub struct IndexTimer(Mutex<Duration>);
+
+impl IndexTimer {
+    fn add(&self, duration: Duration) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Time mongorestore spent restoring indexes, from the timestamps of its log
+/// lines: indexes are restored once all documents are written, so this runs
+/// from its first `restoring indexes` line to its last line
+pub fn index_restore_duration(log: &str) -> Option<Duration> {
+    let timestamp = |line: &str| {
+        let (timestamp, _) = line.split_once('\t')?;
+        chrono::DateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.3f%z").ok()
+    };
+    let started = log
+        .lines()
+        .find(|line| line.contains("restoring indexes for collection"))
+        .and_then(timestamp)?;
+    let finished = log.lines().rev().find_map(timestamp)?;
+    (finished - started).to_std().ok()
+}
+
+/// A failed mongorestore or driver import, with the namespaces it restored
+/// completely before failing
+#[derive(Debug, thiserror::Error)]
+#[error("Import failed: {message}")]
+pub struct ImportError {
+    /// `database.collection` namespaces written without failures
+    pub restored: Vec<String>,
+    pub message: String,
+}
+
+/// Namespaces mongorestore reports as restored without failures, from its
+/// `finished restoring <ns> (<n> documents, <m> failures)` log lines
+pub fn restored_namespaces(log: &str) -> Vec<String> {
+    log.lines()
+        .filter_map(|line| line.split_once("finished restoring ").map(|(_, rest)| rest))
+        .filter_map(|rest| rest.split_once(" ("))
+        .filter(|(_, counts)| counts.contains(" 0 failures"))
+        .map(|(namespace, _)| namespace.to_string())
+        .collect()
+}
+
+/// Import a dumped database, renaming namespaces given as `(from, to)` pairs on the way
 pub async fn import_database(
     config: &MongoConfig,
     database: &str,
-    input_dir: &Path,
-    drop: bool,
-    clear: bool,
+    dump: &DumpLayout,
+    options: ImportOptions<'_>,
 ) -> Result<()> {
     validate_db_name(database)?;
     info!("Importing database {} to {}", database, config.environment);
 
-    // If clear is true but drop is false, clear all collections first
-    if clear && !drop {
-        clear_collections(config, database).await?;
-    }
+    clear_before_import(config, database, &options).await?;
 
     let mut progress = create_progress_bar("Importing");
 
+    // Verify that the database directory exists in the input directory
+    if !dump.has_database(database) {
+        let db_path = dump.database_dir(database);
+        error!("Database directory not found: {}", db_path.display());
+        anyhow::bail!("Database directory not found: {}", db_path.display());
+    }
+
+    let mut command = mongorestore_command(config, database, &options)?;
+    if dump.is_compressed() {
+        command.arg("--gzip");
+    }
+
+    // Pass the dump root - mongorestore expects structure: root/database/collection.bson
+    command.arg(dump.root());
+
+    info!("Running restore with directory: {}", dump.root().display());
+
+    let output = match run_tool(&mut command, "mongorestore", options.timeout).await {
+        Ok(output) => output,
+        Err(e) => {
+            progress.finish_with_message("Import failed");
+            return Err(e);
+        }
+    };
+
+    progress.finish_with_message("Import completed");
+
+    if !output.status.success() {
+        let stderr = str::from_utf8(&output.stderr)?;
+        error!("Import failed: {}", stderr);
+        return Err(ImportError {
+            restored: restored_namespaces(stderr),
+            message: stderr.to_string(),
+        }
+        .into());
+    } else {
+        let stdout = str::from_utf8(&output.stdout)?;
+        info!("Import output: {}", stdout);
+        record_index_restore(&options, &String::from_utf8_lossy(&output.stderr));
+    }
+
+    Ok(())
+}
+
+/// Add the index restore time of a successful mongorestore run to the
+/// timer of its import
+fn record_index_restore(options: &ImportOptions<'_>, log: &str) {
+    if let (Some(timer), Some(duration)) = (options.index_timer, index_restore_duration(log)) {
+        timer.add(duration);
+    }
+}
+
+/// Delete the documents of the target collections being restored when
+/// importing with `clear` but not `drop`: those of `collections`, or without
+/// a selection every collection of the database but the `keep` ones
+pub async fn clear_before_import(
+    config: &MongoConfig,
+    database: &str,
+    options: &ImportOptions<'_>,
+) -> Result<()> {
+    if !options.clear || options.drop {
+        return Ok(());
+    }
+
+    if options.collections.is_empty() {
+        clear_collections(config, database, options.keep).await?;
+    } else {
+        for collection in options
+            .collections
+            .iter()
+            .filter(|c| !options.keep.contains(c))
+        {
+            clear_collection(config, database, collection).await?;
+        }
+    }
+
+    // Collections redirected into other databases are cleared individually
+    let prefix = format!("{}.", database);
+    for (_, to) in options.namespace_renames {
+        if !to.starts_with(&prefix) {
+            if let Some((db, collection)) = to.split_once('.') {
+                clear_collection(config, db, collection).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// mongorestore with the arguments for the import options, restoring the
+/// namespaces of `database` in its input
+fn mongorestore_command(
+    config: &MongoConfig,
+    database: &str,
+    options: &ImportOptions<'_>,
+) -> Result<Command> {
     let bin_path = get_mongodb_bin_path().map_err(|e| {
         error!("Failed to find MongoDB tools: {}", e);
         anyhow::anyhow!("Failed to find mongorestore")
@@ -129,62 +1499,202 @@ pub async fn import_database(
 
     info!("Using mongorestore from: {}", mongorestore_path.display());
 
-    // Verify that the database directory exists in the input directory
-    let db_path = input_dir.join(database);
-    if !db_path.exists() {
-        error!("Database directory not found: {}", db_path.display());
-        anyhow::bail!("Database directory not found: {}", db_path.display());
-    }
-
     // Build the restore command using --nsInclude instead of deprecated --db flag
     let mut command = Command::new(&mongorestore_path);
     command
         .arg("--uri")
         .arg(&config.connection_string)
-        .arg("--nsInclude")
-        .arg(format!("{}.*", database));
+        .args(config.tool_args())
+        .args(namespace_filters(database, options.collections));
 
-    if drop {
+    for (from, to) in options.namespace_renames {
+        command.arg("--nsFrom").arg(from).arg("--nsTo").arg(to);
+    }
+
+    if options.drop {
         command.arg("--drop");
     }
 
-    // Pass parent directory - mongorestore expects structure: input_dir/database/collection.bson
-    command.arg(input_dir);
+    if options.oplog_replay {
+        command.arg("--oplogReplay");
+    }
 
-    info!("Running restore with directory: {}", input_dir.display());
+    if let Some(parallel) = options.parallel_collections {
+        command
+            .arg("--numParallelCollections")
+            .arg(parallel.to_string());
+    }
+    if let Some(workers) = options.insertion_workers {
+        command
+            .arg("--numInsertionWorkersPerCollection")
+            .arg(workers.to_string());
+    }
 
-    let output = command
-        .output()
-        .await
+    // Leave indexes and collection options (e.g. validators) of the target as they are
+    if options.data_only {
+        command.arg("--noIndexRestore").arg("--noOptionsRestore");
+    }
+
+    Ok(command)
+}
+
+/// Copy a database by piping `mongodump --archive` into `mongorestore --archive`,
+/// without writing the dump to disk.
+///
+/// `options` apply as for `import_database`, with `namespace_renames` moving
+/// the collections from the source database to the target. Only the
+/// `exclude` collections of `export` are left out of the archive.
+pub async fn stream_database(
+    source_config: &MongoConfig,
+    source_db: &str,
+    target_config: &MongoConfig,
+    target_db: &str,
+    export: ExportOptions<'_>,
+    compressed: bool,
+    options: ImportOptions<'_>,
+) -> Result<()> {
+    validate_db_name(source_db)?;
+    validate_db_name(target_db)?;
+    info!(
+        "Streaming database {} from {} to {} on {}",
+        source_db, source_config.environment, target_db, target_config.environment
+    );
+
+    clear_before_import(target_config, target_db, &options).await?;
+
+    let mut progress = create_progress_bar("Streaming");
+
+    let bin_path = get_mongodb_bin_path().map_err(|e| {
+        error!("Failed to find MongoDB tools: {}", e);
+        anyhow::anyhow!("Failed to find mongodump")
+    })?;
+    let mut mongodump = Command::new(bin_path.join("mongodump"));
+    mongodump
+        .arg("--uri")
+        .arg(&source_config.connection_string)
+        .args(source_config.tool_args())
+        .arg("--db")
+        .arg(source_db)
+        .arg("--archive");
+    for collection in export.exclude {
+        mongodump.arg("--excludeCollection").arg(collection);
+    }
+    export.apply(&mut mongodump);
+
+    let mut mongorestore = mongorestore_command(target_config, source_db, &options)?;
+    mongorestore.arg("--archive");
+
+    if compressed {
+        mongodump.arg("--gzip");
+        mongorestore.arg("--gzip");
+    }
+
+    let mut dump = mongodump
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to execute mongodump")?;
+    let archive: Stdio = dump
+        .stdout
+        .take()
+        .context("Failed to capture the mongodump output")?
+        .try_into()?;
+    let restore = mongorestore
+        .stdin(archive)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
         .context("Failed to execute mongorestore")?;
 
-    progress.finish_with_message("Import completed");
+    // Both tools run for the whole stream, so the longer timeout applies;
+    // dropping the children on timeout kills them
+    let outputs = async { tokio::try_join!(dump.wait_with_output(), restore.wait_with_output()) };
+    let (dump_output, restore_output) = match export.timeout.max(options.timeout) {
+        Some(timeout) => match tokio::time::timeout(timeout, outputs).await {
+            Ok(outputs) => outputs?,
+            Err(_) => {
+                progress.finish_with_message("Streaming failed");
+                return Err(ToolTimeout {
+                    tool: "mongodump | mongorestore",
+                    timeout,
+                }
+                .into());
+            }
+        },
+        None => outputs.await?,
+    };
+    let dump_stderr = String::from_utf8_lossy(&dump_output.stderr);
+    let restore_stderr = String::from_utf8_lossy(&restore_output.stderr);
 
-    if !output.status.success() {
-        let stderr = str::from_utf8(&output.stderr)?;
-        error!("Import failed: {}", stderr);
-        anyhow::bail!("Import failed: {}", stderr);
-    } else {
-        let stdout = str::from_utf8(&output.stdout)?;
-        info!("Import output: {}", stdout);
+    // Either side failing makes the other one fail too, so report both
+    match (
+        dump_output.status.success(),
+        restore_output.status.success(),
+    ) {
+        (true, true) => {
+            progress.finish_with_message("Streaming completed");
+            info!("Import output: {}", restore_stderr);
+            record_index_restore(&options, &restore_stderr);
+            Ok(())
+        }
+        (false, true) => {
+            progress.finish_with_message("Streaming failed");
+            error!("Export failed: {}", dump_stderr);
+            anyhow::bail!("Export failed: {}", dump_stderr)
+        }
+        (true, false) => {
+            progress.finish_with_message("Streaming failed");
+            error!("Import failed: {}", restore_stderr);
+            Err(ImportError {
+                restored: restored_namespaces(&restore_stderr),
+                message: restore_stderr.to_string(),
+            }
+            .into())
+        }
+        (false, false) => {
+            progress.finish_with_message("Streaming failed");
+            error!("Export failed: {}", dump_stderr);
+            error!("Import failed: {}", restore_stderr);
+            anyhow::bail!(
+                "Export failed: {}\nImport failed: {}",
+                dump_stderr,
+                restore_stderr
+            )
+        }
     }
+}
 
-    Ok(())
+/// Dump a database into a new directory below the backup directory, with
+/// `--gzip` if `compressed` is set
+/// Directory a backup of `database` taken now is written to
+pub fn backup_path(config: &MongoConfig, database: &str) -> std::path::PathBuf {
+    let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+    // The environment keeps source and target backups of a run apart
+    get_backup_dir().join(format!(
+        "backup_{}_{}_{}",
+        config.environment.to_string().to_lowercase(),
+        database,
+        timestamp
+    ))
 }
 
-pub async fn create_backup(config: &MongoConfig, database: &str) -> Result<std::path::PathBuf> {
+pub async fn create_backup(
+    config: &MongoConfig,
+    database: &str,
+    compressed: bool,
+) -> Result<std::path::PathBuf> {
     info!(
         "Creating backup of {} from {}",
         database, config.environment
     );
 
-    let backup_dir = get_backup_dir();
-    let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
-    let backup_path = backup_dir.join(format!("backup_{}_{}", database, timestamp));
-
+    let backup_path = backup_path(config, database);
     std::fs::create_dir_all(&backup_path)?;
 
-    export_database(config, database, &backup_path).await?;
+    let dump = DumpLayout::new(&backup_path).compressed(compressed);
+    export_database(config, database, &dump, ExportOptions::default()).await?;
 
     Ok(backup_path)
 }
@@ -197,12 +1707,89 @@ pub async fn restore_backup(
     info!("Restoring backup of {} to {}", database, config.environment);
 
     // Always use drop=true when restoring a backup to ensure complete restore
-    import_database(config, database, backup_path, true, false).await?;
+    import_database(
+        config,
+        database,
+        &DumpLayout::open(backup_path),
+        ImportOptions {
+            drop: true,
+            ..Default::default()
+        },
+    )
+    .await?;
 
     Ok(())
 }
 
-pub async fn clear_collections(config: &MongoConfig, database: &str) -> Result<()> {
+/// Run a JavaScript file with mongosh against a database and return its output
+pub async fn run_mongosh_script(
+    config: &MongoConfig,
+    database: &str,
+    script: &Path,
+) -> Result<String> {
+    validate_db_name(database)?;
+    info!(
+        "Running script {} against {} on {}",
+        script.display(),
+        database,
+        config.environment
+    );
+
+    let script = script
+        .canonicalize()
+        .context(format!("Script not found: {}", script.display()))?;
+
+    let mongosh_path = get_mongosh_path().map_err(|e| {
+        error!("Failed to find mongosh: {}", e);
+        anyhow::anyhow!("Failed to find mongosh (install it or add it to MONGODB_BIN_PATH)")
+    })?;
+
+    // Switch to the target database before loading the script so `db` points at it
+    // regardless of the database in the connection string
+    let wrapper_dir =
+        crate::config::create_temp_dir().context("Failed to create temporary directory")?;
+    let wrapper = wrapper_dir.path().join("post_sync.js");
+    std::fs::write(
+        &wrapper,
+        format!(
+            "db = db.getSiblingDB({});\nload({});\n",
+            serde_json::to_string(database)?,
+            serde_json::to_string(&script.display().to_string())?
+        ),
+    )?;
+
+    let mut progress = create_progress_bar("Running post-sync script");
+
+    let output = Command::new(mongosh_path)
+        .arg(&config.connection_string)
+        .args(config.shell_args())
+        .arg("--quiet")
+        .arg("--file")
+        .arg(&wrapper)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to execute mongosh")?;
+
+    progress.finish_with_message("Post-sync script completed");
+
+    let stdout = str::from_utf8(&output.stdout)?.to_string();
+    if !output.status.success() {
+        let stderr = str::from_utf8(&output.stderr)?;
+        error!("Script failed: {}", stderr);
+        anyhow::bail!("Script failed: {}{}", stdout, stderr);
+    }
+
+    info!("Script output: {}", stdout);
+    Ok(stdout)
+}
+
+/// Delete all documents of a database, except those of the `keep` collections
+pub async fn clear_collections(
+    config: &MongoConfig,
+    database: &str,
+    keep: &[String],
+) -> Result<()> {
     info!(
         "Clearing all collections in database {} on {}",
         database, config.environment
@@ -210,15 +1797,14 @@ pub async fn clear_collections(config: &MongoConfig, database: &str) -> Result<(
 
     let mut progress = create_progress_bar("Clearing collections");
 
-    let client_options = config.get_client_options().await?;
-    let client = mongodb::Client::with_options(client_options)?;
+    let client = config.client().await?;
     let db = client.database(database);
 
     // Get all collections in the database
     let mut collections = db.list_collection_names().await?;
 
     // Remove system collections
-    collections.retain(|name| !name.starts_with("system."));
+    collections.retain(|name| !name.starts_with("system.") && !keep.contains(name));
 
     // Clear each collection by deleting all documents
     for collection_name in collections {
@@ -231,41 +1817,19 @@ pub async fn clear_collections(config: &MongoConfig, database: &str) -> Result<(
     Ok(())
 }
 
-struct ProgressGuard {
-    pb: ProgressBar,
-    finished: bool,
-}
-
-impl ProgressGuard {
-    fn new(message: &str) -> Self {
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} {msg}")
-                .expect("Invalid progress template - this is a bug"),
-        );
-        pb.set_message(format!("{} in progress...", message));
-        pb.enable_steady_tick(std::time::Duration::from_millis(100));
-        Self {
-            pb,
-            finished: false,
-        }
-    }
-
-    fn finish_with_message(&mut self, msg: &str) {
-        self.pb.finish_with_message(msg.to_string());
-        self.finished = true;
-    }
-}
+/// Delete all documents of a single collection
+async fn clear_collection(config: &MongoConfig, database: &str, collection: &str) -> Result<()> {
+    info!(
+        "Clearing collection {}.{} on {}",
+        database, collection, config.environment
+    );
 
-impl Drop for ProgressGuard {
-    fn drop(&mut self) {
-        if !self.finished {
-            self.pb.finish_and_clear();
-        }
-    }
-}
+    let client = config.client().await?;
+    client
+        .database(database)
+        .collection::<mongodb::bson::Document>(collection)
+        .delete_many(mongodb::bson::doc! {})
+        .await?;
 
-fn create_progress_bar(message: &str) -> ProgressGuard {
-    ProgressGuard::new(message)
+    Ok(())
 }
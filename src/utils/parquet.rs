@@ -0,0 +1,243 @@
+use anyhow::{Context, Result};
+use mongodb::bson::{Bson, Document};
+use parquet::basic::Compression;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Physical type of a flat Parquet column, inferred from document values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int64,
+    Double,
+    Boolean,
+    String,
+}
+
+impl ColumnType {
+    fn schema(&self) -> &'static str {
+        match self {
+            ColumnType::Int64 => "INT64",
+            ColumnType::Double => "DOUBLE",
+            ColumnType::Boolean => "BOOLEAN",
+            ColumnType::String => "BYTE_ARRAY",
+        }
+    }
+}
+
+/// Resolve a dotted field path such as `address.city` in a document
+pub fn get_path<'a>(doc: &'a Document, path: &str) -> Option<&'a Bson> {
+    let mut parts = path.split('.');
+    let mut value = doc.get(parts.next()?)?;
+    for part in parts {
+        value = match value {
+            Bson::Document(inner) => inner.get(part)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Infer a column type from sample values: numbers widen to doubles and
+/// anything mixed or non-scalar falls back to strings
+pub fn infer_column_type<'a>(values: impl Iterator<Item = Option<&'a Bson>>) -> ColumnType {
+    let mut inferred: Option<ColumnType> = None;
+
+    for value in values.flatten() {
+        let current = match value {
+            Bson::Null | Bson::Undefined => continue,
+            Bson::Int32(_) | Bson::Int64(_) => ColumnType::Int64,
+            Bson::Double(_) => ColumnType::Double,
+            Bson::Boolean(_) => ColumnType::Boolean,
+            _ => ColumnType::String,
+        };
+
+        inferred = Some(match (inferred, current) {
+            (None, t) => t,
+            (Some(a), b) if a == b => a,
+            (Some(ColumnType::Int64), ColumnType::Double)
+            | (Some(ColumnType::Double), ColumnType::Int64) => ColumnType::Double,
+            _ => ColumnType::String,
+        });
+    }
+
+    inferred.unwrap_or(ColumnType::String)
+}
+
+fn bson_to_string(value: &Bson) -> String {
+    match value {
+        Bson::String(s) => s.clone(),
+        Bson::ObjectId(id) => id.to_hex(),
+        Bson::DateTime(dt) => dt
+            .try_to_rfc3339_string()
+            .unwrap_or_else(|_| dt.timestamp_millis().to_string()),
+        other => other.clone().into_relaxed_extjson().to_string(),
+    }
+}
+
+/// Writes documents as flat Parquet rows, one column per projected field.
+///
+/// Column types are inferred from the first batch written; later values that
+/// don't fit their column's type fail the batch rather than being dropped.
+pub struct ParquetWriter {
+    fields: Vec<String>,
+    types: Vec<ColumnType>,
+    writer: SerializedFileWriter<File>,
+}
+
+impl ParquetWriter {
+    pub fn create(path: &Path, fields: &[String], first_batch: &[Document]) -> Result<Self> {
+        let types: Vec<ColumnType> = fields
+            .iter()
+            .map(|field| infer_column_type(first_batch.iter().map(|doc| get_path(doc, field))))
+            .collect();
+
+        let names = column_names(fields)?;
+        let columns: Vec<String> = names
+            .iter()
+            .zip(&types)
+            .map(|(name, column_type)| match column_type {
+                ColumnType::String => format!("OPTIONAL BYTE_ARRAY {} (UTF8);", name),
+                other => format!("OPTIONAL {} {};", other.schema(), name),
+            })
+            .collect();
+        let schema = parse_message_type(&format!("message document {{ {} }}", columns.join(" ")))
+            .context("Failed to build Parquet schema")?;
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let file = File::create(path).context(format!("Failed to create {}", path.display()))?;
+        let writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties))?;
+
+        Ok(Self {
+            fields: fields.to_vec(),
+            types,
+            writer,
+        })
+    }
+
+    /// Write a batch of documents as one row group
+    pub fn write_batch(&mut self, docs: &[Document]) -> Result<()> {
+        let mut row_group = self.writer.next_row_group()?;
+
+        for (field, column_type) in self.fields.iter().zip(&self.types) {
+            let mut column = row_group
+                .next_column()?
+                .context("Parquet schema has fewer columns than fields")?;
+
+            let values: Vec<Option<&Bson>> = docs
+                .iter()
+                .map(|doc| {
+                    get_path(doc, field).filter(|v| !matches!(v, Bson::Null | Bson::Undefined))
+                })
+                .collect();
+
+            match column_type {
+                ColumnType::Int64 => {
+                    let converted = values
+                        .iter()
+                        .map(|v| match v {
+                            Some(Bson::Int32(i)) => Ok(Some(*i as i64)),
+                            Some(Bson::Int64(i)) => Ok(Some(*i)),
+                            None => Ok(None),
+                            Some(other) => Err(mismatch(field, *column_type, other)),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let (data, levels) = split_nulls(converted);
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+                ColumnType::Double => {
+                    let converted = values
+                        .iter()
+                        .map(|v| match v {
+                            Some(Bson::Int32(i)) => Ok(Some(*i as f64)),
+                            Some(Bson::Int64(i)) => Ok(Some(*i as f64)),
+                            Some(Bson::Double(d)) => Ok(Some(*d)),
+                            None => Ok(None),
+                            Some(other) => Err(mismatch(field, *column_type, other)),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let (data, levels) = split_nulls(converted);
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+                ColumnType::Boolean => {
+                    let converted = values
+                        .iter()
+                        .map(|v| match v {
+                            Some(Bson::Boolean(b)) => Ok(Some(*b)),
+                            None => Ok(None),
+                            Some(other) => Err(mismatch(field, *column_type, other)),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let (data, levels) = split_nulls(converted);
+                    column
+                        .typed::<BoolType>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+                ColumnType::String => {
+                    let converted: Vec<Option<ByteArray>> = values
+                        .iter()
+                        .map(|v| v.map(|v| ByteArray::from(bson_to_string(v).into_bytes())))
+                        .collect();
+                    let (data, levels) = split_nulls(converted);
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+            }
+
+            column.close()?;
+        }
+
+        row_group.close()?;
+        Ok(())
+    }
+
+    pub fn close(self) -> Result<()> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Parquet column names of projected fields, which can't hold dots or spaces.
+/// Fields that would share a column, such as `a.b` and `a_b`, are rejected.
+pub fn column_names(fields: &[String]) -> Result<Vec<String>> {
+    let mut names: Vec<String> = Vec::with_capacity(fields.len());
+    for field in fields {
+        let name = field.replace(['.', ' '], "_");
+        if let Some(other) = names.iter().position(|existing| *existing == name) {
+            anyhow::bail!(
+                "Fields '{}' and '{}' would both be written to the Parquet column '{}'",
+                fields[other],
+                field,
+                name
+            );
+        }
+        names.push(name);
+    }
+    Ok(names)
+}
+
+fn mismatch(field: &str, column_type: ColumnType, value: &Bson) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Field '{}' holds a {:?} value, but its Parquet column was created as {} from the first batch",
+        field,
+        value.element_type(),
+        column_type.schema()
+    )
+}
+
+/// Split optional values into the non-null values and their definition levels
+fn split_nulls<T>(values: Vec<Option<T>>) -> (Vec<T>, Vec<i16>) {
+    let levels = values.iter().map(|v| i16::from(v.is_some())).collect();
+    (values.into_iter().flatten().collect(), levels)
+}
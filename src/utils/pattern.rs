@@ -0,0 +1,45 @@
+/// Keep only the names matching any of the glob patterns
+pub fn include_matching(names: Vec<String>, patterns: &[String]) -> Vec<String> {
+    names
+        .into_iter()
+        .filter(|name| patterns.iter().any(|pattern| glob_match(pattern, name)))
+        .collect()
+}
+
+/// Drop the names matching any of the glob patterns
+pub fn exclude_matching(names: Vec<String>, patterns: &[String]) -> Vec<String> {
+    names
+        .into_iter()
+        .filter(|name| !patterns.iter().any(|pattern| glob_match(pattern, name)))
+        .collect()
+}
+
+/// Match a name against a glob pattern supporting `*` (any run of characters)
+/// and `?` (exactly one character). Patterns without wildcards match exactly.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` seen and the name position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` absorb one more character and retry
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,92 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::OnceLock;
+
+static MODE: OnceLock<ProgressMode> = OnceLock::new();
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+/// How progress of long-running operations is rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressMode {
+    /// Animated spinners
+    #[default]
+    Fancy,
+    /// One line when an operation starts and one when it finishes, for logs and CI
+    Plain,
+    /// No progress output
+    None,
+}
+
+/// Select the progress style for the rest of the run
+pub fn set_mode(mode: ProgressMode) {
+    let _ = MODE.set(mode);
+}
+
+pub fn mode() -> ProgressMode {
+    MODE.get().copied().unwrap_or_default()
+}
+
+/// Leave out plans and progress for the rest of the run, printing only
+/// results and errors
+pub fn set_quiet(quiet: bool) {
+    let _ = QUIET.set(quiet);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.get().copied().unwrap_or_default()
+}
+
+pub struct ProgressGuard {
+    pb: Option<ProgressBar>,
+    finished: bool,
+}
+
+impl ProgressGuard {
+    fn new(message: &str) -> Self {
+        let pb = match mode() {
+            ProgressMode::Fancy => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{spinner:.green} {msg}")
+                        .expect("Invalid progress template - this is a bug"),
+                );
+                pb.set_message(format!("{} in progress...", message));
+                pb.enable_steady_tick(std::time::Duration::from_millis(100));
+                Some(pb)
+            }
+            ProgressMode::Plain => {
+                println!("{} in progress...", message);
+                None
+            }
+            ProgressMode::None => None,
+        };
+
+        Self {
+            pb,
+            finished: false,
+        }
+    }
+
+    pub fn finish_with_message(&mut self, msg: &str) {
+        match &self.pb {
+            Some(pb) => pb.finish_with_message(msg.to_string()),
+            None if mode() == ProgressMode::Plain => println!("{}", msg),
+            None => {}
+        }
+        self.finished = true;
+    }
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Some(pb) = &self.pb {
+                pb.finish_and_clear();
+            }
+        }
+    }
+}
+
+pub fn create_progress_bar(message: &str) -> ProgressGuard {
+    ProgressGuard::new(message)
+}
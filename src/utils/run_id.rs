@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+
+/// Lets wrapping tools hand in their own ID to correlate a run with their logs
+pub const RUN_ID_VAR: &str = "ARCULA_RUN_ID";
+
+static RUN_ID: OnceLock<String> = OnceLock::new();
+
+/// The ID of this run, taken from `ARCULA_RUN_ID` or generated on first use
+pub fn get() -> &'static str {
+    RUN_ID.get_or_init(|| {
+        std::env::var(RUN_ID_VAR)
+            .ok()
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| generate(Utc::now()))
+    })
+}
+
+/// A new run ID such as `20250101T120000-3fa2c1d8`: the start time, so IDs
+/// sort chronologically, followed by random hex digits
+pub fn generate(now: DateTime<Utc>) -> String {
+    // RandomState is seeded randomly, which is enough to tell runs apart
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u32(now.timestamp_subsec_nanos());
+    hasher.write_u32(std::process::id());
+
+    format!(
+        "{}-{:08x}",
+        now.format("%Y%m%dT%H%M%S"),
+        hasher.finish() as u32
+    )
+}
@@ -0,0 +1,123 @@
+use log::debug;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use crate::config::file::MetricsConfig;
+
+/// Datadog event severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertType {
+    Info,
+    Success,
+    Error,
+}
+
+impl AlertType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AlertType::Info => "info",
+            AlertType::Success => "success",
+            AlertType::Error => "error",
+        }
+    }
+}
+
+fn format_tags(tags: &[(&str, &str)]) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let tags: Vec<String> = tags.iter().map(|(k, v)| format!("{}:{}", k, v)).collect();
+    format!("|#{}", tags.join(","))
+}
+
+/// Format a DogStatsD metric datagram, e.g. `arcula.sync.started:1|c|#source:PROD`
+pub fn format_metric(name: &str, value: u64, kind: &str, tags: &[(&str, &str)]) -> String {
+    format!("{}:{}|{}{}", name, value, kind, format_tags(tags))
+}
+
+/// Format a Datadog event datagram
+pub fn format_event(
+    title: &str,
+    text: &str,
+    alert_type: AlertType,
+    tags: &[(&str, &str)],
+) -> String {
+    // Newlines must be escaped in event text
+    let text = text.replace('\n', "\\n");
+    format!(
+        "_e{{{},{}}}:{}|{}|t:{}{}",
+        title.len(),
+        text.len(),
+        title,
+        text,
+        alert_type.as_str(),
+        format_tags(tags)
+    )
+}
+
+/// Fire-and-forget StatsD client; sending failures are only logged
+pub struct StatsdClient {
+    socket: UdpSocket,
+    address: String,
+    prefix: String,
+    events: bool,
+}
+
+impl StatsdClient {
+    /// Create a client from the `[metrics]` config, or `None` when metrics are disabled
+    pub fn from_config(config: &MetricsConfig) -> Option<Self> {
+        let address = config.statsd.clone()?;
+        let socket = match UdpSocket::bind(if address.starts_with('[') {
+            "[::]:0"
+        } else {
+            "0.0.0.0:0"
+        }) {
+            Ok(socket) => socket,
+            Err(e) => {
+                debug!("Failed to open StatsD socket: {}", e);
+                return None;
+            }
+        };
+
+        Some(Self {
+            socket,
+            address,
+            prefix: config
+                .prefix
+                .clone()
+                .unwrap_or_else(|| "arcula".to_string()),
+            events: config.datadog_events,
+        })
+    }
+
+    fn send(&self, datagram: &str) {
+        if let Err(e) = self.socket.send_to(datagram.as_bytes(), &self.address) {
+            debug!("Failed to send StatsD datagram to {}: {}", self.address, e);
+        }
+    }
+
+    pub fn increment(&self, name: &str, tags: &[(&str, &str)]) {
+        self.send(&format_metric(
+            &format!("{}.{}", self.prefix, name),
+            1,
+            "c",
+            tags,
+        ));
+    }
+
+    pub fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]) {
+        self.send(&format_metric(
+            &format!("{}.{}", self.prefix, name),
+            duration.as_millis() as u64,
+            "ms",
+            tags,
+        ));
+    }
+
+    /// Send a Datadog event if events are enabled
+    pub fn event(&self, title: &str, text: &str, alert_type: AlertType, tags: &[(&str, &str)]) {
+        if self.events {
+            self.send(&format_event(title, text, alert_type, tags));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use colored::Colorize;
+
+/// Minimal aligned text table for terminal output
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    right_aligned: Vec<bool>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+            right_aligned: vec![false; headers.len()],
+        }
+    }
+
+    /// Right-align the given column, typically used for numbers and sizes
+    pub fn align_right(mut self, column: usize) -> Self {
+        if let Some(aligned) = self.right_aligned.get_mut(column) {
+            *aligned = true;
+        }
+        self
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                if let Some(width) = widths.get_mut(i) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+        }
+        widths
+    }
+
+    fn format_row(&self, cells: &[String], widths: &[usize]) -> String {
+        cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                if self.right_aligned[i] {
+                    format!("{:>width$}", cell, width = width)
+                } else {
+                    format!("{:<width$}", cell, width = width)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    }
+
+    /// Print the table with the given indentation
+    pub fn print(&self, indent: usize) {
+        let widths = self.widths();
+        let pad = " ".repeat(indent);
+
+        println!("{}{}", pad, self.format_row(&self.headers, &widths).bold());
+        for row in &self.rows {
+            println!("{}{}", pad, self.format_row(row, &widths));
+        }
+    }
+}
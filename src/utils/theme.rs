@@ -0,0 +1,65 @@
+use inquire::ui::{Attributes, Color, RenderConfig, StyleSheet, Styled};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::file::UiConfig;
+
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// Apply the `[ui]` settings to prompts and colored output
+pub fn init(config: &UiConfig) {
+    ASCII.store(config.ascii, Ordering::Relaxed);
+
+    let color = config.color.unwrap_or(true);
+    if !color {
+        colored::control::set_override(false);
+    }
+
+    inquire::set_global_render_config(render_config(config.high_contrast && color, color));
+}
+
+fn render_config(high_contrast: bool, color: bool) -> RenderConfig<'static> {
+    if !color {
+        return RenderConfig::empty();
+    }
+
+    let mut config = RenderConfig::default_colored();
+    if high_contrast {
+        let bold = |color| StyleSheet::new().with_fg(color).with_attr(Attributes::BOLD);
+        config.prompt_prefix = Styled::new("?").with_fg(Color::LightYellow);
+        config.answered_prompt_prefix = Styled::new(">").with_fg(Color::LightYellow);
+        config.prompt = bold(Color::White);
+        config.answer = bold(Color::LightYellow);
+        config.help_message = StyleSheet::new().with_fg(Color::White);
+        config.highlighted_option_prefix = Styled::new(">").with_fg(Color::LightYellow);
+        config.selected_option = Some(bold(Color::LightYellow));
+        config.selected_checkbox = Styled::new("[x]").with_fg(Color::LightYellow);
+    }
+    config
+}
+
+/// Marker for a successful item
+pub fn check() -> &'static str {
+    if ASCII.load(Ordering::Relaxed) {
+        "+"
+    } else {
+        "✓"
+    }
+}
+
+/// Marker for a failed or disabled item
+pub fn cross() -> &'static str {
+    if ASCII.load(Ordering::Relaxed) {
+        "x"
+    } else {
+        "✗"
+    }
+}
+
+/// Arrow between a source and its target
+pub fn arrow() -> &'static str {
+    if ASCII.load(Ordering::Relaxed) {
+        "->"
+    } else {
+        "→"
+    }
+}
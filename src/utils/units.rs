@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+/// Format a byte count using binary units, e.g. `1.5 GB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Parse a byte size such as `500MB`, `50GB` or `1.5 TB` using binary units.
+///
+/// Units are case-insensitive; a bare number means bytes.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}': expected e.g. '50GB'", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        other => {
+            return Err(format!(
+                "invalid size '{}': unknown unit '{}'",
+                value, other
+            ))
+        }
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parse a duration such as `90s`, `45m`, `2h` or `1h30m`.
+///
+/// Supported units are `s`, `m`, `h` and `d`; a bare number means seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("duration cannot be empty".to_string());
+    }
+    // A bare number means seconds
+    let suffixed = if value.chars().all(|c| c.is_ascii_digit()) {
+        format!("{}s", value)
+    } else {
+        value.to_string()
+    };
+
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in suffixed.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let multiplier = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => {
+                return Err(format!(
+                    "invalid duration '{}': unknown unit '{}'",
+                    value, c
+                ))
+            }
+        };
+        let amount: u64 = number.parse().map_err(|_| {
+            format!(
+                "invalid duration '{}': missing number before '{}'",
+                value, c
+            )
+        })?;
+        total = total.saturating_add(amount.saturating_mul(multiplier));
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(format!(
+            "invalid duration '{}': missing unit after {}",
+            value, number
+        ));
+    }
+    if total == 0 {
+        return Err("duration must be greater than zero".to_string());
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Parse a point in time given as a date such as `2024-01-01` (midnight UTC)
+/// or as an RFC 3339 timestamp such as `2024-01-01T06:00:00+02:00`
+pub fn parse_date(value: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    let value = value.trim();
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc());
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .map_err(|_| {
+            format!(
+                "invalid date '{}': expected e.g. '2024-01-01' or '2024-01-01T06:00:00Z'",
+                value
+            )
+        })
+}
+
+/// Format a duration with hours, minutes and seconds, e.g. `1h30m` or `45s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+
+    let mut formatted = String::new();
+    if hours > 0 {
+        formatted.push_str(&format!("{}h", hours));
+    }
+    if minutes > 0 {
+        formatted.push_str(&format!("{}m", minutes));
+    }
+    if seconds > 0 || formatted.is_empty() {
+        formatted.push_str(&format!("{}s", seconds));
+    }
+    formatted
+}
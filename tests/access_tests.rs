@@ -0,0 +1,111 @@
+use arcula::commands::rpc::Policy;
+use arcula::config::file::ServerConfig;
+use arcula::core::access::{authorize, find_token, hash_token, load_server_config, Role};
+use std::io::Write;
+
+fn server_config(content: &str) -> anyhow::Result<ServerConfig> {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+    load_server_config(file.path())
+}
+
+// This file contains tests for the client tokens of `arcula rpc` and `arcula mcp`
+
+#[test]
+fn test_hash_token() {
+    assert_eq!(
+        hash_token("test"),
+        "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+    );
+}
+
+#[test]
+fn test_find_token() {
+    let config = server_config(&format!(
+        "[tokens.ci]\nrole = \"operator\"\nsha256 = \"{}\"\n\n\
+         [tokens.oncall]\nrole = \"admin\"\nsha256 = \"{}\"\n",
+        hash_token("ci-secret"),
+        hash_token("oncall-secret").to_uppercase()
+    ))
+    .unwrap();
+    let tokens = &config.tokens;
+
+    assert_eq!(
+        find_token(tokens, "ci-secret"),
+        Some(("ci", Role::Operator))
+    );
+    assert_eq!(
+        find_token(tokens, "oncall-secret"),
+        Some(("oncall", Role::Admin))
+    );
+    assert_eq!(find_token(tokens, "guess"), None);
+
+    assert!(server_config("[tokens.ci]\nrole = \"root\"\nsha256 = \"00\"\n").is_err());
+    assert!(server_config("[tokens.ci]\nrole = \"viewer\"\n").is_err());
+}
+
+#[test]
+fn test_missing_server_config_has_no_tokens() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = load_server_config(&dir.path().join("server.toml")).unwrap();
+    assert!(config.tokens.is_empty());
+}
+
+#[test]
+fn test_server_tokens_not_read_from_config_file() {
+    assert!(
+        arcula::config::file::parse("[server.tokens.ci]\nrole = \"admin\"\nsha256 = \"00\"\n")
+            .is_err()
+    );
+}
+
+#[test]
+fn test_authorize_without_tokens() {
+    let config = ServerConfig::default();
+
+    // Clients start their servers, so they can't opt out of the limits by
+    // leaving the tokens out
+    assert_eq!(authorize(&config, None, false).unwrap(), Some(Role::Viewer));
+    assert_eq!(
+        authorize(&config, Some("anything"), false).unwrap(),
+        Some(Role::Viewer)
+    );
+    assert_eq!(authorize(&config, None, true).unwrap(), None);
+}
+
+#[test]
+fn test_authorize_with_tokens() {
+    let config = server_config(&format!(
+        "[tokens.ci]\nrole = \"operator\"\nsha256 = \"{}\"\n",
+        hash_token("ci-secret")
+    ))
+    .unwrap();
+
+    assert_eq!(
+        authorize(&config, Some("ci-secret"), false).unwrap(),
+        Some(Role::Operator)
+    );
+    assert!(authorize(&config, Some("guess"), false).is_err());
+    assert!(authorize(&config, Some(""), false).is_err());
+    // Configured tokens are required even with --allow-unauthenticated
+    assert!(authorize(&config, None, true).is_err());
+}
+
+#[test]
+fn test_policy_for_role() {
+    assert_eq!(
+        Policy::for_role(Role::Viewer),
+        Policy {
+            writes: false,
+            production_targets: false,
+        }
+    );
+    assert_eq!(
+        Policy::for_role(Role::Operator),
+        Policy {
+            writes: true,
+            production_targets: false,
+        }
+    );
+    assert_eq!(Policy::for_role(Role::Admin), Policy::UNRESTRICTED);
+}
@@ -0,0 +1,106 @@
+use arcula::core::approval::{hash_files, read_plan, sign_plan, verify_plan, write_plan};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// This file contains tests for signing and verifying sync plans
+
+const KEY: &[u8] = b"shared-approval-key";
+
+fn requested_at() -> DateTime<Utc> {
+    "2025-01-01T12:00:00Z".parse().unwrap()
+}
+
+fn now() -> DateTime<Utc> {
+    requested_at() + Duration::hours(1)
+}
+
+fn plan() -> arcula::core::approval::SyncPlan {
+    sign_plan(
+        json!({"from": "STG", "to": "PROD", "db": "orders", "backup": true}),
+        BTreeMap::new(),
+        "alice",
+        requested_at(),
+        KEY,
+    )
+}
+
+#[test]
+fn test_signed_plan_verifies() {
+    assert!(verify_plan(&plan(), KEY, now()).is_ok());
+}
+
+#[test]
+fn test_expired_plan_is_rejected() {
+    let plan = plan();
+    assert_eq!(plan.expires_at, "2025-01-02T12:00:00+00:00");
+    assert!(verify_plan(&plan, KEY, requested_at() + Duration::hours(25)).is_err());
+}
+
+#[test]
+fn test_plan_with_changed_file_is_rejected() {
+    let path = std::env::temp_dir().join(format!("arcula_script_{}.sh", rand::random::<u64>()));
+    std::fs::write(&path, "echo reviewed").unwrap();
+
+    let files = hash_files(&[path.as_path()]).unwrap();
+    let plan = sign_plan(
+        json!({"from": "STG", "to": "PROD", "post_sync_script": path}),
+        files,
+        "alice",
+        requested_at(),
+        KEY,
+    );
+    assert!(verify_plan(&plan, KEY, now()).is_ok());
+
+    std::fs::write(&path, "echo swapped").unwrap();
+    let changed = verify_plan(&plan, KEY, now());
+    std::fs::remove_file(&path).unwrap();
+    assert!(changed.is_err());
+
+    // A removed file doesn't pass either
+    assert!(verify_plan(&plan, KEY, now()).is_err());
+    assert!(hash_files(&[Path::new("/nonexistent/arcula_script.sh")]).is_err());
+}
+
+#[test]
+fn test_modified_plan_is_rejected() {
+    let mut modified = plan();
+    modified.sync["db"] = json!("customers");
+    assert!(verify_plan(&modified, KEY, now()).is_err());
+
+    let mut modified = plan();
+    modified.requested_by = "bob".to_string();
+    assert!(verify_plan(&modified, KEY, now()).is_err());
+
+    let mut modified = plan();
+    modified.expires_at = "2030-01-01T00:00:00+00:00".to_string();
+    assert!(verify_plan(&modified, KEY, now()).is_err());
+
+    let mut modified = plan();
+    modified
+        .files
+        .insert("script.sh".to_string(), "00".to_string());
+    assert!(verify_plan(&modified, KEY, now()).is_err());
+
+    let mut modified = plan();
+    modified.signature = "not hex".to_string();
+    assert!(verify_plan(&modified, KEY, now()).is_err());
+}
+
+#[test]
+fn test_plan_signed_with_other_key_is_rejected() {
+    assert!(verify_plan(&plan(), b"another-key", now()).is_err());
+}
+
+#[test]
+fn test_plan_file_round_trip() {
+    let path = std::env::temp_dir().join(format!("arcula_plan_{}.json", rand::random::<u64>()));
+
+    write_plan(&path, &plan()).unwrap();
+    let read = read_plan(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read, plan());
+    assert!(verify_plan(&read, KEY, now()).is_ok());
+}
@@ -0,0 +1,255 @@
+use arcula::core::backup::{
+    compare_checksums, find_run_backups, incomparable_hashes, plan_restore, read_manifest,
+    unchanged_collections, BackupManifest, BackupRole, CollectionChecksum, HashMethod,
+    RestoredCollection,
+};
+use arcula::utils::dump::MANIFEST_FILE;
+use arcula::utils::mongodb::CollectionStats;
+
+// This file contains tests for backup manifests
+
+fn checksum(name: &str, documents: u64, hash: Option<&str>) -> CollectionChecksum {
+    CollectionChecksum {
+        name: name.to_string(),
+        documents,
+        hash: hash.map(String::from),
+        hash_method: HashMethod::DbHash,
+    }
+}
+
+fn scanned(name: &str, documents: u64, hash: &str) -> CollectionChecksum {
+    CollectionChecksum {
+        hash_method: HashMethod::Scan,
+        ..checksum(name, documents, Some(hash))
+    }
+}
+
+#[test]
+fn test_compare_checksums() {
+    let expected = vec![
+        checksum("users", 10, Some("aaa")),
+        checksum("orders", 5, Some("bbb")),
+        checksum("events", 3, None),
+        checksum("logs", 1, Some("ccc")),
+    ];
+    let actual = vec![
+        checksum("users", 10, Some("aaa")),
+        checksum("orders", 5, Some("changed")),
+        // Without a hash on both sides only counts are compared
+        checksum("events", 3, Some("ddd")),
+        checksum("sessions", 2, None),
+    ];
+
+    assert_eq!(
+        compare_checksums(&expected, &actual),
+        vec![
+            "orders: content hash differs",
+            "logs: missing",
+            "sessions: not in backup",
+        ]
+    );
+    assert!(compare_checksums(&expected, &expected).is_empty());
+
+    let recounted = vec![checksum("users", 9, Some("aaa"))];
+    assert_eq!(
+        compare_checksums(&expected[..1], &recounted),
+        vec!["users: 9 documents, expected 10"]
+    );
+}
+
+#[test]
+fn test_unchanged_collections() {
+    let source = vec![
+        checksum("countries", 250, Some("aaa")),
+        checksum("currencies", 180, Some("bbb")),
+        checksum("users", 10, Some("ccc")),
+        checksum("events", 3, None),
+        checksum("orders", 5, Some("ddd")),
+    ];
+    let baseline = vec![
+        checksum("countries", 250, Some("aaa")),
+        checksum("currencies", 179, Some("bbb")),
+        checksum("users", 10, Some("changed")),
+        // Without hashes equal counts don't prove equal content
+        checksum("events", 3, None),
+    ];
+
+    assert_eq!(unchanged_collections(&source, &baseline), vec!["countries"]);
+    assert!(unchanged_collections(&source, &[]).is_empty());
+}
+
+#[test]
+fn test_compare_checksums_of_different_methods() {
+    let expected = vec![
+        checksum("users", 10, Some("aaa")),
+        checksum("orders", 5, Some("bbb")),
+        scanned("events", 3, "ccc"),
+    ];
+    let actual = vec![
+        // A scan never matches dbHash, so different hashes aren't a difference
+        scanned("users", 10, "zzz"),
+        scanned("orders", 4, "bbb"),
+        scanned("events", 3, "changed"),
+    ];
+
+    assert_eq!(
+        compare_checksums(&expected, &actual),
+        vec![
+            "orders: 4 documents, expected 5",
+            "events: content hash differs",
+        ]
+    );
+    assert_eq!(
+        incomparable_hashes(&expected, &actual),
+        vec![
+            "users: dbHash and scan hashes",
+            "orders: dbHash and scan hashes"
+        ]
+    );
+    assert!(incomparable_hashes(&expected, &expected).is_empty());
+
+    // Nor are equal hashes of different methods proof of equal content
+    assert!(unchanged_collections(&[scanned("users", 10, "aaa")], &expected).is_empty());
+}
+
+#[test]
+fn test_hash_method_of_older_manifests() {
+    let collection: CollectionChecksum =
+        serde_json::from_str(r#"{"name": "users", "documents": 10, "hash": "aaa"}"#).unwrap();
+    assert_eq!(collection.hash_method, HashMethod::DbHash);
+
+    let json = serde_json::to_value(scanned("users", 10, "aaa")).unwrap();
+    assert_eq!(json["hash_method"], "scan");
+}
+
+#[test]
+fn test_read_manifest() {
+    let dir = std::env::temp_dir().join(format!("arcula_backup_{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let manifest = BackupManifest {
+        environment: "STG".to_string(),
+        database: "app".to_string(),
+        created_at: "2025-01-01T00:00:00+00:00".to_string(),
+        run_id: Some("20250101T000000-3fa2c1d8".to_string()),
+        role: BackupRole::Target,
+        collections: vec![checksum("users", 10, Some("aaa"))],
+    };
+    std::fs::write(
+        dir.join(MANIFEST_FILE),
+        serde_json::to_string(&manifest).unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(read_manifest(&dir).unwrap(), manifest);
+
+    // Manifests written before run IDs and roles were recorded
+    std::fs::write(
+        dir.join(MANIFEST_FILE),
+        r#"{"environment":"STG","database":"app","created_at":"2025-01-01T00:00:00+00:00","collections":[]}"#,
+    )
+    .unwrap();
+    let old = read_manifest(&dir).unwrap();
+    assert_eq!(old.run_id, None);
+    assert_eq!(old.role, BackupRole::Target);
+
+    let source = BackupManifest {
+        role: BackupRole::Source,
+        ..manifest
+    };
+    let json = serde_json::to_value(&source).unwrap();
+    assert_eq!(json["role"], "source");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(read_manifest(&dir).is_err());
+}
+
+#[test]
+fn test_find_run_backups() {
+    let dir = std::env::temp_dir().join(format!("arcula_backups_{}", rand::random::<u64>()));
+    assert!(find_run_backups(&dir, "run-1").unwrap().is_empty());
+
+    for (name, database, run_id) in [
+        ("backup_app_1", "app", Some("run-1")),
+        ("backup_billing_1", "billing", Some("run-1")),
+        ("backup_app_2", "app", Some("run-2")),
+        ("backup_app_0", "app", None),
+    ] {
+        let path = dir.join(name);
+        std::fs::create_dir_all(&path).unwrap();
+        let manifest = BackupManifest {
+            environment: "STG".to_string(),
+            database: database.to_string(),
+            created_at: "2025-01-01T00:00:00+00:00".to_string(),
+            run_id: run_id.map(String::from),
+            role: BackupRole::Target,
+            collections: vec![checksum("users", 10, Some("aaa"))],
+        };
+        std::fs::write(
+            path.join(MANIFEST_FILE),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+    }
+    // Directories without a manifest are not backups
+    std::fs::create_dir_all(dir.join("scratch")).unwrap();
+
+    let backups = find_run_backups(&dir, "run-1").unwrap();
+    let databases: Vec<_> = backups.iter().map(|(_, m)| m.database.as_str()).collect();
+    assert_eq!(databases, vec!["app", "billing"]);
+    assert_eq!(backups[0].0, dir.join("backup_app_1"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_plan_restore() {
+    let manifest = BackupManifest {
+        environment: "STG".to_string(),
+        database: "app".to_string(),
+        created_at: "2025-01-01T00:00:00+00:00".to_string(),
+        run_id: Some("run-1".to_string()),
+        role: BackupRole::Target,
+        collections: vec![
+            checksum("users", 10, Some("aaa")),
+            checksum("orders", 5, Some("bbb")),
+        ],
+    };
+    let stats = |name: &str, kind: &str, documents: u64| CollectionStats {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        documents,
+        ..Default::default()
+    };
+    let target = vec![
+        stats("users", "collection", 12),
+        stats("sessions", "collection", 3),
+        stats("active_users", "view", 0),
+    ];
+
+    let plan = plan_restore(&manifest, &[], &target);
+    assert_eq!(
+        plan.restored,
+        vec![
+            RestoredCollection {
+                name: "orders".to_string(),
+                backup_documents: 5,
+                target_documents: None,
+            },
+            RestoredCollection {
+                name: "users".to_string(),
+                backup_documents: 10,
+                target_documents: Some(12),
+            },
+        ]
+    );
+    assert_eq!(plan.untouched, vec!["sessions".to_string()]);
+
+    // Collections left out of a partial restore are untouched too
+    let plan = plan_restore(&manifest, &["orders".to_string()], &target);
+    assert_eq!(plan.restored.len(), 1);
+    assert_eq!(
+        plan.untouched,
+        vec!["sessions".to_string(), "users".to_string()]
+    );
+}
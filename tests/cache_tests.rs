@@ -0,0 +1,72 @@
+use arcula::core::cache::{cache_key, entry_path, CachedDatabases};
+use std::path::Path;
+use std::time::Duration;
+
+// This file contains tests for the cached database lists used by interactive prompts
+
+const TTL: Duration = Duration::from_secs(600);
+
+fn entry(key: &str, databases: &[&str], fetched_at: i64) -> CachedDatabases {
+    CachedDatabases {
+        key: key.to_string(),
+        fetched_at,
+        databases: databases.iter().map(|db| db.to_string()).collect(),
+    }
+}
+
+#[test]
+fn test_cache_entry_is_fresh_within_ttl() {
+    let cached = entry("PROD", &["orders"], 1_000);
+
+    assert!(cached.is_fresh(TTL, 1_599));
+    assert!(!cached.is_fresh(TTL, 1_600));
+}
+
+#[test]
+fn test_cache_key_separates_configs_profiles_and_prefixes() {
+    let key = cache_key("PROD", Some(Path::new("arcula.toml")), None, None);
+
+    assert_ne!(
+        key,
+        cache_key("PROD", Some(Path::new("other.toml")), None, None)
+    );
+    assert_ne!(
+        key,
+        cache_key("PROD", Some(Path::new("arcula.toml")), Some("eu"), None)
+    );
+    assert_ne!(
+        key,
+        cache_key("PROD", Some(Path::new("arcula.toml")), None, Some("teamx_"))
+    );
+    assert_ne!(
+        entry_path(Path::new("/state"), &key),
+        entry_path(Path::new("/state"), &cache_key("STG", None, None, None))
+    );
+}
+
+#[test]
+fn test_cache_entries_are_stored_separately() {
+    let dir = std::env::temp_dir()
+        .join(format!("arcula_cache_{}", rand::random::<u64>()))
+        .join("databases");
+
+    let prod = entry("PROD", &["orders", "users"], 42);
+    let stg = entry("STG", &["orders"], 43);
+    prod.save(&entry_path(&dir, &prod.key)).unwrap();
+    stg.save(&entry_path(&dir, &stg.key)).unwrap();
+
+    // Saving one environment leaves the other in place
+    assert_eq!(CachedDatabases::load(&entry_path(&dir, "PROD")), Some(prod));
+    assert_eq!(CachedDatabases::load(&entry_path(&dir, "STG")), Some(stg));
+    std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_cache_load_ignores_unreadable_files() {
+    let path = std::env::temp_dir().join(format!("arcula_cache_{}.json", rand::random::<u64>()));
+    assert_eq!(CachedDatabases::load(&path), None);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert_eq!(CachedDatabases::load(&path), None);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,56 @@
+use arcula::config::{db_prefix, file, scoped_database, unscoped_database, Environment};
+use arcula::core::materialize::CollectionRef;
+
+// This file contains tests for per-environment database prefixes. They load a
+// config file, which stays loaded for the whole test binary.
+
+#[test]
+fn test_db_prefix() {
+    let dir = std::env::temp_dir().join(format!("arcula_db_prefix_{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("arcula.toml");
+    std::fs::write(
+        &path,
+        r#"
+[environments.SHARED]
+uri = "mongodb://shared.example.com:27017"
+db_prefix = "teamx_"
+
+[environments.DEV]
+uri = "mongodb://dev.example.com:27017"
+"#,
+    )
+    .unwrap();
+    file::init(Some(&path), None).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let shared = Environment::new("SHARED");
+    let dev = Environment::new("DEV");
+    assert_eq!(db_prefix(&shared), Some("teamx_"));
+    assert_eq!(db_prefix(&dev), None);
+
+    assert_eq!(scoped_database(&shared, "orders"), "teamx_orders");
+    assert_eq!(scoped_database(&dev, "orders"), "orders");
+
+    assert_eq!(unscoped_database(&shared, "teamx_orders"), Some("orders"));
+    // Other teams' databases and the bare prefix are out of scope
+    assert_eq!(unscoped_database(&shared, "teamy_orders"), None);
+    assert_eq!(unscoped_database(&shared, "teamx_"), None);
+    assert_eq!(
+        unscoped_database(&dev, "teamy_orders"),
+        Some("teamy_orders")
+    );
+
+    // materialize references are scoped like sync databases
+    let reference = "shared:orders.events"
+        .parse::<CollectionRef>()
+        .unwrap()
+        .scoped();
+    assert_eq!(reference.database, "teamx_orders");
+    assert_eq!(reference.collection, "events");
+    let reference = "dev:orders.events"
+        .parse::<CollectionRef>()
+        .unwrap()
+        .scoped();
+    assert_eq!(reference.database, "orders");
+}
@@ -0,0 +1,49 @@
+use arcula::core::progress::SyncPhase;
+use arcula::utils::events::{init, EventKind, ProgressEvent};
+
+// This file contains tests for NDJSON progress events
+
+#[test]
+fn test_progress_event_serialization() {
+    let event = ProgressEvent::new(EventKind::Progress, "app")
+        .phase(SyncPhase::Transform)
+        .collection("events", 1, 3);
+    let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+
+    assert_eq!(json["event"], "progress");
+    assert_eq!(json["run_id"], arcula::utils::run_id::get());
+    assert_eq!(json["phase"], "transform");
+    assert_eq!(json["database"], "app");
+    assert_eq!(json["collection"], "events");
+    assert_eq!(json["done"], 1);
+    assert_eq!(json["total"], 3);
+    assert!(json.get("error").is_none());
+
+    let failed = ProgressEvent::new(EventKind::SyncFailed, "app").error("Import failed");
+    let line = serde_json::to_string(&failed).unwrap();
+    assert!(!line.contains('\n'));
+    assert!(line.contains(r#""event":"sync_failed""#));
+    assert!(line.contains(r#""error":"Import failed""#));
+    assert!(!line.contains("phase"));
+}
+
+#[test]
+fn test_warning_event_serialization() {
+    let event = ProgressEvent::new(EventKind::Warning, "app")
+        .warning("backup_failed", "Failed to create backup");
+    let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+
+    assert_eq!(json["event"], "warning");
+    assert_eq!(json["code"], "backup_failed");
+    assert_eq!(json["message"], "Failed to create backup");
+    assert!(json.get("error").is_none());
+}
+
+#[test]
+fn test_init_rejects_invalid_targets() {
+    assert!(init("stdout").is_err());
+    assert!(init("fd:").is_err());
+    assert!(init("fd:three").is_err());
+    #[cfg(unix)]
+    assert!(init("fd:987654").is_err());
+}
@@ -0,0 +1,125 @@
+use arcula::core::export::parse_fields;
+use arcula::utils::parquet::{
+    column_names, get_path, infer_column_type, ColumnType, ParquetWriter,
+};
+use mongodb::bson::{doc, Bson};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+
+// This file contains tests for analytics export helpers
+
+#[test]
+fn test_parse_fields() {
+    assert_eq!(
+        parse_fields("_id, name,address.city,name").unwrap(),
+        vec!["_id", "name", "address.city"]
+    );
+    assert!(parse_fields(" , ").is_err());
+    assert!(parse_fields("address..city").is_err());
+    assert!(parse_fields(".name").is_err());
+}
+
+#[test]
+fn test_get_path() {
+    let doc = doc! { "name": "acme", "address": { "city": "Tbilisi" }, "tags": ["a"] };
+    assert_eq!(get_path(&doc, "name"), Some(&Bson::String("acme".into())));
+    assert_eq!(
+        get_path(&doc, "address.city"),
+        Some(&Bson::String("Tbilisi".into()))
+    );
+    assert_eq!(get_path(&doc, "address.zip"), None);
+    assert_eq!(get_path(&doc, "name.first"), None);
+}
+
+#[test]
+fn test_infer_column_type() {
+    let ints = [Bson::Int32(1), Bson::Int64(2), Bson::Null];
+    assert_eq!(infer_column_type(ints.iter().map(Some)), ColumnType::Int64);
+
+    let numbers = [Bson::Int32(1), Bson::Double(2.5)];
+    assert_eq!(
+        infer_column_type(numbers.iter().map(Some)),
+        ColumnType::Double
+    );
+
+    let mixed = [Bson::Boolean(true), Bson::Int32(1)];
+    assert_eq!(
+        infer_column_type(mixed.iter().map(Some)),
+        ColumnType::String
+    );
+
+    assert_eq!(
+        infer_column_type([None, None].into_iter()),
+        ColumnType::String
+    );
+}
+
+#[test]
+fn test_parquet_writer_round_trip() {
+    let path =
+        std::env::temp_dir().join(format!("arcula_export_{}.parquet", rand::random::<u64>()));
+    let fields = vec![
+        "name".to_string(),
+        "stats.count".to_string(),
+        "active".to_string(),
+    ];
+    let docs = vec![
+        doc! { "name": "a", "stats": { "count": 1 }, "active": true },
+        doc! { "name": "b", "active": false },
+        doc! { "name": "c", "stats": { "count": "many" } },
+    ];
+
+    let mut writer = ParquetWriter::create(&path, &fields, &docs).unwrap();
+    writer.write_batch(&docs).unwrap();
+    writer.close().unwrap();
+
+    let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!(metadata.file_metadata().num_rows(), 3);
+
+    let columns: Vec<_> = metadata
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|c| c.name().to_string())
+        .collect();
+    assert_eq!(columns, vec!["name", "stats_count", "active"]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parquet_writer_rejects_type_mismatch() {
+    let path =
+        std::env::temp_dir().join(format!("arcula_export_{}.parquet", rand::random::<u64>()));
+    let fields = vec!["count".to_string()];
+    let first = vec![doc! { "count": 1 }, doc! { "count": 2 }];
+
+    let mut writer = ParquetWriter::create(&path, &fields, &first).unwrap();
+    writer.write_batch(&first).unwrap();
+    let err = writer
+        .write_batch(&[doc! { "count": "many" }])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("'count'"), "{}", err);
+    assert!(err.contains("INT64"), "{}", err);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parquet_column_names() {
+    assert_eq!(
+        column_names(&["a.b".to_string(), "c d".to_string()]).unwrap(),
+        vec!["a_b", "c_d"]
+    );
+
+    let fields = vec!["a.b".to_string(), "a_b".to_string()];
+    let err = column_names(&fields).unwrap_err().to_string();
+    assert!(err.contains("'a.b'") && err.contains("'a_b'"), "{}", err);
+
+    let path =
+        std::env::temp_dir().join(format!("arcula_export_{}.parquet", rand::random::<u64>()));
+    assert!(ParquetWriter::create(&path, &fields, &[doc! { "a_b": 1 }]).is_err());
+    assert!(!path.exists());
+}
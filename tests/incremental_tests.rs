@@ -0,0 +1,55 @@
+use arcula::core::incremental::{modified_since_stage, resolve_since, IncrementalState};
+use arcula::utils::units::parse_date;
+use mongodb::bson::{doc, DateTime};
+
+// This file contains tests for incremental syncs
+
+#[test]
+fn test_resolve_since() {
+    let since = parse_date("2024-01-01").unwrap();
+    let state = IncrementalState {
+        field: "updatedAt".to_string(),
+        synced_at: parse_date("2024-03-01T02:00:00Z").unwrap(),
+    };
+
+    // --since wins over the last sync
+    assert_eq!(
+        resolve_since("updatedAt", Some(since), Some(&state)).unwrap(),
+        since
+    );
+    assert_eq!(
+        resolve_since("updatedAt", None, Some(&state)).unwrap(),
+        state.synced_at
+    );
+    // A cutoff for another field says nothing about this one
+    assert!(resolve_since("modifiedAt", None, Some(&state)).is_err());
+    assert!(resolve_since("updatedAt", None, None).is_err());
+}
+
+#[test]
+fn test_incremental_state_round_trip() {
+    let dir = std::env::temp_dir().join(format!("arcula_incremental_{}", rand::random::<u64>()));
+    let path = dir.join("incremental-PROD-app-STG-app.json");
+    assert_eq!(IncrementalState::load(&path).unwrap(), None);
+
+    let state = IncrementalState {
+        field: "updatedAt".to_string(),
+        synced_at: parse_date("2024-03-01T02:00:00Z").unwrap(),
+    };
+    state.save(&path).unwrap();
+    assert_eq!(IncrementalState::load(&path).unwrap(), Some(state));
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(IncrementalState::load(&path).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_modified_since_stage() {
+    let since = parse_date("2024-01-01").unwrap();
+    assert_eq!(
+        modified_since_stage("meta.updatedAt", since),
+        doc! { "$match": { "meta.updatedAt": { "$gt": DateTime::from_millis(1_704_067_200_000) } } }
+    );
+}
@@ -0,0 +1,39 @@
+use std::process::Command;
+
+// This file contains tests for the output of the info command
+
+#[test]
+fn test_info_json_output_is_only_json() {
+    let dir = std::env::temp_dir().join(format!("arcula_info_{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("arcula.toml");
+    std::fs::write(&config, "").unwrap();
+    // info doesn't run the tools, but they must be found
+    for tool in ["mongodump", "mongorestore"] {
+        std::fs::write(dir.join(tool), "").unwrap();
+    }
+
+    // An unreachable environment still gets an entry, with its error
+    let output = Command::new(env!("CARGO_BIN_EXE_arcula"))
+        .args(["--output", "json", "info", "--timeout", "1"])
+        .env_clear()
+        .env("PATH", std::env::var("PATH").unwrap_or_default())
+        .env("HOME", &dir)
+        .env("ARCULA_CONFIG", &config)
+        .env("ARCULA_STATE_DIR", &dir)
+        .env("MONGODB_BIN_PATH", &dir)
+        .env(
+            "MONGO_UNREACHABLE_URI",
+            "mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=200",
+        )
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let environments = json["environments"].as_array().unwrap();
+    assert_eq!(environments.len(), 1);
+    assert_eq!(environments[0]["environment"], "UNREACHABLE");
+    assert!(environments[0]["error"].is_string());
+}
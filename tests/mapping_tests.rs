@@ -0,0 +1,111 @@
+use arcula::core::mapping::{
+    load_database_mapping, parse_csv_collection_mapping, parse_csv_mapping,
+    parse_namespace_mapping, parse_yaml_collection_mapping, parse_yaml_mapping,
+};
+
+// This file contains tests for database mapping file parsing
+
+#[test]
+fn test_parse_yaml_mapping_preserves_order() {
+    let mappings = parse_yaml_mapping("users: users_stg\norders: orders_{date}\n").unwrap();
+
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(mappings[0].source_db, "users");
+    assert_eq!(mappings[0].target_db, "users_stg");
+    assert_eq!(mappings[1].source_db, "orders");
+    assert_eq!(mappings[1].target_db, "orders_{date}");
+}
+
+#[test]
+fn test_parse_csv_mapping_with_header_and_comments() {
+    let content = "source_db,target_db\n# reference data\nusers, users_stg\n\norders,orders_stg\n";
+    let mappings = parse_csv_mapping(content).unwrap();
+
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(mappings[0].source_db, "users");
+    assert_eq!(mappings[0].target_db, "users_stg");
+    assert_eq!(mappings[1].source_db, "orders");
+}
+
+#[test]
+fn test_parse_csv_mapping_rejects_malformed_rows() {
+    assert!(parse_csv_mapping("users,users_stg,extra\n").is_err());
+    assert!(parse_csv_mapping("users,\n").is_err());
+}
+
+#[test]
+fn test_load_database_mapping_by_extension() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let yaml_path = dir.path().join("mappings.yml");
+    std::fs::write(&yaml_path, "users: users_stg\n").unwrap();
+    assert_eq!(load_database_mapping(&yaml_path).unwrap().len(), 1);
+
+    let empty_path = dir.path().join("empty.csv");
+    std::fs::write(&empty_path, "source_db,target_db\n").unwrap();
+    assert!(load_database_mapping(&empty_path).is_err());
+
+    let unknown_path = dir.path().join("mappings.txt");
+    std::fs::write(&unknown_path, "users users_stg\n").unwrap();
+    assert!(load_database_mapping(&unknown_path).is_err());
+}
+
+#[test]
+fn test_parse_collection_mapping_with_redirects() {
+    let mappings =
+        parse_yaml_collection_mapping("users: accounts\ninvoices: billing.invoices.2024\n")
+            .unwrap();
+
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(mappings[0].target_db, None);
+    assert_eq!(
+        mappings[0].namespaces("app"),
+        ("app.users".to_string(), "app.accounts".to_string())
+    );
+    // Only the first dot separates the database; collection names may contain dots
+    assert_eq!(mappings[1].target_db.as_deref(), Some("billing"));
+    assert_eq!(mappings[1].target_collection, "invoices.2024");
+    assert_eq!(
+        mappings[1].namespaces("app"),
+        (
+            "app.invoices".to_string(),
+            "billing.invoices.2024".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_parse_csv_collection_mapping() {
+    let content = "source_collection,target\nusers,accounts\norders,sales.orders\n";
+    let mappings = parse_csv_collection_mapping(content).unwrap();
+
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(mappings[1].target_db.as_deref(), Some("sales"));
+    assert_eq!(mappings[1].target_collection, "orders");
+
+    assert!(parse_csv_collection_mapping("users,sales.\n").is_err());
+    assert!(parse_csv_collection_mapping("users,bad db.users\n").is_err());
+}
+
+#[test]
+fn test_parse_namespace_mapping() {
+    let mapping = parse_namespace_mapping("source.users=target.customers").unwrap();
+    assert_eq!(mapping.source_db.as_deref(), Some("source"));
+    assert_eq!(mapping.source_collection, "users");
+    assert_eq!(mapping.target_db.as_deref(), Some("target"));
+    assert_eq!(
+        mapping.namespaces("source"),
+        ("source.users".to_string(), "target.customers".to_string())
+    );
+
+    // Without database parts the rename applies to every synced database
+    let mapping = parse_namespace_mapping("users=customers").unwrap();
+    assert_eq!(mapping.source_db, None);
+    assert_eq!(mapping.target_db, None);
+    assert_eq!(mapping.target_collection, "customers");
+
+    assert!(parse_namespace_mapping("users").is_err());
+    assert!(parse_namespace_mapping("app.=customers").is_err());
+    assert!(parse_namespace_mapping("users=app.").is_err());
+    assert!(parse_namespace_mapping("bad db.users=customers").is_err());
+}
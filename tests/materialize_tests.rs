@@ -0,0 +1,31 @@
+use arcula::core::materialize::{parse_query, CollectionRef};
+use mongodb::bson::doc;
+
+// This file contains tests for materialize argument parsing
+
+#[test]
+fn test_parse_collection_ref() {
+    let reference: CollectionRef = "prod:app.events".parse().unwrap();
+    assert_eq!(reference.env.name(), "PROD");
+    assert_eq!(reference.database, "app");
+    assert_eq!(reference.collection, "events");
+
+    // Collection names may contain dots
+    let reference: CollectionRef = "DEV:app.events.archive".parse().unwrap();
+    assert_eq!(reference.collection, "events.archive");
+    assert_eq!(reference.to_string(), "DEV:app.events.archive");
+
+    assert!("app.events".parse::<CollectionRef>().is_err());
+    assert!("PROD:app".parse::<CollectionRef>().is_err());
+    assert!("PROD:app.".parse::<CollectionRef>().is_err());
+}
+
+#[test]
+fn test_parse_query() {
+    assert_eq!(
+        parse_query(r#"{"type": "order", "amount": {"$gt": 10}}"#).unwrap(),
+        doc! { "type": "order", "amount": { "$gt": 10 } }
+    );
+    assert!(parse_query("[1, 2]").is_err());
+    assert!(parse_query("{type: order}").is_err());
+}
@@ -0,0 +1,102 @@
+use arcula::commands::mcp::{handle, tools, PROTOCOL_VERSIONS};
+use arcula::commands::rpc::{parse_request, Policy, INVALID_PARAMS};
+use serde_json::{json, Value};
+
+// This file contains tests for the MCP server of `arcula mcp`
+
+const READ_ONLY: Policy = Policy {
+    writes: false,
+    production_targets: false,
+};
+
+async fn request(line: Value, policy: Policy) -> Option<Value> {
+    let request = parse_request(&line.to_string()).unwrap();
+    handle(request, policy)
+        .await
+        .map(|response| serde_json::to_value(response).unwrap())
+}
+
+fn tool_names(policy: Policy) -> Vec<String> {
+    tools(policy)["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_write_tools_need_allow_writes() {
+    let read_only = tool_names(READ_ONLY);
+    assert!(read_only.contains(&"list_databases".to_string()));
+    assert!(read_only.contains(&"history".to_string()));
+    assert!(!read_only.contains(&"sync".to_string()));
+    assert!(!read_only.contains(&"backup".to_string()));
+
+    let writes = tool_names(Policy {
+        writes: true,
+        ..READ_ONLY
+    });
+    assert!(writes.contains(&"sync".to_string()));
+    assert!(writes.contains(&"backup".to_string()));
+}
+
+#[tokio::test]
+async fn test_initialize_negotiates_protocol_version() {
+    let response = request(
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2024-11-05"}}),
+        READ_ONLY,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+    assert_eq!(response["result"]["serverInfo"]["name"], "arcula");
+
+    // Versions the server doesn't know get its newest one
+    let response = request(
+        json!({"jsonrpc": "2.0", "id": 2, "method": "initialize", "params": {"protocolVersion": "1999-01-01"}}),
+        READ_ONLY,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSIONS[0]);
+}
+
+#[tokio::test]
+async fn test_notifications_get_no_response() {
+    let response = request(
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        READ_ONLY,
+    )
+    .await;
+    assert!(response.is_none());
+}
+
+#[tokio::test]
+async fn test_tool_call_errors() {
+    let response = request(
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "drop_database"}}),
+        READ_ONLY,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+    // Refused operations are tool errors the model gets to see
+    let response = request(
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {"name": "sync", "arguments": {"from": "STG", "to": "DEV", "db": "app"}}
+        }),
+        READ_ONLY,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response["result"]["isError"], true);
+    assert!(response["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("not allowed"));
+}
@@ -0,0 +1,172 @@
+use arcula::core::mirror::{change_action, is_resume_lost, ChangeAction, MirrorState};
+use arcula::core::two_way::{check_environments, ConflictTracker, EventOrigin, Side};
+use mongodb::bson::{doc, Bson, Timestamp};
+use mongodb::change_stream::event::{OperationType, ResumeToken};
+
+// This file contains tests for mirroring change streams into a target database
+// and for the conflict detection of two-way syncs
+
+#[test]
+fn test_change_action() {
+    assert_eq!(change_action(&OperationType::Insert), ChangeAction::Upsert);
+    assert_eq!(change_action(&OperationType::Update), ChangeAction::Upsert);
+    assert_eq!(change_action(&OperationType::Replace), ChangeAction::Upsert);
+    assert_eq!(change_action(&OperationType::Delete), ChangeAction::Delete);
+    assert_eq!(
+        change_action(&OperationType::Drop),
+        ChangeAction::DropCollection
+    );
+    assert_eq!(change_action(&OperationType::Rename), ChangeAction::Resync);
+    assert_eq!(
+        change_action(&OperationType::DropDatabase),
+        ChangeAction::Resync
+    );
+    assert_eq!(
+        change_action(&OperationType::Invalidate),
+        ChangeAction::Resync
+    );
+    assert_eq!(
+        change_action(&OperationType::Other("createIndexes".to_string())),
+        ChangeAction::Ignore
+    );
+}
+
+fn token(data: &str) -> ResumeToken {
+    mongodb::bson::from_bson(Bson::Document(doc! { "_data": data })).unwrap()
+}
+
+#[test]
+fn test_mirror_state_records_positions_per_collection() {
+    let mut state = MirrorState::default();
+    assert!(state.stream_position().unwrap().is_none());
+
+    state.reset(&token("8263A1B2C3000000012B")).unwrap();
+    state
+        .record("orders", &token("8263A1B2C3000000022B"))
+        .unwrap();
+    state
+        .record("users", &token("8263A1B2C3000000032B"))
+        .unwrap();
+    state
+        .record("orders", &token("8263A1B2C3000000042B"))
+        .unwrap();
+
+    assert_eq!(
+        state.stream_position().unwrap(),
+        Some(token("8263A1B2C3000000042B"))
+    );
+    assert_eq!(state.collections["orders"].applied, 2);
+    assert_eq!(
+        state.collections["orders"].resume_token,
+        doc! { "_data": "8263A1B2C3000000042B" }
+    );
+    assert_eq!(state.collections["users"].applied, 1);
+
+    // An initial sync starts the positions over
+    state.reset(&token("8263A1B2C3000000052B")).unwrap();
+    assert!(state.collections.is_empty());
+}
+
+#[test]
+fn test_mirror_state_round_trip() {
+    let dir = std::env::temp_dir().join(format!("arcula_mirror_{}", rand::random::<u64>()));
+    let path = dir.join("mirror-PROD-app-STG-app.json");
+    assert_eq!(MirrorState::load(&path).unwrap(), MirrorState::default());
+
+    let mut state = MirrorState::default();
+    state
+        .record("orders", &token("8263A1B2C3000000012B"))
+        .unwrap();
+    state.save(&path).unwrap();
+    assert_eq!(MirrorState::load(&path).unwrap(), state);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(MirrorState::load(&path).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_other_errors_are_not_resume_lost() {
+    assert!(!is_resume_lost(&anyhow::anyhow!("connection refused")));
+}
+
+fn at(time: u32) -> Timestamp {
+    Timestamp { time, increment: 1 }
+}
+
+#[test]
+fn test_two_way_echoes_are_not_replicated_back() {
+    let mut tracker = ConflictTracker::default();
+    let key = doc! { "_id": 1 };
+
+    tracker
+        .record_write(Side::To, "orders", &key, at(10))
+        .unwrap();
+    assert_eq!(
+        tracker
+            .classify(Side::To, "orders", &key, Some(at(10)), true)
+            .unwrap(),
+        EventOrigin::Echo
+    );
+    // Once the echo has been seen, later changes on that side are replicated
+    assert_eq!(
+        tracker
+            .classify(Side::To, "orders", &key, Some(at(5)), false)
+            .unwrap(),
+        EventOrigin::Change
+    );
+}
+
+#[test]
+fn test_two_way_detects_overwritten_changes() {
+    let mut tracker = ConflictTracker::default();
+    let key = doc! { "_id": 1 };
+    tracker
+        .record_write(Side::To, "orders", &key, at(10))
+        .unwrap();
+
+    // Changed on the target before the replicated write landed there
+    assert_eq!(
+        tracker
+            .classify(Side::To, "orders", &key, Some(at(9)), false)
+            .unwrap(),
+        EventOrigin::Conflict
+    );
+    // Changed after it, so it simply replicates
+    assert_eq!(
+        tracker
+            .classify(Side::To, "orders", &key, Some(at(11)), false)
+            .unwrap(),
+        EventOrigin::Change
+    );
+    // Other documents, collections and sides are unaffected
+    let other = doc! { "_id": 2 };
+    assert_eq!(
+        tracker
+            .classify(Side::To, "orders", &other, Some(at(9)), false)
+            .unwrap(),
+        EventOrigin::Change
+    );
+    assert_eq!(
+        tracker
+            .classify(Side::To, "users", &key, Some(at(9)), false)
+            .unwrap(),
+        EventOrigin::Change
+    );
+    assert_eq!(
+        tracker
+            .classify(Side::From, "orders", &key, Some(at(9)), false)
+            .unwrap(),
+        EventOrigin::Change
+    );
+    assert_eq!(Side::From.other(), Side::To);
+}
+
+#[test]
+fn test_two_way_refuses_production() {
+    let env = |name: &str| name.parse().unwrap();
+    assert!(check_environments(&env("DEV"), &env("STG")).is_ok());
+    assert!(check_environments(&env("DEV"), &env("PROD")).is_err());
+    assert!(check_environments(&env("production"), &env("DEV")).is_err());
+    assert!(check_environments(&env("DEV"), &env("DEV")).is_err());
+}
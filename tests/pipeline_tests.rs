@@ -0,0 +1,68 @@
+use arcula::core::pipeline::{newest_stages, parse_pipelines};
+use arcula::core::variables::Variables;
+use mongodb::bson::Bson;
+
+// This file contains tests for aggregation pipeline file parsing
+
+#[test]
+fn test_parse_pipelines_with_extended_json() {
+    let pipelines = parse_pipelines(
+        r#"{
+            "events": [
+                { "$match": { "createdAt": { "$gte": { "$date": "2024-01-01T00:00:00Z" } } } },
+                { "$project": { "payload": 0 } }
+            ]
+        }"#,
+        &Variables::new(),
+    )
+    .unwrap();
+
+    let events = pipelines.get("events").unwrap();
+    assert_eq!(events.len(), 2);
+
+    let created_at = events[0]
+        .get_document("$match")
+        .unwrap()
+        .get_document("createdAt")
+        .unwrap()
+        .get("$gte")
+        .unwrap();
+    assert!(matches!(created_at, Bson::DateTime(_)));
+}
+
+#[test]
+fn test_parse_pipelines_rejects_invalid_stages() {
+    assert!(parse_pipelines(r#"{ "events": { "$match": {} } }"#, &Variables::new()).is_err());
+    assert!(parse_pipelines(
+        r#"{ "events": [ { "$match": {}, "$limit": 5 } ] }"#,
+        &Variables::new()
+    )
+    .is_err());
+    assert!(parse_pipelines(r#"{ "events": [ 5 ] }"#, &Variables::new()).is_err());
+}
+
+#[test]
+fn test_parse_pipelines_substitutes_variables() {
+    let variables = Variables::from([("tenant_id".to_string(), "acme\"co".to_string())]);
+    let pipelines = parse_pipelines(
+        r#"{ "orders": [ { "$match": { "tenant": "{tenant_id}", "note": "{other}" } } ] }"#,
+        &variables,
+    )
+    .unwrap();
+
+    let filter = pipelines["orders"][0].get_document("$match").unwrap();
+    assert_eq!(filter.get_str("tenant").unwrap(), "acme\"co");
+    assert_eq!(filter.get_str("note").unwrap(), "{other}");
+}
+
+#[test]
+fn test_newest_stages() {
+    let stages = newest_stages("createdAt", 1000);
+    assert_eq!(
+        stages,
+        vec![
+            mongodb::bson::doc! { "$sort": { "createdAt": -1 } },
+            mongodb::bson::doc! { "$limit": 1000_i64 },
+        ]
+    );
+}
@@ -0,0 +1,125 @@
+use arcula::core::plan::{add_target_overlap, batch_plan, sync_plan, Operation, Plan};
+use arcula::core::sync::{SyncConfig, SyncOptions, TargetOverlap};
+
+// This file contains tests for rendering the plans of operations
+
+fn sync_config(options: SyncOptions) -> SyncConfig {
+    SyncConfig {
+        source_env: "PROD".parse().unwrap(),
+        target_env: "STG".parse().unwrap(),
+        source_db: "app".to_string(),
+        target_db: "app_copy".to_string(),
+        options,
+    }
+}
+
+fn value<'a>(plan: &'a Plan, label: &str) -> Option<&'a str> {
+    plan.items
+        .iter()
+        .find(|item| item.label == label)
+        .map(|item| item.value.as_str())
+}
+
+#[test]
+fn test_sync_plan_items() {
+    let plan = sync_plan(&sync_config(SyncOptions {
+        create_backup: true,
+        drop_collections: false,
+        collections: vec!["users".to_string(), "orders".to_string()],
+        document_limits: [("*".to_string(), 1000)].into(),
+        ..Default::default()
+    }));
+    assert_eq!(plan.operation, Operation::Sync);
+    assert_eq!(value(&plan, "Create backup"), Some("Yes"));
+    assert_eq!(value(&plan, "Drop collections"), Some("No"));
+    assert_eq!(value(&plan, "Collections"), Some("users, orders"));
+    assert_eq!(
+        value(&plan, "Limit"),
+        Some("first 1000 documents of every collection")
+    );
+    // Settings left at their defaults aren't listed
+    assert_eq!(value(&plan, "Compression"), None);
+}
+
+#[test]
+fn test_batch_plan_items() {
+    let configs = [
+        sync_config(SyncOptions {
+            drop_collections: true,
+            ..Default::default()
+        }),
+        SyncConfig {
+            source_db: "billing".to_string(),
+            target_db: "billing".to_string(),
+            ..sync_config(SyncOptions {
+                drop_collections: true,
+                ..Default::default()
+            })
+        },
+    ];
+    let plan = batch_plan(&configs);
+    assert_eq!(plan.operation, Operation::Sync);
+    assert_eq!(value(&plan, "Databases"), Some("2"));
+    let syncs: Vec<&str> = plan
+        .items
+        .iter()
+        .filter(|item| item.label == "Sync")
+        .map(|item| item.value.as_str())
+        .collect();
+    assert_eq!(syncs.len(), 2);
+    assert!(syncs[1].starts_with("PROD:billing "));
+    assert_eq!(value(&plan, "Drop collections"), Some("Yes"));
+    assert_eq!(value(&plan, "Clear collections"), Some("No"));
+
+    let json = plan.to_json();
+    assert_eq!(json["databases"][0]["target_db"], "app_copy");
+    assert_eq!(json["drop_collections"], true);
+}
+
+#[test]
+fn test_sync_plan_json() {
+    let mut plan = sync_plan(&sync_config(SyncOptions::default()));
+    add_target_overlap(
+        &mut plan,
+        &TargetOverlap {
+            replaced: vec!["users".to_string()],
+            created: Vec::new(),
+            target_only: vec!["audit".to_string()],
+        },
+        false,
+    );
+    let json = plan.to_json();
+    assert_eq!(json["operation"], "sync");
+    assert_eq!(json["source_db"], "app");
+    assert_eq!(json["target_db"], "app_copy");
+    assert_eq!(json["target_overlap"]["target_only"][0], "audit");
+
+    let items = json["items"].as_array().unwrap();
+    let cleared = items
+        .iter()
+        .find(|item| item["label"] == "Only on target (emptied by clear)")
+        .unwrap();
+    assert_eq!(cleared["value"], "audit");
+    assert_eq!(cleared["warning"], true);
+    // Only highlighted items carry the flag
+    assert!(items[0].get("warning").is_none());
+}
+
+#[test]
+fn test_render_text_and_markdown() {
+    colored::control::set_override(false);
+    let mut plan = Plan::new(Operation::Restore);
+    plan.add("Target", "STG:app")
+        .warn("Only on target (left untouched)", "a|b");
+
+    assert_eq!(
+        plan.render_text(),
+        "  Target: STG:app\n  Only on target (left untouched): a|b\n"
+    );
+
+    let markdown = plan.render_markdown();
+    assert!(markdown.starts_with("### Arcula restore plan\n"));
+    assert!(markdown.contains("| **Target** | STG:app |\n"));
+    // Pipes must not break the table
+    assert!(markdown.contains("| **Only on target (left untouched)** | ⚠️ a\\|b |\n"));
+}
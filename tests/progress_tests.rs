@@ -0,0 +1,59 @@
+use arcula::core::progress::{report, set_renderer, ProgressRenderer, SyncPhase, SyncProgress};
+use arcula::utils::events::EventKind;
+use std::sync::Mutex;
+
+// This file contains tests for sync progress reporting
+
+#[test]
+fn test_progress_events() {
+    let started = SyncProgress::PhaseStarted(SyncPhase::Export)
+        .event("app")
+        .unwrap();
+    assert_eq!(started.event, EventKind::PhaseStarted);
+    assert_eq!(started.phase, Some(SyncPhase::Export));
+    assert_eq!(started.database, "app");
+
+    let copied = SyncProgress::CollectionCopied {
+        collection: "users".to_string(),
+        documents: 10,
+        done: 2,
+        total: 3,
+    }
+    .event("app")
+    .unwrap();
+    assert_eq!(copied.event, EventKind::Progress);
+    assert_eq!(copied.phase, Some(SyncPhase::Import));
+    assert_eq!(copied.collection.as_deref(), Some("users"));
+    assert_eq!((copied.done, copied.total), (Some(2), Some(3)));
+
+    assert!(SyncProgress::Exported.event("app").is_none());
+    assert!(SyncProgress::Completed.event("app").is_none());
+}
+
+static RENDERED: Mutex<Vec<(String, SyncProgress)>> = Mutex::new(Vec::new());
+
+struct Recorder;
+
+impl ProgressRenderer for Recorder {
+    fn render(&self, database: &str, progress: &SyncProgress) {
+        RENDERED
+            .lock()
+            .unwrap()
+            .push((database.to_string(), progress.clone()));
+    }
+}
+
+#[test]
+fn test_report_renders_progress() {
+    set_renderer(Recorder);
+    report("app", SyncProgress::DatabaseStarted);
+    report("app", SyncProgress::Completed);
+
+    assert_eq!(
+        *RENDERED.lock().unwrap(),
+        vec![
+            ("app".to_string(), SyncProgress::DatabaseStarted),
+            ("app".to_string(), SyncProgress::Completed),
+        ]
+    );
+}
@@ -0,0 +1,135 @@
+use arcula::core::backup::{BackupManifest, BackupRole, CollectionChecksum};
+use arcula::core::prune::{backup_problem, find_stale_artifacts, ArtifactKind, PruneLocations};
+use arcula::utils::dump::{DumpLayout, MANIFEST_FILE};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// This file contains tests for finding stale artifacts to prune
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn write_backup(path: &Path, collections: &[&str], dumped: &[&str]) {
+    std::fs::create_dir_all(path.join("app")).unwrap();
+    let manifest = BackupManifest {
+        environment: "STG".to_string(),
+        database: "app".to_string(),
+        created_at: "2025-01-01T00:00:00+00:00".to_string(),
+        run_id: None,
+        role: BackupRole::Target,
+        collections: collections
+            .iter()
+            .map(|name| CollectionChecksum {
+                name: name.to_string(),
+                documents: 1,
+                hash: None,
+                hash_method: Default::default(),
+            })
+            .collect(),
+    };
+    std::fs::write(
+        path.join(MANIFEST_FILE),
+        serde_json::to_string(&manifest).unwrap(),
+    )
+    .unwrap();
+    let dump = DumpLayout::new(path);
+    for name in dumped {
+        std::fs::write(dump.collection_file("app", name), b"").unwrap();
+        std::fs::write(dump.metadata_file("app", name), b"{}").unwrap();
+    }
+}
+
+#[test]
+fn test_backup_problem() {
+    let dir = std::env::temp_dir().join(format!("arcula_prune_{}", rand::random::<u64>()));
+
+    // Not checked without a manifest
+    std::fs::create_dir_all(&dir).unwrap();
+    assert_eq!(backup_problem(&dir), None);
+
+    write_backup(&dir, &["users", "orders"], &["users", "orders"]);
+    assert_eq!(backup_problem(&dir), None);
+
+    std::fs::remove_file(dir.join("app").join("orders.bson")).unwrap();
+    assert_eq!(
+        backup_problem(&dir),
+        Some("missing dump of orders".to_string())
+    );
+
+    std::fs::remove_file(dir.join("app").join("users.metadata.json")).unwrap();
+    assert_eq!(
+        backup_problem(&dir),
+        Some("missing dump of users, orders".to_string())
+    );
+
+    std::fs::write(dir.join(MANIFEST_FILE), "{").unwrap();
+    assert!(backup_problem(&dir)
+        .unwrap()
+        .contains("Invalid backup manifest"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_find_stale_artifacts() {
+    let root = std::env::temp_dir().join(format!("arcula_prune_{}", rand::random::<u64>()));
+    let locations = PruneLocations {
+        temp_dir: root.join("tmp"),
+        backup_dir: root.join("backups"),
+        state_dir: root.join("state"),
+    };
+    assert!(find_stale_artifacts(&locations, DAY, SystemTime::now())
+        .unwrap()
+        .is_empty());
+
+    std::fs::create_dir_all(locations.temp_dir.join("arcula-a1b2c3/app")).unwrap();
+    std::fs::write(
+        locations.temp_dir.join("arcula-a1b2c3/app/users.bson"),
+        b"12345",
+    )
+    .unwrap();
+    std::fs::create_dir_all(locations.temp_dir.join("other-tool")).unwrap();
+    write_backup(
+        &locations.backup_dir.join("backup_app_1"),
+        &["users"],
+        &["users"],
+    );
+    write_backup(&locations.backup_dir.join("backup_app_2"), &["users"], &[]);
+    std::fs::create_dir_all(&locations.state_dir).unwrap();
+    std::fs::write(
+        locations.state_dir.join("mirror-STG-app-DEV-app.json"),
+        "{}",
+    )
+    .unwrap();
+    std::fs::write(locations.state_dir.join("cache.tmp123"), "{").unwrap();
+    std::fs::write(locations.state_dir.join("cache.json"), "{}").unwrap();
+
+    // Only broken backups are pruned before the age limit
+    let fresh = find_stale_artifacts(&locations, DAY, SystemTime::now()).unwrap();
+    assert_eq!(fresh.len(), 1);
+    assert_eq!(fresh[0].kind, ArtifactKind::BrokenBackup);
+    assert_eq!(fresh[0].path, locations.backup_dir.join("backup_app_2"));
+
+    let later = SystemTime::now() + 2 * DAY;
+    let stale = find_stale_artifacts(&locations, DAY, later).unwrap();
+    let kinds: Vec<_> = stale.iter().map(|a| a.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ArtifactKind::TempDump,
+            ArtifactKind::BrokenBackup,
+            ArtifactKind::PartialStateFile,
+            ArtifactKind::MirrorState,
+        ]
+    );
+    assert_eq!(stale[0].size, 5);
+
+    for artifact in &stale {
+        artifact.remove().unwrap();
+    }
+    assert!(find_stale_artifacts(&locations, DAY, later)
+        .unwrap()
+        .is_empty());
+    assert!(locations.state_dir.join("cache.json").exists());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
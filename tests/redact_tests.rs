@@ -0,0 +1,117 @@
+use arcula::core::redact::{
+    drop_fields_in_file, fields_for, parse_drop_field, remove_field, DroppedFields,
+};
+use mongodb::bson::{doc, Document};
+use std::io::{Read, Write};
+
+// This file contains tests for dropping fields from synced documents
+
+#[test]
+fn test_parse_drop_field() {
+    assert_eq!(
+        parse_drop_field("users:profile.phone").unwrap(),
+        ("users".to_string(), "profile.phone".to_string())
+    );
+    assert_eq!(
+        parse_drop_field("ssn").unwrap(),
+        ("*".to_string(), "ssn".to_string())
+    );
+
+    assert!(parse_drop_field(":ssn").is_err());
+    assert!(parse_drop_field("users:").is_err());
+    assert!(parse_drop_field("profile..phone").is_err());
+    assert!(parse_drop_field("$ssn").is_err());
+    assert!(parse_drop_field("users:_id").is_err());
+}
+
+#[test]
+fn test_fields_for() {
+    let mut fields = DroppedFields::new();
+    fields.insert("*".to_string(), vec!["ssn".to_string()]);
+    fields.insert(
+        "users".to_string(),
+        vec!["password_hash".to_string(), "ssn".to_string()],
+    );
+
+    assert_eq!(fields_for(&fields, "users"), vec!["ssn", "password_hash"]);
+    assert_eq!(fields_for(&fields, "orders"), vec!["ssn"]);
+    assert!(fields_for(&DroppedFields::new(), "users").is_empty());
+}
+
+#[test]
+fn test_remove_field() {
+    let mut document = doc! {
+        "_id": 1,
+        "ssn": "123-45-6789",
+        "profile": { "phone": "555-0100", "city": "Tbilisi" },
+        "cards": [{ "number": "4111", "brand": "visa" }, { "brand": "amex" }, "legacy"],
+    };
+
+    assert!(remove_field(&mut document, "ssn"));
+    assert!(remove_field(&mut document, "profile.phone"));
+    // Like $unset, paths reach into arrays of embedded documents
+    assert!(remove_field(&mut document, "cards.number"));
+    assert!(!remove_field(&mut document, "profile.email"));
+    assert!(!remove_field(&mut document, "ssn.area"));
+
+    assert_eq!(
+        document,
+        doc! {
+            "_id": 1,
+            "profile": { "city": "Tbilisi" },
+            "cards": [{ "brand": "visa" }, { "brand": "amex" }, "legacy"],
+        }
+    );
+}
+
+fn read_documents(mut bytes: &[u8]) -> Vec<Document> {
+    let mut documents = Vec::new();
+    while !bytes.is_empty() {
+        documents.push(Document::from_reader(&mut bytes).unwrap());
+    }
+    documents
+}
+
+#[test]
+fn test_drop_fields_in_file() {
+    let dir = std::env::temp_dir().join(format!("arcula_redact_{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let documents = [
+        doc! { "_id": 1, "name": "Ann", "password_hash": "x" },
+        doc! { "_id": 2, "name": "Bob" },
+    ];
+    let mut bytes = Vec::new();
+    for document in &documents {
+        document.to_writer(&mut bytes).unwrap();
+    }
+    let expected = vec![
+        doc! { "_id": 1, "name": "Ann" },
+        doc! { "_id": 2, "name": "Bob" },
+    ];
+
+    let plain = dir.join("users.bson");
+    std::fs::write(&plain, &bytes).unwrap();
+    assert_eq!(
+        drop_fields_in_file(&plain, false, &["password_hash"]).unwrap(),
+        1
+    );
+    assert_eq!(read_documents(&std::fs::read(&plain).unwrap()), expected);
+
+    let compressed = dir.join("users.bson.gz");
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&bytes).unwrap();
+    std::fs::write(&compressed, encoder.finish().unwrap()).unwrap();
+    assert_eq!(
+        drop_fields_in_file(&compressed, true, &["password_hash"]).unwrap(),
+        1
+    );
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(std::fs::File::open(&compressed).unwrap())
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(read_documents(&decompressed), expected);
+
+    // Nothing but the rewritten files is left behind
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+use arcula::core::resume::{self, ResumeState};
+use arcula::utils::mongodb::restored_namespaces;
+
+// This file contains tests for resuming failed syncs
+
+#[test]
+fn test_restored_namespaces() {
+    let log = "\
+2025-01-01T00:00:00.000+0000\tpreparing collections to restore from
+2025-01-01T00:00:01.000+0000\tfinished restoring app.users (120 documents, 0 failures)
+2025-01-01T00:00:02.000+0000\tfinished restoring app.orders (80 documents, 3 failures)
+2025-01-01T00:00:03.000+0000\tfinished restoring app.events (10 documents, 0 failures)
+2025-01-01T00:00:04.000+0000\tFailed: app.sessions: error restoring from archive: connection reset";
+
+    // Collections with failed documents must be copied again
+    assert_eq!(restored_namespaces(log), vec!["app.users", "app.events"]);
+    assert!(restored_namespaces("").is_empty());
+}
+
+#[test]
+fn test_resume_state_round_trip() {
+    let dir = std::env::temp_dir().join(format!("arcula_resume_{}", rand::random::<u64>()));
+    let path = dir.join("resume_prod_app_stg_app.json");
+    assert_eq!(ResumeState::load(&path).unwrap(), None);
+    resume::clear(&path).unwrap();
+
+    let mut state = ResumeState {
+        run_id: "20250101T000000-3fa2c1d8".to_string(),
+        backup_path: Some(dir.join("backup_stg_app_20250101000000")),
+        completed: vec!["users".to_string()],
+    };
+    state.record(["orders".to_string(), "users".to_string()]);
+    assert_eq!(state.completed, vec!["orders", "users"]);
+
+    state.save(&path).unwrap();
+    assert_eq!(ResumeState::load(&path).unwrap(), Some(state));
+
+    resume::clear(&path).unwrap();
+    assert_eq!(ResumeState::load(&path).unwrap(), None);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(ResumeState::load(&path).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
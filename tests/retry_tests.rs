@@ -0,0 +1,116 @@
+use anyhow::anyhow;
+use arcula::core::retry::{is_transient, retry, RetryPolicy};
+use arcula::utils::mongodb::ToolTimeout;
+use std::cell::Cell;
+use std::time::Duration;
+
+// This file contains tests for retrying sync steps on transient errors
+
+fn policy(retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        retries,
+        backoff: Duration::from_millis(1),
+    }
+}
+
+#[test]
+fn test_retry_delay_doubles_up_to_a_limit() {
+    let policy = RetryPolicy {
+        retries: 20,
+        backoff: Duration::from_secs(10),
+    };
+    assert_eq!(policy.delay(1), Duration::from_secs(10));
+    assert_eq!(policy.delay(2), Duration::from_secs(20));
+    assert_eq!(policy.delay(3), Duration::from_secs(40));
+    assert_eq!(policy.delay(20), Duration::from_secs(5 * 60));
+}
+
+#[test]
+fn test_transient_errors() {
+    assert!(is_transient(&anyhow!(
+        "Export failed: Failed: error connecting to db server: connection reset by peer"
+    )));
+    assert!(is_transient(
+        &anyhow!("Import failed: server selection error: context deadline exceeded")
+            .context("Import of app failed")
+    ));
+    assert!(is_transient(&anyhow::Error::from(
+        ::mongodb::error::Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset))
+    )));
+
+    assert!(!is_transient(&anyhow!(
+        "Import failed: E11000 duplicate key error"
+    )));
+    assert!(!is_transient(&anyhow!(
+        "Database directory not found: /tmp/x"
+    )));
+    // A tool killed by --timeout is reported instead of retried
+    assert!(!is_transient(
+        &anyhow::Error::from(ToolTimeout {
+            tool: "mongodump",
+            timeout: Duration::from_secs(60),
+        })
+        .context("Export of app failed")
+    ));
+}
+
+#[tokio::test]
+async fn test_retry_until_success() {
+    let runs = Cell::new(0);
+    let retried = Cell::new(Vec::new());
+    let result = retry(
+        policy(2),
+        "Export",
+        |attempt| {
+            let mut seen = retried.take();
+            seen.push((attempt.attempt, attempt.attempts));
+            retried.set(seen);
+        },
+        || {
+            runs.set(runs.get() + 1);
+            let run = runs.get();
+            async move {
+                if run < 3 {
+                    Err(anyhow!("connection refused"))
+                } else {
+                    Ok(run)
+                }
+            }
+        },
+    )
+    .await;
+    assert_eq!(result.unwrap(), 3);
+    assert_eq!(retried.take(), vec![(1, 3), (2, 3)]);
+}
+
+#[tokio::test]
+async fn test_retry_gives_up() {
+    let runs = Cell::new(0);
+    let result: anyhow::Result<()> = retry(
+        policy(1),
+        "Import",
+        |_| {},
+        || {
+            runs.set(runs.get() + 1);
+            async { Err(anyhow!("connection refused")) }
+        },
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(runs.get(), 2);
+
+    // Other errors fail right away
+    let runs = Cell::new(0);
+    let result: anyhow::Result<()> = retry(
+        policy(3),
+        "Import",
+        |_| {},
+        || {
+            runs.set(runs.get() + 1);
+            async { Err(anyhow!("E11000 duplicate key error")) }
+        },
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(runs.get(), 1);
+}
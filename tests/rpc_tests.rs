@@ -0,0 +1,94 @@
+use arcula::commands::rpc::{
+    handle, parse_request, Policy, Response, RpcError, INVALID_PARAMS, INVALID_REQUEST,
+    METHOD_NOT_FOUND, PARSE_ERROR,
+};
+use serde_json::json;
+
+// This file contains tests for the JSON-RPC protocol of `arcula rpc`
+
+fn error_code(response: &Response) -> i64 {
+    response.error.as_ref().map(|e| e.code).unwrap_or_default()
+}
+
+#[test]
+fn test_parse_request() {
+    let request = parse_request(
+        r#"{"jsonrpc": "2.0", "id": 7, "method": "list_databases", "params": {"env": "DEV"}}"#,
+    )
+    .unwrap();
+    assert_eq!(request.id, Some(json!(7)));
+    assert_eq!(request.method, "list_databases");
+    assert_eq!(request.params, json!({"env": "DEV"}));
+
+    let notification = parse_request(r#"{"jsonrpc": "2.0", "method": "sync"}"#).unwrap();
+    assert_eq!(notification.id, None);
+}
+
+#[test]
+fn test_parse_request_errors() {
+    let response = parse_request("{not json").unwrap_err();
+    assert_eq!(error_code(&response), PARSE_ERROR);
+    assert_eq!(response.id, json!(null));
+
+    // The ID is kept when the request can be read far enough
+    let response = parse_request(r#"{"jsonrpc": "2.0", "id": "a"}"#).unwrap_err();
+    assert_eq!(error_code(&response), INVALID_REQUEST);
+    assert_eq!(response.id, json!("a"));
+
+    let response = parse_request(r#"{"jsonrpc": "1.0", "id": 1, "method": "sync"}"#).unwrap_err();
+    assert_eq!(error_code(&response), INVALID_REQUEST);
+}
+
+#[test]
+fn test_response_serialization() {
+    let success = Response::success(json!(1), json!(["orders"]));
+    assert_eq!(
+        serde_json::to_value(&success).unwrap(),
+        json!({"jsonrpc": "2.0", "id": 1, "result": ["orders"]})
+    );
+
+    let failure = Response::failure(json!(2), RpcError::new(METHOD_NOT_FOUND, "Unknown method"));
+    assert_eq!(
+        serde_json::to_value(&failure).unwrap(),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "error": {"code": METHOD_NOT_FOUND, "message": "Unknown method"}
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_handle_rejects_unknown_methods_and_params() {
+    let request =
+        parse_request(r#"{"jsonrpc": "2.0", "id": 1, "method": "drop_everything"}"#).unwrap();
+    assert_eq!(
+        error_code(&handle(request, Policy::UNRESTRICTED).await.unwrap()),
+        METHOD_NOT_FOUND
+    );
+
+    let request = parse_request(
+        r#"{"jsonrpc": "2.0", "id": 2, "method": "list_databases", "params": {"environment": "DEV"}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        error_code(&handle(request, Policy::UNRESTRICTED).await.unwrap()),
+        INVALID_PARAMS
+    );
+
+    // Without a source there is nothing to sync
+    let request = parse_request(
+        r#"{"jsonrpc": "2.0", "id": 3, "method": "sync", "params": {"to": "STG", "db": "orders"}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        error_code(&handle(request, Policy::UNRESTRICTED).await.unwrap()),
+        INVALID_PARAMS
+    );
+}
+
+#[tokio::test]
+async fn test_handle_notifications_get_no_response() {
+    let request = parse_request(r#"{"jsonrpc": "2.0", "method": "drop_everything"}"#).unwrap();
+    assert!(handle(request, Policy::UNRESTRICTED).await.is_none());
+}
@@ -0,0 +1,45 @@
+use arcula::core::sampling::{limit_for, parse_limit, sampling_stages, DocumentLimits, Sampling};
+use mongodb::bson::doc;
+
+// This file contains tests for copying samples of collections with --limit
+
+#[test]
+fn test_parse_limit() {
+    assert_eq!(parse_limit("1000").unwrap(), ("*".to_string(), 1000));
+    assert_eq!(
+        parse_limit("events=50000").unwrap(),
+        ("events".to_string(), 50000)
+    );
+
+    assert!(parse_limit("=10").is_err());
+    assert!(parse_limit("events=").is_err());
+    assert!(parse_limit("events=many").is_err());
+    assert!(parse_limit("0").is_err());
+    assert!(parse_limit("-5").is_err());
+}
+
+#[test]
+fn test_limit_for() {
+    let mut limits = DocumentLimits::new();
+    assert_eq!(limit_for(&limits, "users"), None);
+
+    limits.insert("*".to_string(), 1000);
+    limits.insert("events".to_string(), 50);
+    assert_eq!(limit_for(&limits, "users"), Some(1000));
+    // A collection's own limit wins, also when it is higher
+    assert_eq!(limit_for(&limits, "events"), Some(50));
+    limits.insert("orders".to_string(), 5000);
+    assert_eq!(limit_for(&limits, "orders"), Some(5000));
+}
+
+#[test]
+fn test_sampling_stages() {
+    assert_eq!(
+        sampling_stages(100, Sampling::First),
+        vec![doc! { "$limit": 100_i64 }]
+    );
+    assert_eq!(
+        sampling_stages(100, Sampling::Random),
+        vec![doc! { "$sample": { "size": 100_i64 } }]
+    );
+}
@@ -0,0 +1,61 @@
+use arcula::config::file::MetricsConfig;
+use arcula::utils::statsd::{format_event, format_metric, AlertType, StatsdClient};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+// This file contains tests for StatsD and Datadog event emission
+
+#[test]
+fn test_format_metric() {
+    assert_eq!(
+        format_metric(
+            "arcula.sync.started",
+            1,
+            "c",
+            &[("source", "PROD"), ("db", "app")]
+        ),
+        "arcula.sync.started:1|c|#source:PROD,db:app"
+    );
+    assert_eq!(
+        format_metric("arcula.sync.duration", 250, "ms", &[]),
+        "arcula.sync.duration:250|ms"
+    );
+}
+
+#[test]
+fn test_format_event() {
+    assert_eq!(
+        format_event(
+            "Arcula sync failed",
+            "PROD:app\nboom",
+            AlertType::Error,
+            &[("target", "STG")]
+        ),
+        "_e{18,14}:Arcula sync failed|PROD:app\\nboom|t:error|#target:STG"
+    );
+}
+
+#[test]
+fn test_client_sends_datagrams() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let config = MetricsConfig {
+        statsd: Some(server.local_addr().unwrap().to_string()),
+        prefix: Some("test".to_string()),
+        datadog_events: false,
+    };
+    let client = StatsdClient::from_config(&config).unwrap();
+
+    // Events are disabled, so only the counter arrives
+    client.event("ignored", "", AlertType::Info, &[]);
+    client.increment("sync.completed", &[("db", "app")]);
+
+    let mut buf = [0; 512];
+    let len = server.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"test.sync.completed:1|c|#db:app");
+
+    assert!(StatsdClient::from_config(&MetricsConfig::default()).is_none());
+}
@@ -0,0 +1,812 @@
+use arcula::core::mapping::CollectionMapping;
+use arcula::core::plan::render_markdown_summary;
+use arcula::core::progress::SyncPhase;
+use arcula::core::sync::{
+    apply_tenant_rename, check_source_stats, collection_reports, compute_target_overlap,
+    find_self_sync, match_databases, match_tenant_databases, resolve_target_db_template,
+    CollectionStatus, PhaseReport, SyncConfig, SyncOptions, SyncReport, TransferSize,
+};
+use arcula::core::warnings::{self, SyncWarning, WarningCode};
+use arcula::utils::mongodb::{
+    index_restore_duration, namespace_filters, CollectionStats, DatabaseStats,
+};
+use chrono::TimeZone;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// This file contains tests for sync planning helpers that don't need MongoDB
+
+#[test]
+fn test_match_tenant_databases() {
+    let databases = vec![
+        "tenant_acme".to_string(),
+        "tenant_globex".to_string(),
+        "tenant_".to_string(),
+        "billing".to_string(),
+    ];
+
+    let matched = match_tenant_databases(&databases, "tenant_*");
+    assert_eq!(
+        matched,
+        vec![
+            ("tenant_acme".to_string(), "acme".to_string()),
+            ("tenant_globex".to_string(), "globex".to_string()),
+        ]
+    );
+
+    // The trailing wildcard is optional
+    assert_eq!(match_tenant_databases(&databases, "tenant_").len(), 2);
+    assert!(match_tenant_databases(&databases, "other_").is_empty());
+}
+
+#[test]
+fn test_apply_tenant_rename() {
+    assert_eq!(
+        apply_tenant_rename("tenant_{id}_stg", "acme"),
+        "tenant_acme_stg"
+    );
+    assert_eq!(apply_tenant_rename("fixed_name", "acme"), "fixed_name");
+}
+
+#[test]
+fn test_resolve_target_db_template() {
+    let now = chrono::Utc
+        .with_ymd_and_hms(2025, 3, 14, 15, 9, 26)
+        .unwrap();
+
+    assert_eq!(
+        resolve_target_db_template("{source_db}_copy_{date}", "orders", now).unwrap(),
+        "orders_copy_20250314"
+    );
+    assert_eq!(
+        resolve_target_db_template("snap_{datetime}", "orders", now).unwrap(),
+        "snap_20250314150926"
+    );
+    assert_eq!(
+        resolve_target_db_template("plain", "orders", now).unwrap(),
+        "plain"
+    );
+    assert!(resolve_target_db_template("{source_db}_{unknown}", "orders", now).is_err());
+}
+
+fn sync_config(db: &str) -> SyncConfig {
+    SyncConfig {
+        source_env: "PROD".parse().unwrap(),
+        target_env: "STG".parse().unwrap(),
+        source_db: db.to_string(),
+        target_db: db.to_string(),
+        options: SyncOptions::default(),
+    }
+}
+
+#[test]
+fn test_find_self_sync() {
+    let mut configs = vec![sync_config("app"), sync_config("billing")];
+    assert!(find_self_sync(&configs).is_none());
+
+    // Another database of the same environment is fine
+    configs.push(SyncConfig {
+        target_env: "PROD".parse().unwrap(),
+        target_db: "app_copy".to_string(),
+        ..sync_config("app")
+    });
+    assert!(find_self_sync(&configs).is_none());
+
+    configs.push(SyncConfig {
+        target_env: "PROD".parse().unwrap(),
+        ..sync_config("billing")
+    });
+    let found = find_self_sync(&configs).unwrap();
+    assert_eq!(found.source_db, "billing");
+    assert_eq!(found.target_env, found.source_env);
+}
+
+#[test]
+fn test_collection_reports() {
+    let stats = |name: &str, kind: &str, documents| CollectionStats {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        documents,
+        size: documents * 100,
+        storage_size: 0,
+        index_size: 0,
+    };
+    let reports = collection_reports(
+        &[
+            stats("users", "collection", 3),
+            stats("active_users", "view", 0),
+            stats("audit", "collection", 10),
+            stats("events", "collection", 5),
+        ],
+        &["events".to_string()],
+        &["audit".to_string()],
+        &BTreeMap::from([(
+            "users".to_string(),
+            TransferSize {
+                on_disk: 100,
+                sent: 300,
+            },
+        )]),
+    );
+
+    let summary: Vec<(&str, CollectionStatus, u64)> = reports
+        .iter()
+        .map(|c| (c.name.as_str(), c.status, c.size))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("audit", CollectionStatus::Excluded, 1000),
+            ("events", CollectionStatus::Unchanged, 500),
+            ("users", CollectionStatus::Copied, 300),
+        ]
+    );
+    let users = &reports[2];
+    assert_eq!(users.transfer.map(|t| t.compression_ratio()), Some(3.0));
+    assert!(reports[0].transfer.is_none());
+}
+
+#[test]
+fn test_sync_report_serializes_and_converts() {
+    let mut report = SyncReport::new(&sync_config("orders"));
+    report.duration = Duration::from_millis(1500);
+    report.phases.push(PhaseReport {
+        phase: SyncPhase::Indexes,
+        duration: Duration::from_millis(250),
+    });
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["source_env"], "PROD");
+    assert_eq!(json["target_db"], "orders");
+    assert_eq!(json["duration_seconds"], 1.5);
+    assert_eq!(json["phases"][0]["phase"], "indexes");
+    assert_eq!(json["phases"][0]["duration_seconds"], 0.25);
+    assert!(json["backup_path"].is_null());
+    assert!(report.clone().into_result().is_ok());
+
+    report.error = Some("Import of orders failed".to_string());
+    let error = report.into_result().unwrap_err();
+    assert_eq!(error.to_string(), "Import of orders failed");
+}
+
+#[test]
+fn test_index_restore_duration() {
+    let log = "\
+2025-01-01T00:00:00.000+0000\tpreparing collections to restore from
+2025-01-01T00:00:05.000+0000\tfinished restoring app.users (120 documents, 0 failures)
+2025-01-01T00:00:05.500+0000\trestoring indexes for collection app.users from metadata
+2025-01-01T00:00:06.000+0000\trestoring indexes for collection app.orders from metadata
+2025-01-01T00:00:08.000+0000\t200 document(s) restored successfully. 0 document(s) failed to restore.";
+
+    assert_eq!(
+        index_restore_duration(log),
+        Some(Duration::from_millis(2500))
+    );
+    // Without indexes to restore there is nothing to time
+    assert_eq!(index_restore_duration(log.lines().next().unwrap()), None);
+}
+
+#[test]
+fn test_render_markdown_summary() {
+    let outcome = |db: &str, error: Option<&str>| SyncReport {
+        duration: Duration::from_secs(42),
+        error: error.map(String::from),
+        ..SyncReport::new(&sync_config(db))
+    };
+
+    let mut warned = outcome("events", None);
+    warned.warnings.push(SyncWarning {
+        code: WarningCode::BackupFailed,
+        message: "Failed to create backup, proceeding without backup".to_string(),
+    });
+
+    let mut timed = outcome("users", None);
+    timed.phases = vec![
+        PhaseReport {
+            phase: SyncPhase::Export,
+            duration: Duration::from_secs(12),
+        },
+        PhaseReport {
+            phase: SyncPhase::Indexes,
+            duration: Duration::from_secs(90),
+        },
+    ];
+
+    let markdown = render_markdown_summary(&[
+        timed,
+        outcome("orders", Some("Import failed: a|b\nc")),
+        warned,
+    ]);
+
+    assert!(markdown.starts_with("### Arcula sync: 2 succeeded, 1 failed\n"));
+    assert!(markdown
+        .contains("| ✅ | `PROD:users` | `STG:users` | 42s | export 12s, indexes 1m30s |  |  |\n"));
+    // Pipes and newlines in errors must not break the table
+    assert!(markdown
+        .contains("| ❌ | `PROD:orders` | `STG:orders` | 42s |  |  | Import failed: a\\|b c |\n"));
+    assert!(
+        markdown.contains("| ✅ | `PROD:events` | `STG:events` | 42s |  | `backup_failed` |  |\n")
+    );
+}
+
+#[test]
+fn test_recorded_warnings_are_taken_once() {
+    warnings::record(WarningCode::TargetBusy, "app", "STG: 1 index build running");
+    warnings::record(WarningCode::BackupFailed, "app", "Failed to create backup");
+
+    let recorded = warnings::take();
+    let codes: Vec<&str> = recorded.iter().map(|w| w.code.as_str()).collect();
+    assert_eq!(codes, vec!["target_busy", "backup_failed"]);
+    assert_eq!(recorded[0].message, "STG: 1 index build running");
+    assert!(warnings::take().is_empty());
+}
+
+#[test]
+fn test_check_source_stats_rejects_empty_source() {
+    let empty = DatabaseStats {
+        name: "app".to_string(),
+        ..Default::default()
+    };
+    let no_documents = DatabaseStats {
+        collections: 3,
+        ..empty.clone()
+    };
+
+    let options = SyncOptions::default();
+    assert!(check_source_stats(&empty, &options).is_err());
+    assert!(check_source_stats(&no_documents, &options).is_err());
+
+    let options = SyncOptions {
+        allow_empty_source: true,
+        ..SyncOptions::default()
+    };
+    assert!(check_source_stats(&empty, &options).is_ok());
+}
+
+#[test]
+fn test_check_source_stats_enforces_max_size() {
+    let stats = DatabaseStats {
+        name: "app".to_string(),
+        collections: 2,
+        objects: 100,
+        data_size: 2048,
+        ..Default::default()
+    };
+
+    let mut options = SyncOptions::default();
+    assert!(check_source_stats(&stats, &options).is_ok());
+
+    options.max_source_size = Some(4096);
+    assert!(check_source_stats(&stats, &options).is_ok());
+
+    options.max_source_size = Some(1024);
+    let err = check_source_stats(&stats, &options).unwrap_err();
+    assert!(err.to_string().contains("2.0 KB"));
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+#[test]
+fn test_compute_target_overlap() {
+    let overlap = compute_target_overlap(
+        &names(&["users", "orders", "events"]),
+        &names(&["users", "orders", "legacy_audit"]),
+        "app",
+        &[],
+    );
+
+    assert_eq!(overlap.replaced, names(&["orders", "users"]));
+    assert_eq!(overlap.created, names(&["events"]));
+    assert_eq!(overlap.target_only, names(&["legacy_audit"]));
+}
+
+#[test]
+fn test_compute_target_overlap_applies_collection_map() {
+    let collection_map = vec![
+        CollectionMapping {
+            source_db: None,
+            source_collection: "users".to_string(),
+            target_db: None,
+            target_collection: "customers".to_string(),
+        },
+        CollectionMapping {
+            source_db: None,
+            source_collection: "invoices".to_string(),
+            target_db: Some("billing".to_string()),
+            target_collection: "invoices".to_string(),
+        },
+    ];
+
+    let overlap = compute_target_overlap(
+        &names(&["users", "invoices"]),
+        &names(&["customers", "invoices", "users"]),
+        "app",
+        &collection_map,
+    );
+
+    assert_eq!(overlap.replaced, names(&["customers"]));
+    assert!(overlap.created.is_empty());
+    assert_eq!(overlap.target_only, names(&["invoices", "users"]));
+}
+
+#[test]
+fn test_check_source_stats_schema_only() {
+    let stats = DatabaseStats {
+        name: "app".to_string(),
+        collections: 2,
+        data_size: 1 << 40,
+        ..Default::default()
+    };
+    let options = SyncOptions {
+        schema_only: true,
+        max_source_size: Some(1024),
+        ..SyncOptions::default()
+    };
+
+    // Documents and data size don't matter when only the schema is copied
+    assert!(check_source_stats(&stats, &options).is_ok());
+
+    let no_collections = DatabaseStats {
+        collections: 0,
+        ..stats
+    };
+    assert!(check_source_stats(&no_collections, &options).is_err());
+}
+
+#[test]
+fn test_data_only_clears_instead_of_dropping() {
+    let mut options = SyncOptions {
+        drop_collections: true,
+        clear_collections: false,
+        data_only: true,
+        ..SyncOptions::default()
+    };
+    options.update_collection_settings();
+
+    assert!(!options.drop_collections);
+    assert!(options.clear_collections);
+}
+
+#[test]
+fn test_match_databases() {
+    let databases: Vec<String> = ["tenant_acme", "tenant_globex", "billing", "users"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let patterns = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(
+        match_databases(&databases, &patterns(&["users", "tenant_*"])).unwrap(),
+        vec!["users", "tenant_acme", "tenant_globex"]
+    );
+    // Overlapping patterns select a database once
+    assert_eq!(
+        match_databases(&databases, &patterns(&["tenant_acme", "tenant_*"])).unwrap(),
+        vec!["tenant_acme", "tenant_globex"]
+    );
+
+    let err = match_databases(&databases, &patterns(&["users", "orders_*"])).unwrap_err();
+    assert_eq!(err.to_string(), "No database matches 'orders_*'");
+}
+
+#[test]
+fn test_selected_collections() {
+    let mut options = SyncOptions::default();
+    assert!(options.includes("users"));
+    assert_eq!(namespace_filters("app", &[]), vec!["--nsInclude", "app.*"]);
+
+    options.collections = vec!["users".to_string(), "orders".to_string()];
+    assert!(options.includes("orders"));
+    assert!(!options.includes("events"));
+    assert_eq!(
+        namespace_filters("app", &options.collections),
+        vec!["--nsInclude", "app.users", "--nsInclude", "app.orders"]
+    );
+
+    let options = SyncOptions {
+        exclude_collections: vec!["audit_logs".to_string()],
+        ..SyncOptions::default()
+    };
+    assert!(options.includes("users"));
+    assert!(!options.includes("audit_logs"));
+}
+
+#[test]
+fn test_dump_layout_collections() {
+    use arcula::utils::dump::DumpLayout;
+
+    let dir = std::env::temp_dir().join(format!("arcula_dump_{}", rand::random::<u64>()));
+    let dump = DumpLayout::new(&dir);
+    std::fs::create_dir_all(dump.database_dir("app")).unwrap();
+    for file in [
+        "users.bson",
+        "users.metadata.json",
+        "orders.bson",
+        "orders.metadata.json",
+        "active_users.metadata.json",
+    ] {
+        std::fs::write(dump.database_dir("app").join(file), b"").unwrap();
+    }
+
+    assert!(dump.has_database("app"));
+    assert!(!dump.has_database("other"));
+    let collections = dump.collections("app").unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(collections, vec!["orders", "users"]);
+}
+
+#[test]
+fn test_engine_kind() {
+    use arcula::core::engine::EngineKind;
+    use clap::ValueEnum;
+
+    assert_eq!(SyncOptions::default().engine, EngineKind::Tools);
+    assert_eq!(
+        EngineKind::from_str("driver", false),
+        Ok(EngineKind::Driver)
+    );
+    assert_eq!(EngineKind::Driver.to_string(), "driver");
+    assert_eq!(
+        EngineKind::from_str("stream", false),
+        Ok(EngineKind::Stream)
+    );
+}
+
+#[test]
+fn test_dump_layout_gzip() {
+    use arcula::utils::dump::DumpLayout;
+
+    let dir = std::env::temp_dir().join(format!("arcula_dump_{}", rand::random::<u64>()));
+    let dump = DumpLayout::new(&dir).compressed(true);
+    assert!(dump
+        .collection_file("app", "users")
+        .ends_with("app/users.bson.gz"));
+    assert!(dump
+        .metadata_file("app", "users")
+        .ends_with("app/users.metadata.json.gz"));
+
+    std::fs::create_dir_all(dump.database_dir("app")).unwrap();
+    assert!(!DumpLayout::open(&dir).is_compressed());
+    for file in ["users.bson.gz", "users.metadata.json.gz"] {
+        std::fs::write(dump.database_dir("app").join(file), b"").unwrap();
+    }
+
+    let opened = DumpLayout::open(&dir);
+    let collections = opened.collections("app").unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(opened.is_compressed());
+    assert_eq!(collections, vec!["users"]);
+}
+
+#[test]
+fn test_dump_layout_data_size() {
+    use arcula::utils::dump::DumpLayout;
+    use std::io::Write;
+
+    let dir = std::env::temp_dir().join(format!("arcula_dump_{}", rand::random::<u64>()));
+    let data = vec![7u8; 4096];
+    let plain = DumpLayout::new(&dir);
+    std::fs::create_dir_all(plain.database_dir("app")).unwrap();
+    std::fs::write(plain.collection_file("app", "users"), &data).unwrap();
+
+    let gzip = DumpLayout::new(&dir).compressed(true);
+    let mut encoder = flate2::write::GzEncoder::new(
+        std::fs::File::create(gzip.collection_file("app", "users")).unwrap(),
+        flate2::Compression::default(),
+    );
+    encoder.write_all(&data).unwrap();
+    encoder.finish().unwrap();
+
+    let plain_size = plain.data_size("app", "users").unwrap();
+    let gzip_size = gzip.data_size("app", "users").unwrap();
+    let on_disk = std::fs::metadata(gzip.collection_file("app", "users"))
+        .unwrap()
+        .len();
+    let missing = gzip.data_size("app", "orders");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(plain_size, 4096);
+    assert_eq!(gzip_size, 4096);
+    assert!(on_disk < 4096);
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_dump_layout_oplog() {
+    use arcula::utils::dump::DumpLayout;
+
+    let dir = std::env::temp_dir().join(format!("arcula_dump_{}", rand::random::<u64>()));
+    let dump = DumpLayout::new(&dir);
+    assert_eq!(dump.oplog_file(), dir.join("oplog.bson"));
+    assert!(DumpLayout::new(&dir)
+        .compressed(true)
+        .oplog_file()
+        .ends_with("oplog.bson.gz"));
+
+    std::fs::create_dir_all(dump.database_dir("app")).unwrap();
+    assert!(!dump.has_oplog());
+    std::fs::write(dump.oplog_file(), b"").unwrap();
+    let has_oplog = dump.has_oplog();
+    // The oplog at the root is neither a database nor compressed data
+    let compressed = DumpLayout::open(&dir).is_compressed();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(has_oplog);
+    assert!(!compressed);
+}
+
+#[test]
+fn test_describe_target_activity() {
+    use arcula::core::preflight::describe_activity;
+    use arcula::utils::mongodb::TargetActivity;
+
+    assert_eq!(describe_activity(&TargetActivity::default()), None);
+
+    let activity = TargetActivity {
+        index_builds: vec!["app.users".to_string(), "app.orders".to_string()],
+        balancer_running: true,
+    };
+    assert_eq!(
+        describe_activity(&activity).as_deref(),
+        Some(
+            "index builds in progress on app.users, app.orders; \
+             the shard balancer is migrating chunks"
+        )
+    );
+}
+
+#[test]
+fn test_required_space() {
+    use arcula::core::preflight::required_space;
+
+    let stats = |name: &str, storage_size, index_size| CollectionStats {
+        name: name.to_string(),
+        kind: "collection".to_string(),
+        documents: 0,
+        size: storage_size,
+        storage_size,
+        index_size,
+    };
+    let source = vec![stats("users", 1000, 100), stats("logs", 5000, 500)];
+    let target = vec![stats("users", 800, 80), stats("other", 9000, 900)];
+
+    let options = SyncOptions {
+        drop_collections: false,
+        ..Default::default()
+    };
+    assert_eq!(required_space(&source, &target, &options), 7200);
+
+    // Dropped target collections free their files first
+    let options = SyncOptions {
+        drop_collections: true,
+        ..Default::default()
+    };
+    assert_eq!(required_space(&source, &target, &options), 6320);
+
+    let options = SyncOptions {
+        drop_collections: true,
+        exclude_collections: vec!["logs".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(required_space(&source, &target, &options), 320);
+
+    // Collections trimmed to their newest documents aren't counted
+    let options = SyncOptions {
+        drop_collections: false,
+        skip_larger_than: Some(2000),
+        keep_newest: Some(100),
+        ..Default::default()
+    };
+    assert_eq!(required_space(&source, &target, &options), 1200);
+}
+
+#[test]
+fn test_estimate_collections() {
+    use arcula::core::preflight::estimate_collections;
+
+    let stats = |name: &str, kind: &str, documents, size| CollectionStats {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        documents,
+        size,
+        storage_size: 0,
+        index_size: 0,
+    };
+    let source = vec![
+        stats("users", "collection", 10, 1000),
+        stats("logs", "collection", 1000, 50_000),
+        stats("active_users", "view", 0, 0),
+        stats("audit", "collection", 5, 500),
+    ];
+    let summary = |options: &SyncOptions| -> Vec<(String, u64, u64)> {
+        estimate_collections(&source, options)
+            .into_iter()
+            .map(|c| (c.name, c.documents, c.size))
+            .collect()
+    };
+
+    let options = SyncOptions {
+        exclude_collections: vec!["audit".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        summary(&options),
+        vec![
+            ("logs".to_string(), 1000, 50_000),
+            ("users".to_string(), 10, 1000)
+        ]
+    );
+
+    // Oversized collections are left out, or counted with their newest documents
+    let options = SyncOptions {
+        skip_larger_than: Some(2000),
+        ..Default::default()
+    };
+    assert_eq!(summary(&options).len(), 2);
+    let options = SyncOptions {
+        skip_larger_than: Some(2000),
+        keep_newest: Some(100),
+        ..Default::default()
+    };
+    assert_eq!(summary(&options)[1], ("logs".to_string(), 100, 5000));
+
+    // Limited collections are counted with their limit of documents
+    let options = SyncOptions {
+        document_limits: [("*".to_string(), 8), ("logs".to_string(), 200)].into(),
+        ..Default::default()
+    };
+    assert_eq!(
+        summary(&options),
+        vec![
+            ("audit".to_string(), 5, 500),
+            ("logs".to_string(), 200, 10_000),
+            ("users".to_string(), 8, 800)
+        ]
+    );
+}
+
+#[test]
+fn test_oversized_collections() {
+    use arcula::core::sync::oversized_collections;
+
+    let stats = |name: &str, kind: &str, size| CollectionStats {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        documents: 0,
+        size,
+        storage_size: 0,
+        index_size: 0,
+    };
+    let stats = vec![
+        stats("users", "collection", 1000),
+        stats("sessions", "collection", 6000),
+        stats("audit_log", "collection", 9000),
+        stats("big_view", "view", 9000),
+        stats("events", "timeseries", 5000),
+    ];
+
+    assert!(oversized_collections(&stats, &SyncOptions::default()).is_empty());
+
+    let options = SyncOptions {
+        skip_larger_than: Some(5000),
+        ..Default::default()
+    };
+    assert_eq!(
+        oversized_collections(&stats, &options),
+        vec!["audit_log", "sessions"]
+    );
+
+    // Already excluded collections aren't reported again
+    let options = SyncOptions {
+        skip_larger_than: Some(5000),
+        exclude_collections: vec!["sessions".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(oversized_collections(&stats, &options), vec!["audit_log"]);
+}
+
+#[test]
+fn test_limit_oversized() {
+    use arcula::core::sync::limit_oversized;
+    use mongodb::bson::doc;
+
+    let oversized = vec!["audit_log".to_string(), "events".to_string()];
+
+    // Without --keep-newest they are excluded
+    let options = SyncOptions {
+        skip_larger_than: Some(5000),
+        exclude_collections: vec!["sessions".to_string()],
+        ..Default::default()
+    };
+    let limited = limit_oversized(&options, oversized.clone());
+    assert_eq!(
+        limited.exclude_collections,
+        vec!["sessions", "audit_log", "events"]
+    );
+    assert!(limited.pipelines.is_empty());
+
+    // With it they keep their newest documents, before their own pipeline
+    let options = SyncOptions {
+        skip_larger_than: Some(5000),
+        keep_newest: Some(10),
+        newest_by: Some("createdAt".to_string()),
+        pipelines: [(
+            "events".to_string(),
+            vec![doc! { "$project": { "payload": 0 } }],
+        )]
+        .into(),
+        ..Default::default()
+    };
+    let limited = limit_oversized(&options, oversized);
+    assert!(limited.exclude_collections.is_empty());
+    assert_eq!(
+        limited.pipelines["audit_log"],
+        vec![
+            doc! { "$sort": { "createdAt": -1 } },
+            doc! { "$limit": 10_i64 }
+        ]
+    );
+    assert_eq!(
+        limited.pipelines["events"],
+        vec![
+            doc! { "$sort": { "createdAt": -1 } },
+            doc! { "$limit": 10_i64 },
+            doc! { "$project": { "payload": 0 } }
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_wait_for_target_gives_up() {
+    use arcula::config::{Environment, MongoConfig};
+    use arcula::core::preflight::wait_for_target;
+
+    // Nothing listens on port 1, and the wait is shorter than one poll interval
+    let target = MongoConfig {
+        connection_string: "mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=200".to_string(),
+        environment: Environment::new("TEST_TARGET"),
+    };
+    let error = wait_for_target(&target, "app", Duration::from_secs(1))
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("didn't accept connections within 1s"));
+}
+
+#[test]
+fn test_tools_engine_keeps_dump_dir() {
+    use arcula::core::engine::ToolsEngine;
+
+    let dir = std::env::temp_dir().join(format!("arcula_keep_{}", rand::random::<u64>()));
+    let dumps = dir.join("dumps");
+    drop(ToolsEngine::in_dir(&dumps, true).unwrap());
+    let kept = dumps.is_dir();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(kept);
+}
+
+#[test]
+fn test_staging_dir() {
+    use arcula::config::create_temp_dir_in;
+
+    let dir = std::env::temp_dir().join(format!("arcula_staging_{}", rand::random::<u64>()));
+    let options = SyncOptions {
+        temp_dir: Some(dir.clone()),
+        ..Default::default()
+    };
+    assert_eq!(options.staging_dir(), dir);
+
+    // The staging directory is created when missing
+    let temp = create_temp_dir_in(&options.staging_dir()).unwrap();
+    let inside = temp.path().starts_with(&dir);
+    drop(temp);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(inside);
+}
@@ -0,0 +1,142 @@
+use arcula::config::file;
+use arcula::core::transform::{
+    render_template, rules_for, template_paths, CollectionTransforms, Transform, TransformRule,
+};
+use mongodb::bson::{doc, oid::ObjectId, Bson};
+
+// This file contains tests for the transform rules applied to synced documents
+
+fn rename(field: &str, to: &str) -> TransformRule {
+    TransformRule::Rename {
+        field: field.to_string(),
+        to: to.to_string(),
+    }
+}
+
+#[test]
+fn test_parse_transforms_defaults() {
+    let config = file::parse(
+        r#"
+[defaults.transforms]
+"*" = [{ op = "set", field = "synced", value = true }]
+users = [
+  { op = "rename", field = "name", to = "profile.full_name" },
+  { op = "drop", field = "ssn" },
+  { op = "template", field = "email", template = "user-{_id}@example.com" },
+]
+"#,
+    )
+    .unwrap();
+    let transforms = &config.defaults.transforms;
+    assert_eq!(
+        transforms["*"],
+        vec![TransformRule::Set {
+            field: "synced".to_string(),
+            value: Bson::Boolean(true),
+        }]
+    );
+    assert_eq!(transforms["users"][0], rename("name", "profile.full_name"));
+    assert_eq!(
+        transforms["users"][2].to_string(),
+        "template email = \"user-{_id}@example.com\""
+    );
+
+    assert!(
+        file::parse("[defaults.transforms]\nusers = [{ op = \"hash\", field = \"ssn\" }]\n")
+            .is_err()
+    );
+}
+
+#[test]
+fn test_rules_for() {
+    let mut transforms = CollectionTransforms::new();
+    transforms.insert("users".to_string(), vec![rename("name", "full_name")]);
+    transforms.insert(
+        "*".to_string(),
+        vec![TransformRule::Drop {
+            field: "ssn".to_string(),
+        }],
+    );
+
+    let rules = rules_for(&transforms, "users");
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].field(), "ssn");
+    assert_eq!(rules_for(&transforms, "orders").len(), 1);
+    assert!(rules_for(&CollectionTransforms::new(), "users").is_empty());
+}
+
+#[test]
+fn test_apply_rules() {
+    let mut document = doc! { "_id": 1, "name": "Ann", "ssn": "123", "tags": ["a"] };
+    let rules = vec![
+        rename("name", "profile.full_name"),
+        TransformRule::Set {
+            field: "env".to_string(),
+            value: Bson::String("staging".to_string()),
+        },
+        TransformRule::Drop {
+            field: "ssn".to_string(),
+        },
+        TransformRule::Template {
+            field: "email".to_string(),
+            template: "{profile.full_name}-{_id}@example.com".to_string(),
+        },
+    ];
+    assert!(rules.apply(&mut document).unwrap());
+    assert_eq!(
+        document,
+        doc! {
+            "_id": 1,
+            "tags": ["a"],
+            "profile": { "full_name": "Ann" },
+            "env": "staging",
+            "email": "Ann-1@example.com",
+        }
+    );
+
+    // Renaming a missing field changes nothing, and a rename through a value
+    // that isn't a document leaves the field where it was
+    let mut document = doc! { "_id": 1, "name": "Ann", "tags": ["a"] };
+    assert!(!rename("missing", "other").apply(&mut document).unwrap());
+    assert!(!rename("name", "tags.first").apply(&mut document).unwrap());
+    assert_eq!(document, doc! { "_id": 1, "tags": ["a"], "name": "Ann" });
+}
+
+#[test]
+fn test_render_template() {
+    let id = ObjectId::parse_str("65a1b2c3d4e5f60718293a4b").unwrap();
+    let document = doc! { "_id": id, "tenant": { "slug": "acme" }, "n": 3 };
+    assert_eq!(
+        render_template("user-{_id}@{tenant.slug}.test", &document).unwrap(),
+        "user-65a1b2c3d4e5f60718293a4b@acme.test"
+    );
+    assert_eq!(render_template("{n}-{missing}", &document).unwrap(), "3-");
+    assert_eq!(render_template("plain", &document).unwrap(), "plain");
+}
+
+#[test]
+fn test_validate_rules() {
+    assert_eq!(
+        template_paths("{a}-{b.c}").unwrap(),
+        vec!["a".to_string(), "b.c".to_string()]
+    );
+    assert!(template_paths("{a").is_err());
+    assert!(template_paths("a}").is_err());
+    assert!(template_paths("{}").is_err());
+    assert!(template_paths("{a..b}").is_err());
+
+    assert!(rename("name", "full_name").validate().is_ok());
+    assert!(rename("name", "name").validate().is_err());
+    assert!(rename("name", "_id").validate().is_err());
+    assert!(TransformRule::Drop {
+        field: "$where".to_string()
+    }
+    .validate()
+    .is_err());
+    assert!(TransformRule::Template {
+        field: "email".to_string(),
+        template: "{id".to_string(),
+    }
+    .validate()
+    .is_err());
+}
@@ -0,0 +1,205 @@
+use arcula::utils::disk::parse_df_available;
+use arcula::utils::docker::{parse_published_port, target_image};
+use arcula::utils::github::escape_data;
+use arcula::utils::mongodb::{id_range_filters, range_boundaries};
+use arcula::utils::pattern::{exclude_matching, glob_match, include_matching};
+use arcula::utils::run_id::generate;
+use arcula::utils::units::{format_bytes, format_duration, parse_date, parse_duration, parse_size};
+use std::time::Duration;
+
+// This file contains tests for formatting and parsing helpers
+
+#[test]
+fn test_format_bytes() {
+    assert_eq!(format_bytes(0), "0 B");
+    assert_eq!(format_bytes(1023), "1023 B");
+    assert_eq!(format_bytes(1536), "1.5 KB");
+    assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GB");
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("analytics*", "analytics"));
+    assert!(glob_match("analytics*", "analytics_eu"));
+    assert!(!glob_match("analytics*", "web_analytics"));
+    assert!(glob_match("*_test", "orders_test"));
+    assert!(glob_match("tenant_?", "tenant_a"));
+    assert!(!glob_match("tenant_?", "tenant_ab"));
+    assert!(glob_match("*a*b*", "xxaxxbxx"));
+    assert!(glob_match("orders", "orders"));
+    assert!(!glob_match("orders", "orders_old"));
+}
+
+#[test]
+fn test_exclude_matching() {
+    let names = vec!["orders", "orders_test", "users", "import_tmp"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let patterns = vec!["*_test".to_string(), "*_tmp".to_string()];
+
+    assert_eq!(exclude_matching(names, &patterns), vec!["orders", "users"]);
+    assert_eq!(
+        exclude_matching(vec!["orders".to_string()], &[]),
+        vec!["orders"]
+    );
+}
+
+#[test]
+fn test_include_matching() {
+    let names = vec!["tenant_acme", "tenant_globex", "billing"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    assert_eq!(
+        include_matching(names, &["tenant_*".to_string()]),
+        vec!["tenant_acme", "tenant_globex"]
+    );
+    assert!(include_matching(vec!["billing".to_string()], &[]).is_empty());
+}
+
+#[test]
+fn test_generate_run_id() {
+    let now = chrono::DateTime::parse_from_rfc3339("2025-01-01T12:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let id = generate(now);
+
+    assert!(id.starts_with("20250101T120000-"), "{}", id);
+    assert_eq!(id.len(), "20250101T120000-".len() + 8);
+    assert_ne!(generate(now), id);
+}
+
+#[test]
+fn test_escape_workflow_command_data() {
+    assert_eq!(escape_data("50% done\nnext"), "50%25 done%0Anext");
+    assert_eq!(escape_data("plain"), "plain");
+}
+
+#[test]
+fn test_parse_date() {
+    let midnight = parse_date("2024-01-01").unwrap();
+    assert_eq!(midnight.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+    assert_eq!(
+        parse_date("2024-01-01T06:00:00+02:00")
+            .unwrap()
+            .to_rfc3339(),
+        "2024-01-01T04:00:00+00:00"
+    );
+    assert_eq!(parse_date(" 2024-01-01T00:00:00Z ").unwrap(), midnight);
+
+    assert!(parse_date("").is_err());
+    assert!(parse_date("2024-13-01").is_err());
+    assert!(parse_date("yesterday").is_err());
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("45m"), Ok(Duration::from_secs(45 * 60)));
+    assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 3600)));
+    assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+    assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("0").is_err());
+    assert!(parse_duration("2x").is_err());
+    assert!(parse_duration("h").is_err());
+    assert!(parse_duration("1h30").is_err());
+}
+
+#[test]
+fn test_format_duration() {
+    assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+    assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+    assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m");
+    assert_eq!(format_duration(Duration::from_secs(7205)), "2h5s");
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("512"), Ok(512));
+    assert_eq!(parse_size("500MB"), Ok(500 * 1024 * 1024));
+    assert_eq!(parse_size("50gb"), Ok(50 * 1024 * 1024 * 1024));
+    assert_eq!(parse_size("1.5 TB"), Ok(3 * (1 << 39)));
+    assert_eq!(parse_size("2K"), Ok(2048));
+
+    assert!(parse_size("").is_err());
+    assert!(parse_size("GB").is_err());
+    assert!(parse_size("10PB").is_err());
+}
+
+#[test]
+fn test_range_boundaries() {
+    use mongodb::bson::Bson;
+
+    let ids: Vec<Bson> = (0..20).map(Bson::Int32).collect();
+    assert_eq!(
+        range_boundaries(&ids, 4),
+        vec![Bson::Int32(5), Bson::Int32(10), Bson::Int32(15)]
+    );
+
+    // Repeated samples give fewer ranges, other types are left out
+    let mut ids: Vec<Bson> = vec![Bson::Int32(1); 4];
+    ids.extend(vec![Bson::Int64(7); 4]);
+    ids.push(Bson::String("z".to_string()));
+    assert_eq!(
+        range_boundaries(&ids, 4),
+        vec![Bson::Int32(1), Bson::Int64(7)]
+    );
+
+    assert!(range_boundaries(&[], 4).is_empty());
+}
+
+#[test]
+fn test_id_range_filters() {
+    use mongodb::bson::{doc, Bson};
+
+    assert_eq!(id_range_filters(&[]), vec![doc! {}]);
+
+    let filters = id_range_filters(&[Bson::Int32(10), Bson::Int32(20)]);
+    assert_eq!(
+        filters,
+        vec![
+            doc! { "_id": { "$lt": 10 } },
+            doc! { "_id": { "$gte": 10, "$lt": 20 } },
+            doc! { "_id": { "$gte": 20 } },
+            doc! { "_id": { "$not": { "$type": "number" } } },
+        ]
+    );
+
+    let id = mongodb::bson::oid::ObjectId::new();
+    let filters = id_range_filters(&[Bson::ObjectId(id)]);
+    assert_eq!(filters[2], doc! { "_id": { "$not": { "$type": 7 } } });
+}
+
+#[test]
+fn test_parse_df_available() {
+    let output = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+                  /dev/vda         264212084 58331256  42471020      58% /\n";
+    assert_eq!(parse_df_available(output), Some(42_471_020 * 1024));
+    assert_eq!(
+        parse_df_available("Filesystem 1024-blocks Used Available\n"),
+        None
+    );
+    assert_eq!(parse_df_available(""), None);
+}
+
+#[test]
+fn test_docker_target_image() {
+    assert_eq!(target_image("docker:mongo:7"), Some("mongo:7"));
+    assert_eq!(target_image("docker:"), Some("mongo:latest"));
+    assert_eq!(target_image("LOCAL"), None);
+    assert_eq!(target_image("@staging"), None);
+}
+
+#[test]
+fn test_parse_published_port() {
+    assert_eq!(parse_published_port("127.0.0.1:49153\n"), Some(49153));
+    assert_eq!(
+        parse_published_port("0.0.0.0:32768\n[::]:32768\n"),
+        Some(32768)
+    );
+    assert_eq!(parse_published_port(""), None);
+}
@@ -0,0 +1,90 @@
+use arcula::core::verify::{compare_checksums, compare_samples, CountCheck, SampleComparison};
+use mongodb::bson::{doc, oid::ObjectId, RawDocumentBuf};
+use std::collections::BTreeMap;
+
+// This file contains tests for post-import verification helpers
+
+fn raw(doc: mongodb::bson::Document) -> RawDocumentBuf {
+    RawDocumentBuf::from_document(&doc).unwrap()
+}
+
+#[test]
+fn test_compare_samples_matches_by_id() {
+    let id = ObjectId::new();
+    let source = vec![
+        raw(doc! { "_id": id, "name": "a" }),
+        raw(doc! { "_id": 1, "name": "b" }),
+        raw(doc! { "_id": 2, "name": "c" }),
+        raw(doc! { "_id": 3, "name": "d" }),
+    ];
+    let target = vec![
+        raw(doc! { "_id": 2, "name": "changed" }),
+        raw(doc! { "_id": 1, "name": "b" }),
+        raw(doc! { "_id": id, "name": "a" }),
+        // Same value with a different BSON type is a different document
+        raw(doc! { "_id": 3_i64, "name": "d" }),
+    ];
+
+    let comparison = compare_samples(&source, &target).unwrap();
+    assert_eq!(
+        comparison,
+        SampleComparison {
+            checked: 4,
+            missing: 1,
+            mismatched: 1,
+        }
+    );
+    assert!(!comparison.is_match());
+}
+
+#[test]
+fn test_compare_samples_detects_field_order_changes() {
+    let source = vec![raw(doc! { "_id": 1, "a": 1, "b": 2 })];
+    let target = vec![raw(doc! { "_id": 1, "b": 2, "a": 1 })];
+
+    assert_eq!(compare_samples(&source, &target).unwrap().mismatched, 1);
+    assert!(compare_samples(&source, &source).unwrap().is_match());
+}
+
+#[test]
+fn test_count_check() {
+    assert!(CountCheck::new("users", 10, 10, true).matches);
+    assert!(!CountCheck::new("users", 10, 12, true).matches);
+    assert!(!CountCheck::new("users", 10, 8, true).matches);
+
+    // A target keeping its own documents may have more, never fewer
+    assert!(CountCheck::new("users", 10, 12, false).matches);
+    assert!(!CountCheck::new("users", 10, 8, false).matches);
+}
+
+#[test]
+fn test_compare_checksums() {
+    let hashes = |entries: &[(&str, &str)]| -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(c, h)| (c.to_string(), h.to_string()))
+            .collect()
+    };
+    let source = hashes(&[
+        ("events", "e1"),
+        ("orders", "o1"),
+        ("system.js", "s1"),
+        ("users", "u1"),
+        ("views_source", "v1"),
+    ]);
+    let target = hashes(&[("events", "e1"), ("orders", "o2"), ("views_source", "v2")]);
+
+    let checks = compare_checksums(&source, &target, &["views_source".to_string()]);
+    let summary: Vec<(&str, Option<&str>, bool)> = checks
+        .iter()
+        .map(|c| (c.collection.as_str(), c.target.as_deref(), c.matches))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("events", Some("e1"), true),
+            ("orders", Some("o2"), false),
+            ("users", None, false),
+        ]
+    );
+}